@@ -1,15 +1,18 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 pub const DEFAULT_STORAGE_BASE_DIR: &str = "./saving";
 pub const DEFAULT_HLS_OUTPUT_DIR: &str = "hls";
 pub const DEFAULT_RECORD_OUTPUT_DIR: &str = "recordings";
 pub const DEFAULT_SNAPSHOT_OUTPUT_DIR: &str = "snapshots";
+pub const DEFAULT_EXPORT_OUTPUT_DIR: &str = "exports";
 
 /// Legacy full-path defaults used by module `Default` impls when no config is provided.
 pub const DEFAULT_HLS_DIR: &str = "./saving/hls";
 pub const DEFAULT_RECORD_DIR: &str = "./saving/recordings";
 pub const DEFAULT_SNAPSHOT_DIR: &str = "./saving/snapshots";
+pub const DEFAULT_EXPORT_DIR: &str = "./saving/exports";
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -64,6 +67,42 @@ pub struct HlsConfig {
     pub segment_duration: Option<f64>,
     pub max_segments: Option<usize>,
     pub output_dir: Option<String>,
+    /// LL-HLS: partial segments and blocking playlist reload.
+    pub low_latency: Option<bool>,
+    /// LL-HLS part target in seconds (default 0.25).
+    pub part_duration: Option<f64>,
+    /// "ts" (default) or "fmp4" (CMAF); `?format=fmp4` adds fMP4 to a TS stream.
+    pub segment_format: Option<String>,
+    /// "disk" (default) or "memory" (segments kept in RAM, no disk I/O).
+    pub storage: Option<String>,
+    /// Total memory cap across streams in memory storage mode (default 256).
+    pub memory_limit_mb: Option<u64>,
+    /// Seconds of DVR history for time-shift (`?offset=`, `_HLS_start=`); 0 = live only.
+    pub dvr_window_sec: Option<u64>,
+    /// Per-stream DVR windows in seconds (`[server.hls.dvr_streams]`, `<id> = <secs>`).
+    #[serde(default)]
+    pub dvr_streams: HashMap<String, u64>,
+    /// Segment encryption for paid streams (`[server.hls.encryption]`).
+    pub encryption: Option<HlsEncryptionConfig>,
+    /// ID3 timed metadata (TS metadata PID / fMP4 `emsg`) from the metadata API.
+    pub timed_metadata: Option<bool>,
+    /// Analysis event kinds also emitted as timed metadata (e.g. "face_detected").
+    #[serde(default)]
+    pub metadata_events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HlsEncryptionConfig {
+    pub enabled: bool,
+    /// "aes-128" (default) or "sample-aes" (H.264/AAC only).
+    pub method: Option<String>,
+    /// New key every N segments (default 10).
+    pub key_rotation_segments: Option<u64>,
+    /// HMAC secret for `?token=` on key requests; required when enabled.
+    pub token_secret: Option<String>,
+    /// Stream id patterns to encrypt (`*`, `?`); empty encrypts every stream.
+    #[serde(default)]
+    pub streams: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -75,9 +114,49 @@ pub struct HttpFlvConfig {
 pub struct RecordConfig {
     pub enabled: bool,
     pub output_dir: Option<String>,
+    /// Directory for MP4 exports, relative to the storage root unless absolute.
+    pub export_dir: Option<String>,
     pub default_format: Option<String>,
     pub segment_duration_sec: Option<u64>,
     pub align_keyframe: Option<bool>,
+    /// Delete segments older than this (all streams unless overridden).
+    pub max_age_hours: Option<u64>,
+    /// Cap on total recorded bytes across all streams.
+    pub max_total_mb: Option<u64>,
+    /// Delete oldest segments while free space on the record volume is below this.
+    pub min_free_mb: Option<u64>,
+    pub retention_check_interval_sec: Option<u64>,
+    /// Per-stream overrides keyed by stream id (`[record.stream_retention.<id>]`).
+    #[serde(default)]
+    pub stream_retention: HashMap<String, StreamRetentionConfig>,
+    /// Start recording automatically when a matching stream starts publishing
+    /// (`[[record.rules]]`); the first matching rule wins.
+    #[serde(default)]
+    pub rules: Vec<RecordRuleConfig>,
+    /// Seconds an event clip keeps recording after the last trigger.
+    pub event_post_roll_sec: Option<u64>,
+    /// Analysis event kinds that trigger an event clip (e.g. "face_detected").
+    #[serde(default)]
+    pub event_triggers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RecordRuleConfig {
+    /// Stream id glob (`*` and `?`); matches every stream when omitted.
+    pub stream: Option<String>,
+    /// Source protocol: "rtmp", "rtsp", "webrtc" or "http".
+    pub protocol: Option<String>,
+    /// "push" or "pull".
+    pub source: Option<String>,
+    pub format: Option<String>,
+    pub segment_duration_sec: Option<u64>,
+    pub align_keyframe: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamRetentionConfig {
+    pub max_age_hours: Option<u64>,
+    pub max_total_mb: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -159,6 +238,14 @@ impl Config {
         )
     }
 
+    pub fn export_output_dir(&self) -> PathBuf {
+        Self::resolve_output_dir(
+            self.record.as_ref().and_then(|r| r.export_dir.as_deref()),
+            DEFAULT_EXPORT_OUTPUT_DIR,
+            self.storage_base_dir(),
+        )
+    }
+
     pub fn snapshot_output_dir(&self) -> PathBuf {
         Self::resolve_output_dir(
             self.snapshot.as_ref().and_then(|s| s.output_dir.as_deref()),
@@ -170,6 +257,7 @@ impl Config {
     pub fn ensure_storage_dirs(&self) -> std::io::Result<()> {
         std::fs::create_dir_all(self.hls_output_dir())?;
         std::fs::create_dir_all(&self.record_output_dir())?;
+        std::fs::create_dir_all(self.export_output_dir())?;
         std::fs::create_dir_all(&self.snapshot_output_dir())?;
         Ok(())
     }
@@ -188,6 +276,16 @@ impl Default for Config {
                     segment_duration: Some(1.0),
                     max_segments: Some(1),
                     output_dir: Some(DEFAULT_HLS_OUTPUT_DIR.to_string()),
+                    low_latency: None,
+                    part_duration: None,
+                    segment_format: None,
+                    storage: None,
+                    memory_limit_mb: None,
+                    dvr_window_sec: None,
+                    dvr_streams: HashMap::new(),
+                    encryption: None,
+                    timed_metadata: None,
+                    metadata_events: Vec::new(),
                 }),
                 http_flv: Some(HttpFlvConfig { enabled: true }),
             },
@@ -197,9 +295,18 @@ impl Default for Config {
             record: Some(RecordConfig {
                 enabled: false,
                 output_dir: Some(DEFAULT_RECORD_OUTPUT_DIR.to_string()),
+                export_dir: Some(DEFAULT_EXPORT_OUTPUT_DIR.to_string()),
                 default_format: Some("ts".to_string()),
                 segment_duration_sec: Some(300),
                 align_keyframe: Some(true),
+                max_age_hours: None,
+                max_total_mb: None,
+                min_free_mb: None,
+                retention_check_interval_sec: Some(60),
+                stream_retention: HashMap::new(),
+                rules: Vec::new(),
+                event_post_roll_sec: Some(10),
+                event_triggers: Vec::new(),
             }),
             analysis: Some(AnalysisConfig {
                 enabled: false,
@@ -299,6 +406,96 @@ max_files = 5
         assert_eq!(config.snapshot_output_dir(), PathBuf::from("/custom/snapshots"));
     }
 
+    #[test]
+    fn record_retention_and_rules_parse() {
+        let config: Config = toml::from_str(
+            r#"
+[server.rtmp]
+port = 1935
+[server.rtsp]
+port = 554
+[server.webrtc]
+port = 9080
+[server.http]
+port = 8081
+[record]
+enabled = true
+max_age_hours = 720
+min_free_mb = 2048
+event_post_roll_sec = 15
+event_triggers = ["face_detected"]
+[record.stream_retention.lobby]
+max_age_hours = 24
+max_total_mb = 512
+[[record.rules]]
+stream = "cam-*"
+source = "pull"
+format = "mp4"
+segment_duration_sec = 60
+[log]
+level = "info"
+path = "./logs/media-server.log"
+max_size_mb = 10
+max_files = 5
+"#,
+        )
+        .unwrap();
+
+        let record = config.record.unwrap();
+        assert_eq!(record.max_age_hours, Some(720));
+        assert_eq!(record.min_free_mb, Some(2048));
+        assert_eq!(record.max_total_mb, None);
+        let lobby = &record.stream_retention["lobby"];
+        assert_eq!(lobby.max_age_hours, Some(24));
+        assert_eq!(lobby.max_total_mb, Some(512));
+        assert_eq!(record.event_post_roll_sec, Some(15));
+        assert_eq!(record.event_triggers, vec!["face_detected"]);
+        assert_eq!(record.rules.len(), 1);
+        assert_eq!(record.rules[0].stream.as_deref(), Some("cam-*"));
+        assert_eq!(record.rules[0].source.as_deref(), Some("pull"));
+        assert_eq!(record.rules[0].segment_duration_sec, Some(60));
+    }
+
+    #[test]
+    fn hls_dvr_windows_parse_per_stream() {
+        let config: Config = toml::from_str(
+            r#"
+[server.rtmp]
+port = 1935
+[server.rtsp]
+port = 554
+[server.webrtc]
+port = 9080
+[server.http]
+port = 8081
+[server.hls]
+enabled = true
+dvr_window_sec = 600
+[server.hls.dvr_streams]
+lobby = 7200
+[server.hls.encryption]
+enabled = true
+method = "sample-aes"
+token_secret = "s3cret"
+streams = ["paid-*"]
+[log]
+level = "info"
+path = "./logs/media-server.log"
+max_size_mb = 10
+max_files = 5
+"#,
+        )
+        .unwrap();
+
+        let hls = config.server.hls.unwrap();
+        assert_eq!(hls.dvr_window_sec, Some(600));
+        assert_eq!(hls.dvr_streams["lobby"], 7200);
+        let encryption = hls.encryption.unwrap();
+        assert_eq!(encryption.method.as_deref(), Some("sample-aes"));
+        assert_eq!(encryption.key_rotation_segments, None);
+        assert_eq!(encryption.streams, vec!["paid-*"]);
+    }
+
     #[test]
     fn default_subdirs_used_when_output_dir_missing() {
         let config: Config = toml::from_str(
@@ -118,6 +118,11 @@ impl DispatchReader {
         self.cursor = self.hub.snap(SnapMode::LatestIdr);
     }
 
+    /// Rewind to the oldest IDR still in the ring (replays the cached GOPs).
+    pub fn snap_to_oldest_idr(&mut self) {
+        self.cursor = self.hub.snap(SnapMode::OldestIdr);
+    }
+
     pub fn cursor_media_lag_ms(&self) -> Option<u64> {
         self.media_lag_from_seq_ms(self.cursor)
     }
@@ -484,7 +489,11 @@ impl DispatchReader {
 }
 
 fn is_playable_idr(frame: &MediaFrame) -> bool {
-    is_playable_video(frame) && (frame.is_keyframe || is_keyframe_annex_b(&frame.data))
+    is_playable_video(frame)
+        && match frame.codec {
+            CodecType::H265 => is_video_keyframe(frame),
+            _ => frame.is_keyframe || is_keyframe_annex_b(&frame.data),
+        }
 }
 
 pub fn last_playable_idr_seq(hub: &StreamHub, from_seq: u64, to_seq: u64) -> Option<u64> {
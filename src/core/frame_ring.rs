@@ -14,6 +14,8 @@ const MIN_GOPS_RETAINED: usize = 1;
 pub enum SnapMode {
     LiveEdge,
     LatestIdr,
+    /// Oldest retained IDR (pre-roll for event clips).
+    OldestIdr,
 }
 
 #[derive(Debug, Clone)]
@@ -118,6 +120,10 @@ impl FrameRing {
         self.idr_seqs.back().copied()
     }
 
+    pub fn oldest_idr_seq(&self) -> Option<u64> {
+        self.idr_seqs.front().copied()
+    }
+
     pub fn latest_idr_frame(&self) -> Option<MediaFrame> {
         let seq = self.latest_idr_seq()?;
         self.get(seq).map(|f| f.to_media_frame())
@@ -127,6 +133,7 @@ impl FrameRing {
         match mode {
             SnapMode::LiveEdge => self.latest_seq(),
             SnapMode::LatestIdr => self.latest_idr_seq().unwrap_or_else(|| self.latest_seq()),
+            SnapMode::OldestIdr => self.oldest_idr_seq().unwrap_or_else(|| self.latest_seq()),
         }
     }
 
@@ -194,13 +201,18 @@ impl Default for FrameRing {
 }
 
 pub fn is_video_keyframe(frame: &MediaFrame) -> bool {
-    if !matches!(frame.codec, CodecType::H264 | CodecType::H265) {
+    if !frame.codec.is_video() {
         return false;
     }
     if frame.is_keyframe {
         return true;
     }
-    annex_b_contains_idr(&frame.data)
+    // AV1/VP9 keyframes are only known from the ingest tag header
+    match frame.codec {
+        CodecType::H264 => annex_b_contains_idr(&frame.data),
+        CodecType::H265 => super::h265::contains_irap_nalu(&frame.data),
+        _ => false,
+    }
 }
 
 fn annex_b_contains_idr(data: &[u8]) -> bool {
@@ -223,7 +235,7 @@ fn annex_b_contains_idr(data: &[u8]) -> bool {
 }
 
 pub fn is_playable_video(frame: &MediaFrame) -> bool {
-    matches!(frame.codec, CodecType::H264 | CodecType::H265) && !frame.data.is_empty()
+    frame.codec.is_video() && !frame.data.is_empty()
 }
 
 #[cfg(test)]
@@ -259,6 +271,19 @@ mod tests {
         ring.push(video_frame(3, true));
         ring.push(video_frame(4, false));
         assert_eq!(ring.snap(SnapMode::LatestIdr), 2);
+        assert_eq!(ring.snap(SnapMode::OldestIdr), 0);
+    }
+
+    #[test]
+    fn oldest_idr_follows_gop_eviction() {
+        let mut ring = FrameRing::with_capacity(4, 1024);
+        ring.push(video_frame(1, true));
+        ring.push(video_frame(2, false));
+        ring.push(video_frame(3, false));
+        ring.push(video_frame(4, true));
+        ring.push(video_frame(5, false));
+        assert_eq!(ring.oldest_idr_seq(), Some(3));
+        assert_eq!(ring.snap(SnapMode::OldestIdr), 3);
     }
 
     #[test]
@@ -275,4 +300,30 @@ mod tests {
         assert!(ring.get(3).is_some());
         assert!(ring.get(4).is_some());
     }
+
+    #[test]
+    fn hevc_irap_marks_gop_start() {
+        let mut ring = FrameRing::new();
+        let cra = MediaFrame::new(
+            "s1".into(),
+            0,
+            1,
+            Bytes::from(vec![0, 0, 0, 1, 0x2A, 0x01, 0xAF]),
+            false,
+            CodecType::H265,
+        );
+        let trail = MediaFrame::new(
+            "s1".into(),
+            0,
+            2,
+            Bytes::from(vec![0, 0, 0, 1, 0x02, 0x01, 0xD0]),
+            false,
+            CodecType::H265,
+        );
+        assert!(is_video_keyframe(&cra));
+        assert!(!is_video_keyframe(&trail));
+        ring.push(cra);
+        ring.push(trail);
+        assert_eq!(ring.latest_idr_seq(), Some(0));
+    }
 }
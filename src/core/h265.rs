@@ -0,0 +1,289 @@
+//! H.265/HEVC bitstream helpers: NAL header types, IRAP detection, hvcC records.
+
+use crate::server::webrtc::h264_util::{
+    ensure_annex_b, iter_annex_b_nal_ranges, nal_to_rbsp, BitReader,
+};
+
+pub const NAL_VPS: u8 = 32;
+pub const NAL_SPS: u8 = 33;
+pub const NAL_PPS: u8 = 34;
+pub const NAL_AUD: u8 = 35;
+pub const NAL_SEI_PREFIX: u8 = 39;
+pub const NAL_SEI_SUFFIX: u8 = 40;
+
+/// NAL type from the first byte of the 2-byte HEVC NAL header.
+pub fn nal_type(header: u8) -> u8 {
+    (header >> 1) & 0x3F
+}
+
+/// BLA/IDR/CRA (16..=21) start a decodable GOP.
+pub fn is_irap_type(nal_type: u8) -> bool {
+    (16..=21).contains(&nal_type)
+}
+
+fn nal_types(data: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    iter_annex_b_nal_ranges(data)
+        .into_iter()
+        .filter_map(move |(start, _)| data.get(start).map(|b| nal_type(*b)))
+}
+
+pub fn contains_irap_nalu(data: &[u8]) -> bool {
+    nal_types(data).any(is_irap_type)
+}
+
+/// True if buffer contains a VCL NAL (slice segment types 0..=31).
+pub fn contains_vcl_nalu(data: &[u8]) -> bool {
+    nal_types(data).any(|t| t < NAL_VPS)
+}
+
+pub fn contains_parameter_set_nalu(data: &[u8]) -> bool {
+    nal_types(data).any(|t| matches!(t, NAL_VPS | NAL_SPS | NAL_PPS))
+}
+
+/// True when the buffer only carries VPS/SPS/PPS/AUD/SEI (not video slices).
+pub fn is_parameter_set_only(data: &[u8]) -> bool {
+    let mut types = nal_types(data).peekable();
+    types.peek().is_some()
+        && types.all(|t| {
+            matches!(
+                t,
+                NAL_VPS | NAL_SPS | NAL_PPS | NAL_AUD | NAL_SEI_PREFIX | NAL_SEI_SUFFIX
+            )
+        })
+}
+
+/// Optional (VPS, SPS, PPS) NALUs found in a buffer.
+pub type ParameterSets = (Option<Vec<u8>>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Extract raw VPS/SPS/PPS NALUs (without start codes) from Annex B.
+pub fn extract_parameter_sets(data: &[u8]) -> ParameterSets {
+    let mut vps = None;
+    let mut sps = None;
+    let mut pps = None;
+    for (start, end) in iter_annex_b_nal_ranges(data) {
+        match data.get(start).map(|b| nal_type(*b)) {
+            Some(NAL_VPS) => vps = Some(data[start..end].to_vec()),
+            Some(NAL_SPS) => sps = Some(data[start..end].to_vec()),
+            Some(NAL_PPS) => pps = Some(data[start..end].to_vec()),
+            _ => {}
+        }
+    }
+    (vps, sps, pps)
+}
+
+/// Build Annex B with VPS + SPS + PPS + slices for reliable decoder startup.
+pub fn annex_b_with_config(vps: &[u8], sps: &[u8], pps: &[u8], access_unit: &[u8]) -> Vec<u8> {
+    let au = ensure_annex_b(access_unit);
+    if contains_parameter_set_nalu(&au) {
+        return au;
+    }
+    let mut out = Vec::new();
+    for nalu in [vps, sps, pps] {
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(nalu);
+    }
+    out.extend_from_slice(&au);
+    out
+}
+
+/// (sps_max_sub_layers_minus1, temporal_id_nesting, general profile_tier_level 12 bytes)
+fn sps_general_profile(sps: &[u8]) -> Option<(u8, bool, [u8; 12])> {
+    let rbsp = nal_to_rbsp(sps);
+    if rbsp.len() < 15 {
+        return None;
+    }
+    let max_sub_layers_minus1 = (rbsp[2] >> 1) & 0x07;
+    let temporal_id_nesting = rbsp[2] & 0x01 != 0;
+    let mut ptl = [0u8; 12];
+    ptl.copy_from_slice(&rbsp[3..15]);
+    Some((max_sub_layers_minus1, temporal_id_nesting, ptl))
+}
+
+/// Picture size (width, height) after conformance-window cropping, from an SPS NALU.
+pub fn sps_dimensions(sps: &[u8]) -> Option<(u32, u32)> {
+    let rbsp = nal_to_rbsp(sps);
+    let mut r = BitReader::new(rbsp.get(2..)?);
+    r.skip_bits(4)?; // sps_video_parameter_set_id
+    let max_sub_layers_minus1 = r.read_bits(3)?;
+    r.skip_bits(1)?; // sps_temporal_id_nesting_flag
+    r.skip_bits(96)?; // general profile_tier_level
+    let mut sub_layer_flags = Vec::new();
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layer_flags.push((r.read_bit()?, r.read_bit()?));
+    }
+    if max_sub_layers_minus1 > 0 {
+        r.skip_bits(2 * (8 - max_sub_layers_minus1 as usize))?;
+    }
+    for (profile_present, level_present) in sub_layer_flags {
+        if profile_present == 1 {
+            r.skip_bits(88)?;
+        }
+        if level_present == 1 {
+            r.skip_bits(8)?;
+        }
+    }
+    r.read_ue()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = r.read_ue()?;
+    if chroma_format_idc == 3 {
+        r.read_bit()?; // separate_colour_plane_flag
+    }
+    let width = r.read_ue()?;
+    let height = r.read_ue()?;
+    if r.read_bit()? == 0 {
+        return Some((width, height));
+    }
+    let (left, right, top, bottom) = (r.read_ue()?, r.read_ue()?, r.read_ue()?, r.read_ue()?);
+    let (sub_width, sub_height) = match chroma_format_idc {
+        1 => (2, 2),
+        2 => (2, 1),
+        _ => (1, 1),
+    };
+    Some((
+        width.checked_sub((left + right) * sub_width)?,
+        height.checked_sub((top + bottom) * sub_height)?,
+    ))
+}
+
+/// RFC 6381 codec string (`hvc1.1.6.L93.B0`) from an SPS, for manifests.
+pub fn codec_string(sps: &[u8]) -> String {
+    let Some((_, _, ptl)) = sps_general_profile(sps) else {
+        return "hvc1.1.6.L93.B0".to_string();
+    };
+    let profile_space = ["", "A", "B", "C"][(ptl[0] >> 6) as usize];
+    let tier = if ptl[0] & 0x20 != 0 { 'H' } else { 'L' };
+    let profile_idc = ptl[0] & 0x1F;
+    let compat = u32::from_be_bytes([ptl[1], ptl[2], ptl[3], ptl[4]]).reverse_bits();
+    let mut s = format!(
+        "hvc1.{}{}.{:X}.{}{}",
+        profile_space, profile_idc, compat, tier, ptl[11]
+    );
+    let constraints = &ptl[5..11];
+    let last = constraints
+        .iter()
+        .rposition(|b| *b != 0)
+        .map(|i| i + 1)
+        .unwrap_or(1);
+    for b in &constraints[..last] {
+        s.push_str(&format!(".{:X}", b));
+    }
+    s
+}
+
+/// HEVCDecoderConfigurationRecord (ISO/IEC 14496-15) with 4-byte NALU lengths.
+pub fn build_hvcc(vps: &[u8], sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let (max_sub_layers_minus1, nesting, ptl) = sps_general_profile(sps).unwrap_or((
+        0,
+        true,
+        [0x01, 0x60, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 93],
+    ));
+    let mut out = Vec::with_capacity(23 + vps.len() + sps.len() + pps.len() + 15);
+    out.push(0x01);
+    out.extend_from_slice(&ptl);
+    out.extend_from_slice(&[0xF0, 0x00]); // min_spatial_segmentation_idc
+    out.push(0xFC); // parallelismType
+    out.push(0xFD); // chromaFormat 4:2:0
+    out.push(0xF8); // bitDepthLumaMinus8
+    out.push(0xF8); // bitDepthChromaMinus8
+    out.extend_from_slice(&[0x00, 0x00]); // avgFrameRate
+    out.push(((max_sub_layers_minus1 + 1) << 3) | ((nesting as u8) << 2) | 0x03);
+    out.push(3);
+    for (ty, nalu) in [(NAL_VPS, vps), (NAL_SPS, sps), (NAL_PPS, pps)] {
+        out.push(0x80 | ty);
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(nalu.len() as u16).to_be_bytes());
+        out.extend_from_slice(nalu);
+    }
+    out
+}
+
+/// Parse VPS/SPS/PPS out of an hvcC record.
+pub fn parse_hvcc(record: &[u8]) -> Option<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    if record.len() < 23 {
+        return None;
+    }
+    let mut vps = None;
+    let mut sps = None;
+    let mut pps = None;
+    let num_arrays = record[22];
+    let mut i = 23;
+    for _ in 0..num_arrays {
+        if i + 3 > record.len() {
+            return None;
+        }
+        let ty = record[i] & 0x3F;
+        let count = u16::from_be_bytes([record[i + 1], record[i + 2]]);
+        i += 3;
+        for _ in 0..count {
+            if i + 2 > record.len() {
+                return None;
+            }
+            let len = u16::from_be_bytes([record[i], record[i + 1]]) as usize;
+            i += 2;
+            let nalu = record.get(i..i + len)?.to_vec();
+            i += len;
+            match ty {
+                NAL_VPS if vps.is_none() => vps = Some(nalu),
+                NAL_SPS if sps.is_none() => sps = Some(nalu),
+                NAL_PPS if pps.is_none() => pps = Some(nalu),
+                _ => {}
+            }
+        }
+    }
+    Some((vps?, sps?, pps?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1080p Main profile parameter sets captured from an IP camera.
+    const VPS: &[u8] = &[
+        0x40, 0x01, 0x0C, 0x01, 0xFF, 0xFF, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x03, 0x00, 0x7B, 0x95, 0x98, 0x09,
+    ];
+    const SPS: &[u8] = &[
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x7B, 0xA0, 0x03, 0xC0, 0x80, 0x10, 0xE5, 0x96, 0x56, 0x69, 0x24, 0xCA, 0xE0,
+    ];
+    const PPS: &[u8] = &[0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40];
+
+    #[test]
+    fn detects_irap_and_parameter_sets() {
+        let idr = [0, 0, 0, 1, 0x26, 0x01, 0xAF];
+        let trail = [0, 0, 0, 1, 0x02, 0x01, 0xD0];
+        assert!(contains_irap_nalu(&idr));
+        assert!(!contains_irap_nalu(&trail));
+        assert!(contains_vcl_nalu(&trail));
+
+        let config = annex_b_with_config(VPS, SPS, PPS, &idr);
+        assert!(is_parameter_set_only(&config[..config.len() - idr.len()]));
+        let (vps, sps, pps) = extract_parameter_sets(&config);
+        assert_eq!(vps.as_deref(), Some(VPS));
+        assert_eq!(sps.as_deref(), Some(SPS));
+        assert_eq!(pps.as_deref(), Some(PPS));
+    }
+
+    #[test]
+    fn hvcc_round_trips_parameter_sets() {
+        let record = build_hvcc(VPS, SPS, PPS);
+        assert_eq!(record[0], 1);
+        assert_eq!(record[1], 0x01, "Main profile, main tier");
+        assert_eq!(record[12], 0x7B, "level 4.1");
+        assert_eq!(record[21] & 0x03, 3);
+        let (vps, sps, pps) = parse_hvcc(&record).unwrap();
+        assert_eq!(
+            (vps.as_slice(), sps.as_slice(), pps.as_slice()),
+            (VPS, SPS, PPS)
+        );
+    }
+
+    #[test]
+    fn sps_dimensions_reads_conformance_window() {
+        assert_eq!(sps_dimensions(SPS), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn codec_string_from_sps() {
+        assert_eq!(codec_string(SPS), "hvc1.1.6.L123.90");
+    }
+}
@@ -5,7 +5,7 @@ use std::time::{Duration, Instant};
 use tracing::info;
 
 use super::dispatch::{last_playable_idr_seq, DispatchError, DispatchReader};
-use super::{CodecType, MediaFrame, StreamManager};
+use super::{h265, CodecType, MediaFrame, StreamManager};
 use crate::server::webrtc::h264_util::{
     contains_idr_nalu, contains_sps_or_pps_nalu, ensure_annex_b, is_parameter_set_only,
 };
@@ -14,40 +14,73 @@ use crate::server::webrtc::{annex_b_with_config, request_publisher_keyframe};
 const LIVE_IDR_WAIT: Duration = Duration::from_millis(800);
 
 pub fn is_playable_video_frame(frame: &MediaFrame) -> bool {
-    if !matches!(frame.codec, CodecType::H264 | CodecType::H265) {
+    if matches!(frame.codec, CodecType::AV1 | CodecType::VP9) {
+        return !frame.data.is_empty();
+    }
+    if !frame.codec.is_nal_video() {
         return false;
     }
     let data = ensure_annex_b(&frame.data);
-    !data.is_empty() && !is_parameter_set_only(&data)
+    if data.is_empty() {
+        return false;
+    }
+    match frame.codec {
+        CodecType::H265 => !h265::is_parameter_set_only(&data),
+        _ => !is_parameter_set_only(&data),
+    }
 }
 
 pub fn is_idr_frame(frame: &MediaFrame) -> bool {
-    frame.is_keyframe || contains_idr_nalu(&ensure_annex_b(&frame.data))
+    if frame.is_keyframe {
+        return true;
+    }
+    if !frame.codec.is_nal_video() {
+        return false;
+    }
+    let data = ensure_annex_b(&frame.data);
+    match frame.codec {
+        CodecType::H265 => h265::contains_irap_nalu(&data),
+        _ => contains_idr_nalu(&data),
+    }
 }
 
-pub fn prepend_h264_config(
+/// Prepend SPS/PPS (plus VPS for HEVC) to keyframes so decoders can start on them.
+pub fn prepend_video_config(
     manager: &StreamManager,
     stream_id: &str,
     frame: &MediaFrame,
 ) -> Vec<u8> {
+    // AV1/VP9 decoders start from the sequence header record, not in-band config
+    if !frame.codec.is_nal_video() {
+        return frame.data.to_vec();
+    }
     let au = ensure_annex_b(&frame.data);
-    if !(frame.is_keyframe || is_idr_frame(frame) || contains_sps_or_pps_nalu(&au)) {
+    let has_config = match frame.codec {
+        CodecType::H265 => h265::contains_parameter_set_nalu(&au),
+        _ => contains_sps_or_pps_nalu(&au),
+    };
+    if !(frame.is_keyframe || is_idr_frame(frame) || has_config) {
         return au;
     }
     if let Some(stream) = manager.get_stream(&stream_id.to_string()) {
-        if let (Some(sps), Some(pps)) = (&stream.sps, &stream.pps) {
-            return annex_b_with_config(sps, pps, &au);
+        match (frame.codec, &stream.vps, &stream.sps, &stream.pps) {
+            (CodecType::H265, Some(vps), Some(sps), Some(pps)) => {
+                return h265::annex_b_with_config(vps, sps, pps, &au);
+            }
+            (CodecType::H265, ..) => {}
+            (_, _, Some(sps), Some(pps)) => return annex_b_with_config(sps, pps, &au),
+            _ => {}
         }
     }
     au
 }
 
-pub fn prepare_h264_play_frame(
+pub fn prepare_video_play_frame(
     manager: &StreamManager,
     stream_id: &str,
     frame: MediaFrame,
 ) -> MediaFrame {
-    let annex = prepend_h264_config(manager, stream_id, &frame);
+    let annex = prepend_video_config(manager, stream_id, &frame);
     MediaFrame::new(
         stream_id.to_string(),
         frame.track_id,
@@ -92,7 +125,7 @@ pub async fn prime_live_play(
                         idr.timestamp,
                         idr.data.len()
                     );
-                    return Some(prepare_h264_play_frame(manager, stream_id, idr.clone()));
+                    return Some(prepare_video_play_frame(manager, stream_id, idr.clone()));
                 }
             }
             Ok(Ok(_)) => continue,
@@ -117,7 +150,7 @@ pub async fn prime_live_play(
                 idr_seq,
                 latest
             );
-            return Some(prepare_h264_play_frame(manager, stream_id, idr));
+            return Some(prepare_video_play_frame(manager, stream_id, idr));
         }
     }
 
@@ -1,25 +1,28 @@
 mod config;
 pub mod dispatch;
 mod frame_ring;
+pub mod h265;
 pub mod live_play;
 pub mod protocol;
 mod pusher;
 mod stream_hub;
 mod stream_manager;
+mod supervisor;
 mod tester;
 mod timestamp;
 
 pub use dispatch::{coalesce_flv_batch, DispatchError, DispatchPolicy, DispatchReader};
 pub use frame_ring::{is_playable_video, is_video_keyframe, FrameRing, SnapMode};
 pub use stream_hub::StreamHub;
-pub use stream_manager::StreamManager;
+pub use stream_manager::{StreamLifecycleEvent, StreamManager};
 
 use bytes::Bytes;
 use std::collections::HashMap;
 
 pub use config::{
-    AnalysisConfig, Config, HttpConfig, RecordConfig, RtmpConfig, RtspConfig, ServerConfig,
-    SnapshotConfig, StorageConfig, WebrtcConfig, DEFAULT_HLS_DIR, DEFAULT_HLS_OUTPUT_DIR,
+    AnalysisConfig, Config, HttpConfig, RecordConfig, RecordRuleConfig, RtmpConfig, RtspConfig,
+    ServerConfig, SnapshotConfig, StorageConfig, StreamRetentionConfig, WebrtcConfig,
+    DEFAULT_EXPORT_DIR, DEFAULT_EXPORT_OUTPUT_DIR, DEFAULT_HLS_DIR, DEFAULT_HLS_OUTPUT_DIR,
     DEFAULT_RECORD_DIR, DEFAULT_RECORD_OUTPUT_DIR, DEFAULT_SNAPSHOT_DIR,
     DEFAULT_SNAPSHOT_OUTPUT_DIR, DEFAULT_STORAGE_BASE_DIR,
 };
@@ -28,6 +31,7 @@ pub use live_play::{
 };
 pub use protocol::{ProtocolInfo, ProtocolRegistry, ProtocolType, StreamSink};
 pub use pusher::*;
+pub use supervisor::{job_runner, JobKind, JobSupervisor, ReconnectPolicy};
 pub use tester::StreamTester;
 pub use timestamp::{
     flv_timestamp_ms, media_frame_timestamp_delta_ms, media_timestamp_delta_ms,
@@ -48,6 +52,7 @@ pub struct Stream {
     pub protocol: StreamProtocol,
     pub pull_url: Option<String>,
     // Codec parameters extracted from RTP stream
+    pub vps: Option<Vec<u8>>,
     pub sps: Option<Vec<u8>>,
     pub pps: Option<Vec<u8>>,
 }
@@ -56,6 +61,23 @@ impl Stream {
     pub fn add_track(&mut self, track: Track) {
         self.tracks.push(track);
     }
+
+    /// Codec of the first video track (H264 when the stream has none yet).
+    pub fn video_codec(&self) -> CodecType {
+        self.tracks
+            .iter()
+            .map(|t| t.codec)
+            .find(|c| c.is_video())
+            .unwrap_or(CodecType::H264)
+    }
+
+    /// AV1/VP9 decoder configuration record of the video track, once announced.
+    pub fn video_config_record(&self) -> Option<Vec<u8>> {
+        self.tracks
+            .iter()
+            .find(|t| t.codec.is_video())
+            .and_then(|t| t.video_config_record())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -145,6 +167,7 @@ pub enum StreamProtocol {
     RTMP,
     WebRTC,
     HTTP,
+    HLS,
     Unknown,
 }
 
@@ -285,8 +308,47 @@ impl Track {
         self.extra_params.insert(key.to_string(), value.to_string());
         self
     }
+
+    /// AAC AudioSpecificConfig, kept as the RFC 3640 fmtp `config=` hex string.
+    pub fn audio_specific_config(&self) -> Option<Vec<u8>> {
+        self.hex_param(AUDIO_CONFIG_PARAM)
+            .filter(|asc| asc.len() >= 2)
+    }
+
+    pub fn set_audio_specific_config(&mut self, asc: &[u8]) {
+        self.set_hex_param(AUDIO_CONFIG_PARAM, asc);
+    }
+
+    /// AV1/VP9 decoder configuration record (`av1C` / `vpcC`) from the publisher.
+    pub fn video_config_record(&self) -> Option<Vec<u8>> {
+        self.hex_param(VIDEO_CONFIG_PARAM)
+            .filter(|record| !record.is_empty())
+    }
+
+    pub fn set_video_config_record(&mut self, record: &[u8]) {
+        self.set_hex_param(VIDEO_CONFIG_PARAM, record);
+    }
+
+    fn hex_param(&self, key: &str) -> Option<Vec<u8>> {
+        let hex = self.extra_params.get(key)?;
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+
+    fn set_hex_param(&mut self, key: &str, data: &[u8]) {
+        let hex = data.iter().map(|b| format!("{:02x}", b)).collect();
+        self.extra_params.insert(key.to_string(), hex);
+    }
 }
 
+/// Track extra param holding the AAC AudioSpecificConfig (hex, as in SDP fmtp).
+pub const AUDIO_CONFIG_PARAM: &str = "config";
+
+/// Track extra param holding the AV1/VP9 decoder configuration record (hex).
+pub const VIDEO_CONFIG_PARAM: &str = "video-config";
+
 /// Default H264 + AAC tracks for push streams without explicit SDP (e.g. RTMP publish).
 pub fn default_live_tracks() -> Vec<Track> {
     vec![
@@ -299,6 +361,8 @@ pub fn default_live_tracks() -> Vec<Track> {
 pub enum CodecType {
     H264,
     H265,
+    AV1,
+    VP9,
     AAC,
     Opus,
     G711,
@@ -306,6 +370,18 @@ pub enum CodecType {
 }
 
 impl CodecType {
+    pub fn is_video(&self) -> bool {
+        matches!(
+            self,
+            CodecType::H264 | CodecType::H265 | CodecType::AV1 | CodecType::VP9
+        )
+    }
+
+    /// Video carried as H.264/H.265 NAL units (Annex B in the hub).
+    pub fn is_nal_video(&self) -> bool {
+        matches!(self, CodecType::H264 | CodecType::H265)
+    }
+
     pub fn from_pt(pt: u8) -> Self {
         match pt {
             0 => CodecType::G711,
@@ -322,6 +398,8 @@ impl CodecType {
         match self {
             CodecType::H264 => "video/H264",
             CodecType::H265 => "video/H265",
+            CodecType::AV1 => "video/AV1",
+            CodecType::VP9 => "video/VP9",
             CodecType::AAC => "audio/mp4a-latm",
             CodecType::Opus => "audio/opus",
             CodecType::G711 => "audio、PCMU",
@@ -31,6 +31,7 @@ impl StreamHub {
             source: StreamSourceMode::Push,
             protocol: StreamProtocol::Unknown,
             pull_url: None,
+            vps: None,
             sps: None,
             pps: None,
         })
@@ -220,6 +221,7 @@ mod tests {
             source: StreamSourceMode::Pull,
             protocol: StreamProtocol::RTSP,
             pull_url: Some("rtsp://example/live".to_string()),
+            vps: None,
             sps: Some(vec![0x67, 0x42]),
             pps: Some(vec![0x68, 0xce]),
         }
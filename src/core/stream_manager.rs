@@ -5,26 +5,46 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use parking_lot::RwLock;
+use tokio::sync::broadcast;
 use tracing::{debug, info, warn};
 
 use super::dispatch::{DispatchPolicy, DispatchReader};
+use super::h265;
 use super::stream_hub::StreamHub;
 use super::{
     CodecType, MediaFrame, PlaybackStatus, ReceiverId, ReceiverStatus, Stream, StreamId,
     StreamProtocol, StreamReceiver, StreamSinkMode, StreamSourceMode, StreamStatus, Track,
 };
 
+const LIFECYCLE_CHANNEL_CAPACITY: usize = 256;
+
+/// Publisher transitions, for services that follow streams (e.g. auto-record).
+#[derive(Debug, Clone)]
+pub enum StreamLifecycleEvent {
+    /// Stream entered `Publishing`; carries the stream snapshot at that point.
+    Publishing(Stream),
+    /// Stream left `Publishing` (unpublished, stopped, error or removed).
+    Unpublished(StreamId),
+}
+
 pub struct StreamManager {
     hubs: RwLock<HashMap<StreamId, Arc<StreamHub>>>,
+    lifecycle_tx: broadcast::Sender<StreamLifecycleEvent>,
 }
 
 impl StreamManager {
     pub fn new() -> Self {
+        let (lifecycle_tx, _) = broadcast::channel(LIFECYCLE_CHANNEL_CAPACITY);
         Self {
             hubs: RwLock::new(HashMap::new()),
+            lifecycle_tx,
         }
     }
 
+    pub fn subscribe_lifecycle(&self) -> broadcast::Receiver<StreamLifecycleEvent> {
+        self.lifecycle_tx.subscribe()
+    }
+
     pub fn create_stream(
         &self,
         stream_id: &str,
@@ -48,6 +68,7 @@ impl StreamManager {
             source,
             protocol,
             pull_url,
+            vps: None,
             sps: None,
             pps: None,
         };
@@ -118,6 +139,125 @@ impl StreamManager {
         }
     }
 
+    pub fn set_stream_hevc_config(
+        &self,
+        stream_id: &str,
+        vps: Vec<u8>,
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+    ) {
+        if let Some(hub) = self.get_hub(stream_id) {
+            hub.update_stream(|stream| {
+                if stream.vps.is_none() {
+                    info!(
+                        "[Core] Setting VPS ({}), SPS ({}) and PPS ({}) for stream {}",
+                        vps.len(),
+                        sps.len(),
+                        pps.len(),
+                        stream_id
+                    );
+                }
+                stream.vps = Some(vps);
+                stream.sps = Some(sps);
+                stream.pps = Some(pps);
+            });
+        }
+    }
+
+    /// Merge VPS/SPS/PPS from a single HEVC NALU into stream codec config.
+    pub fn merge_stream_hevc_nalu_config(&self, stream_id: &str, nalu: &[u8]) {
+        if nalu.len() < 2 {
+            return;
+        }
+        let nal_type = h265::nal_type(nalu[0]);
+        if !matches!(nal_type, h265::NAL_VPS | h265::NAL_SPS | h265::NAL_PPS) {
+            return;
+        }
+        if let Some(hub) = self.get_hub(stream_id) {
+            hub.update_stream(|stream| {
+                match nal_type {
+                    h265::NAL_VPS if stream.vps.is_none() => stream.vps = Some(nalu.to_vec()),
+                    h265::NAL_SPS if stream.sps.is_none() => stream.sps = Some(nalu.to_vec()),
+                    h265::NAL_PPS if stream.pps.is_none() => stream.pps = Some(nalu.to_vec()),
+                    _ => {}
+                }
+                if let (Some(vps), Some(sps), Some(pps)) = (&stream.vps, &stream.sps, &stream.pps) {
+                    info!(
+                        "[Core] Stream {} HEVC config ready (vps={} sps={} pps={})",
+                        stream_id,
+                        vps.len(),
+                        sps.len(),
+                        pps.len()
+                    );
+                }
+            });
+        }
+    }
+
+    /// Record the AAC AudioSpecificConfig and its sample rate on the stream's audio track.
+    pub fn set_stream_audio_config(&self, stream_id: &str, asc: &[u8], sample_rate: u32) {
+        if let Some(hub) = self.get_hub(stream_id) {
+            hub.update_stream(|stream| {
+                let Some(track) = stream.tracks.iter_mut().find(|t| t.codec == CodecType::AAC)
+                else {
+                    debug!(
+                        "[Core] Stream {} has no AAC track for AudioSpecificConfig",
+                        stream_id
+                    );
+                    return;
+                };
+                if track.audio_specific_config().as_deref() != Some(asc) {
+                    info!(
+                        "[Core] Stream {} AAC config {:02x?} ({} Hz)",
+                        stream_id, asc, sample_rate
+                    );
+                }
+                track.set_audio_specific_config(asc);
+                track.clock_rate = sample_rate;
+            });
+        }
+    }
+
+    /// Record an AV1/VP9 decoder configuration record and switch the video track codec.
+    pub fn set_stream_video_config_record(&self, stream_id: &str, codec: CodecType, record: &[u8]) {
+        self.set_stream_video_codec(stream_id, codec);
+        if let Some(hub) = self.get_hub(stream_id) {
+            hub.update_stream(|stream| {
+                if let Some(track) = stream.tracks.iter_mut().find(|t| t.codec.is_video()) {
+                    if track.video_config_record().as_deref() != Some(record) {
+                        info!(
+                            "[Core] Stream {} {:?} config record ({} bytes)",
+                            stream_id,
+                            codec,
+                            record.len()
+                        );
+                    }
+                    track.set_video_config_record(record);
+                }
+            });
+        }
+    }
+
+    /// Switch the stream's video track codec (e.g. RTMP publisher announced HEVC).
+    pub fn set_stream_video_codec(&self, stream_id: &str, codec: CodecType) {
+        if let Some(hub) = self.get_hub(stream_id) {
+            hub.update_stream(|stream| {
+                if let Some(track) = stream.tracks.iter_mut().find(|t| t.codec.is_video()) {
+                    if track.codec != codec {
+                        info!(
+                            "[Core] Stream {} video codec {:?} -> {:?}",
+                            stream_id, track.codec, codec
+                        );
+                        track.codec = codec;
+                        if codec == CodecType::H265 && track.payload_type == 96 {
+                            track.payload_type = 98;
+                        }
+                    }
+                }
+            });
+        }
+    }
+
     pub fn ensure_stream_hub(&self, stream_id: &str) {
         if self.get_hub(stream_id).is_some() {
             debug!("[Core] StreamHub already exists for stream '{}'", stream_id);
@@ -149,7 +289,16 @@ impl StreamManager {
     }
 
     pub fn remove_stream(&self, stream_id: &StreamId) -> Option<Stream> {
-        self.hubs.write().remove(stream_id).map(|hub| hub.stream())
+        let removed = self.hubs.write().remove(stream_id).map(|hub| hub.stream());
+        if removed
+            .as_ref()
+            .is_some_and(|stream| stream.status.is_publishing())
+        {
+            let _ = self
+                .lifecycle_tx
+                .send(StreamLifecycleEvent::Unpublished(stream_id.clone()));
+        }
+        removed
     }
 
     pub fn get_stream(&self, stream_id: &StreamId) -> Option<Stream> {
@@ -163,14 +312,28 @@ impl StreamManager {
 
     pub fn set_status(&self, stream_id: &str, status: StreamStatus) -> Result<()> {
         if let Some(hub) = self.get_hub(stream_id) {
-            hub.update_stream(|stream| {
+            let was_publishing = hub.update_stream(|stream| {
                 let old_status = stream.status.clone();
                 stream.status = status.clone();
                 info!(
                     "[Core] Stream {} status changed from {:?} to {:?}",
                     stream_id, old_status, status
                 );
+                old_status.is_publishing()
             });
+            let event = match (was_publishing, &status) {
+                (false, StreamStatus::Publishing) => {
+                    Some(StreamLifecycleEvent::Publishing(hub.stream()))
+                }
+                (
+                    true,
+                    StreamStatus::Unpublished | StreamStatus::Stopped | StreamStatus::Error(_),
+                ) => Some(StreamLifecycleEvent::Unpublished(stream_id.to_string())),
+                _ => None,
+            };
+            if let Some(event) = event {
+                let _ = self.lifecycle_tx.send(event);
+            }
             Ok(())
         } else {
             Err(anyhow::anyhow!("Stream {} not found", stream_id))
@@ -427,6 +590,7 @@ impl StreamManager {
         };
         hub.reset();
         hub.update_stream(|stream| {
+            stream.vps = None;
             stream.sps = None;
             stream.pps = None;
         });
@@ -705,6 +869,41 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn lifecycle_events_fire_on_publishing_transitions() {
+        let manager = StreamManager::new();
+        let mut events = manager.subscribe_lifecycle();
+        create_test_stream(&manager, "s");
+
+        manager.set_publishing("s").unwrap();
+        manager.set_publishing("s").unwrap();
+        manager.set_unpublished("s").unwrap();
+        manager.set_unpublished("s").unwrap();
+        manager.set_publishing("s").unwrap();
+        manager.remove_stream(&"s".to_string());
+
+        match events.try_recv().unwrap() {
+            StreamLifecycleEvent::Publishing(stream) => {
+                assert_eq!(stream.id, "s");
+                assert_eq!(stream.protocol, StreamProtocol::RTSP);
+                assert!(stream.status.is_publishing());
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(
+            matches!(events.try_recv().unwrap(), StreamLifecycleEvent::Unpublished(id) if id == "s")
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            StreamLifecycleEvent::Publishing(_)
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            StreamLifecycleEvent::Unpublished(_)
+        ));
+        assert!(events.try_recv().is_err());
+    }
+
     #[test]
     fn remove_stream_returns_stream_snapshot_and_removes_hub() {
         let manager = StreamManager::new();
@@ -731,6 +930,28 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn merge_hevc_nalu_config_collects_vps_sps_pps() {
+        let manager = StreamManager::new();
+        create_test_stream(&manager, "s");
+        manager.set_stream_tracks("s", crate::core::default_live_tracks());
+
+        manager.merge_stream_hevc_nalu_config("s", &[0x40, 0x01, 0x0C]);
+        manager.merge_stream_hevc_nalu_config("s", &[0x42, 0x01, 0x01]);
+        manager.merge_stream_hevc_nalu_config("s", &[0x26, 0x01, 0xAF]);
+        manager.merge_stream_hevc_nalu_config("s", &[0x44, 0x01, 0xC1]);
+        manager.set_stream_video_codec("s", CodecType::H265);
+
+        let stream = manager.get_stream(&"s".to_string()).unwrap();
+        assert_eq!(stream.vps.as_deref(), Some(&[0x40, 0x01, 0x0C][..]));
+        assert_eq!(stream.sps.as_deref(), Some(&[0x42, 0x01, 0x01][..]));
+        assert_eq!(stream.pps.as_deref(), Some(&[0x44, 0x01, 0xC1][..]));
+        assert_eq!(stream.video_codec(), CodecType::H265);
+
+        manager.reset_stream_media("s");
+        assert!(manager.get_stream(&"s".to_string()).unwrap().vps.is_none());
+    }
+
     #[test]
     fn reset_stream_media_clears_ring_and_codec_config() {
         let manager = StreamManager::new();
@@ -0,0 +1,452 @@
+//! Supervised pull/push jobs: each job re-runs its connect routine with
+//! exponential backoff until it is deleted through the API.
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::AbortHandle;
+use tracing::{info, warn};
+
+use super::{StreamLifecycleEvent, StreamManager, StreamProtocol};
+
+pub type JobId = String;
+
+/// One connection attempt; resolves when the remote session ends.
+pub type JobRunner = Arc<dyn Fn(JobContext) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// Wrap an async connect routine as a `JobRunner`.
+pub fn job_runner<F, Fut>(run: F) -> JobRunner
+where
+    F: Fn(JobContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    Arc::new(move |ctx| Box::pin(run(ctx)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Pull,
+    Push,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Pull => "pull",
+            JobKind::Push => "push",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobHealth {
+    /// An attempt is in flight but the remote session is not confirmed yet.
+    Connecting,
+    Running,
+    /// The last attempt ended; waiting out the backoff delay.
+    Reconnecting,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// A run lasting at least this long resets the backoff.
+    pub stable_after: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            stable_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay after `failures` consecutive short-lived attempts.
+    pub fn delay(&self, failures: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(1u32 << failures.min(16))
+            .min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub stream_id: String,
+    pub protocol: String,
+    pub remote_url: String,
+    pub health: JobHealth,
+    pub attempts: u64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub created_at_ms: u64,
+    pub running_since_ms: Option<u64>,
+    pub retry_in_ms: Option<u64>,
+}
+
+struct Job {
+    info: Mutex<JobInfo>,
+    runner: JobRunner,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl Job {
+    fn kind(&self) -> JobKind {
+        self.info.lock().kind
+    }
+}
+
+/// Handle passed to each attempt so it can report progress back to the supervisor.
+#[derive(Clone)]
+pub struct JobContext {
+    job: Arc<Job>,
+}
+
+impl JobContext {
+    /// Id of the job this attempt belongs to.
+    pub fn job_id(&self) -> JobId {
+        self.job.info.lock().id.clone()
+    }
+
+    /// Mark the current attempt as connected (e.g. once the remote accepted publish).
+    pub fn set_running(&self) {
+        set_health(&self.job, JobHealth::Running);
+    }
+}
+
+pub struct JobSupervisor {
+    stream_manager: Arc<StreamManager>,
+    policy: ReconnectPolicy,
+    jobs: RwLock<HashMap<JobId, Arc<Job>>>,
+}
+
+impl JobSupervisor {
+    pub fn new(stream_manager: Arc<StreamManager>, policy: ReconnectPolicy) -> Self {
+        Self {
+            stream_manager,
+            policy,
+            jobs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Register a job and start its first attempt.
+    pub async fn spawn(
+        &self,
+        kind: JobKind,
+        stream_id: &str,
+        protocol: StreamProtocol,
+        remote_url: &str,
+        runner: JobRunner,
+    ) -> JobInfo {
+        let id = format!("{}_{}", kind.as_str(), uuid::Uuid::new_v4());
+        let job = Arc::new(Job {
+            info: Mutex::new(JobInfo {
+                id: id.clone(),
+                kind,
+                stream_id: stream_id.to_string(),
+                protocol: format!("{:?}", protocol),
+                remote_url: remote_url.to_string(),
+                health: JobHealth::Connecting,
+                attempts: 0,
+                consecutive_failures: 0,
+                last_error: None,
+                created_at_ms: now_ms(),
+                running_since_ms: None,
+                retry_in_ms: None,
+            }),
+            runner,
+            task: Mutex::new(None),
+        });
+        info!(
+            "[Supervisor] Start {} job {} stream='{}' url={}",
+            kind.as_str(),
+            id,
+            stream_id,
+            remote_url
+        );
+        self.jobs.write().insert(id, Arc::clone(&job));
+        self.launch(&job);
+        let info = job.info.lock().clone();
+        info
+    }
+
+    pub fn list(&self, kind: JobKind) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self
+            .jobs
+            .read()
+            .values()
+            .filter(|job| job.kind() == kind)
+            .map(|job| job.info.lock().clone())
+            .collect();
+        jobs.sort_by_key(|job| job.created_at_ms);
+        jobs
+    }
+
+    pub fn get(&self, kind: JobKind, id: &str) -> Option<JobInfo> {
+        self.find(kind, id).map(|job| job.info.lock().clone())
+    }
+
+    /// Abort the job and forget it. A stopped pull leaves its local stream `Stopped`.
+    pub async fn stop(&self, kind: JobKind, id: &str) -> Option<JobInfo> {
+        self.find(kind, id)?;
+        let job = self.jobs.write().remove(id)?;
+        if let Some(task) = job.task.lock().take() {
+            task.abort();
+        }
+        set_health(&job, JobHealth::Stopped);
+        let info = job.info.lock().clone();
+        if kind == JobKind::Pull {
+            let _ = self.stream_manager.set_stopped(&info.stream_id);
+        }
+        info!("[Supervisor] Stopped {} job {}", kind.as_str(), id);
+        Some(info)
+    }
+
+    /// Drop the current attempt (or pending backoff) and reconnect immediately.
+    pub fn restart(&self, kind: JobKind, id: &str) -> Option<JobInfo> {
+        let job = self.find(kind, id)?;
+        job.info.lock().consecutive_failures = 0;
+        info!("[Supervisor] Restart {} job {}", kind.as_str(), id);
+        self.launch(&job);
+        let info = job.info.lock().clone();
+        Some(info)
+    }
+
+    fn find(&self, kind: JobKind, id: &str) -> Option<Arc<Job>> {
+        self.jobs
+            .read()
+            .get(id)
+            .filter(|job| job.kind() == kind)
+            .cloned()
+    }
+
+    fn launch(&self, job: &Arc<Job>) {
+        let task = tokio::spawn(run_job(
+            Arc::clone(job),
+            Arc::clone(&self.stream_manager),
+            self.policy,
+        ));
+        if let Some(previous) = job.task.lock().replace(task.abort_handle()) {
+            previous.abort();
+        }
+    }
+}
+
+async fn run_job(job: Arc<Job>, stream_manager: Arc<StreamManager>, policy: ReconnectPolicy) {
+    let (id, kind, stream_id) = {
+        let info = job.info.lock();
+        (info.id.clone(), info.kind, info.stream_id.clone())
+    };
+    let ctx = JobContext {
+        job: Arc::clone(&job),
+    };
+
+    loop {
+        job.info.lock().attempts += 1;
+        set_health(&job, JobHealth::Connecting);
+        let started_at = Instant::now();
+
+        // Pulls have no explicit hook: the local stream going live marks them running.
+        let mut events = stream_manager.subscribe_lifecycle();
+        let mut attempt = (job.runner)(ctx.clone());
+        let result = loop {
+            tokio::select! {
+                result = &mut attempt => break result,
+                event = events.recv() => match event {
+                    Ok(StreamLifecycleEvent::Publishing(stream))
+                        if kind == JobKind::Pull && stream.id == stream_id =>
+                    {
+                        ctx.set_running();
+                    }
+                    Err(RecvError::Closed) => break attempt.await,
+                    _ => {}
+                },
+            }
+        };
+
+        let error = match result {
+            Ok(()) => "remote session ended".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let delay = {
+            let mut info = job.info.lock();
+            if started_at.elapsed() >= policy.stable_after {
+                info.consecutive_failures = 0;
+            }
+            let delay = policy.delay(info.consecutive_failures);
+            info.consecutive_failures += 1;
+            info.last_error = Some(error.clone());
+            info.retry_in_ms = Some(delay.as_millis() as u64);
+            delay
+        };
+        warn!(
+            "[Supervisor] {} job {} stream='{}' ended: {} (retry in {}ms)",
+            kind.as_str(),
+            id,
+            stream_id,
+            error,
+            delay.as_millis()
+        );
+        set_health(&job, JobHealth::Reconnecting);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn set_health(job: &Job, health: JobHealth) {
+    let mut info = job.info.lock();
+    if info.health == health {
+        return;
+    }
+    info.health = health;
+    info.running_since_ms = (health == JobHealth::Running).then(now_ms);
+    if health != JobHealth::Reconnecting {
+        info.retry_in_ms = None;
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::StreamSourceMode;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn supervisor() -> (JobSupervisor, Arc<StreamManager>) {
+        let manager = Arc::new(StreamManager::new());
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(40),
+            stable_after: Duration::from_secs(30),
+        };
+        let supervisor = JobSupervisor::new(manager.clone(), policy);
+        (supervisor, manager)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(4), Duration::from_secs(16));
+        assert_eq!(policy.delay(5), Duration::from_secs(30));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn failing_job_reconnects_and_records_last_error() {
+        let (supervisor, _) = supervisor();
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let runner = job_runner(move |_| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move { Err(anyhow::anyhow!("connection refused #{}", n)) }
+        });
+        let job = supervisor
+            .spawn(
+                JobKind::Pull,
+                "s",
+                StreamProtocol::RTMP,
+                "rtmp://h/a/s",
+                runner,
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+
+        let info = supervisor.get(JobKind::Pull, &job.id).unwrap();
+        assert!(calls.load(Ordering::SeqCst) >= 3);
+        assert!(info.attempts >= 3);
+        assert!(info.consecutive_failures >= 3);
+        assert!(info
+            .last_error
+            .as_deref()
+            .unwrap()
+            .starts_with("connection refused"));
+        assert!(supervisor.get(JobKind::Push, &job.id).is_none());
+    }
+
+    #[tokio::test]
+    async fn pull_job_runs_once_stream_publishes() {
+        let (supervisor, manager) = supervisor();
+        let publisher = manager.clone();
+        let runner = job_runner(move |_| {
+            let manager = publisher.clone();
+            async move {
+                manager.create_stream("s", StreamSourceMode::Pull, StreamProtocol::RTSP, None);
+                let _ = manager.set_publishing("s");
+                std::future::pending::<Result<()>>().await
+            }
+        });
+        let job = supervisor
+            .spawn(
+                JobKind::Pull,
+                "s",
+                StreamProtocol::RTSP,
+                "rtsp://h/s",
+                runner,
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let info = supervisor.get(JobKind::Pull, &job.id).unwrap();
+        assert_eq!(info.health, JobHealth::Running);
+        assert!(info.running_since_ms.is_some());
+
+        let stopped = supervisor.stop(JobKind::Pull, &job.id).await.unwrap();
+        assert_eq!(stopped.health, JobHealth::Stopped);
+        assert!(supervisor.list(JobKind::Pull).is_empty());
+    }
+
+    #[tokio::test]
+    async fn push_job_reports_running_and_restarts() {
+        let (supervisor, _) = supervisor();
+        let runner = job_runner(|ctx| async move {
+            ctx.set_running();
+            std::future::pending::<Result<()>>().await
+        });
+        let job = supervisor
+            .spawn(
+                JobKind::Push,
+                "s",
+                StreamProtocol::RTMP,
+                "rtmp://h/a/k",
+                runner,
+            )
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            supervisor.get(JobKind::Push, &job.id).unwrap().health,
+            JobHealth::Running
+        );
+
+        let restarted = supervisor.restart(JobKind::Push, &job.id).unwrap();
+        assert_eq!(restarted.consecutive_failures, 0);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(supervisor.get(JobKind::Push, &job.id).unwrap().attempts, 2);
+
+        supervisor.stop(JobKind::Push, &job.id).await.unwrap();
+        assert!(supervisor.get(JobKind::Push, &job.id).is_none());
+        assert!(supervisor.list(JobKind::Push).is_empty());
+    }
+}
@@ -14,7 +14,7 @@ use tracing_subscriber::{
     prelude::*,
 };
 
-use crate::core::{Config, StreamManager};
+use crate::core::{Config, JobSupervisor, ReconnectPolicy, RecordRuleConfig, StreamManager};
 use crate::process::analysis;
 use crate::process::record::{self, RecordFormat, RecorderManager};
 use crate::process::snapshot::{self, SnapshotManager};
@@ -45,6 +45,44 @@ fn read_config() -> Result<Config> {
     }
 }
 
+/// `[[record.rules]]` to recorder rules; a misspelled `source` or `format`
+/// fails startup instead of widening or silently changing the rule.
+fn record_rules(rules: &[RecordRuleConfig]) -> Result<Vec<record::RecordRule>> {
+    rules
+        .iter()
+        .map(|rule| {
+            let source = rule
+                .source
+                .as_deref()
+                .map(|source| {
+                    record::parse_source_mode(source).with_context(|| {
+                        format!("[[record.rules]] unknown source '{source}' (push or pull)")
+                    })
+                })
+                .transpose()?;
+            let format = rule
+                .format
+                .as_deref()
+                .map(|format| {
+                    RecordFormat::parse(format).with_context(|| {
+                        format!("[[record.rules]] unknown format '{format}' (ts or mp4)")
+                    })
+                })
+                .transpose()?;
+            Ok(record::RecordRule {
+                stream_pattern: rule.stream.clone(),
+                protocol: rule.protocol.clone(),
+                source,
+                format,
+                segment_duration: rule
+                    .segment_duration_sec
+                    .map(|secs| std::time::Duration::from_secs(secs.max(1))),
+                align_keyframe: rule.align_keyframe,
+            })
+        })
+        .collect()
+}
+
 fn parse_log_level(level_str: &str) -> LevelFilter {
     match level_str.to_lowercase().as_str() {
         "trace" => LevelFilter::TRACE,
@@ -151,6 +189,58 @@ async fn main() -> Result<()> {
     let stream_manager = Arc::new(StreamManager::new());
 
     // Initialize HLS server
+    if let Some(encryption) = config
+        .server
+        .hls
+        .as_ref()
+        .and_then(|h| h.encryption.as_ref())
+        .filter(|e| e.enabled)
+    {
+        if encryption.token_secret.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!(
+                "[server.hls.encryption] is enabled without token_secret; keys would be served to anyone"
+            );
+        }
+    }
+    // Misspelled values fail startup instead of falling back to the defaults
+    let hls_segment_format = config
+        .server
+        .hls
+        .as_ref()
+        .and_then(|h| h.segment_format.as_deref())
+        .map(|format| {
+            hls::HlsSegmentFormat::parse(format).with_context(|| {
+                format!("[server.hls] unknown segment_format '{format}' (ts or fmp4)")
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let hls_storage = config
+        .server
+        .hls
+        .as_ref()
+        .and_then(|h| h.storage.as_deref())
+        .map(|storage| {
+            hls::HlsStorage::parse(storage).with_context(|| {
+                format!("[server.hls] unknown storage '{storage}' (disk or memory)")
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let hls_encryption_method = config
+        .server
+        .hls
+        .as_ref()
+        .and_then(|h| h.encryption.as_ref())
+        .filter(|e| e.enabled)
+        .and_then(|e| e.method.as_deref())
+        .map(|method| {
+            hls::encryption::HlsEncryptionMethod::parse(method).with_context(|| {
+                format!("[server.hls.encryption] unknown method '{method}' (aes-128 or sample-aes)")
+            })
+        })
+        .transpose()?
+        .unwrap_or(hls::encryption::HlsEncryptionMethod::Aes128);
     let hls_config = config
         .server
         .hls
@@ -160,6 +250,29 @@ async fn main() -> Result<()> {
             segment_duration: h.segment_duration.unwrap_or(1.0),
             max_segments: h.max_segments.unwrap_or(1),
             output_dir: config.hls_output_dir(),
+            low_latency: h.low_latency.unwrap_or(false),
+            part_duration: h.part_duration.unwrap_or(hls::DEFAULT_PART_DURATION),
+            segment_format: hls_segment_format,
+            storage: hls_storage,
+            memory_limit_mb: h.memory_limit_mb.unwrap_or(hls::DEFAULT_MEMORY_LIMIT_MB),
+            dvr_window: h.dvr_window_sec.unwrap_or(0) as f64,
+            dvr_stream_windows: h
+                .dvr_streams
+                .iter()
+                .map(|(stream_id, secs)| (stream_id.clone(), *secs as f64))
+                .collect(),
+            encryption: h.encryption.as_ref().filter(|e| e.enabled).and_then(|e| {
+                Some(hls::encryption::HlsEncryptionConfig {
+                    method: hls_encryption_method,
+                    key_rotation_segments: e
+                        .key_rotation_segments
+                        .unwrap_or(hls::encryption::DEFAULT_KEY_ROTATION_SEGMENTS),
+                    token_secret: e.token_secret.clone()?,
+                    streams: e.streams.clone(),
+                })
+            }),
+            timed_metadata: h.timed_metadata.unwrap_or(false),
+            metadata_events: h.metadata_events.clone(),
         })
         .unwrap_or_else(|| HlsModuleConfig {
             output_dir: config.hls_output_dir(),
@@ -179,11 +292,6 @@ async fn main() -> Result<()> {
         None
     };
 
-    let rtsp_server = rtsp::RtspServer::new(
-        stream_manager.clone(),
-        config.server.rtsp.port,
-        hls_server_publish.clone(),
-    );
     let webrtc_server = webrtc::WebrtcServer::new(
         stream_manager.clone(),
         config.server.webrtc.port,
@@ -211,24 +319,68 @@ async fn main() -> Result<()> {
         None
     };
 
+    let record_rules = config
+        .record
+        .as_ref()
+        .map(|c| record_rules(&c.rules))
+        .transpose()?
+        .unwrap_or_default();
+    let record_default_format = config
+        .record
+        .as_ref()
+        .and_then(|c| c.default_format.as_deref())
+        .map(|format| {
+            RecordFormat::parse(format)
+                .with_context(|| format!("[record] unknown default_format '{format}' (ts or mp4)"))
+        })
+        .transpose()?
+        .unwrap_or(RecordFormat::Ts);
     let record_config = config
         .record
         .as_ref()
         .map(|c| record::RecordConfig {
             enabled: c.enabled,
             base_dir: config.record_output_dir(),
-            default_format: c
-                .default_format
-                .as_deref()
-                .and_then(RecordFormat::parse)
-                .unwrap_or(RecordFormat::Ts),
+            export_dir: config.export_output_dir(),
+            default_format: record_default_format,
             segment_duration: std::time::Duration::from_secs(
                 c.segment_duration_sec.unwrap_or(300).max(1),
             ),
             align_keyframe: c.align_keyframe.unwrap_or(true),
+            retention: record::RetentionConfig {
+                global: record::RetentionLimits {
+                    max_age: c
+                        .max_age_hours
+                        .map(|hours| std::time::Duration::from_secs(hours * 3600)),
+                    max_total_bytes: c.max_total_mb.map(|mb| mb * 1024 * 1024),
+                },
+                min_free_bytes: c.min_free_mb.map(|mb| mb * 1024 * 1024),
+                check_interval: std::time::Duration::from_secs(
+                    c.retention_check_interval_sec.unwrap_or(60).max(1),
+                ),
+                streams: c
+                    .stream_retention
+                    .iter()
+                    .map(|(stream_id, limits)| {
+                        (
+                            stream_id.clone(),
+                            record::RetentionLimits {
+                                max_age: limits
+                                    .max_age_hours
+                                    .map(|hours| std::time::Duration::from_secs(hours * 3600)),
+                                max_total_bytes: limits.max_total_mb.map(|mb| mb * 1024 * 1024),
+                            },
+                        )
+                    })
+                    .collect(),
+            },
+            rules: record_rules,
+            event_post_roll: std::time::Duration::from_secs(c.event_post_roll_sec.unwrap_or(10)),
+            event_triggers: c.event_triggers.clone(),
         })
         .unwrap_or_else(|| record::RecordConfig {
             base_dir: config.record_output_dir(),
+            export_dir: config.export_output_dir(),
             ..Default::default()
         });
     let recorder_manager = Arc::new(RecorderManager::new(
@@ -236,6 +388,11 @@ async fn main() -> Result<()> {
         record_config.clone(),
     ));
     let recorder_http = if record_config.enabled {
+        if let Err(err) = recorder_manager.rebuild_index().await {
+            tracing::warn!("[Record] failed to rebuild recording index: {err:?}");
+        }
+        recorder_manager.start_retention_janitor();
+        recorder_manager.start_auto_record();
         Some(recorder_manager.clone())
     } else {
         None
@@ -268,6 +425,10 @@ async fn main() -> Result<()> {
         analysis_config.clone(),
     ));
     let analysis_http = if analysis_config.enabled {
+        recorder_manager.start_event_triggers(analysis_manager.subscribe_events());
+        if hls_config.enabled {
+            hls_server.start_metadata_events(analysis_manager.subscribe_events());
+        }
         Some(analysis_manager.clone())
     } else {
         None
@@ -301,20 +462,36 @@ async fn main() -> Result<()> {
         None
     };
 
-    let http_server = http::HttpServer::new(
+    let job_supervisor = Arc::new(JobSupervisor::new(
         stream_manager.clone(),
+        ReconnectPolicy::default(),
+    ));
+
+    let rtsp_server = rtsp::RtspServer::new(
+        stream_manager.clone(),
+        config.server.rtsp.port,
+        hls_server_publish.clone(),
+        recorder_http.clone(),
+    );
+
+    let http_server = http::HttpServer::new(
         config.server.http.port,
-        hls_server_http,
-        http_flv_server_http,
-        recorder_http,
-        analysis_http,
-        snapshot_http,
+        http::HttpContext {
+            stream_manager: stream_manager.clone(),
+            hls_server: hls_server_http,
+            http_flv_server: http_flv_server_http,
+            recorder: recorder_http.clone(),
+            analysis: analysis_http,
+            snapshot: snapshot_http,
+            jobs: job_supervisor,
+        },
     );
 
     let rtmp_server = rtmp::RtmpServer::new(
         stream_manager.clone(),
         config.server.rtmp.port,
         hls_server_rtmp,
+        recorder_http,
     );
 
     let rtmp_handle = tokio::spawn(async move { rtmp_server.start().await });
@@ -329,11 +506,16 @@ async fn main() -> Result<()> {
     info!("  RTSP:  rtsp://localhost:{}", config.server.rtsp.port);
     info!("  RTMP:  rtmp://localhost:{}", config.server.rtmp.port);
     info!("  HTTP:  http://localhost:{}", config.server.http.port);
-    info!(
-        "  HLS:   http://localhost:{}/hls/<stream_id>/live.m3u8 (dir: {})",
-        config.server.http.port,
-        hls_config.output_dir
-    );
+    match hls_config.storage {
+        hls::HlsStorage::Disk => info!(
+            "  HLS:   http://localhost:{}/hls/<stream_id>/live.m3u8 (dir: {})",
+            config.server.http.port, hls_config.output_dir
+        ),
+        hls::HlsStorage::Memory => info!(
+            "  HLS:   http://localhost:{}/hls/<stream_id>/live.m3u8 (memory, cap {} MiB)",
+            config.server.http.port, hls_config.memory_limit_mb
+        ),
+    }
     if record_config.enabled {
         info!(
             "  Record API:   http://localhost:{}/api/record/start (dir: {})",
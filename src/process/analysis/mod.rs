@@ -7,15 +7,17 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::process::Command;
+use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
-use crate::core::live_play::prepend_h264_config;
+use crate::core::live_play::prepend_video_config;
 use crate::core::{
     media_frame_timestamp_delta_ms, CodecType, DispatchError, DispatchPolicy, MediaFrame,
     StreamManager,
 };
 
 const MAX_EVENTS_PER_STREAM: usize = 256;
+const EVENT_CHANNEL_CAPACITY: usize = 256;
 const L1_METRICS_PLUGIN: &str = "l1_metrics";
 const FACE_DETECTION_PLUGIN: &str = "face_detection";
 
@@ -108,19 +110,27 @@ pub struct AnalysisManager {
     active: Arc<RwLock<HashMap<String, ActiveAnalysisSession>>>,
     metrics: Arc<RwLock<HashMap<String, AnalysisMetrics>>>,
     events: Arc<RwLock<HashMap<String, VecDeque<AnalysisEvent>>>>,
+    event_tx: broadcast::Sender<AnalysisEvent>,
 }
 
 impl AnalysisManager {
     pub fn new(stream_manager: Arc<StreamManager>, config: AnalysisConfig) -> Self {
+        let (event_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         Self {
             stream_manager,
             config,
             active: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(RwLock::new(HashMap::new())),
             events: Arc::new(RwLock::new(HashMap::new())),
+            event_tx,
         }
     }
 
+    /// Live feed of every event as it is recorded (all streams).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AnalysisEvent> {
+        self.event_tx.subscribe()
+    }
+
     pub fn enabled(&self) -> bool {
         self.config.enabled
     }
@@ -177,6 +187,7 @@ impl AnalysisManager {
             info: info.clone(),
             metrics: self.metrics.clone(),
             events: self.events.clone(),
+            event_tx: self.event_tx.clone(),
             max_events: self.config.max_events_per_stream,
             config: self.config.clone(),
         };
@@ -214,6 +225,7 @@ struct AnalysisTask {
     info: AnalysisSessionInfo,
     metrics: Arc<RwLock<HashMap<String, AnalysisMetrics>>>,
     events: Arc<RwLock<HashMap<String, VecDeque<AnalysisEvent>>>>,
+    event_tx: broadcast::Sender<AnalysisEvent>,
     max_events: usize,
     config: AnalysisConfig,
 }
@@ -303,6 +315,7 @@ impl AnalysisTask {
         while stream_events.len() >= self.max_events {
             stream_events.pop_front();
         }
+        let _ = self.event_tx.send(event.clone());
         stream_events.push_back(event);
     }
 
@@ -629,7 +642,7 @@ impl FaceDetector {
             sanitize_path_component(stream_id),
             now
         ));
-        let data = prepend_h264_config(stream_manager, stream_id, frame);
+        let data = prepend_video_config(stream_manager, stream_id, frame);
         debug!(
             "[Analysis][FaceDetection] write h264 frame stream='{}' ts={} h264='{}' bytes={}",
             stream_id,
@@ -842,7 +855,9 @@ impl MetricsState {
         self.metrics.updated_at_ms = now_ms();
 
         match frame.codec {
-            CodecType::H264 | CodecType::H265 => self.observe_video(frame),
+            CodecType::H264 | CodecType::H265 | CodecType::AV1 | CodecType::VP9 => {
+                self.observe_video(frame)
+            }
             CodecType::AAC | CodecType::Opus | CodecType::G711 => self.observe_audio(frame),
             CodecType::Unknown => None,
         }
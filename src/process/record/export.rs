@@ -0,0 +1,323 @@
+/// Export a wall-clock range of TS recordings as one MP4 file.
+/// The cut starts at the last keyframe at or before the requested start so the
+/// file decodes from its first frame; frames at or after the end are dropped.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use super::playback::{is_video, segment_frames, RangeCutter};
+use super::{now_ms, RecordFormat, RecordingEntry};
+use crate::core::{CodecType, MediaFrame};
+use crate::server::hls::fmp4_muxer::{AudioCodecConfig, Fmp4Muxer, VideoCodecConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportRequest {
+    pub stream_id: String,
+    /// Wall-clock range in Unix milliseconds, `[start_ms, end_ms)`.
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportJob {
+    pub id: String,
+    pub stream_id: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub created_at_ms: u64,
+    pub completed_at_ms: Option<u64>,
+    /// "pending", "running", "completed" or "failed".
+    pub status: String,
+    /// Recorded segments overlapping the range.
+    pub segments: usize,
+    pub path: Option<String>,
+    pub url: Option<String>,
+    pub bytes: u64,
+    /// Wall-clock time of the first exported frame (the keyframe cut).
+    pub media_start_ms: Option<u64>,
+    pub duration_ms: u64,
+    pub video_frames: u64,
+    pub audio_frames: u64,
+    pub error: Option<String>,
+}
+
+impl ExportJob {
+    pub fn new(id: String, req: &ExportRequest, segments: usize) -> Self {
+        Self {
+            id,
+            stream_id: req.stream_id.clone(),
+            start_ms: req.start_ms,
+            end_ms: req.end_ms,
+            created_at_ms: now_ms(),
+            completed_at_ms: None,
+            status: "pending".to_string(),
+            segments,
+            path: None,
+            url: None,
+            bytes: 0,
+            media_start_ms: None,
+            duration_ms: 0,
+            video_frames: 0,
+            audio_frames: 0,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub media_start_ms: u64,
+    pub duration_ms: u64,
+    pub video_frames: u64,
+    pub audio_frames: u64,
+    pub bytes: u64,
+}
+
+/// Run one export job to completion, keeping its entry in `jobs` up to date.
+pub(super) async fn run_export_job(
+    jobs: Arc<RwLock<Vec<ExportJob>>>,
+    job: ExportJob,
+    segments: Vec<RecordingEntry>,
+    output: PathBuf,
+) {
+    update_job(&jobs, &job.id, |entry| entry.status = "running".to_string());
+    let (start_ms, end_ms) = (job.start_ms, job.end_ms);
+    let path = output.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        export_mp4(&segments, start_ms, end_ms, &path)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|result| result);
+
+    match result {
+        Ok(summary) => {
+            info!(
+                "[Record] export complete id={} path={} bytes={} duration={}ms",
+                job.id,
+                output.display(),
+                summary.bytes,
+                summary.duration_ms
+            );
+            update_job(&jobs, &job.id, |entry| {
+                entry.status = "completed".to_string();
+                entry.completed_at_ms = Some(now_ms());
+                entry.path = Some(output.to_string_lossy().to_string());
+                entry.url = Some(format!("/api/recordings/exports/{}.mp4", entry.id));
+                entry.bytes = summary.bytes;
+                entry.media_start_ms = Some(summary.media_start_ms);
+                entry.duration_ms = summary.duration_ms;
+                entry.video_frames = summary.video_frames;
+                entry.audio_frames = summary.audio_frames;
+            });
+        }
+        Err(err) => {
+            error!("[Record] export failed id={}: {err:?}", job.id);
+            let _ = tokio::fs::remove_file(&output).await;
+            update_job(&jobs, &job.id, |entry| {
+                entry.status = "failed".to_string();
+                entry.completed_at_ms = Some(now_ms());
+                entry.error = Some(err.to_string());
+            });
+        }
+    }
+}
+
+fn update_job(jobs: &RwLock<Vec<ExportJob>>, id: &str, update: impl FnOnce(&mut ExportJob)) {
+    if let Some(entry) = jobs.write().iter_mut().find(|entry| entry.id == id) {
+        update(entry);
+    }
+}
+
+/// Remux `[start_ms, end_ms)` of `segments` (oldest first) into a fragmented
+/// MP4 at `output`. Blocking; segments are read one at a time.
+pub fn export_mp4(
+    segments: &[RecordingEntry],
+    start_ms: u64,
+    end_ms: u64,
+    output: &Path,
+) -> Result<ExportSummary> {
+    let file =
+        std::fs::File::create(output).with_context(|| format!("create {}", output.display()))?;
+    let mut cutter = RangeCutter::new(start_ms, end_ms);
+    let mut writer = Mp4Writer::new(std::io::BufWriter::new(file));
+    for entry in segments {
+        if entry.format != RecordFormat::Ts || entry.started_at_ms >= end_ms {
+            continue;
+        }
+        let data = std::fs::read(&entry.path).with_context(|| format!("read {}", entry.path))?;
+        let (layout, frames) = segment_frames(entry, &data);
+        cutter.has_video |= layout.has_video;
+        writer.has_video |= layout.has_video;
+        writer.has_audio |= layout.has_audio;
+        for frame in frames {
+            for frame in cutter.push(frame) {
+                writer.write(frame)?;
+            }
+        }
+    }
+    writer.finish()
+}
+
+/// Writes the init segment once the decoder configs are known, then fragments.
+struct Mp4Writer<W: Write> {
+    out: W,
+    muxer: Fmp4Muxer,
+    has_video: bool,
+    has_audio: bool,
+    video_ready: bool,
+    audio_ready: bool,
+    header_written: bool,
+    /// Frames held until the init segment can be written.
+    pending: Vec<MediaFrame>,
+    origin_ms: Option<u64>,
+    last_ms: u64,
+    summary: ExportSummary,
+}
+
+impl<W: Write> Mp4Writer<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            muxer: Fmp4Muxer::new(),
+            has_video: false,
+            has_audio: false,
+            video_ready: false,
+            audio_ready: false,
+            header_written: false,
+            pending: Vec::new(),
+            origin_ms: None,
+            last_ms: 0,
+            summary: ExportSummary::default(),
+        }
+    }
+
+    fn write(&mut self, frame: MediaFrame) -> Result<()> {
+        if self.header_written {
+            return self.mux(frame);
+        }
+        if is_video(&frame) && !self.video_ready {
+            if let Some(config) = VideoCodecConfig::from_keyframe(&frame) {
+                self.muxer.set_video_config(config);
+                self.video_ready = true;
+            }
+        } else if frame.codec == CodecType::AAC && !self.audio_ready {
+            if let Some(config) = AudioCodecConfig::from_adts(&frame.data) {
+                self.muxer.set_audio_config(config);
+                self.audio_ready = true;
+            }
+        }
+        self.pending.push(frame);
+        if (self.video_ready || !self.has_video) && (self.audio_ready || !self.has_audio) {
+            self.write_header()?;
+        }
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let init = self.muxer.init_segment();
+        self.write_bytes(&init)?;
+        self.header_written = true;
+        for frame in std::mem::take(&mut self.pending) {
+            self.mux(frame)?;
+        }
+        Ok(())
+    }
+
+    fn mux(&mut self, mut frame: MediaFrame) -> Result<()> {
+        let origin_ms = *self.origin_ms.get_or_insert(frame.timestamp);
+        self.last_ms = self.last_ms.max(frame.timestamp);
+        if is_video(&frame) {
+            if !self.video_ready {
+                return Ok(());
+            }
+            self.summary.video_frames += 1;
+        } else if self.audio_ready {
+            self.summary.audio_frames += 1;
+        } else {
+            return Ok(());
+        }
+        frame.timestamp = frame.timestamp.saturating_sub(origin_ms);
+        if let Some(fragment) = self.muxer.push_frame(&frame) {
+            self.write_bytes(&fragment)?;
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
+        self.out.write_all(data)?;
+        self.summary.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ExportSummary> {
+        if !self.header_written {
+            if self.pending.is_empty() {
+                return Err(anyhow!("no frames in range"));
+            }
+            if self.has_video && !self.video_ready {
+                return Err(anyhow!("no decodable keyframe in range"));
+            }
+            // Audio track declared but silent within the range: write video only.
+            self.has_audio = self.audio_ready;
+            self.write_header()?;
+        }
+        if let Some(fragment) = self.muxer.flush() {
+            self.write_bytes(&fragment)?;
+        }
+        self.out.flush()?;
+        let origin_ms = self.origin_ms.unwrap_or_default();
+        self.summary.media_start_ms = origin_ms;
+        self.summary.duration_ms = self.last_ms.saturating_sub(origin_ms);
+        Ok(self.summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::playback::tests::write_segment;
+    use super::*;
+    use crate::process::record::recovery::probe_fmp4;
+
+    #[test]
+    fn export_cuts_at_preceding_keyframe_across_segments() {
+        let dir = std::env::temp_dir().join(format!("vcp_record_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = write_segment(&dir, "a", 100_000, 75);
+        let second = write_segment(&dir, "b", 103_000, 75);
+        let output = dir.join("out.mp4");
+
+        // 101_500 falls inside the GOP starting at 101_000; 104_200 is in the second file.
+        let summary = export_mp4(&[first, second], 101_500, 104_200, &output).unwrap();
+        assert_eq!(summary.media_start_ms, 101_000);
+        assert_eq!(summary.video_frames, 80, "frames 101_000..104_200 at 40ms");
+        assert_eq!(summary.duration_ms, 104_160 - 101_000);
+
+        let data = std::fs::read(&output).unwrap();
+        assert_eq!(summary.bytes, data.len() as u64);
+        let probe = probe_fmp4(&data);
+        assert_eq!(probe.video_frames, 80);
+        assert_eq!(probe.keyframes, 4);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn export_fails_without_frames_in_range() {
+        let dir =
+            std::env::temp_dir().join(format!("vcp_record_export_empty_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let segment = write_segment(&dir, "a", 100_000, 25);
+        let result = export_mp4(&[segment], 200_000, 201_000, &dir.join("out.mp4"));
+        assert!(result.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,32 +2,63 @@ use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use time::{OffsetDateTime, UtcOffset};
 use tokio::io::AsyncWriteExt;
-use tokio::sync::watch;
-use tracing::{error, info};
+use tokio::sync::{broadcast, watch};
+use tracing::{error, info, warn};
 
-use crate::core::live_play::prepend_h264_config;
+use crate::core::live_play::prepend_video_config;
 use crate::core::{
-    CodecType, DispatchError, DispatchPolicy, FlvPlayTimeline, MediaFrame, StreamManager,
-    DEFAULT_RECORD_DIR, MILLISECOND_CLOCK_RATE,
+    CodecType, DispatchError, DispatchPolicy, FlvPlayTimeline, MediaFrame, Stream,
+    StreamLifecycleEvent, StreamManager, DEFAULT_EXPORT_DIR, DEFAULT_RECORD_DIR,
+    MILLISECOND_CLOCK_RATE,
 };
+use crate::process::analysis::AnalysisEvent;
+use crate::server::hls::fmp4_muxer::{AudioCodecConfig, Fmp4Muxer, VideoCodecConfig};
 use crate::server::hls::ts_muxer::TsMuxer;
 
+mod export;
+mod playback;
+mod recovery;
+mod retention;
+mod rules;
+mod vod;
+
+pub use export::{ExportJob, ExportRequest};
+pub use playback::{PacedReader, RecordingMediaInfo, RecordingReader};
+use retention::{disk_free_bytes, plan_deletions};
+pub use retention::{RetentionConfig, RetentionLimits};
+pub use rules::{glob_match, parse_source_mode, RecordRule};
+use vod::VOD_SEGMENT_TARGET_MS;
+pub use vod::{
+    build_vod_playlist, open_vod_segment, parse_vod_segment_filename, resolve_byte_range,
+    vod_content_type,
+};
+
 const DEFAULT_SEGMENT_DURATION_SEC: u64 = 300;
 const ACTIVE_INDEX_FLUSH_INTERVAL_MS: u64 = 5_000;
+const MAX_RECORD_EVENTS: usize = 256;
+const DEFAULT_EVENT_POST_ROLL_SEC: u64 = 10;
 
 #[derive(Debug, Clone)]
 pub struct RecordConfig {
     pub enabled: bool,
     pub base_dir: PathBuf,
+    /// Where exported MP4 files are written.
+    pub export_dir: PathBuf,
     pub default_format: RecordFormat,
     pub segment_duration: Duration,
     pub align_keyframe: bool,
+    pub retention: RetentionConfig,
+    pub rules: Vec<RecordRule>,
+    /// How long an event clip keeps recording after its last trigger.
+    pub event_post_roll: Duration,
+    /// Analysis event kinds (e.g. "face_detected") that trigger a clip.
+    pub event_triggers: Vec<String>,
 }
 
 impl Default for RecordConfig {
@@ -35,9 +66,14 @@ impl Default for RecordConfig {
         Self {
             enabled: false,
             base_dir: PathBuf::from(DEFAULT_RECORD_DIR),
+            export_dir: PathBuf::from(DEFAULT_EXPORT_DIR),
             default_format: RecordFormat::Ts,
             segment_duration: Duration::from_secs(DEFAULT_SEGMENT_DURATION_SEC),
             align_keyframe: true,
+            retention: RetentionConfig::default(),
+            rules: Vec::new(),
+            event_post_roll: Duration::from_secs(DEFAULT_EVENT_POST_ROLL_SEC),
+            event_triggers: Vec::new(),
         }
     }
 }
@@ -46,12 +82,14 @@ impl Default for RecordConfig {
 #[serde(rename_all = "lowercase")]
 pub enum RecordFormat {
     Ts,
+    Mp4,
 }
 
 impl RecordFormat {
     pub fn parse(value: &str) -> Option<Self> {
         match value.to_ascii_lowercase().as_str() {
             "ts" | "mpegts" => Some(Self::Ts),
+            "mp4" | "fmp4" => Some(Self::Mp4),
             _ => None,
         }
     }
@@ -59,11 +97,12 @@ impl RecordFormat {
     fn extension(self) -> &'static str {
         match self {
             Self::Ts => "ts",
+            Self::Mp4 => "mp4",
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordingEntry {
     pub id: String,
     pub stream_id: String,
@@ -78,6 +117,33 @@ pub struct RecordingEntry {
     pub audio_frames: u64,
     pub keyframes: u64,
     pub status: String,
+    /// Leading container header (TS PAT/PMT or MP4 init segment) in bytes.
+    #[serde(default)]
+    pub header_bytes: u64,
+    /// Media timestamps restart here instead of continuing the previous segment.
+    #[serde(default)]
+    pub discontinuity: bool,
+    /// Event that started the clip this segment belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+    /// Keyframe positions where VOD playback may split the file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cuts: Vec<KeyframeCut>,
+}
+
+/// Byte offset of a keyframe (or the fragment starting with it) and its media
+/// time since the first frame of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyframeCut {
+    pub offset: u64,
+    pub at_ms: u64,
+}
+
+impl RecordingEntry {
+    /// Finished segments, including ones rebuilt from disk after a crash.
+    pub fn is_playable(&self) -> bool {
+        matches!(self.status.as_str(), "completed" | "recovered")
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -88,6 +154,8 @@ pub struct RecordSessionInfo {
     pub started_at_ms: u64,
     pub segment_duration_ms: u64,
     pub align_keyframe: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -98,15 +166,38 @@ pub struct StartRecordRequest {
     pub align_keyframe: Option<bool>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct TriggerRecordRequest {
+    pub stream_id: String,
+    pub reason: Option<String>,
+    pub post_roll_sec: Option<u64>,
+    pub format: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct StopRecordRequest {
     pub stream_id: Option<String>,
     pub session_id: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordEvent {
+    pub stream_id: String,
+    pub timestamp_ms: u64,
+    pub kind: String,
+    pub message: String,
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
 struct ActiveRecordSession {
     info: RecordSessionInfo,
     stop_tx: watch::Sender<bool>,
+    /// Event clips only: end of the post-roll window.
+    deadline_tx: Option<watch::Sender<tokio::time::Instant>>,
 }
 
 #[derive(Clone)]
@@ -115,6 +206,12 @@ pub struct RecorderManager {
     config: RecordConfig,
     active: Arc<RwLock<HashMap<String, ActiveRecordSession>>>,
     index: Arc<RwLock<Vec<RecordingEntry>>>,
+    events: Arc<RwLock<VecDeque<RecordEvent>>>,
+    /// Sessions started by `config.rules`, stream id -> session id.
+    auto_sessions: Arc<RwLock<HashMap<String, String>>>,
+    /// Event clips in progress, keyed by stream id (independent of `active`).
+    clips: Arc<RwLock<HashMap<String, ActiveRecordSession>>>,
+    exports: Arc<RwLock<Vec<ExportJob>>>,
 }
 
 impl RecorderManager {
@@ -124,7 +221,282 @@ impl RecorderManager {
             config,
             active: Arc::new(RwLock::new(HashMap::new())),
             index: Arc::new(RwLock::new(Vec::new())),
+            events: Arc::new(RwLock::new(VecDeque::new())),
+            auto_sessions: Arc::new(RwLock::new(HashMap::new())),
+            clips: Arc::new(RwLock::new(HashMap::new())),
+            exports: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Rebuild the index from the files under `base_dir` (call once at startup,
+    /// before any session starts). Returns the number of segments indexed.
+    pub async fn rebuild_index(&self) -> Result<usize> {
+        let base_dir = self.config.base_dir.clone();
+        let entries =
+            tokio::task::spawn_blocking(move || recovery::scan_recordings(&base_dir)).await?;
+        let recovered = entries
+            .iter()
+            .filter(|entry| entry.status == "recovered")
+            .count();
+        let index = {
+            let mut index = self.index.write();
+            let rebuilt: HashSet<&str> = entries.iter().map(|entry| entry.id.as_str()).collect();
+            index.retain(|entry| !rebuilt.contains(entry.id.as_str()));
+            index.splice(0..0, entries.iter().cloned());
+            index.clone()
+        };
+        rewrite_index_files(&index).await?;
+        info!(
+            "[Record] index rebuilt from {}: {} segments ({} recovered)",
+            self.config.base_dir.display(),
+            entries.len(),
+            recovered
+        );
+        Ok(entries.len())
+    }
+
+    /// Follow publisher transitions and apply `config.rules`: start a session
+    /// when a matching stream starts publishing, stop it when the publisher leaves.
+    pub fn start_auto_record(&self) {
+        if !self.config.enabled || self.config.rules.is_empty() {
+            return;
+        }
+        info!(
+            "[Record] auto-record enabled with {} rule(s)",
+            self.config.rules.len()
+        );
+        let mut lifecycle = self.stream_manager.subscribe_lifecycle();
+        self.reconcile_auto_sessions();
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match lifecycle.recv().await {
+                    Ok(StreamLifecycleEvent::Publishing(stream)) => manager.auto_start(&stream),
+                    Ok(StreamLifecycleEvent::Unpublished(stream_id)) => {
+                        manager.auto_stop(&stream_id)
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("[Record] auto-record missed {skipped} stream events, resyncing");
+                        manager.reconcile_auto_sessions();
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Bring auto sessions in line with the streams currently publishing.
+    fn reconcile_auto_sessions(&self) {
+        let auto_streams: Vec<String> = self.auto_sessions.read().keys().cloned().collect();
+        for stream_id in auto_streams {
+            let publishing = self
+                .stream_manager
+                .get_stream(&stream_id)
+                .is_some_and(|stream| stream.status.is_publishing());
+            if !publishing {
+                self.auto_stop(&stream_id);
+            }
+        }
+        for stream_id in self.stream_manager.list_streams() {
+            if let Some(stream) = self.stream_manager.get_stream(&stream_id) {
+                if stream.status.is_publishing() {
+                    self.auto_start(&stream);
+                }
+            }
+        }
+    }
+
+    fn auto_start(&self, stream: &Stream) {
+        let Some(rule) = self.config.rules.iter().find(|rule| rule.matches(stream)) else {
+            return;
+        };
+        if self.active.read().contains_key(&stream.id) {
+            return;
+        }
+        let req = StartRecordRequest {
+            stream_id: stream.id.clone(),
+            format: rule.format.map(|format| format.extension().to_string()),
+            segment_duration: rule.segment_duration.map(|duration| duration.as_secs()),
+            align_keyframe: rule.align_keyframe,
+        };
+        match self.start(req) {
+            Ok(info) => {
+                info!(
+                    "[Record] auto-record started stream='{}' session={}",
+                    stream.id, info.session_id
+                );
+                self.auto_sessions
+                    .write()
+                    .insert(stream.id.clone(), info.session_id.clone());
+                self.push_event(RecordEvent {
+                    stream_id: stream.id.clone(),
+                    timestamp_ms: now_ms(),
+                    kind: "auto_started".to_string(),
+                    message: "recording started by rule".to_string(),
+                    session_id: info.session_id,
+                    recording_id: None,
+                    data: None,
+                });
+            }
+            Err(err) => warn!(
+                "[Record] auto-record failed for stream='{}': {err}",
+                stream.id
+            ),
+        }
+    }
+
+    fn auto_stop(&self, stream_id: &str) {
+        let Some(session_id) = self.auto_sessions.write().remove(stream_id) else {
+            return;
+        };
+        // A manual stop (and possibly a manual restart) may have happened since.
+        let req = StopRecordRequest {
+            stream_id: None,
+            session_id: Some(session_id.clone()),
+        };
+        if self.stop(req).is_ok() {
+            info!(
+                "[Record] auto-record stopped stream='{}' session={}",
+                stream_id, session_id
+            );
+            self.push_event(RecordEvent {
+                stream_id: stream_id.to_string(),
+                timestamp_ms: now_ms(),
+                kind: "auto_stopped".to_string(),
+                message: "publisher left".to_string(),
+                session_id,
+                recording_id: None,
+                data: None,
+            });
+        }
+    }
+
+    /// Recent recorder events (newest last), optionally for one stream.
+    pub fn events(&self, stream_id: Option<&str>) -> Vec<RecordEvent> {
+        self.events
+            .read()
+            .iter()
+            .filter(|event| stream_id.map(|id| event.stream_id == id).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    /// Periodically enforce `config.retention`; no-op when no limit is set.
+    pub fn start_retention_janitor(&self) {
+        let retention = &self.config.retention;
+        if !retention.is_enabled() {
+            return;
+        }
+        info!(
+            "[Record] retention janitor started (interval={}s)",
+            retention.check_interval.as_secs()
+        );
+        let manager = self.clone();
+        let check_interval = retention.check_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                manager.enforce_retention().await;
+            }
+        });
+    }
+
+    /// Delete segments that violate the retention policy, oldest first.
+    /// Returns the number of segments removed.
+    pub async fn enforce_retention(&self) -> usize {
+        let entries = self.index.read().clone();
+        let free_bytes = disk_free_bytes(&self.config.base_dir);
+        let planned = plan_deletions(&entries, &self.config.retention, now_ms(), free_bytes);
+        if planned.is_empty() {
+            return 0;
+        }
+
+        let mut removed = Vec::new();
+        for deletion in planned {
+            match tokio::fs::remove_file(&deletion.entry.path).await {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    warn!(
+                        "[Record] retention failed to delete {}: {}",
+                        deletion.entry.path, err
+                    );
+                    continue;
+                }
+            }
+            removed.push(deletion);
+        }
+        if removed.is_empty() {
+            return 0;
+        }
+
+        let removed_ids: HashSet<&str> = removed.iter().map(|d| d.entry.id.as_str()).collect();
+        let remaining = {
+            let mut index = self.index.write();
+            index.retain(|entry| !removed_ids.contains(entry.id.as_str()));
+            index.clone()
+        };
+
+        if let Err(err) = rewrite_index_files(&remaining).await {
+            warn!("[Record] retention failed to update index: {err:?}");
+        }
+        let emptied: HashSet<&Path> = removed
+            .iter()
+            .filter_map(|d| Path::new(&d.entry.path).parent())
+            .filter(|dir| {
+                !remaining
+                    .iter()
+                    .any(|entry| Path::new(&entry.path).parent() == Some(*dir))
+            })
+            .collect();
+        for dir in emptied {
+            if let Err(err) = remove_empty_segment_dir(dir).await {
+                warn!(
+                    "[Record] retention failed to clean up {}: {err:?}",
+                    dir.display()
+                );
+            }
         }
+
+        for deletion in &removed {
+            let entry = &deletion.entry;
+            info!(
+                "[Record] retention deleted {} stream='{}' reason={} bytes={}",
+                entry.id,
+                entry.stream_id,
+                deletion.reason.as_str(),
+                entry.bytes
+            );
+            self.push_event(RecordEvent {
+                stream_id: entry.stream_id.clone(),
+                timestamp_ms: now_ms(),
+                kind: "deleted".to_string(),
+                message: format!(
+                    "recording deleted by retention ({})",
+                    deletion.reason.as_str()
+                ),
+                session_id: entry.session_id.clone(),
+                recording_id: Some(entry.id.clone()),
+                data: Some(serde_json::json!({
+                    "reason": deletion.reason.as_str(),
+                    "path": entry.path,
+                    "bytes": entry.bytes,
+                    "started_at_ms": entry.started_at_ms,
+                    "ended_at_ms": entry.ended_at_ms,
+                })),
+            });
+        }
+        removed.len()
+    }
+
+    fn push_event(&self, event: RecordEvent) {
+        let mut events = self.events.write();
+        while events.len() >= MAX_RECORD_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
     }
 
     pub fn list_recordings(&self, stream_id: Option<&str>) -> Vec<RecordingEntry> {
@@ -136,25 +508,126 @@ impl RecorderManager {
             .collect()
     }
 
-    pub fn active_sessions(&self) -> Vec<RecordSessionInfo> {
-        self.active
+    pub fn find_recording(&self, id: &str) -> Option<RecordingEntry> {
+        self.index
             .read()
-            .values()
-            .map(|session| session.info.clone())
+            .iter()
+            .find(|entry| entry.id == id)
+            .cloned()
+    }
+
+    /// Completed segments of a stream overlapping `[start_ms, end_ms)`, oldest first.
+    pub fn completed_recordings_in_range(
+        &self,
+        stream_id: &str,
+        start_ms: Option<u64>,
+        end_ms: Option<u64>,
+    ) -> Vec<RecordingEntry> {
+        let mut entries: Vec<RecordingEntry> = self
+            .index
+            .read()
+            .iter()
+            .filter(|entry| entry.stream_id == stream_id && entry.is_playable())
+            .filter(|entry| {
+                start_ms
+                    .map(|start| entry.ended_at_ms > start)
+                    .unwrap_or(true)
+            })
+            .filter(|entry| end_ms.map(|end| entry.started_at_ms < end).unwrap_or(true))
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.started_at_ms);
+        entries
+    }
+
+    fn ts_recordings_in_range(
+        &self,
+        stream_id: &str,
+        start_ms: Option<u64>,
+        end_ms: Option<u64>,
+    ) -> Vec<RecordingEntry> {
+        self.completed_recordings_in_range(stream_id, start_ms, end_ms)
+            .into_iter()
+            .filter(|entry| entry.format == RecordFormat::Ts)
             .collect()
     }
 
-    pub fn start(&self, req: StartRecordRequest) -> Result<RecordSessionInfo> {
-        if !self.config.enabled {
-            return Err(anyhow!("recording is disabled"));
+    /// Wall-clock span `(start_ms, end_ms)` covered by the stream's TS recordings.
+    pub fn recorded_span(&self, stream_id: &str) -> Option<(u64, u64)> {
+        let segments = self.ts_recordings_in_range(stream_id, None, None);
+        let start = segments.first()?.started_at_ms;
+        let end = segments.iter().map(|entry| entry.ended_at_ms).max()?;
+        Some((start, end))
+    }
+
+    /// Open the stream's TS recordings for sequential playback from `start_ms`.
+    pub async fn open_playback(&self, stream_id: &str, start_ms: u64) -> Result<RecordingReader> {
+        let segments = self.ts_recordings_in_range(stream_id, Some(start_ms), None);
+        if segments.is_empty() {
+            return Err(anyhow!("no TS recordings at or after {start_ms}"));
         }
+        RecordingReader::open(segments, start_ms).await
+    }
+
+    /// Queue an MP4 export of `[start_ms, end_ms)` from the stream's TS segments.
+    pub fn export(&self, req: ExportRequest) -> Result<ExportJob> {
         if req.stream_id.trim().is_empty() {
             return Err(anyhow!("missing stream_id"));
         }
-        let stream = self
-            .stream_manager
-            .get_stream(&req.stream_id)
-            .ok_or_else(|| anyhow!("stream not found"))?;
+        if req.end_ms <= req.start_ms {
+            return Err(anyhow!("end_ms must be after start_ms"));
+        }
+        let segments =
+            self.ts_recordings_in_range(&req.stream_id, Some(req.start_ms), Some(req.end_ms));
+        if segments.is_empty() {
+            return Err(anyhow!("no TS recordings in range"));
+        }
+
+        let id = format!(
+            "export_{}_{}",
+            sanitize_path_component(&req.stream_id),
+            now_ms()
+        );
+        let job = ExportJob::new(id.clone(), &req, segments.len());
+        self.exports.write().push(job.clone());
+        info!(
+            "[Record] export queued id={} stream={} range={}..{} segments={}",
+            id,
+            req.stream_id,
+            req.start_ms,
+            req.end_ms,
+            segments.len()
+        );
+        let output = self.config.export_dir.join(format!("{id}.mp4"));
+        tokio::spawn(export::run_export_job(
+            self.exports.clone(),
+            job.clone(),
+            segments,
+            output,
+        ));
+        Ok(job)
+    }
+
+    pub fn export_job(&self, id: &str) -> Option<ExportJob> {
+        self.exports.read().iter().find(|job| job.id == id).cloned()
+    }
+
+    pub fn list_exports(&self) -> Vec<ExportJob> {
+        self.exports.read().clone()
+    }
+
+    pub fn active_sessions(&self) -> Vec<RecordSessionInfo> {
+        let active = self.active.read();
+        let clips = self.clips.read();
+        active
+            .values()
+            .chain(clips.values())
+            .map(|session| session.info.clone())
+            .collect()
+    }
+
+    pub fn start(&self, req: StartRecordRequest) -> Result<RecordSessionInfo> {
+        let stream = self.lookup_stream(&req.stream_id)?;
         if self.active.read().contains_key(&req.stream_id) {
             return Err(anyhow!("recording already active for stream"));
         }
@@ -170,6 +643,162 @@ impl RecorderManager {
                 .max(1),
         );
         let align_keyframe = req.align_keyframe.unwrap_or(self.config.align_keyframe);
+        let session_id = format!("rec_{}_{}", req.stream_id, now_ms());
+        let session = self.spawn_session(
+            &stream,
+            session_id,
+            format,
+            segment_duration,
+            align_keyframe,
+            None,
+        );
+        let info = session.info.clone();
+        self.active.write().insert(req.stream_id, session);
+        Ok(info)
+    }
+
+    /// Record an event clip: starts from the oldest IDR cached for the stream
+    /// (pre-roll) and runs until `post_roll` after the last trigger. Triggers
+    /// for a stream with a clip in progress only extend it.
+    pub fn trigger(&self, req: TriggerRecordRequest) -> Result<RecordSessionInfo> {
+        let stream = self.lookup_stream(&req.stream_id)?;
+        let reason = req.reason.unwrap_or_else(|| "api".to_string());
+        let post_roll = req
+            .post_roll_sec
+            .map(Duration::from_secs)
+            .unwrap_or(self.config.event_post_roll);
+        let deadline = tokio::time::Instant::now() + post_roll;
+
+        let mut clips = self.clips.write();
+        if let Some(session) = clips.get(&req.stream_id) {
+            if let Some(deadline_tx) = &session.deadline_tx {
+                deadline_tx.send_modify(|current| *current = (*current).max(deadline));
+            }
+            return Ok(session.info.clone());
+        }
+
+        let format = req
+            .format
+            .as_deref()
+            .and_then(RecordFormat::parse)
+            .unwrap_or(self.config.default_format);
+        let session_id = format!("clip_{}_{}", req.stream_id, now_ms());
+        let (deadline_tx, deadline_rx) = watch::channel(deadline);
+        let mut session = self.spawn_session(
+            &stream,
+            session_id,
+            format,
+            self.config.segment_duration,
+            self.config.align_keyframe,
+            Some(ClipControl {
+                reason: reason.clone(),
+                deadline_rx,
+                clips: self.clips.clone(),
+            }),
+        );
+        session.deadline_tx = Some(deadline_tx);
+        let info = session.info.clone();
+        clips.insert(req.stream_id.clone(), session);
+        drop(clips);
+
+        info!(
+            "[Record] clip started stream='{}' session={} reason={} post_roll={}s",
+            req.stream_id,
+            info.session_id,
+            reason,
+            post_roll.as_secs()
+        );
+        self.push_event(RecordEvent {
+            stream_id: req.stream_id,
+            timestamp_ms: now_ms(),
+            kind: "clip_started".to_string(),
+            message: format!("event clip started ({reason})"),
+            session_id: info.session_id.clone(),
+            recording_id: None,
+            data: Some(serde_json::json!({
+                "reason": reason,
+                "post_roll_ms": post_roll.as_millis() as u64,
+            })),
+        });
+        Ok(info)
+    }
+
+    /// Trigger clips from analysis events whose kind is in `config.event_triggers`.
+    pub fn start_event_triggers(&self, mut events: broadcast::Receiver<AnalysisEvent>) {
+        if !self.config.enabled || self.config.event_triggers.is_empty() {
+            return;
+        }
+        info!(
+            "[Record] event clips enabled for {:?}",
+            self.config.event_triggers
+        );
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if !manager.config.event_triggers.contains(&event.kind) {
+                            continue;
+                        }
+                        let req = TriggerRecordRequest {
+                            stream_id: event.stream_id.clone(),
+                            reason: Some(event.kind),
+                            post_roll_sec: None,
+                            format: None,
+                        };
+                        if let Err(err) = manager.trigger(req) {
+                            warn!(
+                                "[Record] event clip failed for stream='{}': {err}",
+                                event.stream_id
+                            );
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("[Record] event clips missed {skipped} analysis events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    pub fn stop(&self, req: StopRecordRequest) -> Result<RecordSessionInfo> {
+        let session = if let Some(stream_id) = req.stream_id {
+            let session = self.active.write().remove(&stream_id);
+            session.or_else(|| self.clips.write().remove(&stream_id))
+        } else if let Some(session_id) = req.session_id {
+            let session = take_session(&self.active, &session_id);
+            session.or_else(|| take_session(&self.clips, &session_id))
+        } else {
+            return Err(anyhow!("missing stream_id or session_id"));
+        };
+
+        let session = session.ok_or_else(|| anyhow!("recording session not found"))?;
+        let _ = session.stop_tx.send(true);
+        Ok(session.info)
+    }
+
+    fn lookup_stream(&self, stream_id: &str) -> Result<Stream> {
+        if !self.config.enabled {
+            return Err(anyhow!("recording is disabled"));
+        }
+        if stream_id.trim().is_empty() {
+            return Err(anyhow!("missing stream_id"));
+        }
+        self.stream_manager
+            .get_stream(&stream_id.to_string())
+            .ok_or_else(|| anyhow!("stream not found"))
+    }
+
+    fn spawn_session(
+        &self,
+        stream: &Stream,
+        session_id: String,
+        format: RecordFormat,
+        segment_duration: Duration,
+        align_keyframe: bool,
+        clip: Option<ClipControl>,
+    ) -> ActiveRecordSession {
         let declared_has_video = stream.tracks.is_empty()
             || stream
                 .tracks
@@ -180,20 +809,20 @@ impl RecorderManager {
                 .tracks
                 .iter()
                 .any(|track| matches!(track.codec, CodecType::AAC));
-        let session_id = format!("rec_{}_{}", req.stream_id, now_ms());
         let info = RecordSessionInfo {
             session_id: session_id.clone(),
-            stream_id: req.stream_id.clone(),
+            stream_id: stream.id.clone(),
             format,
             started_at_ms: now_ms(),
             segment_duration_ms: segment_duration.as_millis() as u64,
             align_keyframe,
+            trigger: clip.as_ref().map(|clip| clip.reason.clone()),
         };
 
         let (stop_tx, stop_rx) = watch::channel(false);
         let task = RecordTask {
             stream_manager: self.stream_manager.clone(),
-            stream_id: req.stream_id.clone(),
+            stream_id: stream.id.clone(),
             session_id,
             format,
             base_dir: self.config.base_dir.clone(),
@@ -203,44 +832,68 @@ impl RecorderManager {
             declared_has_audio,
             index: self.index.clone(),
             stop_rx,
+            clip,
         };
         let _handle = tokio::spawn(async move {
+            let clip_owner = task
+                .clip
+                .as_ref()
+                .map(|clip| (clip.clips.clone(), task.session_id.clone()));
             if let Err(err) = task.run().await {
                 error!("[Record] session failed: {err:?}");
             }
+            if let Some((clips, session_id)) = clip_owner {
+                take_session(&clips, &session_id);
+            }
         });
 
-        self.active.write().insert(
-            req.stream_id,
-            ActiveRecordSession {
-                info: info.clone(),
-                stop_tx,
-            },
-        );
-        Ok(info)
+        ActiveRecordSession {
+            info,
+            stop_tx,
+            deadline_tx: None,
+        }
     }
+}
 
-    pub fn stop(&self, req: StopRecordRequest) -> Result<RecordSessionInfo> {
-        let key = if let Some(stream_id) = req.stream_id {
-            stream_id
-        } else if let Some(session_id) = req.session_id {
-            self.active
-                .read()
-                .iter()
-                .find(|(_, session)| session.info.session_id == session_id)
-                .map(|(stream_id, _)| stream_id.clone())
-                .ok_or_else(|| anyhow!("recording session not found"))?
-        } else {
-            return Err(anyhow!("missing stream_id or session_id"));
-        };
+fn take_session(
+    sessions: &RwLock<HashMap<String, ActiveRecordSession>>,
+    session_id: &str,
+) -> Option<ActiveRecordSession> {
+    let mut sessions = sessions.write();
+    let stream_id = sessions
+        .iter()
+        .find(|(_, session)| session.info.session_id == session_id)
+        .map(|(stream_id, _)| stream_id.clone())?;
+    sessions.remove(&stream_id)
+}
 
-        let session = self
-            .active
-            .write()
-            .remove(&key)
-            .ok_or_else(|| anyhow!("recording session not found"))?;
-        let _ = session.stop_tx.send(true);
-        Ok(session.info)
+/// Post-roll state of an event clip session.
+struct ClipControl {
+    reason: String,
+    deadline_rx: watch::Receiver<tokio::time::Instant>,
+    clips: Arc<RwLock<HashMap<String, ActiveRecordSession>>>,
+}
+
+impl ClipControl {
+    async fn sleep_until_deadline(&self) {
+        let deadline = *self.deadline_rx.borrow();
+        tokio::time::sleep_until(deadline).await;
+    }
+
+    /// Checked under the `clips` lock so a concurrent trigger either extends
+    /// the deadline first or finds the session gone and starts a new clip.
+    fn expire(&self, stream_id: &str, session_id: &str) -> bool {
+        let mut clips = self.clips.write();
+        if *self.deadline_rx.borrow() > tokio::time::Instant::now() {
+            return false;
+        }
+        if clips
+            .get(stream_id)
+            .is_some_and(|session| session.info.session_id == session_id)
+        {
+            clips.remove(stream_id);
+        }
+        true
     }
 }
 
@@ -256,6 +909,7 @@ struct RecordTask {
     declared_has_audio: bool,
     index: Arc<RwLock<Vec<RecordingEntry>>>,
     stop_rx: watch::Receiver<bool>,
+    clip: Option<ClipControl>,
 }
 
 impl RecordTask {
@@ -269,8 +923,13 @@ impl RecordTask {
             .stream_manager
             .dispatch_subscribe(&self.stream_id, DispatchPolicy::SequentialFromIdr)
             .ok_or_else(|| anyhow!("stream hub not available"))?;
+        let mut pre_roll_ms = 0;
+        if self.clip.is_some() {
+            reader.snap_to_oldest_idr();
+            pre_roll_ms = reader.cursor_media_lag_ms().unwrap_or(0);
+        }
 
-        let mut writer = SegmentWriter::new(&self)?;
+        let mut writer = SegmentWriter::new(&self, pre_roll_ms)?;
         writer.persist_index(&self.index, "recording").await?;
         loop {
             let frames = tokio::select! {
@@ -280,6 +939,16 @@ impl RecordTask {
                     }
                     continue;
                 }
+                _ = clip_deadline(&self.clip) => {
+                    let expired = self
+                        .clip
+                        .as_ref()
+                        .is_some_and(|clip| clip.expire(&self.stream_id, &self.session_id));
+                    if expired {
+                        break;
+                    }
+                    continue;
+                }
                 result = reader.recv_batch() => match result {
                     Ok(frames) if !frames.is_empty() => frames,
                     Ok(_) => continue,
@@ -288,7 +957,7 @@ impl RecordTask {
             };
             if reader.take_muxer_resync() {
                 writer.finish(&self.index).await?;
-                writer = SegmentWriter::new(&self)?;
+                writer = SegmentWriter::new(&self, 0)?;
                 writer.persist_index(&self.index, "recording").await?;
             }
             for frame in frames {
@@ -300,14 +969,22 @@ impl RecordTask {
     }
 }
 
+async fn clip_deadline(clip: &Option<ClipControl>) {
+    match clip {
+        Some(clip) => clip.sleep_until_deadline().await,
+        None => std::future::pending().await,
+    }
+}
+
 struct SegmentWriter {
     id: String,
     stream_id: String,
     session_id: String,
+    trigger: Option<String>,
     format: RecordFormat,
     path: PathBuf,
     file: tokio::fs::File,
-    muxer: TsMuxer,
+    muxer: SegmentMuxer,
     timeline: FlvPlayTimeline,
     started_at_ms: u64,
     ended_at_ms: u64,
@@ -317,13 +994,19 @@ struct SegmentWriter {
     audio_frames: u64,
     keyframes: u64,
     bytes: u64,
+    header_bytes: u64,
     header_written: bool,
+    discontinuity: bool,
     last_index_flush_ms: u64,
+    /// Mux timestamp of the first frame written to the file.
+    first_media_ms: Option<u64>,
+    cuts: Vec<KeyframeCut>,
 }
 
 impl SegmentWriter {
-    fn new(task: &RecordTask) -> Result<Self> {
-        let started_at_ms = now_ms();
+    /// `backdate_ms` moves the start time back to cover replayed pre-roll frames.
+    fn new(task: &RecordTask, backdate_ms: u64) -> Result<Self> {
+        let started_at_ms = now_ms().saturating_sub(backdate_ms);
         let dir = task
             .base_dir
             .join(sanitize_path_component(&task.stream_id))
@@ -337,10 +1020,11 @@ impl SegmentWriter {
             id,
             stream_id: task.stream_id.clone(),
             session_id: task.session_id.clone(),
+            trigger: task.clip.as_ref().map(|clip| clip.reason.clone()),
             format: task.format,
             path,
             file: tokio::fs::File::from_std(file),
-            muxer: TsMuxer::new(),
+            muxer: SegmentMuxer::new(task.format),
             timeline: FlvPlayTimeline::default(),
             started_at_ms,
             ended_at_ms: started_at_ms,
@@ -350,15 +1034,19 @@ impl SegmentWriter {
             audio_frames: 0,
             keyframes: 0,
             bytes: 0,
+            header_bytes: 0,
             header_written: false,
+            discontinuity: true,
             last_index_flush_ms: started_at_ms,
+            first_media_ms: None,
+            cuts: Vec::new(),
         })
     }
 
     async fn write_frame(&mut self, task: &RecordTask, frame: MediaFrame) -> Result<()> {
         if should_rotate(self, task, &frame) {
             self.finish(&task.index).await?;
-            *self = Self::new(task)?;
+            self.rotate(task)?;
         }
 
         let is_video = matches!(frame.codec, CodecType::H264 | CodecType::H265);
@@ -369,37 +1057,95 @@ impl SegmentWriter {
 
         let frame = if is_video {
             self.has_video = true;
-            self.video_frames += 1;
-            if frame.is_keyframe {
-                self.keyframes += 1;
-            }
             prepare_video_frame_for_record(&task.stream_manager, &task.stream_id, frame)
         } else {
             self.has_audio = true;
-            self.audio_frames += 1;
             frame
         };
 
         if !self.header_written {
-            let header = self.muxer.generate_pat_pmt(self.has_video, self.has_audio);
+            let stream = task.stream_manager.get_stream(&task.stream_id);
+            let video_codec = if is_video {
+                frame.codec
+            } else {
+                stream
+                    .as_ref()
+                    .map(|stream| stream.video_codec())
+                    .unwrap_or(CodecType::H264)
+            };
+            // Hub AAC is raw, so the sample rate/channels come from the track's ASC.
+            let audio = self.has_audio.then(|| {
+                stream
+                    .as_ref()
+                    .and_then(AudioCodecConfig::from_stream)
+                    .unwrap_or_default()
+            });
+            // MP4 needs the decoder config first; drop frames until a usable keyframe.
+            let Some(header) = self
+                .muxer
+                .header(&frame, video_codec, self.has_video, audio)
+            else {
+                return Ok(());
+            };
             self.write_bytes(&header).await?;
+            self.header_bytes = header.len() as u64;
             self.header_written = true;
+            // Gives crash recovery a resume point from the first frame on
+            self.persist_index(&task.index, "recording").await?;
+        }
+
+        if is_video {
+            self.video_frames += 1;
+            if frame.is_keyframe {
+                self.keyframes += 1;
+            }
+        } else {
+            self.audio_frames += 1;
         }
 
         let mux_ts_ms = self.timeline.map(&frame) as u64;
-        self.muxer.update_pcr(mux_ts_ms);
         let frame = frame
             .with_timestamp(mux_ts_ms)
             .with_clock_rate(MILLISECOND_CLOCK_RATE);
-        let data = self.muxer.frame_to_ts(&frame);
+        let media_ms = mux_ts_ms.saturating_sub(*self.first_media_ms.get_or_insert(mux_ts_ms));
+        let offset_before = self.bytes;
+        let data = self.muxer.mux(&frame);
         if !data.is_empty() {
             self.write_bytes(&data).await?;
             self.ended_at_ms = now_ms();
+        }
+        if is_video && frame.is_keyframe {
+            // A keyframe closes the previous MP4 fragment and opens its own
+            let offset = match self.format {
+                RecordFormat::Ts => offset_before,
+                RecordFormat::Mp4 => self.bytes,
+            };
+            self.note_keyframe_cut(offset, media_ms);
+        }
+        if !data.is_empty() {
             self.flush_active_index_if_due(&task.index).await?;
         }
         Ok(())
     }
 
+    /// Remember a VOD split point once a target duration has passed since the last one.
+    fn note_keyframe_cut(&mut self, offset: u64, at_ms: u64) {
+        let last_ms = self.cuts.last().map(|cut| cut.at_ms).unwrap_or(0);
+        if offset > self.header_bytes && at_ms >= last_ms + VOD_SEGMENT_TARGET_MS {
+            self.cuts.push(KeyframeCut { offset, at_ms });
+        }
+    }
+
+    /// Start the next file of the session; the mux timeline carries over so
+    /// consecutive segments play back without a timestamp reset.
+    fn rotate(&mut self, task: &RecordTask) -> Result<()> {
+        let timeline = std::mem::take(&mut self.timeline);
+        *self = Self::new(task, 0)?;
+        self.timeline = timeline;
+        self.discontinuity = false;
+        Ok(())
+    }
+
     async fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
         self.file.write_all(data).await?;
         self.bytes += data.len() as u64;
@@ -429,6 +1175,11 @@ impl SegmentWriter {
     }
 
     async fn finish(&mut self, index: &Arc<RwLock<Vec<RecordingEntry>>>) -> Result<()> {
+        let tail = self.muxer.finish();
+        if !tail.is_empty() {
+            self.write_bytes(&tail).await?;
+            self.ended_at_ms = now_ms();
+        }
         self.file.flush().await?;
         if self.bytes == 0 || self.video_frames == 0 {
             self.persist_index(index, "empty").await?;
@@ -459,6 +1210,71 @@ impl SegmentWriter {
             audio_frames: self.audio_frames,
             keyframes: self.keyframes,
             status: status.to_string(),
+            header_bytes: self.header_bytes,
+            discontinuity: self.discontinuity,
+            trigger: self.trigger.clone(),
+            cuts: self.cuts.clone(),
+        }
+    }
+}
+
+/// Container writer behind a [`SegmentWriter`].
+enum SegmentMuxer {
+    Ts(TsMuxer),
+    Mp4(Fmp4Muxer),
+}
+
+impl SegmentMuxer {
+    fn new(format: RecordFormat) -> Self {
+        match format {
+            RecordFormat::Ts => Self::Ts(TsMuxer::new()),
+            RecordFormat::Mp4 => Self::Mp4(Fmp4Muxer::new()),
+        }
+    }
+
+    /// File header (PAT/PMT or MP4 init segment); `None` while MP4 still lacks SPS/PPS.
+    fn header(
+        &mut self,
+        frame: &MediaFrame,
+        video_codec: CodecType,
+        has_video: bool,
+        audio: Option<AudioCodecConfig>,
+    ) -> Option<Vec<u8>> {
+        match self {
+            Self::Ts(muxer) => {
+                muxer.set_video_codec(video_codec);
+                if let Some(audio) = &audio {
+                    muxer.set_audio_config(audio.clone());
+                }
+                Some(muxer.generate_pat_pmt(has_video, audio.is_some()))
+            }
+            Self::Mp4(muxer) => {
+                if has_video {
+                    muxer.set_video_config(VideoCodecConfig::from_keyframe(frame)?);
+                }
+                if let Some(audio) = audio {
+                    muxer.set_audio_config(audio);
+                }
+                Some(muxer.init_segment())
+            }
+        }
+    }
+
+    fn mux(&mut self, frame: &MediaFrame) -> Vec<u8> {
+        match self {
+            Self::Ts(muxer) => {
+                muxer.update_pcr(frame.timestamp);
+                muxer.frame_to_ts(frame)
+            }
+            Self::Mp4(muxer) => muxer.push_frame(frame).unwrap_or_default(),
+        }
+    }
+
+    /// Trailing bytes to write before the file is closed.
+    fn finish(&mut self) -> Vec<u8> {
+        match self {
+            Self::Ts(_) => Vec::new(),
+            Self::Mp4(muxer) => muxer.flush().unwrap_or_default(),
         }
     }
 }
@@ -481,10 +1297,10 @@ fn prepare_video_frame_for_record(
     stream_id: &str,
     frame: MediaFrame,
 ) -> MediaFrame {
-    if !matches!(frame.codec, CodecType::H264) {
+    if !frame.codec.is_video() {
         return frame;
     }
-    let data = prepend_h264_config(manager, stream_id, &frame);
+    let data = prepend_video_config(manager, stream_id, &frame);
     let prepared = MediaFrame::new(
         frame.stream_id.clone(),
         frame.track_id,
@@ -523,6 +1339,36 @@ async fn write_index_file(segment_path: &Path, entries: &[RecordingEntry]) -> Re
     Ok(())
 }
 
+/// Every segment directory holds a copy of the full index.
+async fn rewrite_index_files(entries: &[RecordingEntry]) -> Result<()> {
+    let mut dirs: HashMap<&Path, &RecordingEntry> = HashMap::new();
+    for entry in entries {
+        if let Some(dir) = Path::new(&entry.path).parent() {
+            dirs.entry(dir).or_insert(entry);
+        }
+    }
+    for entry in dirs.into_values() {
+        write_index_file(Path::new(&entry.path), entries).await?;
+    }
+    Ok(())
+}
+
+/// Drop the index of a date directory with no segments left, then the
+/// directory and its (now empty) stream directory.
+async fn remove_empty_segment_dir(dir: &Path) -> Result<()> {
+    match tokio::fs::remove_file(dir.join("index.json")).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    if tokio::fs::remove_dir(dir).await.is_ok() {
+        if let Some(stream_dir) = dir.parent() {
+            let _ = tokio::fs::remove_dir(stream_dir).await;
+        }
+    }
+    Ok(())
+}
+
 fn sanitize_path_component(input: &str) -> String {
     input
         .chars()
@@ -573,10 +1419,264 @@ mod tests {
     use crate::core::{StreamProtocol, StreamSourceMode, VIDEO_RTP_CLOCK_RATE};
 
     #[test]
-    fn parses_ts_format_aliases() {
+    fn parses_record_format_aliases() {
         assert_eq!(RecordFormat::parse("ts"), Some(RecordFormat::Ts));
         assert_eq!(RecordFormat::parse("mpegts"), Some(RecordFormat::Ts));
-        assert_eq!(RecordFormat::parse("mp4"), None);
+        assert_eq!(RecordFormat::parse("mp4"), Some(RecordFormat::Mp4));
+        assert_eq!(RecordFormat::parse("FMP4"), Some(RecordFormat::Mp4));
+        assert_eq!(RecordFormat::parse("flv"), None);
+    }
+
+    #[test]
+    fn mp4_header_waits_for_keyframe_with_parameter_sets() {
+        let mut muxer = SegmentMuxer::new(RecordFormat::Mp4);
+        let audio = MediaFrame::new(
+            "s".to_string(),
+            1,
+            0,
+            Bytes::from_static(&[0x21, 0x10]),
+            false,
+            CodecType::AAC,
+        );
+        let config = AudioCodecConfig::aac_lc(48_000, 2);
+        assert!(muxer
+            .header(&audio, CodecType::H264, true, Some(config.clone()))
+            .is_none());
+
+        let keyframe = MediaFrame::new(
+            "s".to_string(),
+            0,
+            0,
+            Bytes::from_static(&[
+                0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72, 0, 0, 0, 1, 0x68,
+                0xCE, 0x3C, 0x80, 0, 0, 0, 1, 0x65, 0x88,
+            ]),
+            true,
+            CodecType::H264,
+        );
+        let init = muxer
+            .header(&keyframe, CodecType::H264, true, Some(config.clone()))
+            .unwrap();
+        assert!(init.windows(4).any(|w| w == b"avcC"));
+        assert!(init.windows(4).any(|w| w == b"esds"));
+        assert!(init.windows(2).any(|w| w == config.asc.as_slice()));
+        assert!(
+            muxer.mux(&keyframe).is_empty(),
+            "fragment closes on next keyframe"
+        );
+        assert!(muxer.finish().windows(4).any(|w| w == b"mdat"));
+    }
+
+    #[tokio::test]
+    async fn retention_deletes_expired_segment_and_updates_index() {
+        let base_dir =
+            std::env::temp_dir().join(format!("vcp_record_retention_{}", std::process::id()));
+        let old_dir = base_dir.join("cam").join("19700101");
+        let new_dir = base_dir.join("cam").join("20260702");
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::create_dir_all(&new_dir).unwrap();
+
+        let segment = |dir: &Path, id: &str, started_at_ms: u64| {
+            let path = dir.join(format!("{id}.ts"));
+            std::fs::write(&path, b"ts").unwrap();
+            std::fs::write(dir.join("index.json"), b"[]").unwrap();
+            RecordingEntry {
+                id: id.to_string(),
+                stream_id: "cam".to_string(),
+                session_id: "rec".to_string(),
+                format: RecordFormat::Ts,
+                started_at_ms,
+                ended_at_ms: started_at_ms + 1_000,
+                duration_ms: 1_000,
+                path: path.to_string_lossy().to_string(),
+                bytes: 2,
+                video_frames: 1,
+                audio_frames: 0,
+                keyframes: 1,
+                status: "completed".to_string(),
+                header_bytes: 0,
+                discontinuity: false,
+                trigger: None,
+                cuts: Vec::new(),
+            }
+        };
+        let old = segment(&old_dir, "old", 0);
+        let fresh = segment(&new_dir, "fresh", now_ms());
+
+        let mut config = RecordConfig {
+            enabled: true,
+            base_dir: base_dir.clone(),
+            ..Default::default()
+        };
+        config.retention.global.max_age = Some(Duration::from_secs(3600));
+        let manager = RecorderManager::new(Arc::new(StreamManager::new()), config);
+        manager.index.write().extend([old.clone(), fresh.clone()]);
+
+        assert_eq!(manager.enforce_retention().await, 1);
+        assert!(!Path::new(&old.path).exists());
+        assert!(!old_dir.exists(), "emptied date directory is removed");
+        assert!(Path::new(&fresh.path).exists());
+        let index = std::fs::read_to_string(new_dir.join("index.json")).unwrap();
+        assert!(index.contains("\"fresh\"") && !index.contains("\"old\""));
+        let events = manager.events(Some("cam"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "deleted");
+        assert_eq!(events[0].recording_id.as_deref(), Some("old"));
+
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[tokio::test]
+    async fn rebuild_index_recovers_unindexed_segment_and_drops_orphans() {
+        use crate::server::hls::ts_muxer::TsMuxer;
+
+        let base_dir =
+            std::env::temp_dir().join(format!("vcp_record_rebuild_{}", std::process::id()));
+        let dir = base_dir.join("cam").join("20260702");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut muxer = TsMuxer::new();
+        let mut data = muxer.generate_pat_pmt(true, false);
+        for i in 0..10u64 {
+            let frame = MediaFrame::new(
+                "cam".to_string(),
+                0,
+                i * 40,
+                Bytes::from_static(&[0, 0, 0, 1, 0x65, 0x88]),
+                i == 0,
+                CodecType::H264,
+            );
+            data.extend(muxer.frame_to_ts(&frame));
+        }
+        let segment = dir.join("rec_cam_1000_1000.ts");
+        std::fs::write(&segment, &data).unwrap();
+        std::fs::write(
+            dir.join("index.json"),
+            br#"[{"id":"gone","stream_id":"cam","session_id":"rec","format":"ts","started_at_ms":1,"ended_at_ms":2,"duration_ms":1,"path":"/nowhere/gone.ts","bytes":1,"video_frames":1,"audio_frames":0,"keyframes":1,"status":"completed"}]"#,
+        )
+        .unwrap();
+
+        let config = RecordConfig {
+            enabled: true,
+            base_dir: base_dir.clone(),
+            ..Default::default()
+        };
+        let manager = RecorderManager::new(Arc::new(StreamManager::new()), config);
+        assert_eq!(manager.rebuild_index().await.unwrap(), 1);
+
+        let index = manager.index.read().clone();
+        assert_eq!(index.len(), 1);
+        let entry = &index[0];
+        assert_eq!(entry.stream_id, "cam");
+        assert_eq!(entry.started_at_ms, 1000);
+        assert_eq!(entry.status, "recovered");
+        assert_eq!(entry.video_frames, 10);
+        assert_eq!(entry.duration_ms, 360);
+        assert!(entry.is_playable());
+        let written = std::fs::read_to_string(dir.join("index.json")).unwrap();
+        assert!(written.contains("recovered") && !written.contains("\"gone\""));
+
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[tokio::test]
+    async fn auto_record_follows_publisher_for_matching_streams() {
+        let stream_manager = Arc::new(StreamManager::new());
+        stream_manager.create_stream("cam-1", StreamSourceMode::Pull, StreamProtocol::RTSP, None);
+        stream_manager.create_stream("desk", StreamSourceMode::Push, StreamProtocol::RTMP, None);
+        let config = RecordConfig {
+            enabled: true,
+            base_dir: std::env::temp_dir().join(format!("vcp_record_auto_{}", std::process::id())),
+            rules: vec![RecordRule {
+                stream_pattern: Some("cam-*".to_string()),
+                format: Some(RecordFormat::Mp4),
+                segment_duration: Some(Duration::from_secs(60)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let manager = RecorderManager::new(stream_manager.clone(), config);
+        manager.start_auto_record();
+
+        stream_manager.set_publishing("cam-1").unwrap();
+        stream_manager.set_publishing("desk").unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let sessions = manager.active_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].stream_id, "cam-1");
+        assert_eq!(sessions[0].format, RecordFormat::Mp4);
+        assert_eq!(sessions[0].segment_duration_ms, 60_000);
+
+        stream_manager.set_unpublished("cam-1").unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(manager.active_sessions().is_empty());
+        let kinds: Vec<String> = manager
+            .events(Some("cam-1"))
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(kinds, vec!["auto_started", "auto_stopped"]);
+    }
+
+    #[tokio::test]
+    async fn trigger_records_pre_roll_and_extends_post_roll() {
+        let stream_manager = Arc::new(StreamManager::new());
+        stream_manager.create_stream("door", StreamSourceMode::Push, StreamProtocol::RTMP, None);
+        for (ts, keyframe) in [(0, true), (1_000, false), (2_000, true), (3_000, false)] {
+            let nal = if keyframe { 0x65 } else { 0x41 };
+            stream_manager.publish_frame(
+                MediaFrame::new(
+                    "door".to_string(),
+                    0,
+                    ts,
+                    Bytes::from(vec![0, 0, 0, 1, nal, 0x88]),
+                    keyframe,
+                    CodecType::H264,
+                )
+                .with_clock_rate(MILLISECOND_CLOCK_RATE),
+            );
+        }
+        let base_dir = std::env::temp_dir().join(format!("vcp_record_clip_{}", std::process::id()));
+        let manager = RecorderManager::new(
+            stream_manager,
+            RecordConfig {
+                enabled: true,
+                base_dir: base_dir.clone(),
+                ..Default::default()
+            },
+        );
+        let trigger = |reason: &str| TriggerRecordRequest {
+            stream_id: "door".to_string(),
+            reason: Some(reason.to_string()),
+            post_roll_sec: Some(1),
+            format: None,
+        };
+
+        let before_ms = now_ms();
+        let first = manager.trigger(trigger("motion")).unwrap();
+        let second = manager.trigger(trigger("motion_again")).unwrap();
+        assert_eq!(
+            first.session_id, second.session_id,
+            "retrigger extends the clip"
+        );
+        assert_eq!(first.trigger.as_deref(), Some("motion"));
+        assert_eq!(manager.active_sessions().len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert!(
+            manager.active_sessions().is_empty(),
+            "clip ends after post-roll"
+        );
+        let clips = manager.list_recordings(Some("door"));
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].status, "completed");
+        assert_eq!(clips[0].trigger.as_deref(), Some("motion"));
+        assert_eq!(
+            clips[0].keyframes, 2,
+            "pre-roll starts at the oldest cached IDR"
+        );
+        assert!(clips[0].started_at_ms + 3_000 <= before_ms + 100);
+
+        let _ = std::fs::remove_dir_all(&base_dir);
     }
 
     #[test]
@@ -633,6 +1733,10 @@ mod tests {
             audio_frames: 0,
             keyframes: 0,
             status: "recording".to_string(),
+            header_bytes: 0,
+            discontinuity: true,
+            trigger: None,
+            cuts: Vec::new(),
         };
         upsert_index_entry(&index, entry.clone());
 
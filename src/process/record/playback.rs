@@ -0,0 +1,425 @@
+/// Sequential reading of TS recordings: frames in file order with wall-clock
+/// timestamps, starting on a keyframe. Shared by MP4 export and RTSP playback.
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use tracing::warn;
+
+use super::RecordingEntry;
+use crate::core::{CodecType, MediaFrame, AAC_DEFAULT_CLOCK_RATE};
+use crate::server::hls::fmp4_muxer::{AudioCodecConfig, VideoCodecConfig};
+use crate::server::hls::ts_demuxer::{demux_ts, pts_diff_ms, split_adts_frames, TsLayout, TsTrack};
+
+const AAC_FRAME_SAMPLES: u64 = 1024;
+const VIDEO_TRACK: u8 = 0;
+const AUDIO_TRACK: u8 = 1;
+/// Jumps between consecutive frames beyond this are recording gaps.
+const MAX_MEDIA_GAP_MS: f64 = 10_000.0;
+/// Presentation time given to a collapsed gap.
+const GAP_STEP_MS: f64 = 40.0;
+
+/// Decoder configuration found at the start of a playback.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingMediaInfo {
+    pub video: Option<VideoCodecConfig>,
+    pub audio: Option<AudioCodecConfig>,
+}
+
+/// Pulls frames out of recorded segments one segment at a time.
+pub struct RecordingReader {
+    pending: VecDeque<RecordingEntry>,
+    frames: VecDeque<MediaFrame>,
+    start_ms: u64,
+    has_video: bool,
+    keyframes_only: bool,
+    info: RecordingMediaInfo,
+}
+
+impl RecordingReader {
+    /// Open `segments` (oldest first) at the last keyframe at or before
+    /// `start_ms`, or at the first keyframe after it when none precedes it.
+    pub async fn open(segments: Vec<RecordingEntry>, start_ms: u64) -> Result<Self> {
+        let mut reader = Self {
+            pending: segments.into(),
+            frames: VecDeque::new(),
+            start_ms,
+            has_video: false,
+            keyframes_only: false,
+            info: RecordingMediaInfo::default(),
+        };
+        let mut cutter = RangeCutter::new(start_ms, u64::MAX);
+        while reader.frames.is_empty() {
+            let Some((layout, frames)) = reader.load_next().await else {
+                return Err(anyhow!("no decodable frames at or after {start_ms}"));
+            };
+            cutter.has_video |= layout.has_video;
+            for frame in frames {
+                reader.frames.extend(cutter.push(frame));
+            }
+        }
+        reader.start_ms = reader.frames[0].timestamp;
+        for frame in &reader.frames {
+            if reader.info.video.is_none() {
+                reader.info.video = VideoCodecConfig::from_keyframe(frame);
+            }
+            if reader.info.audio.is_none() && frame.codec == CodecType::AAC {
+                reader.info.audio = AudioCodecConfig::from_adts(&frame.data);
+            }
+        }
+        Ok(reader)
+    }
+
+    /// Wall-clock time of the first frame (the keyframe cut).
+    pub fn start_ms(&self) -> u64 {
+        self.start_ms
+    }
+
+    pub fn info(&self) -> &RecordingMediaInfo {
+        &self.info
+    }
+
+    /// Skip everything but video keyframes (fast playback). Ignored for
+    /// audio-only recordings.
+    pub fn set_keyframes_only(&mut self, keyframes_only: bool) {
+        self.keyframes_only = keyframes_only;
+    }
+
+    /// Next frame in file order, or `None` once every segment is consumed.
+    /// Unreadable segments are logged and skipped.
+    pub async fn next_frame(&mut self) -> Option<MediaFrame> {
+        loop {
+            while let Some(frame) = self.frames.pop_front() {
+                if self.keyframes_only && self.has_video && !(is_video(&frame) && frame.is_keyframe)
+                {
+                    continue;
+                }
+                return Some(frame);
+            }
+            let (_, frames) = self.load_next().await?;
+            self.frames.extend(frames);
+        }
+    }
+
+    async fn load_next(&mut self) -> Option<(TsLayout, Vec<MediaFrame>)> {
+        while let Some(entry) = self.pending.pop_front() {
+            let path = entry.path.clone();
+            let loaded = tokio::task::spawn_blocking(move || {
+                std::fs::read(&entry.path).map(|data| segment_frames(&entry, &data))
+            })
+            .await;
+            match loaded {
+                Ok(Ok((layout, frames))) => {
+                    self.has_video |= layout.has_video;
+                    return Some((layout, frames));
+                }
+                Ok(Err(err)) => warn!("[Record] playback skipped {path}: {err}"),
+                Err(err) => warn!("[Record] playback skipped {path}: {err}"),
+            }
+        }
+        None
+    }
+}
+
+/// Maps recorded wall-clock timestamps to presentation offsets (ms) from the
+/// start of a PLAY, divided by the scale. Gaps between recordings collapse to
+/// one frame interval so playback runs straight through them.
+pub struct PlaybackClock {
+    scale: f64,
+    base_media_ms: Option<u64>,
+    base_offset_ms: f64,
+    last_media_ms: u64,
+}
+
+impl PlaybackClock {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            base_media_ms: None,
+            base_offset_ms: 0.0,
+            last_media_ms: 0,
+        }
+    }
+
+    pub fn offset_ms(&mut self, media_ms: u64) -> f64 {
+        let Some(base_media_ms) = self.base_media_ms else {
+            self.base_media_ms = Some(media_ms);
+            self.last_media_ms = media_ms;
+            return 0.0;
+        };
+        let jump = media_ms as f64 - self.last_media_ms as f64;
+        if jump.abs() > MAX_MEDIA_GAP_MS {
+            self.base_offset_ms =
+                self.offset_from(base_media_ms, self.last_media_ms) + GAP_STEP_MS / self.scale;
+            self.base_media_ms = Some(media_ms);
+            self.last_media_ms = media_ms;
+            return self.base_offset_ms;
+        }
+        self.last_media_ms = self.last_media_ms.max(media_ms);
+        self.offset_from(base_media_ms, media_ms).max(0.0)
+    }
+
+    fn offset_from(&self, base_media_ms: u64, media_ms: u64) -> f64 {
+        self.base_offset_ms + (media_ms as f64 - base_media_ms as f64) / self.scale
+    }
+}
+
+/// Releases a reader's frames at `scale` times real time.
+pub struct PacedReader {
+    reader: RecordingReader,
+    clock: PlaybackClock,
+    started: tokio::time::Instant,
+}
+
+impl PacedReader {
+    pub fn new(reader: RecordingReader, scale: f64) -> Self {
+        Self {
+            reader,
+            clock: PlaybackClock::new(scale),
+            started: tokio::time::Instant::now(),
+        }
+    }
+
+    /// Next frame once it is due, with its presentation offset in ms from the
+    /// first frame.
+    pub async fn next_frame(&mut self) -> Option<(MediaFrame, f64)> {
+        let frame = self.reader.next_frame().await?;
+        let offset_ms = self.clock.offset_ms(frame.timestamp);
+        tokio::time::sleep_until(
+            self.started + std::time::Duration::from_micros((offset_ms * 1000.0) as u64),
+        )
+        .await;
+        Some((frame, offset_ms))
+    }
+}
+
+/// Frames of one TS segment in file order, stamped with wall-clock milliseconds
+/// (`started_at_ms` plus the PTS offset from the segment's first PES).
+pub(super) fn segment_frames(entry: &RecordingEntry, data: &[u8]) -> (TsLayout, Vec<MediaFrame>) {
+    let mut units = Vec::new();
+    let layout = demux_ts(data, |pes| {
+        if let Some(pts) = pes.pts {
+            let keyframe = pes.is_keyframe();
+            units.push((pes.offset, pes.track, pts, keyframe, pes.payload.to_vec()));
+        }
+    });
+    units.sort_by_key(|unit| unit.0);
+    let Some(&(_, _, base_pts, _, _)) = units.first() else {
+        return (layout, Vec::new());
+    };
+
+    let mut frames = Vec::with_capacity(units.len());
+    for (_, track, pts, keyframe, payload) in units {
+        let wall_ms = (entry.started_at_ms as i64 + pts_diff_ms(base_pts, pts)).max(0) as u64;
+        match track {
+            TsTrack::Video(codec) => frames.push(MediaFrame::new(
+                entry.stream_id.clone(),
+                VIDEO_TRACK,
+                wall_ms,
+                Bytes::from(payload),
+                keyframe,
+                codec,
+            )),
+            TsTrack::Audio => {
+                for (i, adts) in split_adts_frames(&payload).into_iter().enumerate() {
+                    let sample_rate = AudioCodecConfig::from_adts(adts)
+                        .map(|config| config.sample_rate)
+                        .unwrap_or(AAC_DEFAULT_CLOCK_RATE)
+                        as u64;
+                    frames.push(MediaFrame::new(
+                        entry.stream_id.clone(),
+                        AUDIO_TRACK,
+                        wall_ms + i as u64 * AAC_FRAME_SAMPLES * 1000 / sample_rate,
+                        Bytes::copy_from_slice(adts),
+                        false,
+                        CodecType::AAC,
+                    ));
+                }
+            }
+        }
+    }
+    (layout, frames)
+}
+
+pub(super) fn is_video(frame: &MediaFrame) -> bool {
+    matches!(frame.codec, CodecType::H264 | CodecType::H265)
+}
+
+/// Picks the frames of `[start_ms, end_ms)` out of a wall-clock ordered feed,
+/// starting at the last cut point at or before `start_ms`.
+pub(super) struct RangeCutter {
+    start_ms: u64,
+    end_ms: u64,
+    /// Cut on video keyframes; audio-only recordings cut on any frame.
+    pub(super) has_video: bool,
+    /// Frames since the last cut point before `start_ms`.
+    gop: Vec<MediaFrame>,
+    /// Timestamp of the first exported frame once the cut is made.
+    cut_ms: Option<u64>,
+}
+
+impl RangeCutter {
+    pub(super) fn new(start_ms: u64, end_ms: u64) -> Self {
+        Self {
+            start_ms,
+            end_ms,
+            has_video: false,
+            gop: Vec::new(),
+            cut_ms: None,
+        }
+    }
+
+    fn is_cut_point(&self, frame: &MediaFrame) -> bool {
+        if self.has_video {
+            is_video(frame) && frame.is_keyframe
+        } else {
+            true
+        }
+    }
+
+    pub(super) fn push(&mut self, frame: MediaFrame) -> Vec<MediaFrame> {
+        if frame.timestamp >= self.end_ms {
+            return Vec::new();
+        }
+        if let Some(cut_ms) = self.cut_ms {
+            return if frame.timestamp >= cut_ms {
+                vec![frame]
+            } else {
+                Vec::new()
+            };
+        }
+        if frame.timestamp < self.start_ms {
+            if self.is_cut_point(&frame) {
+                self.gop = vec![frame];
+            } else if !self.gop.is_empty() {
+                self.gop.push(frame);
+            }
+            return Vec::new();
+        }
+        // First frame inside the range: cut at the held keyframe, or wait for one.
+        if self.gop.is_empty() && !self.is_cut_point(&frame) {
+            return Vec::new();
+        }
+        let mut out = std::mem::take(&mut self.gop);
+        out.push(frame);
+        let cut_ms = out[0].timestamp;
+        self.cut_ms = Some(cut_ms);
+        out.retain(|frame| frame.timestamp >= cut_ms);
+        out
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::process::record::RecordFormat;
+    use crate::server::hls::ts_muxer::TsMuxer;
+    use std::path::Path;
+
+    const SPS: [u8; 9] = [0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+    const PPS: [u8; 4] = [0x68, 0xCE, 0x3C, 0x80];
+
+    /// 40 ms frames with a keyframe (carrying SPS/PPS) every second.
+    pub(in crate::process::record) fn write_segment(
+        dir: &Path,
+        id: &str,
+        started_at_ms: u64,
+        frames: u64,
+    ) -> RecordingEntry {
+        let mut muxer = TsMuxer::new();
+        let mut data = muxer.generate_pat_pmt(true, false);
+        for i in 0..frames {
+            let keyframe = i % 25 == 0;
+            let mut payload = Vec::new();
+            if keyframe {
+                for nal in [&SPS[..], &PPS[..], &[0x65, 0x88, 0x84][..]] {
+                    payload.extend_from_slice(&[0, 0, 0, 1]);
+                    payload.extend_from_slice(nal);
+                }
+            } else {
+                payload.extend_from_slice(&[0, 0, 0, 1, 0x41, 0x9A, 0x02]);
+            }
+            let frame = MediaFrame::new(
+                "cam".to_string(),
+                0,
+                5_000 + i * 40,
+                Bytes::from(payload),
+                keyframe,
+                CodecType::H264,
+            );
+            data.extend(muxer.frame_to_ts(&frame));
+        }
+        let path = dir.join(format!("{id}.ts"));
+        std::fs::write(&path, &data).unwrap();
+        RecordingEntry {
+            id: id.to_string(),
+            stream_id: "cam".to_string(),
+            session_id: "rec".to_string(),
+            format: RecordFormat::Ts,
+            started_at_ms,
+            ended_at_ms: started_at_ms + frames * 40,
+            duration_ms: frames * 40,
+            path: path.to_string_lossy().to_string(),
+            bytes: data.len() as u64,
+            video_frames: frames,
+            audio_frames: 0,
+            keyframes: frames.div_ceil(25),
+            status: "completed".to_string(),
+            header_bytes: 376,
+            discontinuity: false,
+            trigger: None,
+            cuts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn playback_clock_scales_and_collapses_gaps() {
+        let mut clock = PlaybackClock::new(2.0);
+        assert_eq!(clock.offset_ms(100_000), 0.0);
+        assert_eq!(clock.offset_ms(101_000), 500.0);
+        // Audio slightly behind video stays on the same timeline.
+        assert_eq!(clock.offset_ms(100_980), 490.0);
+        // A one-minute hole between recordings plays as a single step.
+        assert_eq!(clock.offset_ms(161_000), 520.0);
+        assert_eq!(clock.offset_ms(162_000), 1_020.0);
+    }
+
+    #[tokio::test]
+    async fn reader_starts_at_keyframe_and_crosses_segments() {
+        let dir = std::env::temp_dir().join(format!("vcp_record_playback_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let segments = vec![
+            write_segment(&dir, "a", 100_000, 75),
+            write_segment(&dir, "b", 103_000, 75),
+        ];
+
+        let mut reader = RecordingReader::open(segments.clone(), 102_500)
+            .await
+            .unwrap();
+        assert_eq!(reader.start_ms(), 102_000);
+        assert!(matches!(
+            reader.info().video,
+            Some(VideoCodecConfig::Avc { .. })
+        ));
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame().await {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 25 + 75);
+        assert!(frames[0].is_keyframe);
+        assert_eq!(frames.last().unwrap().timestamp, 103_000 + 74 * 40);
+
+        let mut reader = RecordingReader::open(segments, 100_000).await.unwrap();
+        reader.set_keyframes_only(true);
+        let mut keyframes = Vec::new();
+        while let Some(frame) = reader.next_frame().await {
+            keyframes.push(frame.timestamp);
+        }
+        assert_eq!(
+            keyframes,
+            vec![100_000, 101_000, 102_000, 103_000, 104_000, 105_000]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,674 @@
+/// Startup reconciliation of on-disk recordings with their `index.json` files.
+/// Segments without a completed index entry (crash, lost flush) are probed to
+/// recover frame counts and duration and are reported as `recovered`. Only the
+/// header and the bytes written after the last index flush are read, and the
+/// file is cut back to its last complete frame so it is not probed again.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use tracing::{info, warn};
+
+use super::{RecordFormat, RecordingEntry};
+use crate::server::hls::ts_demuxer::{
+    demux_ts, split_adts_frames, TsTrack, PTS_CLOCK_PER_MS, TS_PACKET_SIZE,
+};
+const SAMPLE_IS_NON_SYNC: u32 = 0x0001_0000;
+/// Start of an unindexed TS file read to find the end of its PAT/PMT.
+const HEAD_PROBE_BYTES: u64 = 64 * 1024;
+/// Media read from the end of a file that has no index entry to resume from.
+const MAX_TAIL_PROBE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Media facts read back from a segment file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaProbe {
+    pub video_frames: u64,
+    pub audio_frames: u64,
+    pub keyframes: u64,
+    pub duration_ms: u64,
+    pub header_bytes: u64,
+    /// Length up to the last complete TS packet / MP4 fragment.
+    pub valid_bytes: u64,
+}
+
+/// Scan `base_dir/<stream>/<date>/` and return one entry per segment file.
+/// Index entries whose file is gone are dropped.
+pub fn scan_recordings(base_dir: &Path) -> Vec<RecordingEntry> {
+    let mut indexed: HashMap<String, RecordingEntry> = HashMap::new();
+    let mut files = Vec::new();
+    for stream_dir in subdirs(base_dir) {
+        let stream_name = stream_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        for date_dir in subdirs(&stream_dir) {
+            if let Ok(data) = std::fs::read(date_dir.join("index.json")) {
+                match serde_json::from_slice::<Vec<RecordingEntry>>(&data) {
+                    Ok(entries) => {
+                        for entry in entries {
+                            merge_index_entry(&mut indexed, entry);
+                        }
+                    }
+                    Err(err) => warn!(
+                        "[Record] unreadable index {}: {err}",
+                        date_dir.join("index.json").display()
+                    ),
+                }
+            }
+            let Ok(dir) = std::fs::read_dir(&date_dir) else {
+                continue;
+            };
+            for file in dir.flatten() {
+                let path = file.path();
+                let format = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("ts") => RecordFormat::Ts,
+                    Some("mp4") => RecordFormat::Mp4,
+                    _ => continue,
+                };
+                files.push((path, format, stream_name.clone()));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (path, format, stream_name) in files {
+        let Some(id) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let path_str = path.to_string_lossy().to_string();
+        match indexed.remove(&id) {
+            Some(mut entry) if entry.status != "recording" && entry.bytes == size => {
+                entry.path = path_str;
+                entries.push(entry);
+            }
+            previous => {
+                // The last index flush covers the file up to `bytes`
+                let resume = previous.as_ref().filter(|entry| {
+                    entry.header_bytes > 0
+                        && entry.bytes >= entry.header_bytes
+                        && entry.bytes <= size
+                });
+                let probe = match probe_file(&path, format, size, resume) {
+                    Ok(probe) => probe,
+                    Err(err) => {
+                        warn!("[Record] cannot read {}: {err}", path.display());
+                        continue;
+                    }
+                };
+                if probe.header_bytes > 0 && probe.valid_bytes < size {
+                    // Drop the torn tail so the next scan matches the index as-is
+                    let truncated = std::fs::OpenOptions::new()
+                        .write(true)
+                        .open(&path)
+                        .and_then(|file| file.set_len(probe.valid_bytes));
+                    if let Err(err) = truncated {
+                        warn!("[Record] cannot truncate {}: {err}", path.display());
+                    }
+                }
+                let entry = recovered_entry(id, path_str, format, &stream_name, previous, probe);
+                info!(
+                    "[Record] recovered segment id={} status={} frames={} duration={}ms",
+                    entry.id, entry.status, entry.video_frames, entry.duration_ms
+                );
+                entries.push(entry);
+            }
+        }
+    }
+    for orphan in indexed.values() {
+        warn!(
+            "[Record] dropping index entry {} (missing {})",
+            orphan.id, orphan.path
+        );
+    }
+    entries.sort_by_key(|entry| entry.started_at_ms);
+    entries
+}
+
+/// Probe the header plus the media after `resume` (the last index flush, whose
+/// counts are carried over). Without one only the last
+/// [`MAX_TAIL_PROBE_BYTES`] are read and the counts cover just that window.
+fn probe_file(
+    path: &Path,
+    format: RecordFormat,
+    size: u64,
+    resume: Option<&RecordingEntry>,
+) -> std::io::Result<MediaProbe> {
+    let mut file = File::open(path)?;
+    let (header_bytes, tail_start) = match resume {
+        Some(entry) => (entry.header_bytes, entry.bytes),
+        None => {
+            let window_start = size.saturating_sub(MAX_TAIL_PROBE_BYTES);
+            match format {
+                RecordFormat::Ts => {
+                    let head = read_range(&mut file, 0, size.min(HEAD_PROBE_BYTES))?;
+                    let header_bytes = demux_ts(&head, |_| {}).header_bytes;
+                    // Stay on packet boundaries; a PES cut at the start is skipped
+                    let packets = window_start
+                        .saturating_sub(header_bytes)
+                        .div_ceil(TS_PACKET_SIZE as u64);
+                    (header_bytes, header_bytes + packets * TS_PACKET_SIZE as u64)
+                }
+                RecordFormat::Mp4 => {
+                    let boxes = top_level_boxes(&mut file, size)?;
+                    let header_bytes = boxes
+                        .iter()
+                        .find(|(box_type, _, _)| box_type == b"moov")
+                        .map(|(_, _, end)| *end)
+                        .unwrap_or(0);
+                    let tail_start = boxes
+                        .iter()
+                        .find(|(box_type, start, _)| {
+                            box_type == b"moof" && *start >= header_bytes.max(window_start)
+                        })
+                        .map(|(_, start, _)| *start)
+                        .unwrap_or(size);
+                    (header_bytes, tail_start)
+                }
+            }
+        }
+    };
+    if header_bytes == 0 {
+        return Ok(MediaProbe::default());
+    }
+
+    let mut data = read_range(&mut file, 0, header_bytes)?;
+    let tail_start = tail_start.max(header_bytes);
+    data.extend(read_range(&mut file, tail_start, size - tail_start)?);
+    let mut probe = match format {
+        RecordFormat::Ts => probe_ts(&data),
+        RecordFormat::Mp4 => probe_fmp4(&data),
+    };
+    probe.header_bytes = header_bytes;
+    probe.valid_bytes = tail_start + probe.valid_bytes.saturating_sub(header_bytes);
+    if let Some(entry) = resume {
+        probe.video_frames += entry.video_frames;
+        probe.audio_frames += entry.audio_frames;
+        probe.keyframes += entry.keyframes;
+        probe.duration_ms += entry.duration_ms;
+    }
+    Ok(probe)
+}
+
+fn read_range(file: &mut File, start: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(start))?;
+    let mut data = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// `(type, start, end)` of the complete top-level boxes, read box header by
+/// box header without loading the payloads.
+fn top_level_boxes(file: &mut File, size: u64) -> std::io::Result<Vec<([u8; 4], u64, u64)>> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= size {
+        let header = read_range(file, offset, 16.min(size - offset))?;
+        let mut box_type = [0u8; 4];
+        box_type.copy_from_slice(&header[4..8]);
+        let box_size = match read_u32(&header, 0).unwrap_or(0) {
+            0 => size - offset,
+            1 => match header.get(8..16) {
+                Some(large) => u64::from_be_bytes(large.try_into().unwrap_or([0; 8])),
+                None => break,
+            },
+            box_size => u64::from(box_size),
+        };
+        if box_size < 8 || offset + box_size > size {
+            break;
+        }
+        boxes.push((box_type, offset, offset + box_size));
+        offset += box_size;
+    }
+    Ok(boxes)
+}
+
+fn subdirs(dir: &Path) -> Vec<std::path::PathBuf> {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<_> = read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Every `index.json` holds the whole index; keep the most final copy.
+fn merge_index_entry(indexed: &mut HashMap<String, RecordingEntry>, entry: RecordingEntry) {
+    let rank = |e: &RecordingEntry| (e.status != "recording", e.ended_at_ms);
+    match indexed.get(&entry.id) {
+        Some(existing) if rank(existing) >= rank(&entry) => {}
+        _ => {
+            indexed.insert(entry.id.clone(), entry);
+        }
+    }
+}
+
+fn recovered_entry(
+    id: String,
+    path: String,
+    format: RecordFormat,
+    stream_name: &str,
+    previous: Option<RecordingEntry>,
+    probe: MediaProbe,
+) -> RecordingEntry {
+    // Segment ids are `<session_id>_<started_at_ms>`.
+    let (id_session, id_started) = id
+        .rsplit_once('_')
+        .and_then(|(session, started)| Some((session.to_string(), started.parse::<u64>().ok()?)))
+        .unwrap_or_else(|| (id.clone(), 0));
+    let (stream_id, session_id, started_at_ms, discontinuity, trigger) = match previous {
+        Some(entry) => (
+            entry.stream_id,
+            entry.session_id,
+            entry.started_at_ms,
+            entry.discontinuity,
+            entry.trigger,
+        ),
+        None => (stream_name.to_string(), id_session, id_started, true, None),
+    };
+    let status = if probe.video_frames + probe.audio_frames == 0 {
+        "empty"
+    } else {
+        "recovered"
+    };
+    RecordingEntry {
+        id,
+        stream_id,
+        session_id,
+        format,
+        started_at_ms,
+        ended_at_ms: started_at_ms + probe.duration_ms,
+        duration_ms: probe.duration_ms,
+        path,
+        bytes: probe.valid_bytes,
+        video_frames: probe.video_frames,
+        audio_frames: probe.audio_frames,
+        keyframes: probe.keyframes,
+        status: status.to_string(),
+        header_bytes: probe.header_bytes,
+        discontinuity,
+        trigger,
+        cuts: Vec::new(),
+    }
+}
+
+#[derive(Default)]
+struct PtsRange {
+    first: Option<u64>,
+    last: Option<u64>,
+}
+
+impl PtsRange {
+    fn add(&mut self, pts: u64) {
+        self.first = Some(self.first.map_or(pts, |first| first.min(pts)));
+        self.last = Some(self.last.map_or(pts, |last| last.max(pts)));
+    }
+
+    fn span_ms(&self) -> Option<u64> {
+        Some(self.last?.saturating_sub(self.first?) / PTS_CLOCK_PER_MS)
+    }
+}
+
+/// Count the frames of an MPEG-TS segment via [`demux_ts`].
+pub fn probe_ts(data: &[u8]) -> MediaProbe {
+    let mut probe = MediaProbe::default();
+    let mut video_pts = PtsRange::default();
+    let mut audio_pts = PtsRange::default();
+    let layout = demux_ts(data, |pes| match pes.track {
+        TsTrack::Video(_) => {
+            probe.video_frames += 1;
+            if pes.is_keyframe() {
+                probe.keyframes += 1;
+            }
+            if let Some(pts) = pes.pts {
+                video_pts.add(pts);
+            }
+        }
+        TsTrack::Audio => {
+            probe.audio_frames += (split_adts_frames(pes.payload).len() as u64).max(1);
+            if let Some(pts) = pes.pts {
+                audio_pts.add(pts);
+            }
+        }
+    });
+    probe.header_bytes = layout.header_bytes;
+    probe.valid_bytes = layout.valid_bytes;
+    probe.duration_ms = video_pts
+        .span_ms()
+        .or_else(|| audio_pts.span_ms())
+        .unwrap_or(0);
+    probe
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Child boxes of an MP4 container body; stops at the first truncated box.
+fn child_boxes(data: &[u8]) -> Vec<([u8; 4], &[u8], usize)> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let Some(size) = read_u32(data, offset) else {
+            break;
+        };
+        let mut box_type = [0u8; 4];
+        box_type.copy_from_slice(&data[offset + 4..offset + 8]);
+        let (header, size) = match size {
+            0 => (8, data.len() - offset),
+            1 => match data
+                .get(offset + 8..offset + 16)
+                .map(|b| u64::from_be_bytes(b.try_into().unwrap_or([0; 8])))
+            {
+                Some(large) => (16, large as usize),
+                None => break,
+            },
+            size => (8, size as usize),
+        };
+        if size < header || offset + size > data.len() {
+            break;
+        }
+        boxes.push((
+            box_type,
+            &data[offset + header..offset + size],
+            offset + size,
+        ));
+        offset += size;
+    }
+    boxes
+}
+
+fn find_child<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    child_boxes(data)
+        .into_iter()
+        .find(|(found, _, _)| found == box_type)
+        .map(|(_, body, _)| body)
+}
+
+#[derive(Default)]
+struct TrackStats {
+    samples: u64,
+    sync_samples: u64,
+    duration: u64,
+}
+
+/// Walk a fragmented MP4: init segment, then `moof`+`mdat` pairs. Fragments
+/// whose `mdat` is incomplete are not counted.
+pub fn probe_fmp4(data: &[u8]) -> MediaProbe {
+    let mut probe = MediaProbe::default();
+    // track_id -> (is_video, timescale)
+    let mut tracks: HashMap<u32, (bool, u32)> = HashMap::new();
+    let mut totals: HashMap<u32, TrackStats> = HashMap::new();
+    let mut pending: Vec<(u32, TrackStats)> = Vec::new();
+
+    for (box_type, body, end) in child_boxes(data) {
+        match &box_type {
+            b"moov" => {
+                for (child_type, trak, _) in child_boxes(body) {
+                    if &child_type != b"trak" {
+                        continue;
+                    }
+                    if let Some((track_id, is_video, timescale)) = parse_trak(trak) {
+                        tracks.insert(track_id, (is_video, timescale));
+                    }
+                }
+                probe.header_bytes = end as u64;
+                probe.valid_bytes = end as u64;
+            }
+            b"moof" => {
+                pending = child_boxes(body)
+                    .into_iter()
+                    .filter(|(child_type, _, _)| child_type == b"traf")
+                    .filter_map(|(_, traf, _)| parse_traf(traf))
+                    .collect();
+            }
+            b"mdat" => {
+                for (track_id, stats) in pending.drain(..) {
+                    let total = totals.entry(track_id).or_default();
+                    total.samples += stats.samples;
+                    total.sync_samples += stats.sync_samples;
+                    total.duration += stats.duration;
+                }
+                probe.valid_bytes = end as u64;
+            }
+            _ => probe.valid_bytes = end as u64,
+        }
+    }
+
+    let mut duration_ms = None;
+    for (track_id, stats) in &totals {
+        let Some(&(is_video, timescale)) = tracks.get(track_id) else {
+            continue;
+        };
+        let track_ms = stats.duration * 1000 / u64::from(timescale.max(1));
+        if is_video {
+            probe.video_frames += stats.samples;
+            probe.keyframes += stats.sync_samples;
+            duration_ms = Some(track_ms);
+        } else {
+            probe.audio_frames += stats.samples;
+            duration_ms = duration_ms.or(Some(track_ms));
+        }
+    }
+    probe.duration_ms = duration_ms.unwrap_or(0);
+    probe
+}
+
+/// `trak` → (track_ID, is video, media timescale).
+fn parse_trak(trak: &[u8]) -> Option<(u32, bool, u32)> {
+    let tkhd = find_child(trak, b"tkhd")?;
+    let track_id = if tkhd.first() == Some(&1) {
+        read_u32(tkhd, 20)?
+    } else {
+        read_u32(tkhd, 12)?
+    };
+    let mdia = find_child(trak, b"mdia")?;
+    let mdhd = find_child(mdia, b"mdhd")?;
+    let timescale = if mdhd.first() == Some(&1) {
+        read_u32(mdhd, 20)?
+    } else {
+        read_u32(mdhd, 12)?
+    };
+    let hdlr = find_child(mdia, b"hdlr")?;
+    let is_video = hdlr.get(8..12) == Some(b"vide".as_slice());
+    Some((track_id, is_video, timescale))
+}
+
+fn parse_traf(traf: &[u8]) -> Option<(u32, TrackStats)> {
+    let tfhd = find_child(traf, b"tfhd")?;
+    let track_id = read_u32(tfhd, 4)?;
+    let mut stats = TrackStats::default();
+    for (box_type, trun, _) in child_boxes(traf) {
+        if &box_type != b"trun" {
+            continue;
+        }
+        let flags = read_u32(trun, 0)? & 0x00FF_FFFF;
+        let count = read_u32(trun, 4)?;
+        let mut offset = 8;
+        if flags & 0x001 != 0 {
+            offset += 4;
+        }
+        let mut first_flags = None;
+        if flags & 0x004 != 0 {
+            first_flags = read_u32(trun, offset);
+            offset += 4;
+        }
+        for i in 0..count {
+            let mut sample_flags = if i == 0 { first_flags } else { None };
+            if flags & 0x100 != 0 {
+                stats.duration += u64::from(read_u32(trun, offset)?);
+                offset += 4;
+            }
+            if flags & 0x200 != 0 {
+                offset += 4;
+            }
+            if flags & 0x400 != 0 {
+                sample_flags = read_u32(trun, offset);
+                offset += 4;
+            }
+            if flags & 0x800 != 0 {
+                offset += 4;
+            }
+            stats.samples += 1;
+            if sample_flags.is_some_and(|f| f & SAMPLE_IS_NON_SYNC == 0) {
+                stats.sync_samples += 1;
+            }
+        }
+    }
+    Some((track_id, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{CodecType, MediaFrame};
+    use crate::server::hls::fmp4_muxer::{Fmp4Muxer, VideoCodecConfig};
+    use crate::server::hls::ts_demuxer::TS_PACKET_SIZE;
+    use crate::server::hls::ts_muxer::TsMuxer;
+    use bytes::Bytes;
+
+    fn video(ts_ms: u64, keyframe: bool) -> MediaFrame {
+        let nal: &[u8] = if keyframe {
+            &[0, 0, 0, 1, 0x65, 0x88, 0x84]
+        } else {
+            &[0, 0, 0, 1, 0x41, 0x9A, 0x02]
+        };
+        MediaFrame::new(
+            "cam".to_string(),
+            0,
+            ts_ms,
+            Bytes::copy_from_slice(nal),
+            keyframe,
+            CodecType::H264,
+        )
+    }
+
+    #[test]
+    fn ts_probe_counts_frames_and_drops_truncated_tail() {
+        let mut muxer = TsMuxer::new();
+        let mut data = muxer.generate_pat_pmt(true, false);
+        for i in 0..50u64 {
+            data.extend(muxer.frame_to_ts(&video(i * 40, i % 25 == 0)));
+        }
+        let complete = probe_ts(&data);
+        assert_eq!(complete.video_frames, 50);
+        assert_eq!(complete.keyframes, 2);
+        assert_eq!(complete.duration_ms, 49 * 40);
+        assert_eq!(complete.header_bytes, 2 * TS_PACKET_SIZE as u64);
+        assert_eq!(complete.valid_bytes, data.len() as u64);
+
+        data.truncate(data.len() - 100);
+        let truncated = probe_ts(&data);
+        assert_eq!(truncated.video_frames, 49, "cut final PES is not counted");
+        assert_eq!(truncated.valid_bytes % TS_PACKET_SIZE as u64, 0);
+        assert!(truncated.valid_bytes < data.len() as u64);
+    }
+
+    #[test]
+    fn scan_resumes_after_last_index_flush_and_truncates_torn_tail() {
+        let base_dir =
+            std::env::temp_dir().join(format!("vcp_record_recovery_{}", std::process::id()));
+        let dir = base_dir.join("cam").join("20260702");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut muxer = TsMuxer::new();
+        let mut data = muxer.generate_pat_pmt(true, false);
+        let header_bytes = data.len() as u64;
+        for i in 0..20u64 {
+            data.extend(muxer.frame_to_ts(&video(i * 40, i % 10 == 0)));
+        }
+        let flushed = data.len() as u64;
+        for i in 20..30u64 {
+            data.extend(muxer.frame_to_ts(&video(i * 40, i % 10 == 0)));
+        }
+        let complete = data.len() as u64;
+        data.extend_from_slice(&[0x47; 100]);
+        let path = dir.join("rec_5000.ts");
+        std::fs::write(&path, &data).unwrap();
+
+        // Counts above what the flushed bytes hold show the head is not re-read
+        let flushed_entry = RecordingEntry {
+            id: "rec_5000".to_string(),
+            stream_id: "cam".to_string(),
+            session_id: "rec".to_string(),
+            format: RecordFormat::Ts,
+            started_at_ms: 5_000,
+            ended_at_ms: 5_800,
+            duration_ms: 800,
+            path: path.to_string_lossy().to_string(),
+            bytes: flushed,
+            video_frames: 100,
+            audio_frames: 0,
+            keyframes: 7,
+            status: "recording".to_string(),
+            header_bytes,
+            discontinuity: false,
+            trigger: None,
+            cuts: Vec::new(),
+        };
+        std::fs::write(
+            dir.join("index.json"),
+            serde_json::to_vec(&[flushed_entry]).unwrap(),
+        )
+        .unwrap();
+
+        let entries = scan_recordings(&base_dir);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, "recovered");
+        assert_eq!(entries[0].video_frames, 110);
+        assert_eq!(entries[0].keyframes, 8);
+        assert_eq!(entries[0].duration_ms, 800 + 9 * 40);
+        assert_eq!(entries[0].bytes, complete);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete);
+
+        // Once indexed as recovered the file matches its entry and is kept as-is
+        std::fs::write(
+            dir.join("index.json"),
+            serde_json::to_vec(&entries).unwrap(),
+        )
+        .unwrap();
+        let rescanned = scan_recordings(&base_dir);
+        assert_eq!(rescanned[0].video_frames, 110);
+        assert_eq!(rescanned[0].bytes, complete);
+
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn fmp4_probe_stops_at_incomplete_fragment() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer.set_video_config(VideoCodecConfig::Avc {
+            sps: vec![0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72],
+            pps: vec![0x68, 0xCE, 0x3C, 0x80],
+        });
+        let mut data = muxer.init_segment();
+        let init_len = data.len() as u64;
+        for i in 0..75u64 {
+            if let Some(fragment) = muxer.push_frame(&video(i * 40, i % 25 == 0)) {
+                data.extend(fragment);
+            }
+        }
+        let complete_len = data.len() as u64;
+        data.extend(muxer.flush().unwrap());
+
+        let full = probe_fmp4(&data);
+        assert_eq!(full.header_bytes, init_len);
+        assert_eq!(full.video_frames, 75);
+        assert_eq!(full.keyframes, 3);
+        assert_eq!(full.duration_ms, 3_000);
+
+        data.truncate(data.len() - 10);
+        let truncated = probe_fmp4(&data);
+        assert_eq!(truncated.video_frames, 50);
+        assert_eq!(truncated.valid_bytes, complete_len);
+        assert_eq!(truncated.duration_ms, 2_000);
+    }
+}
@@ -0,0 +1,292 @@
+/// Retention policy for recorded segments.
+/// Segments are deleted oldest first by age, per-stream quota, global quota and
+/// free-space watermark; segments still being written are never touched.
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+use super::RecordingEntry;
+
+const DEFAULT_CHECK_INTERVAL_SEC: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionLimits {
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// Applies to every stream; `max_total_bytes` caps the sum over all streams.
+    pub global: RetentionLimits,
+    pub min_free_bytes: Option<u64>,
+    pub check_interval: Duration,
+    /// Per-stream overrides; `max_age` replaces the global one, `max_total_bytes`
+    /// is an additional cap on that stream alone.
+    pub streams: HashMap<String, RetentionLimits>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            global: RetentionLimits::default(),
+            min_free_bytes: None,
+            check_interval: Duration::from_secs(DEFAULT_CHECK_INTERVAL_SEC),
+            streams: HashMap::new(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.global.max_age.is_some()
+            || self.global.max_total_bytes.is_some()
+            || self.min_free_bytes.is_some()
+            || self
+                .streams
+                .values()
+                .any(|limits| limits.max_age.is_some() || limits.max_total_bytes.is_some())
+    }
+
+    fn max_age_for(&self, stream_id: &str) -> Option<Duration> {
+        self.streams
+            .get(stream_id)
+            .and_then(|limits| limits.max_age)
+            .or(self.global.max_age)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionReason {
+    MaxAge,
+    StreamQuota,
+    TotalQuota,
+    FreeSpace,
+}
+
+impl DeletionReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MaxAge => "max_age",
+            Self::StreamQuota => "stream_quota",
+            Self::TotalQuota => "total_quota",
+            Self::FreeSpace => "free_space",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedDeletion {
+    pub entry: RecordingEntry,
+    pub reason: DeletionReason,
+}
+
+/// Decide which segments to delete. `free_bytes` is the free space on the
+/// recording volume, `None` when it cannot be determined.
+pub fn plan_deletions(
+    entries: &[RecordingEntry],
+    config: &RetentionConfig,
+    now_ms: u64,
+    free_bytes: Option<u64>,
+) -> Vec<PlannedDeletion> {
+    let mut candidates: Vec<&RecordingEntry> = entries
+        .iter()
+        .filter(|entry| entry.status != "recording")
+        .collect();
+    candidates.sort_by_key(|entry| entry.started_at_ms);
+
+    let mut planned: Vec<PlannedDeletion> = Vec::new();
+    let mut deleted: HashSet<&str> = HashSet::new();
+    for entry in &candidates {
+        let Some(max_age) = config.max_age_for(&entry.stream_id) else {
+            continue;
+        };
+        if now_ms.saturating_sub(entry.ended_at_ms) > max_age.as_millis() as u64 {
+            deleted.insert(entry.id.as_str());
+            mark(&mut planned, entry, DeletionReason::MaxAge);
+        }
+    }
+
+    for (stream_id, limits) in &config.streams {
+        let Some(quota) = limits.max_total_bytes else {
+            continue;
+        };
+        let mut used: u64 = entries
+            .iter()
+            .filter(|e| &e.stream_id == stream_id && !deleted.contains(e.id.as_str()))
+            .map(|e| e.bytes)
+            .sum();
+        for entry in candidates.iter().filter(|e| &e.stream_id == stream_id) {
+            if used <= quota {
+                break;
+            }
+            if deleted.insert(entry.id.as_str()) {
+                used = used.saturating_sub(entry.bytes);
+                mark(&mut planned, entry, DeletionReason::StreamQuota);
+            }
+        }
+    }
+
+    if let Some(quota) = config.global.max_total_bytes {
+        let mut used: u64 = entries
+            .iter()
+            .filter(|e| !deleted.contains(e.id.as_str()))
+            .map(|e| e.bytes)
+            .sum();
+        for entry in &candidates {
+            if used <= quota {
+                break;
+            }
+            if deleted.insert(entry.id.as_str()) {
+                used = used.saturating_sub(entry.bytes);
+                mark(&mut planned, entry, DeletionReason::TotalQuota);
+            }
+        }
+    }
+
+    if let (Some(min_free), Some(free)) = (config.min_free_bytes, free_bytes) {
+        let mut free = free
+            + planned
+                .iter()
+                .map(|deletion| deletion.entry.bytes)
+                .sum::<u64>();
+        for entry in &candidates {
+            if free >= min_free {
+                break;
+            }
+            if deleted.insert(entry.id.as_str()) {
+                free += entry.bytes;
+                mark(&mut planned, entry, DeletionReason::FreeSpace);
+            }
+        }
+    }
+
+    planned
+}
+
+fn mark(planned: &mut Vec<PlannedDeletion>, entry: &RecordingEntry, reason: DeletionReason) {
+    planned.push(PlannedDeletion {
+        entry: entry.clone(),
+        reason,
+    });
+}
+
+/// Free bytes available to unprivileged users on the volume holding `path`.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+pub fn disk_free_bytes(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `statvfs` only writes into the zeroed struct we pass in.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn disk_free_bytes(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::record::RecordFormat;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    fn entry(id: &str, stream_id: &str, start: u64, bytes: u64) -> RecordingEntry {
+        RecordingEntry {
+            id: id.to_string(),
+            stream_id: stream_id.to_string(),
+            session_id: "rec".to_string(),
+            format: RecordFormat::Ts,
+            started_at_ms: start,
+            ended_at_ms: start + HOUR_MS,
+            duration_ms: HOUR_MS,
+            path: format!("{id}.ts"),
+            bytes,
+            video_frames: 1,
+            audio_frames: 0,
+            keyframes: 1,
+            status: "completed".to_string(),
+            header_bytes: 0,
+            discontinuity: false,
+            trigger: None,
+            cuts: Vec::new(),
+        }
+    }
+
+    fn ids(planned: &[PlannedDeletion]) -> Vec<(&str, DeletionReason)> {
+        planned
+            .iter()
+            .map(|deletion| (deletion.entry.id.as_str(), deletion.reason))
+            .collect()
+    }
+
+    #[test]
+    fn age_uses_stream_override_and_skips_active_segments() {
+        let mut active = entry("lobby_old_active", "lobby", 0, 10);
+        active.status = "recording".to_string();
+        let entries = vec![
+            entry("cam_old", "cam", 0, 10),
+            entry("lobby_old", "lobby", 0, 10),
+            entry("lobby_new", "lobby", 22 * HOUR_MS, 10),
+            active,
+        ];
+        let mut config = RetentionConfig::default();
+        config.global.max_age = Some(Duration::from_secs(48 * 3600));
+        config.streams.insert(
+            "lobby".to_string(),
+            RetentionLimits {
+                max_age: Some(Duration::from_secs(3600)),
+                max_total_bytes: None,
+            },
+        );
+
+        let planned = plan_deletions(&entries, &config, 23 * HOUR_MS, None);
+        assert_eq!(ids(&planned), vec![("lobby_old", DeletionReason::MaxAge)]);
+    }
+
+    #[test]
+    fn quotas_and_free_space_delete_oldest_first() {
+        let entries = vec![
+            entry("a3", "a", 3 * HOUR_MS, 100),
+            entry("a1", "a", HOUR_MS, 100),
+            entry("a2", "a", 2 * HOUR_MS, 100),
+            entry("b1", "b", 0, 100),
+            entry("b2", "b", 4 * HOUR_MS, 100),
+        ];
+        let mut config = RetentionConfig::default();
+        config.streams.insert(
+            "a".to_string(),
+            RetentionLimits {
+                max_age: None,
+                max_total_bytes: Some(200),
+            },
+        );
+        config.global.max_total_bytes = Some(300);
+        config.min_free_bytes = Some(1_100);
+
+        let planned = plan_deletions(&entries, &config, 5 * HOUR_MS, Some(850));
+        assert_eq!(
+            ids(&planned),
+            vec![
+                ("a1", DeletionReason::StreamQuota),
+                ("b1", DeletionReason::TotalQuota),
+                ("a2", DeletionReason::FreeSpace),
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_without_limits() {
+        let config = RetentionConfig::default();
+        assert!(!config.is_enabled());
+        assert!(plan_deletions(&[entry("a", "a", 0, 1)], &config, u64::MAX, Some(0)).is_empty());
+    }
+}
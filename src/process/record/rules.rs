@@ -0,0 +1,141 @@
+/// Rules that start a recording when a matching stream begins publishing.
+use std::time::Duration;
+
+use super::RecordFormat;
+use crate::core::{Stream, StreamSourceMode};
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordRule {
+    /// Stream id glob (`*` any run, `?` one char); `None` matches every stream.
+    pub stream_pattern: Option<String>,
+    /// Protocol name as reported by the streams API, case-insensitive (e.g. "rtsp").
+    pub protocol: Option<String>,
+    pub source: Option<StreamSourceMode>,
+    pub format: Option<RecordFormat>,
+    pub segment_duration: Option<Duration>,
+    pub align_keyframe: Option<bool>,
+}
+
+impl RecordRule {
+    pub fn matches(&self, stream: &Stream) -> bool {
+        if let Some(pattern) = &self.stream_pattern {
+            if !glob_match(pattern, &stream.id) {
+                return false;
+            }
+        }
+        if let Some(protocol) = &self.protocol {
+            if !format!("{:?}", stream.protocol).eq_ignore_ascii_case(protocol) {
+                return false;
+            }
+        }
+        self.source
+            .as_ref()
+            .map(|source| *source == stream.source)
+            .unwrap_or(true)
+    }
+}
+
+pub fn parse_source_mode(value: &str) -> Option<StreamSourceMode> {
+    match value.to_ascii_lowercase().as_str() {
+        "push" => Some(StreamSourceMode::Push),
+        "pull" => Some(StreamSourceMode::Pull),
+        _ => None,
+    }
+}
+
+/// Shell-style match over the whole string: `*` matches any run, `?` one char.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    backtrack = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{PlaybackStatus, StreamProtocol, StreamStatus};
+
+    fn stream(id: &str, protocol: StreamProtocol, source: StreamSourceMode) -> Stream {
+        Stream {
+            id: id.to_string(),
+            tracks: Vec::new(),
+            status: StreamStatus::Publishing,
+            playback_status: PlaybackStatus::Idle,
+            source,
+            protocol,
+            pull_url: None,
+            vps: None,
+            sps: None,
+            pps: None,
+        }
+    }
+
+    #[test]
+    fn glob_matches_whole_stream_id() {
+        assert!(glob_match("cam-*", "cam-lobby"));
+        assert!(glob_match("cam-*", "cam-"));
+        assert!(glob_match("*-door-?", "north-door-2"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("cam-*", "backup-cam-1"));
+        assert!(!glob_match("cam-?", "cam-12"));
+        assert!(!glob_match("lobby", "lobby2"));
+    }
+
+    #[test]
+    fn rule_filters_by_protocol_and_source() {
+        let rule = RecordRule {
+            stream_pattern: Some("cam-*".to_string()),
+            protocol: Some("rtsp".to_string()),
+            source: parse_source_mode("Pull"),
+            ..Default::default()
+        };
+        assert!(rule.matches(&stream(
+            "cam-1",
+            StreamProtocol::RTSP,
+            StreamSourceMode::Pull
+        )));
+        assert!(!rule.matches(&stream(
+            "cam-1",
+            StreamProtocol::RTMP,
+            StreamSourceMode::Pull
+        )));
+        assert!(!rule.matches(&stream(
+            "cam-1",
+            StreamProtocol::RTSP,
+            StreamSourceMode::Push
+        )));
+        assert!(!rule.matches(&stream(
+            "desk",
+            StreamProtocol::RTSP,
+            StreamSourceMode::Pull
+        )));
+        assert!(RecordRule::default().matches(&stream(
+            "any",
+            StreamProtocol::WebRTC,
+            StreamSourceMode::Push
+        )));
+    }
+}
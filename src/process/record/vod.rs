@@ -0,0 +1,282 @@
+/// HLS VOD playlists over recorded segments.
+/// Each recording is split at the keyframe cuts noted while it was written
+/// into `EXT-X-BYTERANGE` media segments; the file header (TS PAT/PMT or MP4
+/// init segment) is announced once per file with `EXT-X-MAP`.
+use anyhow::Result;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{RecordFormat, RecordingEntry};
+use crate::server::hls::m3u8::M3u8Generator;
+
+/// Wall-clock hole between segments that is reported as a discontinuity.
+const VOD_GAP_TOLERANCE_MS: u64 = 1_000;
+/// Media time between keyframe cuts noted while recording.
+pub(super) const VOD_SEGMENT_TARGET_MS: u64 = 6_000;
+
+/// One `EXT-X-BYTERANGE` media segment of a recording.
+struct VodSegment<'a> {
+    entry: &'a RecordingEntry,
+    offset: u64,
+    len: u64,
+    /// Start relative to `entry.started_at_ms`.
+    start_ms: u64,
+    duration_ms: u64,
+}
+
+impl VodSegment<'_> {
+    fn wall_clock_range(&self) -> (u64, u64) {
+        let start = self.entry.started_at_ms + self.start_ms;
+        (start, start + self.duration_ms)
+    }
+}
+
+/// Split a recording at its keyframe cuts; recordings without cuts (written
+/// before cuts were tracked, or rebuilt after a crash) stay one segment.
+fn split_entry(entry: &RecordingEntry) -> Vec<VodSegment<'_>> {
+    let mut bounds = vec![(entry.header_bytes, 0)];
+    for cut in &entry.cuts {
+        let (last_offset, last_ms) = bounds[bounds.len() - 1];
+        if cut.offset > last_offset && cut.offset < entry.bytes && cut.at_ms >= last_ms {
+            bounds.push((cut.offset, cut.at_ms));
+        }
+    }
+    let end_ms = entry.duration_ms.max(bounds[bounds.len() - 1].1);
+    bounds.push((entry.bytes, end_ms));
+    bounds
+        .windows(2)
+        .filter(|pair| pair[1].0 > pair[0].0)
+        .map(|pair| VodSegment {
+            entry,
+            offset: pair[0].0,
+            len: pair[1].0 - pair[0].0,
+            start_ms: pair[0].1,
+            duration_ms: pair[1].1 - pair[0].1,
+        })
+        .collect()
+}
+
+/// `#EXT-X-PLAYLIST-TYPE:VOD` playlist limited to the segments overlapping
+/// `[start_ms, end_ms)`; URIs are relative (`segments/<id>.<ext>`).
+pub fn build_vod_playlist(
+    entries: &[RecordingEntry],
+    start_ms: Option<u64>,
+    end_ms: Option<u64>,
+) -> String {
+    let segments: Vec<VodSegment> = entries
+        .iter()
+        .flat_map(split_entry)
+        .filter(|segment| {
+            let (from, to) = segment.wall_clock_range();
+            start_ms.map(|start| to > start).unwrap_or(true)
+                && end_ms.map(|end| from < end).unwrap_or(true)
+        })
+        .collect();
+    let has_mp4 = segments
+        .iter()
+        .any(|segment| segment.entry.format == RecordFormat::Mp4);
+    let target_duration = segments
+        .iter()
+        .map(|segment| segment.duration_ms.div_ceil(1000))
+        .max()
+        .unwrap_or(1)
+        .max(1);
+
+    let mut output = String::new();
+    output.push_str("#EXTM3U\r\n");
+    // EXT-X-MAP on MPEG-TS needs version 6, fMP4 needs 7
+    output.push_str(&format!(
+        "#EXT-X-VERSION:{}\r\n",
+        if has_mp4 { 7 } else { 6 }
+    ));
+    output.push_str("#EXT-X-PLAYLIST-TYPE:VOD\r\n");
+    output.push_str("#EXT-X-INDEPENDENT-SEGMENTS\r\n");
+    output.push_str(&format!("#EXT-X-TARGETDURATION:{}\r\n", target_duration));
+    output.push_str("#EXT-X-MEDIA-SEQUENCE:0\r\n");
+
+    let mut previous: Option<&RecordingEntry> = None;
+    for segment in &segments {
+        let entry = segment.entry;
+        let uri = format!("segments/{}.{}", entry.id, entry.format.extension());
+        if previous.map(|prev| prev.id != entry.id).unwrap_or(true) {
+            if let Some(prev) = previous {
+                let gap = entry.started_at_ms.saturating_sub(prev.ended_at_ms);
+                if entry.discontinuity || gap > VOD_GAP_TOLERANCE_MS || entry.format != prev.format
+                {
+                    output.push_str("#EXT-X-DISCONTINUITY\r\n");
+                }
+            }
+            output.push_str(&format!(
+                "#EXT-X-MAP:URI=\"{}\",BYTERANGE=\"{}@0\"\r\n",
+                uri, entry.header_bytes
+            ));
+        }
+        let (from, _) = segment.wall_clock_range();
+        output.push_str(&format!(
+            "#EXT-X-PROGRAM-DATE-TIME:{}\r\n",
+            M3u8Generator::format_program_date_time(UNIX_EPOCH + Duration::from_millis(from))
+        ));
+        output.push_str(&format!(
+            "#EXTINF:{:.3},\r\n",
+            segment.duration_ms as f64 / 1000.0
+        ));
+        output.push_str(&format!(
+            "#EXT-X-BYTERANGE:{}@{}\r\n",
+            segment.len, segment.offset
+        ));
+        output.push_str(&uri);
+        output.push_str("\r\n");
+        previous = Some(entry);
+    }
+
+    output.push_str("#EXT-X-ENDLIST\r\n");
+    output
+}
+
+/// Recording id and format behind a segment filename (`<id>.ts` / `<id>.mp4`).
+pub fn parse_vod_segment_filename(filename: &str) -> Option<(&str, RecordFormat)> {
+    if let Some(id) = filename.strip_suffix(".ts") {
+        Some((id, RecordFormat::Ts))
+    } else {
+        filename
+            .strip_suffix(".mp4")
+            .map(|id| (id, RecordFormat::Mp4))
+    }
+}
+
+pub fn vod_content_type(format: RecordFormat) -> &'static str {
+    match format {
+        RecordFormat::Ts => "video/mp2t",
+        RecordFormat::Mp4 => "video/mp4",
+    }
+}
+
+/// Resolve a `Range: bytes=...` header against a `len`-byte file as
+/// `(start, count)`; no header selects the whole file, `None` means 416.
+pub fn resolve_byte_range(header: Option<&str>, len: u64) -> Option<(u64, u64)> {
+    let Some(header) = header else {
+        return (len > 0).then_some((0, len));
+    };
+    let (first, last) = header.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = if first.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: u64 = last.trim().parse().ok()?;
+        (len.saturating_sub(suffix), len.checked_sub(1)?)
+    } else {
+        let start: u64 = first.trim().parse().ok()?;
+        let end = match last.trim() {
+            "" => len.checked_sub(1)?,
+            last => last.parse::<u64>().ok()?.min(len.checked_sub(1)?),
+        };
+        (start, end)
+    };
+    (start <= end && start < len).then(|| (start, end - start + 1))
+}
+
+/// Reader over `count` bytes of the recording file starting at `start`.
+pub async fn open_vod_segment(
+    entry: &RecordingEntry,
+    start: u64,
+    count: u64,
+) -> Result<tokio::io::Take<tokio::fs::File>> {
+    let mut file = tokio::fs::File::open(&entry.path).await?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    Ok(file.take(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::KeyframeCut;
+    use super::*;
+
+    fn entry(id: &str, format: RecordFormat, start: u64, end: u64) -> RecordingEntry {
+        RecordingEntry {
+            id: id.to_string(),
+            stream_id: "cam".to_string(),
+            session_id: "rec".to_string(),
+            format,
+            started_at_ms: start,
+            ended_at_ms: end,
+            duration_ms: end - start,
+            path: format!("{id}.bin"),
+            bytes: 4096,
+            video_frames: 1,
+            audio_frames: 0,
+            keyframes: 1,
+            status: "completed".to_string(),
+            header_bytes: 600,
+            discontinuity: false,
+            trigger: None,
+            cuts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn vod_playlist_splits_at_keyframe_cuts_and_trims_window() {
+        let mut first = entry("a", RecordFormat::Ts, 0, 18_000);
+        first.discontinuity = true;
+        first.cuts = vec![
+            KeyframeCut {
+                offset: 1_600,
+                at_ms: 6_000,
+            },
+            KeyframeCut {
+                offset: 2_600,
+                at_ms: 12_000,
+            },
+        ];
+        let second = entry("b", RecordFormat::Ts, 18_010, 24_000);
+        let third = entry("c", RecordFormat::Mp4, 30_000, 35_500);
+        let entries = vec![first, second, third];
+
+        let playlist = build_vod_playlist(&entries, None, None);
+        assert!(playlist.contains("#EXT-X-PLAYLIST-TYPE:VOD\r\n"));
+        assert!(playlist.contains("#EXT-X-VERSION:7\r\n"));
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:6\r\n"));
+        assert!(playlist.contains("#EXT-X-MAP:URI=\"segments/a.ts\",BYTERANGE=\"600@0\"\r\n"));
+        assert!(
+            playlist.contains("#EXTINF:6.000,\r\n#EXT-X-BYTERANGE:1000@600\r\nsegments/a.ts\r\n")
+        );
+        assert!(
+            playlist.contains("#EXTINF:6.000,\r\n#EXT-X-BYTERANGE:1496@2600\r\nsegments/a.ts\r\n")
+        );
+        assert!(
+            playlist.contains("#EXTINF:5.990,\r\n#EXT-X-BYTERANGE:3496@600\r\nsegments/b.ts\r\n")
+        );
+        assert_eq!(playlist.matches("#EXT-X-DISCONTINUITY\r\n").count(), 1);
+        assert!(playlist.contains(
+            "#EXT-X-DISCONTINUITY\r\n#EXT-X-MAP:URI=\"segments/c.mp4\",BYTERANGE=\"600@0\"\r\n"
+        ));
+        assert!(playlist.ends_with("segments/c.mp4\r\n#EXT-X-ENDLIST\r\n"));
+
+        let trimmed = build_vod_playlist(&entries, Some(7_000), Some(20_000));
+        assert_eq!(trimmed.matches("#EXTINF").count(), 3);
+        assert!(!trimmed.contains("#EXT-X-BYTERANGE:1000@600\r\n"));
+        assert!(trimmed.contains("#EXT-X-BYTERANGE:1000@1600\r\nsegments/a.ts\r\n"));
+        assert!(!trimmed.contains("segments/c.mp4"));
+    }
+
+    #[test]
+    fn parses_segment_filenames_and_byte_ranges() {
+        assert_eq!(
+            parse_vod_segment_filename("rec_1_2.ts"),
+            Some(("rec_1_2", RecordFormat::Ts))
+        );
+        assert_eq!(
+            parse_vod_segment_filename("rec_1_2.mp4"),
+            Some(("rec_1_2", RecordFormat::Mp4))
+        );
+        assert_eq!(parse_vod_segment_filename("rec_1_2.m4s"), None);
+
+        assert_eq!(resolve_byte_range(None, 100), Some((0, 100)));
+        assert_eq!(resolve_byte_range(Some("bytes=10-19"), 100), Some((10, 10)));
+        assert_eq!(resolve_byte_range(Some("bytes=90-"), 100), Some((90, 10)));
+        assert_eq!(resolve_byte_range(Some("bytes=-30"), 100), Some((70, 30)));
+        assert_eq!(
+            resolve_byte_range(Some("bytes=50-500"), 100),
+            Some((50, 50))
+        );
+        assert_eq!(resolve_byte_range(Some("bytes=100-"), 100), None);
+        assert_eq!(resolve_byte_range(Some("items=0-1"), 100), None);
+    }
+}
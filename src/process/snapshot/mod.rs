@@ -10,7 +10,7 @@ use tokio::process::Command;
 use tokio::time::{sleep, Instant};
 use tracing::{error, info, warn};
 
-use crate::core::live_play::{is_idr_frame, is_playable_video_frame, prepend_h264_config};
+use crate::core::live_play::{is_idr_frame, is_playable_video_frame, prepend_video_config};
 use crate::core::{CodecType, MediaFrame, StreamManager, DEFAULT_SNAPSHOT_DIR};
 use crate::server::webrtc::request_publisher_keyframe;
 
@@ -269,7 +269,7 @@ fn prepare_h264_snapshot_frame(
     stream_id: &str,
     frame: MediaFrame,
 ) -> MediaFrame {
-    let data = prepend_h264_config(manager, stream_id, &frame);
+    let data = prepend_video_config(manager, stream_id, &frame);
     let prepared = MediaFrame::new(
         frame.stream_id,
         frame.track_id,
@@ -0,0 +1,846 @@
+/// Fragmented MP4 (ISO BMFF) muxer
+/// Builds an init segment (ftyp + moov) and keyframe-aligned moof/mdat fragments.
+use crate::core::{h265, is_video_keyframe, CodecType, MediaFrame, Stream, AAC_DEFAULT_CLOCK_RATE};
+use crate::server::webrtc::h264_util::{
+    build_avcc, extract_sps_pps, iter_annex_b_nal_ranges, sps_dimensions, BitReader,
+};
+
+const VIDEO_TRACK_ID: u32 = 1;
+const AUDIO_TRACK_ID: u32 = 2;
+const VIDEO_TIMESCALE: u32 = 90_000;
+const MOVIE_TIMESCALE: u32 = 1_000;
+/// Samples per AAC frame; used for the trailing sample of a fragment.
+const AAC_FRAME_SAMPLES: u32 = 1024;
+/// Audio-only streams are cut into fragments of about this length.
+const AUDIO_ONLY_FRAGMENT_MS: u64 = 1_000;
+/// Fallback duration for the last video sample (25 fps).
+const DEFAULT_VIDEO_SAMPLE_DURATION: u32 = VIDEO_TIMESCALE / 25;
+
+const SAMPLE_FLAGS_SYNC: u32 = 0x0200_0000;
+const SAMPLE_FLAGS_NON_SYNC: u32 = 0x0101_0000;
+
+const IDENTITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// Decoder configuration for the video track sample entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoCodecConfig {
+    Avc {
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+    },
+    Hevc {
+        vps: Vec<u8>,
+        sps: Vec<u8>,
+        pps: Vec<u8>,
+    },
+}
+
+impl VideoCodecConfig {
+    /// Sample entry config from a keyframe that already carries its parameter sets.
+    pub fn from_keyframe(frame: &MediaFrame) -> Option<Self> {
+        if !is_video_keyframe(frame) {
+            return None;
+        }
+        match frame.codec {
+            CodecType::H264 => {
+                let (sps, pps) = extract_sps_pps(&frame.data);
+                Some(Self::Avc {
+                    sps: sps?,
+                    pps: pps?,
+                })
+            }
+            CodecType::H265 => {
+                let (vps, sps, pps) = h265::extract_parameter_sets(&frame.data);
+                Some(Self::Hevc {
+                    vps: vps?,
+                    sps: sps?,
+                    pps: pps?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Avc { sps, .. } => sps_dimensions(sps),
+            Self::Hevc { sps, .. } => h265::sps_dimensions(sps),
+        }
+        .unwrap_or((0, 0))
+    }
+}
+
+/// AAC AudioSpecificConfig plus the values mirrored into the `mp4a` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioCodecConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub asc: Vec<u8>,
+}
+
+impl AudioCodecConfig {
+    /// AAC-LC config for the given sample rate/channel count.
+    pub fn aac_lc(sample_rate: u32, channels: u16) -> Self {
+        let freq_index = aac_sample_rate_index(sample_rate);
+        let asc = vec![
+            (2 << 3) | (freq_index >> 1),
+            ((freq_index & 0x01) << 7) | ((channels as u8 & 0x0F) << 3),
+        ];
+        Self {
+            sample_rate,
+            channels,
+            asc,
+        }
+    }
+
+    /// Parse an AudioSpecificConfig (RTMP sequence header, SDP `config=`).
+    pub fn from_asc(asc: &[u8]) -> Option<Self> {
+        let mut bits = BitReader::new(asc);
+        if bits.read_bits(5)? == 31 {
+            bits.read_bits(6)?; // audioObjectTypeExt
+        }
+        let sample_rate = match bits.read_bits(4)? {
+            0x0F => bits.read_bits(24)?,
+            index => *AAC_SAMPLE_RATES.get(index as usize)?,
+        };
+        let channels = bits.read_bits(4)? as u16;
+        Some(Self {
+            sample_rate,
+            channels,
+            asc: asc.to_vec(),
+        })
+    }
+
+    /// Config of the stream's AAC track: the announced ASC, else AAC-LC stereo at
+    /// the track rate (44.1 kHz when that is not an AAC rate).
+    pub fn from_stream(stream: &Stream) -> Option<Self> {
+        let track = stream.tracks.iter().find(|t| t.codec == CodecType::AAC)?;
+        let announced = track
+            .audio_specific_config()
+            .and_then(|asc| Self::from_asc(&asc));
+        Some(announced.unwrap_or_else(|| {
+            if AAC_SAMPLE_RATES.contains(&track.clock_rate) {
+                Self::aac_lc(track.clock_rate, 2)
+            } else {
+                Self::default()
+            }
+        }))
+    }
+
+    /// Derive the config from an ADTS header, if the frame carries one.
+    pub fn from_adts(data: &[u8]) -> Option<Self> {
+        if data.len() < 7 || data[0] != 0xFF || data[1] & 0xF0 != 0xF0 {
+            return None;
+        }
+        let object_type = ((data[2] >> 6) & 0x03) + 1;
+        let freq_index = (data[2] >> 2) & 0x0F;
+        let channels = ((data[2] & 0x01) << 2) | (data[3] >> 6);
+        let sample_rate = *AAC_SAMPLE_RATES.get(freq_index as usize)?;
+        let asc = vec![
+            (object_type << 3) | (freq_index >> 1),
+            ((freq_index & 0x01) << 7) | (channels << 3),
+        ];
+        Some(Self {
+            sample_rate,
+            channels: channels as u16,
+            asc,
+        })
+    }
+
+    /// 7-byte ADTS header (no CRC) for a raw AAC frame of `payload_len` bytes.
+    pub fn adts_header(&self, payload_len: usize) -> [u8; 7] {
+        // ADTS only carries the 2-bit profile of object types 1-4 (LC = 2).
+        let object_type = self.asc.first().map(|b| b >> 3).unwrap_or(2);
+        let profile = if (1..=4).contains(&object_type) {
+            object_type - 1
+        } else {
+            1
+        };
+        let freq_index = aac_sample_rate_index(self.sample_rate);
+        let channels = (self.channels as u8) & 0x07;
+        let frame_len = payload_len + 7;
+        [
+            0xFF,
+            0xF1, // MPEG-4, layer 0, no CRC
+            (profile << 6) | (freq_index << 2) | (channels >> 2),
+            ((channels & 0x03) << 6) | ((frame_len >> 11) as u8 & 0x03),
+            ((frame_len >> 3) & 0xFF) as u8,
+            (((frame_len & 0x07) as u8) << 5) | 0x1F,
+            0xFC,
+        ]
+    }
+}
+
+impl Default for AudioCodecConfig {
+    fn default() -> Self {
+        Self::aac_lc(AAC_DEFAULT_CLOCK_RATE, 2)
+    }
+}
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025, 8_000,
+    7_350,
+];
+
+fn aac_sample_rate_index(sample_rate: u32) -> u8 {
+    AAC_SAMPLE_RATES
+        .iter()
+        .position(|rate| *rate == sample_rate)
+        .unwrap_or(4) as u8
+}
+
+#[derive(Debug)]
+struct Sample {
+    dts: u64,
+    data: Vec<u8>,
+    is_sync: bool,
+}
+
+/// Per-track fragment state.
+#[derive(Debug, Default)]
+struct TrackFragmenter {
+    samples: Vec<Sample>,
+    last_duration: Option<u32>,
+}
+
+impl TrackFragmenter {
+    fn span(&self) -> u64 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => last.dts - first.dts,
+            _ => 0,
+        }
+    }
+
+    /// Sample durations; the last one is bounded by `next_dts` when known.
+    fn durations(&mut self, next_dts: Option<u64>, fallback: u32) -> Vec<u32> {
+        let mut durations: Vec<u32> = self
+            .samples
+            .windows(2)
+            .map(|w| (w[1].dts.saturating_sub(w[0].dts)) as u32)
+            .collect();
+        let last = match (next_dts, self.samples.last()) {
+            (Some(next), Some(last)) if next > last.dts => (next - last.dts) as u32,
+            _ => durations
+                .last()
+                .copied()
+                .or(self.last_duration)
+                .unwrap_or(fallback),
+        };
+        if !self.samples.is_empty() {
+            durations.push(last);
+            self.last_duration = Some(last);
+        }
+        durations
+    }
+}
+
+/// Fragmented MP4 muxer (video track 1, audio track 2).
+///
+/// Frame timestamps are milliseconds, matching [`super::ts_muxer::TsMuxer`].
+pub struct Fmp4Muxer {
+    video: Option<VideoCodecConfig>,
+    audio: Option<AudioCodecConfig>,
+    sequence_number: u32,
+    video_track: TrackFragmenter,
+    audio_track: TrackFragmenter,
+    fragment_per_frame: bool,
+}
+
+impl Fmp4Muxer {
+    pub fn new() -> Self {
+        Self {
+            video: None,
+            audio: None,
+            sequence_number: 0,
+            video_track: TrackFragmenter::default(),
+            audio_track: TrackFragmenter::default(),
+            fragment_per_frame: false,
+        }
+    }
+
+    /// Close a fragment on every video frame instead of every keyframe (low-latency streaming).
+    pub fn set_fragment_per_frame(&mut self, enabled: bool) {
+        self.fragment_per_frame = enabled;
+    }
+
+    pub fn set_video_config(&mut self, config: VideoCodecConfig) {
+        self.video = Some(config);
+    }
+
+    pub fn set_audio_config(&mut self, config: AudioCodecConfig) {
+        self.audio = Some(config);
+    }
+
+    fn audio_timescale(&self) -> u32 {
+        self.audio
+            .as_ref()
+            .map(|a| a.sample_rate)
+            .unwrap_or(AAC_DEFAULT_CLOCK_RATE)
+    }
+
+    /// ftyp + moov describing the configured tracks.
+    pub fn init_segment(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let codec_brand: &[u8; 4] = match self.video {
+            Some(VideoCodecConfig::Hevc { .. }) => b"hvc1",
+            _ => b"avc1",
+        };
+        write_box(&mut out, b"ftyp", |b| {
+            b.extend_from_slice(b"isom");
+            b.extend_from_slice(&0x200u32.to_be_bytes());
+            for brand in [b"isom", b"iso6", b"mp41", codec_brand] {
+                b.extend_from_slice(brand);
+            }
+        });
+        write_box(&mut out, b"moov", |b| {
+            write_mvhd(b);
+            if let Some(video) = &self.video {
+                write_video_trak(b, video);
+            }
+            if let Some(audio) = &self.audio {
+                write_audio_trak(b, audio);
+            }
+            write_box(b, b"mvex", |b| {
+                if self.video.is_some() {
+                    write_trex(b, VIDEO_TRACK_ID);
+                }
+                if self.audio.is_some() {
+                    write_trex(b, AUDIO_TRACK_ID);
+                }
+            });
+        });
+        out
+    }
+
+    /// Queue a frame; returns a finished moof+mdat when a fragment closes.
+    ///
+    /// Video fragments close on each keyframe (or each frame, see
+    /// [`Self::set_fragment_per_frame`]); audio-only streams close roughly every second.
+    pub fn push_frame(&mut self, frame: &MediaFrame) -> Option<Vec<u8>> {
+        match frame.codec {
+            CodecType::H264 | CodecType::H265 if self.video.is_some() => {
+                let data = annex_b_to_length_prefixed(frame.codec, &frame.data);
+                if data.is_empty() {
+                    return None;
+                }
+                let is_sync = is_video_keyframe(frame);
+                let dts = frame.timestamp * (VIDEO_TIMESCALE / 1000) as u64;
+                let closes = is_sync || self.fragment_per_frame;
+                let fragment = if closes && !self.video_track.samples.is_empty() {
+                    self.build_fragment(Some(dts))
+                } else {
+                    None
+                };
+                self.video_track.samples.push(Sample { dts, data, is_sync });
+                fragment
+            }
+            CodecType::AAC if self.audio.is_some() => {
+                let data = strip_adts(&frame.data);
+                if data.is_empty() {
+                    return None;
+                }
+                let dts = frame.timestamp * self.audio_timescale() as u64 / 1000;
+                self.audio_track.samples.push(Sample {
+                    dts,
+                    data: data.to_vec(),
+                    is_sync: true,
+                });
+                let audio_only_due = self.video.is_none()
+                    && self.audio_track.span() * 1000 / self.audio_timescale() as u64
+                        >= AUDIO_ONLY_FRAGMENT_MS;
+                if audio_only_due {
+                    self.build_fragment(None)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Sample bytes waiting for the open fragment to close.
+    pub fn queued_bytes(&self) -> usize {
+        self.video_track
+            .samples
+            .iter()
+            .chain(&self.audio_track.samples)
+            .map(|sample| sample.data.len())
+            .sum()
+    }
+
+    /// Emit whatever is queued as a final fragment.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        self.build_fragment(None)
+    }
+
+    fn build_fragment(&mut self, next_video_dts: Option<u64>) -> Option<Vec<u8>> {
+        if self.video_track.samples.is_empty() && self.audio_track.samples.is_empty() {
+            return None;
+        }
+        self.sequence_number += 1;
+        let video_durations = self
+            .video_track
+            .durations(next_video_dts, DEFAULT_VIDEO_SAMPLE_DURATION);
+        let audio_durations = self.audio_track.durations(None, AAC_FRAME_SAMPLES);
+        let video_samples = std::mem::take(&mut self.video_track.samples);
+        let audio_samples = std::mem::take(&mut self.audio_track.samples);
+
+        let mut out = Vec::new();
+        let mut data_offset_fields = Vec::new();
+        write_box(&mut out, b"moof", |b| {
+            write_full_box(b, b"mfhd", 0, 0, |b| {
+                b.extend_from_slice(&self.sequence_number.to_be_bytes());
+            });
+            for (track_id, samples, durations) in [
+                (VIDEO_TRACK_ID, &video_samples, &video_durations),
+                (AUDIO_TRACK_ID, &audio_samples, &audio_durations),
+            ] {
+                if samples.is_empty() {
+                    continue;
+                }
+                write_box(b, b"traf", |b| {
+                    // default-base-is-moof
+                    write_full_box(b, b"tfhd", 0, 0x02_0000, |b| {
+                        b.extend_from_slice(&track_id.to_be_bytes());
+                    });
+                    write_full_box(b, b"tfdt", 1, 0, |b| {
+                        b.extend_from_slice(&samples[0].dts.to_be_bytes());
+                    });
+                    // data-offset, sample-duration, sample-size, sample-flags
+                    write_full_box(b, b"trun", 0, 0x00_0701, |b| {
+                        b.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+                        data_offset_fields.push(b.len());
+                        b.extend_from_slice(&0u32.to_be_bytes());
+                        for (sample, duration) in samples.iter().zip(durations) {
+                            let flags = if sample.is_sync {
+                                SAMPLE_FLAGS_SYNC
+                            } else {
+                                SAMPLE_FLAGS_NON_SYNC
+                            };
+                            b.extend_from_slice(&duration.to_be_bytes());
+                            b.extend_from_slice(&(sample.data.len() as u32).to_be_bytes());
+                            b.extend_from_slice(&flags.to_be_bytes());
+                        }
+                    });
+                });
+            }
+        });
+
+        // Patch trun data offsets: relative to moof start, past the mdat header.
+        let mut offset = out.len() as u32 + 8;
+        let track_sizes = [&video_samples, &audio_samples]
+            .into_iter()
+            .filter(|samples| !samples.is_empty())
+            .map(|samples| samples.iter().map(|s| s.data.len() as u32).sum::<u32>());
+        for (field, size) in data_offset_fields.into_iter().zip(track_sizes) {
+            out[field..field + 4].copy_from_slice(&offset.to_be_bytes());
+            offset += size;
+        }
+
+        write_box(&mut out, b"mdat", |b| {
+            for sample in video_samples.iter().chain(audio_samples.iter()) {
+                b.extend_from_slice(&sample.data);
+            }
+        });
+        Some(out)
+    }
+}
+
+impl Default for Fmp4Muxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Annex B → 4-byte length-prefixed NALUs; AUD and parameter sets live in the sample entry.
+fn annex_b_to_length_prefixed(codec: CodecType, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (start, end) in iter_annex_b_nal_ranges(data) {
+        let header = data[start];
+        let skip = match codec {
+            CodecType::H265 => matches!(
+                h265::nal_type(header),
+                h265::NAL_VPS | h265::NAL_SPS | h265::NAL_PPS | h265::NAL_AUD
+            ),
+            _ => matches!(header & 0x1F, 7..=9),
+        };
+        if skip {
+            continue;
+        }
+        out.extend_from_slice(&((end - start) as u32).to_be_bytes());
+        out.extend_from_slice(&data[start..end]);
+    }
+    out
+}
+
+/// Raw AAC access unit without the ADTS header.
+fn strip_adts(data: &[u8]) -> &[u8] {
+    if data.len() >= 7 && data[0] == 0xFF && data[1] & 0xF0 == 0xF0 {
+        let header_len = if data[1] & 0x01 == 0 { 9 } else { 7 };
+        return data.get(header_len..).unwrap_or_default();
+    }
+    data
+}
+
+/// Version 1 `emsg` box (absolute presentation time in ms) placed before a fragment.
+pub fn emsg_box(
+    presentation_time_ms: u64,
+    id: u32,
+    scheme_id_uri: &str,
+    message: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_full_box(&mut out, b"emsg", 1, 0, |b| {
+        b.extend_from_slice(&MOVIE_TIMESCALE.to_be_bytes());
+        b.extend_from_slice(&presentation_time_ms.to_be_bytes());
+        b.extend_from_slice(&0xFFFF_FFFFu32.to_be_bytes()); // Unknown duration
+        b.extend_from_slice(&id.to_be_bytes());
+        b.extend_from_slice(scheme_id_uri.as_bytes());
+        b.push(0);
+        b.push(0); // Empty value
+        b.extend_from_slice(message);
+    });
+    out
+}
+
+fn write_box(out: &mut Vec<u8>, box_type: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(box_type);
+    body(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    out: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, box_type, |b| {
+        b.extend_from_slice(&(((version as u32) << 24) | (flags & 0x00FF_FFFF)).to_be_bytes());
+        body(b);
+    });
+}
+
+fn write_matrix(b: &mut Vec<u8>) {
+    for v in IDENTITY_MATRIX {
+        b.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn write_mvhd(b: &mut Vec<u8>) {
+    write_full_box(b, b"mvhd", 0, 0, |b| {
+        b.extend_from_slice(&[0; 8]); // creation/modification time
+        b.extend_from_slice(&MOVIE_TIMESCALE.to_be_bytes());
+        b.extend_from_slice(&0u32.to_be_bytes()); // duration (fragmented)
+        b.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+        b.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+        b.extend_from_slice(&[0; 10]);
+        write_matrix(b);
+        b.extend_from_slice(&[0; 24]); // pre_defined
+        b.extend_from_slice(&(AUDIO_TRACK_ID + 1).to_be_bytes()); // next_track_ID
+    });
+}
+
+fn write_trex(b: &mut Vec<u8>, track_id: u32) {
+    write_full_box(b, b"trex", 0, 0, |b| {
+        b.extend_from_slice(&track_id.to_be_bytes());
+        b.extend_from_slice(&1u32.to_be_bytes()); // default_sample_description_index
+        b.extend_from_slice(&[0; 12]); // default duration/size/flags
+    });
+}
+
+fn write_tkhd(b: &mut Vec<u8>, track_id: u32, is_audio: bool, width: u32, height: u32) {
+    // track_enabled | track_in_movie
+    write_full_box(b, b"tkhd", 0, 0x03, |b| {
+        b.extend_from_slice(&[0; 8]);
+        b.extend_from_slice(&track_id.to_be_bytes());
+        b.extend_from_slice(&[0; 4]);
+        b.extend_from_slice(&0u32.to_be_bytes()); // duration
+        b.extend_from_slice(&[0; 8]);
+        b.extend_from_slice(&0u16.to_be_bytes()); // layer
+        b.extend_from_slice(&0u16.to_be_bytes()); // alternate_group
+        let volume: u16 = if is_audio { 0x0100 } else { 0 };
+        b.extend_from_slice(&volume.to_be_bytes());
+        b.extend_from_slice(&[0; 2]);
+        write_matrix(b);
+        b.extend_from_slice(&(width << 16).to_be_bytes());
+        b.extend_from_slice(&(height << 16).to_be_bytes());
+    });
+}
+
+fn write_mdhd(b: &mut Vec<u8>, timescale: u32) {
+    write_full_box(b, b"mdhd", 0, 0, |b| {
+        b.extend_from_slice(&[0; 8]);
+        b.extend_from_slice(&timescale.to_be_bytes());
+        b.extend_from_slice(&0u32.to_be_bytes());
+        b.extend_from_slice(&0x55C4u16.to_be_bytes()); // language "und"
+        b.extend_from_slice(&[0; 2]);
+    });
+}
+
+fn write_hdlr(b: &mut Vec<u8>, handler: &[u8; 4], name: &str) {
+    write_full_box(b, b"hdlr", 0, 0, |b| {
+        b.extend_from_slice(&[0; 4]);
+        b.extend_from_slice(handler);
+        b.extend_from_slice(&[0; 12]);
+        b.extend_from_slice(name.as_bytes());
+        b.push(0);
+    });
+}
+
+fn write_dinf(b: &mut Vec<u8>) {
+    write_box(b, b"dinf", |b| {
+        write_full_box(b, b"dref", 0, 0, |b| {
+            b.extend_from_slice(&1u32.to_be_bytes());
+            write_full_box(b, b"url ", 0, 0x01, |_| {}); // self-contained
+        });
+    });
+}
+
+/// stbl with one sample entry and empty sample tables (samples live in fragments).
+fn write_stbl(b: &mut Vec<u8>, sample_entry: impl FnOnce(&mut Vec<u8>)) {
+    write_box(b, b"stbl", |b| {
+        write_full_box(b, b"stsd", 0, 0, |b| {
+            b.extend_from_slice(&1u32.to_be_bytes());
+            sample_entry(b);
+        });
+        for box_type in [b"stts", b"stsc", b"stco"] {
+            write_full_box(b, box_type, 0, 0, |b| {
+                b.extend_from_slice(&0u32.to_be_bytes())
+            });
+        }
+        write_full_box(b, b"stsz", 0, 0, |b| b.extend_from_slice(&[0; 8]));
+    });
+}
+
+fn write_video_trak(b: &mut Vec<u8>, config: &VideoCodecConfig) {
+    let (width, height) = config.dimensions();
+    write_box(b, b"trak", |b| {
+        write_tkhd(b, VIDEO_TRACK_ID, false, width, height);
+        write_box(b, b"mdia", |b| {
+            write_mdhd(b, VIDEO_TIMESCALE);
+            write_hdlr(b, b"vide", "VideoHandler");
+            write_box(b, b"minf", |b| {
+                write_full_box(b, b"vmhd", 0, 0x01, |b| b.extend_from_slice(&[0; 8]));
+                write_dinf(b);
+                write_stbl(b, |b| write_visual_sample_entry(b, config, width, height));
+            });
+        });
+    });
+}
+
+fn write_visual_sample_entry(b: &mut Vec<u8>, config: &VideoCodecConfig, width: u32, height: u32) {
+    let (entry_type, config_type, record): (&[u8; 4], &[u8; 4], Vec<u8>) = match config {
+        VideoCodecConfig::Avc { sps, pps } => (b"avc1", b"avcC", build_avcc(sps, pps)),
+        VideoCodecConfig::Hevc { vps, sps, pps } => {
+            (b"hvc1", b"hvcC", h265::build_hvcc(vps, sps, pps))
+        }
+    };
+    write_box(b, entry_type, |b| {
+        b.extend_from_slice(&[0; 6]);
+        b.extend_from_slice(&1u16.to_be_bytes()); // data_reference_index
+        b.extend_from_slice(&[0; 16]); // pre_defined + reserved
+        b.extend_from_slice(&(width as u16).to_be_bytes());
+        b.extend_from_slice(&(height as u16).to_be_bytes());
+        b.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+        b.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        b.extend_from_slice(&[0; 4]);
+        b.extend_from_slice(&1u16.to_be_bytes()); // frame_count
+        b.extend_from_slice(&[0; 32]); // compressorname
+        b.extend_from_slice(&0x0018u16.to_be_bytes()); // depth
+        b.extend_from_slice(&(-1i16).to_be_bytes());
+        write_box(b, config_type, |b| b.extend_from_slice(&record));
+    });
+}
+
+fn write_audio_trak(b: &mut Vec<u8>, config: &AudioCodecConfig) {
+    write_box(b, b"trak", |b| {
+        write_tkhd(b, AUDIO_TRACK_ID, true, 0, 0);
+        write_box(b, b"mdia", |b| {
+            write_mdhd(b, config.sample_rate);
+            write_hdlr(b, b"soun", "SoundHandler");
+            write_box(b, b"minf", |b| {
+                write_full_box(b, b"smhd", 0, 0, |b| b.extend_from_slice(&[0; 4]));
+                write_dinf(b);
+                write_stbl(b, |b| write_mp4a_sample_entry(b, config));
+            });
+        });
+    });
+}
+
+fn write_mp4a_sample_entry(b: &mut Vec<u8>, config: &AudioCodecConfig) {
+    write_box(b, b"mp4a", |b| {
+        b.extend_from_slice(&[0; 6]);
+        b.extend_from_slice(&1u16.to_be_bytes()); // data_reference_index
+        b.extend_from_slice(&[0; 8]);
+        b.extend_from_slice(&config.channels.to_be_bytes());
+        b.extend_from_slice(&16u16.to_be_bytes()); // samplesize
+        b.extend_from_slice(&[0; 4]);
+        b.extend_from_slice(&(config.sample_rate << 16).to_be_bytes());
+        write_esds(b, &config.asc);
+    });
+}
+
+/// ES_Descriptor → DecoderConfigDescriptor (AAC, 0x40) → DecoderSpecificInfo (ASC).
+fn write_esds(b: &mut Vec<u8>, asc: &[u8]) {
+    write_full_box(b, b"esds", 0, 0, |b| {
+        let decoder_specific_len = 2 + asc.len();
+        let decoder_config_len = 13 + decoder_specific_len;
+        let es_len = 3 + 2 + decoder_config_len + 3;
+        b.extend_from_slice(&[0x03, es_len as u8]);
+        b.extend_from_slice(&(AUDIO_TRACK_ID as u16).to_be_bytes());
+        b.push(0x00); // flags
+        b.extend_from_slice(&[0x04, decoder_config_len as u8]);
+        b.push(0x40); // objectTypeIndication: MPEG-4 Audio
+        b.push(0x15); // streamType audio, upStream 0, reserved 1
+        b.extend_from_slice(&[0; 3]); // bufferSizeDB
+        b.extend_from_slice(&0u32.to_be_bytes()); // maxBitrate
+        b.extend_from_slice(&0u32.to_be_bytes()); // avgBitrate
+        b.extend_from_slice(&[0x05, asc.len() as u8]);
+        b.extend_from_slice(asc);
+        b.extend_from_slice(&[0x06, 0x01, 0x02]); // SLConfigDescriptor
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    const SPS: &[u8] = &[0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+    const PPS: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+
+    fn find_box(data: &[u8], box_type: &[u8; 4]) -> Option<usize> {
+        data.windows(4).position(|w| w == box_type).map(|i| i - 4)
+    }
+
+    fn video(ts: u64, nal: &[u8], keyframe: bool) -> MediaFrame {
+        let mut data = vec![0, 0, 0, 1];
+        data.extend_from_slice(nal);
+        MediaFrame::new(
+            "s".into(),
+            0,
+            ts,
+            Bytes::from(data),
+            keyframe,
+            CodecType::H264,
+        )
+    }
+
+    fn muxer() -> Fmp4Muxer {
+        let mut muxer = Fmp4Muxer::new();
+        muxer.set_video_config(VideoCodecConfig::Avc {
+            sps: SPS.to_vec(),
+            pps: PPS.to_vec(),
+        });
+        muxer.set_audio_config(AudioCodecConfig::default());
+        muxer
+    }
+
+    #[test]
+    fn init_segment_carries_avcc_and_esds() {
+        let init = muxer().init_segment();
+        assert_eq!(&init[4..8], b"ftyp");
+        let avcc = find_box(&init, b"avcC").unwrap();
+        assert_eq!(&init[avcc + 8..avcc + 12], &[0x01, 0x42, 0x00, 0x1F]);
+        let tkhd = find_box(&init, b"tkhd").unwrap();
+        assert_eq!(
+            &init[tkhd + 84..tkhd + 92],
+            &[0x05, 0x00, 0, 0, 0x02, 0xD0, 0, 0]
+        );
+        let esds = find_box(&init, b"esds").unwrap();
+        let asc_at = init[esds..]
+            .windows(2)
+            .position(|w| w == [0x05, 0x02])
+            .unwrap();
+        assert_eq!(&init[esds + asc_at + 2..esds + asc_at + 4], &[0x12, 0x10]);
+    }
+
+    #[test]
+    fn fragments_close_on_keyframes() {
+        let mut muxer = muxer();
+        let mut idr = vec![0x09, 0xF0, 0, 0, 0, 1];
+        idr.extend_from_slice(&[0x65, 0x88, 0x84]);
+        assert!(muxer.push_frame(&video(0, &idr, true)).is_none());
+        assert!(muxer.push_frame(&video(40, &[0x41, 0x9A], false)).is_none());
+        let fragment = muxer.push_frame(&video(80, &[0x65, 0x88], true)).unwrap();
+
+        let trun = find_box(&fragment, b"trun").unwrap();
+        assert_eq!(&fragment[trun + 12..trun + 16], &2u32.to_be_bytes());
+        let data_offset = u32::from_be_bytes(fragment[trun + 16..trun + 20].try_into().unwrap());
+        let mdat = find_box(&fragment, b"mdat").unwrap();
+        assert_eq!(data_offset as usize, mdat + 8);
+        // First sample: 40 ms at 90 kHz, 7 bytes (AUD stripped), sync flags.
+        assert_eq!(&fragment[trun + 20..trun + 24], &3600u32.to_be_bytes());
+        assert_eq!(&fragment[trun + 24..trun + 28], &7u32.to_be_bytes());
+        assert_eq!(
+            &fragment[trun + 28..trun + 32],
+            &SAMPLE_FLAGS_SYNC.to_be_bytes()
+        );
+        assert_eq!(
+            &fragment[mdat + 8..mdat + 15],
+            &[0, 0, 0, 3, 0x65, 0x88, 0x84]
+        );
+
+        let last = muxer.flush().unwrap();
+        let tfdt = find_box(&last, b"tfdt").unwrap();
+        assert_eq!(&last[tfdt + 12..tfdt + 20], &7200u64.to_be_bytes());
+    }
+
+    #[test]
+    fn per_frame_fragments_close_on_every_video_frame() {
+        let mut muxer = muxer();
+        muxer.set_fragment_per_frame(true);
+        assert!(muxer.push_frame(&video(0, &[0x65, 0x88], true)).is_none());
+        let first = muxer.push_frame(&video(40, &[0x41, 0x9A], false)).unwrap();
+        let second = muxer.push_frame(&video(80, &[0x41, 0x9B], false)).unwrap();
+
+        for (fragment, flags) in [
+            (&first, SAMPLE_FLAGS_SYNC),
+            (&second, SAMPLE_FLAGS_NON_SYNC),
+        ] {
+            let trun = find_box(fragment, b"trun").unwrap();
+            assert_eq!(&fragment[trun + 12..trun + 16], &1u32.to_be_bytes());
+            assert_eq!(&fragment[trun + 20..trun + 24], &3600u32.to_be_bytes());
+            assert_eq!(&fragment[trun + 28..trun + 32], &flags.to_be_bytes());
+        }
+        let tfdt = find_box(&second, b"tfdt").unwrap();
+        assert_eq!(&second[tfdt + 12..tfdt + 20], &3600u64.to_be_bytes());
+    }
+
+    #[test]
+    fn audio_config_from_asc() {
+        let config = AudioCodecConfig::from_asc(&[0x11, 0x90]).unwrap();
+        assert_eq!((config.sample_rate, config.channels), (48_000, 2));
+        assert_eq!(config.asc, vec![0x11, 0x90]);
+        let mono = AudioCodecConfig::from_asc(&[0x14, 0x08]).unwrap();
+        assert_eq!((mono.sample_rate, mono.channels), (16_000, 1));
+        assert!(AudioCodecConfig::from_asc(&[0x12]).is_none());
+    }
+
+    #[test]
+    fn audio_config_from_adts_header() {
+        let adts = [0xFF, 0xF1, 0x4C, 0x80, 0x01, 0x3F, 0xFC, 0xAA];
+        let config = AudioCodecConfig::from_adts(&adts).unwrap();
+        assert_eq!(config.sample_rate, 48_000);
+        assert_eq!(config.channels, 2);
+        assert_eq!(config.asc, vec![0x11, 0x90]);
+        assert_eq!(strip_adts(&adts), &[0xAA]);
+    }
+
+    #[test]
+    fn emsg_v1_carries_absolute_time_and_message() {
+        let emsg = emsg_box(12_345, 7, "urn:test", b"ID3");
+        assert_eq!(&emsg[..4], &(emsg.len() as u32).to_be_bytes());
+        assert_eq!(&emsg[4..8], b"emsg");
+        assert_eq!(emsg[8], 1);
+        assert_eq!(&emsg[12..16], &MOVIE_TIMESCALE.to_be_bytes());
+        assert_eq!(&emsg[16..24], &12_345u64.to_be_bytes());
+        assert_eq!(&emsg[28..32], &7u32.to_be_bytes());
+        assert!(emsg.ends_with(b"urn:test\0\0ID3"));
+    }
+}
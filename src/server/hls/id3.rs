@@ -0,0 +1,79 @@
+/// ID3 timed metadata for HLS.
+/// Cues are ID3v2.4 tags with one TXXX frame (description = cue kind, value =
+/// JSON payload), carried on a TS metadata PID or in fMP4 `emsg` boxes.
+use serde_json::Value;
+
+/// `emsg` scheme for ID3 tags in CMAF segments.
+pub const ID3_EMSG_SCHEME: &str = "https://aomedia.org/emsg/ID3";
+
+/// A metadata cue waiting to be muxed at the live edge.
+#[derive(Debug, Clone)]
+pub struct TimedMetadata {
+    /// Cue kind, e.g. "api" or an analysis event kind
+    pub kind: String,
+    pub payload: Value,
+}
+
+impl TimedMetadata {
+    pub fn new(kind: &str, payload: Value) -> Self {
+        Self {
+            kind: kind.to_string(),
+            payload,
+        }
+    }
+
+    /// ID3 tag carrying the JSON payload in a TXXX frame.
+    pub fn id3_tag(&self) -> Vec<u8> {
+        txxx_tag(&self.kind, &self.payload.to_string())
+    }
+}
+
+/// 28-bit synchsafe integer used for ID3v2.4 sizes.
+fn synchsafe(size: usize) -> [u8; 4] {
+    [
+        ((size >> 21) & 0x7F) as u8,
+        ((size >> 14) & 0x7F) as u8,
+        ((size >> 7) & 0x7F) as u8,
+        (size & 0x7F) as u8,
+    ]
+}
+
+/// ID3v2.4 tag with a single UTF-8 TXXX (user text) frame.
+pub fn txxx_tag(description: &str, value: &str) -> Vec<u8> {
+    let mut frame_body = vec![0x03]; // UTF-8
+    frame_body.extend_from_slice(description.as_bytes());
+    frame_body.push(0x00);
+    frame_body.extend_from_slice(value.as_bytes());
+
+    let mut frame = Vec::with_capacity(10 + frame_body.len());
+    frame.extend_from_slice(b"TXXX");
+    frame.extend_from_slice(&synchsafe(frame_body.len()));
+    frame.extend_from_slice(&[0x00, 0x00]); // Frame flags
+    frame.extend_from_slice(&frame_body);
+
+    let mut tag = Vec::with_capacity(10 + frame.len());
+    tag.extend_from_slice(b"ID3");
+    tag.extend_from_slice(&[0x04, 0x00, 0x00]); // v2.4.0, no flags
+    tag.extend_from_slice(&synchsafe(frame.len()));
+    tag.extend_from_slice(&frame);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn txxx_tag_layout() {
+        let cue = TimedMetadata::new("motion", json!({"box": [1, 2, 3, 4]}));
+        let tag = cue.id3_tag();
+        let value = br#"{"box":[1,2,3,4]}"#;
+        assert_eq!(&tag[..6], b"ID3\x04\x00\x00");
+        assert_eq!(tag.len(), 10 + 10 + 1 + b"motion\0".len() + value.len());
+        assert_eq!(&tag[6..10], &synchsafe(tag.len() - 10));
+        assert_eq!(&tag[10..14], b"TXXX");
+        assert!(tag.ends_with(b"motion\0{\"box\":[1,2,3,4]}"));
+        assert_eq!(synchsafe(200), [0, 0, 1, 72]);
+    }
+}
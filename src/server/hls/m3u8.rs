@@ -4,6 +4,12 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use tracing::debug;
 
+use super::encryption::{key_filename, HlsEncryptionMethod};
+use super::HlsSegmentFormat;
+
+/// fMP4 initialization segment referenced by `#EXT-X-MAP`.
+pub const INIT_SEGMENT_FILENAME: &str = "init.mp4";
+
 /// A single segment in the playlist
 #[derive(Debug, Clone)]
 pub struct Segment {
@@ -17,6 +23,36 @@ pub struct Segment {
     pub program_date_time: SystemTime,
     /// Whether this segment starts after a timestamp discontinuity
     pub discontinuity: bool,
+    /// LL-HLS partial segments making up this segment (empty in normal mode)
+    pub parts: Vec<Part>,
+}
+
+/// An LL-HLS partial segment (`#EXT-X-PART`)
+#[derive(Debug, Clone)]
+pub struct Part {
+    /// Media sequence number of the parent segment
+    pub sequence: u64,
+    /// Index within the parent segment
+    pub index: u32,
+    /// Duration in seconds
+    pub duration: f64,
+    /// Whether the part starts with a keyframe
+    pub independent: bool,
+}
+
+/// Completed segments whose parts are still listed (~3 target durations).
+const PART_LISTED_SEGMENTS: usize = 3;
+
+/// Which of the retained segments a playlist lists.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PlaylistWindow {
+    /// The last `max_segments` segments
+    #[default]
+    Live,
+    /// Time-shift: start about this many seconds behind the live edge
+    Offset(f64),
+    /// Time-shift: start at the segment covering this wall-clock time
+    Since(SystemTime),
 }
 
 /// M3U8 playlist generator
@@ -25,9 +61,11 @@ pub struct M3u8Generator {
     pub target_duration: f64,
     /// Maximum number of segments to keep in the playlist
     pub max_segments: usize,
-    /// Media sequence number (increments as segments are removed)
+    /// DVR history kept behind the live window, in seconds (0 = live window only)
+    dvr_window: f64,
+    /// Sequence of the oldest retained segment (increments as segments are removed)
     media_sequence: u64,
-    /// List of segments
+    /// Retained segments: DVR history followed by the live window
     segments: VecDeque<Segment>,
     /// Next segment sequence number
     next_sequence: u64,
@@ -35,6 +73,16 @@ pub struct M3u8Generator {
     discontinuity_sequence: u64,
     /// Whether the stream has ended
     ended: bool,
+    /// LL-HLS part target in seconds; `None` for plain HLS
+    part_target: Option<f64>,
+    /// Parts of the segment still being muxed
+    pending_parts: Vec<Part>,
+    /// Container of the listed segments and parts
+    format: HlsSegmentFormat,
+    /// First segment of the on-demand fMP4 rendition of a TS stream
+    fmp4_from: Option<u64>,
+    /// `#EXT-X-KEY` method and key rotation interval (segments)
+    encryption: Option<(HlsEncryptionMethod, u64)>,
 }
 
 impl M3u8Generator {
@@ -42,14 +90,49 @@ impl M3u8Generator {
         Self {
             target_duration,
             max_segments: max_segments.max(1),
+            dvr_window: 0.0,
             media_sequence: 0,
             segments: VecDeque::new(),
             next_sequence: 0,
             discontinuity_sequence: 0,
             ended: false,
+            part_target: None,
+            pending_parts: Vec::new(),
+            format: HlsSegmentFormat::Ts,
+            fmp4_from: None,
+            encryption: None,
         }
     }
 
+    /// Low-latency playlist with `#EXT-X-PART`s of about `part_target` seconds.
+    pub fn with_part_target(mut self, part_target: f64) -> Self {
+        self.part_target = Some(part_target);
+        self
+    }
+
+    /// List `format` segments (fMP4 playlists reference the init segment).
+    pub fn with_segment_format(mut self, format: HlsSegmentFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Keep about `dvr_window` seconds of segments for time-shifted playlists.
+    pub fn with_dvr_window(mut self, dvr_window: f64) -> Self {
+        self.dvr_window = dvr_window.max(0.0);
+        self
+    }
+
+    /// Advertise `#EXT-X-KEY` with a new key every `key_rotation_segments` segments.
+    pub fn set_encryption(&mut self, method: HlsEncryptionMethod, key_rotation_segments: u64) {
+        self.encryption = Some((method, key_rotation_segments.max(1)));
+    }
+
+    /// Stop listing LL-HLS parts (their media is not published).
+    pub fn disable_parts(&mut self) {
+        self.part_target = None;
+        self.pending_parts.clear();
+    }
+
     /// Get the target segment duration
     pub fn target_duration(&self) -> f64 {
         self.target_duration
@@ -66,20 +149,32 @@ impl M3u8Generator {
     ) {
         let filename = Self::segment_filename(sequence);
 
+        let parts = std::mem::take(&mut self.pending_parts)
+            .into_iter()
+            .filter(|part| part.sequence == sequence)
+            .collect();
         let segment = Segment {
             sequence,
             duration,
             filename,
             program_date_time,
             discontinuity,
+            parts,
         };
 
         self.segments.push_back(segment);
         self.next_sequence = sequence + 1;
 
+        // Drop the oldest segment once the rest still covers the DVR window.
+        let mut retained_secs: f64 = self.segments.iter().map(|s| s.duration).sum();
         while self.segments.len() > self.max_segments {
+            let oldest = self.segments[0].duration;
+            if retained_secs - oldest < self.dvr_window {
+                break;
+            }
             if let Some(removed) = self.segments.pop_front() {
-                self.media_sequence += 1;
+                retained_secs -= removed.duration;
+                self.media_sequence = removed.sequence + 1;
                 if removed.discontinuity {
                     self.discontinuity_sequence += 1;
                 }
@@ -87,24 +182,74 @@ impl M3u8Generator {
         }
 
         debug!(
-            "[HLS] Added segment seq={}, duration={:.2}s, media_seq={}, window={}",
+            "[HLS] Added segment seq={}, duration={:.2}s, media_seq={}, window={}, retained={:.1}s",
             sequence,
             duration,
-            self.media_sequence,
-            self.segments.len()
+            self.media_sequence(),
+            self.segment_count(),
+            retained_secs
         );
     }
 
-    fn playlist_target_duration(&self) -> u64 {
-        let observed = self
-            .segments
+    /// Add a part of the open segment `sequence` (which becomes the next `add_segment`).
+    pub fn add_part(&mut self, sequence: u64, index: u32, duration: f64, independent: bool) {
+        self.pending_parts.push(Part {
+            sequence,
+            index,
+            duration,
+            independent,
+        });
+    }
+
+    /// Segments from `sequence` on (and their parts) also exist as fMP4.
+    pub fn set_fmp4_from(&mut self, sequence: u64) {
+        self.fmp4_from.get_or_insert(sequence);
+    }
+
+    fn lists(&self, format: HlsSegmentFormat, sequence: u64) -> bool {
+        format == self.format
+            || (format == HlsSegmentFormat::Fmp4
+                && self.fmp4_from.is_some_and(|from| sequence >= from))
+    }
+
+    /// Whether a playlist in `format` would list any segment or part yet.
+    pub fn has_format_media(&self, format: HlsSegmentFormat) -> bool {
+        self.segments
+            .iter()
+            .map(|segment| segment.sequence)
+            .chain(self.pending_parts.iter().map(|part| part.sequence))
+            .any(|sequence| self.lists(format, sequence))
+    }
+
+    /// Drop parts of the open segment (its media was discarded after a lag snap).
+    pub fn discard_pending_parts(&mut self) {
+        self.pending_parts.clear();
+    }
+
+    /// Whether a blocking reload for `_HLS_msn`/`_HLS_part` can be answered:
+    /// segment `msn` is complete, or its part `part` has been published.
+    pub fn has_media(&self, msn: u64, part: Option<u32>) -> bool {
+        if msn < self.next_sequence {
+            return true;
+        }
+        match part {
+            Some(part) => self
+                .pending_parts
+                .iter()
+                .any(|p| p.sequence == msn && p.index >= part),
+            None => false,
+        }
+    }
+
+    fn playlist_target_duration(&self, segments: &[&Segment]) -> u64 {
+        let observed = segments
             .iter()
             .map(|s| s.duration)
             .fold(self.target_duration, f64::max);
         observed.ceil().max(1.0) as u64
     }
 
-    fn format_program_date_time(t: SystemTime) -> String {
+    pub(crate) fn format_program_date_time(t: SystemTime) -> String {
         let dur = t.duration_since(UNIX_EPOCH).unwrap_or_default();
         let total_ms = dur.as_millis();
         let secs = (total_ms / 1000) as i64;
@@ -127,39 +272,145 @@ impl M3u8Generator {
 
     /// Generate the M3U8 playlist content (live sliding window).
     pub fn generate(&self) -> String {
+        self.generate_window(self.format, PlaylistWindow::Live)
+    }
+
+    /// Index of the first segment listed for `window`; time-shifted windows
+    /// always include the live window and are clamped to the DVR history.
+    fn window_start(segments: &[&Segment], live_start: usize, window: PlaylistWindow) -> usize {
+        let start = match window {
+            PlaylistWindow::Live => live_start,
+            PlaylistWindow::Offset(secs) => {
+                let mut behind = 0.0;
+                segments
+                    .iter()
+                    .rposition(|segment| {
+                        behind += segment.duration;
+                        behind >= secs
+                    })
+                    .unwrap_or(0)
+            }
+            PlaylistWindow::Since(at) => segments
+                .iter()
+                .position(|segment| {
+                    segment.program_date_time + Duration::from_secs_f64(segment.duration) > at
+                })
+                .unwrap_or(live_start),
+        };
+        start.min(live_start)
+    }
+
+    /// Playlist over the `format` rendition listing the segments selected by
+    /// `window`; time-shifted playlists tell players to start at their first
+    /// segment. An fMP4 rendition of a TS stream starts at [`Self::set_fmp4_from`].
+    pub fn generate_window(&self, format: HlsSegmentFormat, window: PlaylistWindow) -> String {
         let mut output = String::new();
+        let retained: Vec<&Segment> = self
+            .segments
+            .iter()
+            .filter(|segment| self.lists(format, segment.sequence))
+            .collect();
+        let live_start = retained.len().saturating_sub(self.max_segments);
+        let segments = &retained[Self::window_start(&retained, live_start, window)..];
+        let media_sequence = segments
+            .first()
+            .map(|segment| segment.sequence)
+            .unwrap_or(self.next_sequence)
+            .max(self.media_sequence);
+        // Discontinuities in retained segments ahead of the window (or of the
+        // fMP4 rendition) still count.
+        let discontinuity_sequence = self.discontinuity_sequence
+            + self
+                .segments
+                .iter()
+                .filter(|segment| segment.sequence < media_sequence && segment.discontinuity)
+                .count() as u64;
 
         output.push_str("#EXTM3U\r\n");
-        output.push_str("#EXT-X-VERSION:3\r\n");
+        let version = match (format, self.part_target) {
+            (HlsSegmentFormat::Fmp4, _) => 7,
+            (HlsSegmentFormat::Ts, Some(_)) => 6,
+            (HlsSegmentFormat::Ts, None) => 3,
+        };
+        let version = match self.encryption {
+            Some((HlsEncryptionMethod::SampleAes, _)) => version.max(5),
+            _ => version,
+        };
+        output.push_str(&format!("#EXT-X-VERSION:{}\r\n", version));
         output.push_str("#EXT-X-INDEPENDENT-SEGMENTS\r\n");
         output.push_str(&format!(
             "#EXT-X-TARGETDURATION:{}\r\n",
-            self.playlist_target_duration()
-        ));
-        output.push_str(&format!(
-            "#EXT-X-MEDIA-SEQUENCE:{}\r\n",
-            self.media_sequence
+            self.playlist_target_duration(segments)
         ));
-        if self.discontinuity_sequence > 0 {
+        if window != PlaylistWindow::Live {
+            output.push_str("#EXT-X-START:TIME-OFFSET=0,PRECISE=YES\r\n");
+        }
+        if let Some(part_target) = self.part_target {
+            output.push_str(&format!(
+                "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK={:.3}\r\n",
+                part_target * 3.0
+            ));
+            output.push_str(&format!(
+                "#EXT-X-PART-INF:PART-TARGET={:.3}\r\n",
+                part_target
+            ));
+        }
+        output.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\r\n", media_sequence));
+        if discontinuity_sequence > 0 {
             output.push_str(&format!(
                 "#EXT-X-DISCONTINUITY-SEQUENCE:{}\r\n",
-                self.discontinuity_sequence
+                discontinuity_sequence
             ));
         }
+        if format == HlsSegmentFormat::Fmp4 {
+            output.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\r\n", INIT_SEGMENT_FILENAME));
+        }
 
-        for segment in &self.segments {
+        let parts_from = segments.len().saturating_sub(PART_LISTED_SEGMENTS);
+        let mut listed_key = None;
+        for (i, segment) in segments.iter().enumerate() {
             if segment.discontinuity {
                 output.push_str("#EXT-X-DISCONTINUITY\r\n");
             }
+            self.push_key(&mut output, segment.sequence, &mut listed_key);
             output.push_str(&format!(
                 "#EXT-X-PROGRAM-DATE-TIME:{}\r\n",
                 Self::format_program_date_time(segment.program_date_time)
             ));
+            if i >= parts_from {
+                Self::push_parts(&mut output, format, &segment.parts);
+            }
             output.push_str(&format!("#EXTINF:{:.3},\r\n", segment.duration));
-            output.push_str(&segment.filename);
+            match format {
+                HlsSegmentFormat::Ts => output.push_str(&segment.filename),
+                HlsSegmentFormat::Fmp4 => {
+                    output.push_str(&Self::media_filename(format, segment.sequence, None))
+                }
+            }
             output.push_str("\r\n");
         }
 
+        if self.part_target.is_some() && !self.ended {
+            let pending: Vec<Part> = self
+                .pending_parts
+                .iter()
+                .filter(|part| self.lists(format, part.sequence))
+                .cloned()
+                .collect();
+            if let Some(part) = pending.first() {
+                self.push_key(&mut output, part.sequence, &mut listed_key);
+            }
+            Self::push_parts(&mut output, format, &pending);
+            let (sequence, index) = pending
+                .last()
+                .map(|part| (part.sequence, part.index + 1))
+                .unwrap_or((self.next_sequence, 0));
+            output.push_str(&format!(
+                "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"{}\"\r\n",
+                Self::media_filename(format, sequence, Some(index))
+            ));
+        }
+
         if self.ended {
             output.push_str("#EXT-X-ENDLIST\r\n");
         }
@@ -167,14 +418,57 @@ impl M3u8Generator {
         output
     }
 
-    /// Get the current media sequence number
+    /// `#EXT-X-KEY` ahead of the first media using the key of `sequence`.
+    fn push_key(&self, output: &mut String, sequence: u64, listed: &mut Option<u64>) {
+        let Some((method, rotation)) = self.encryption else {
+            return;
+        };
+        let index = sequence / rotation;
+        if *listed == Some(index) {
+            return;
+        }
+        *listed = Some(index);
+        output.push_str(&format!(
+            "#EXT-X-KEY:METHOD={},URI=\"{}\"\r\n",
+            method.as_m3u8(),
+            key_filename(index)
+        ));
+    }
+
+    fn push_parts(output: &mut String, format: HlsSegmentFormat, parts: &[Part]) {
+        for part in parts {
+            output.push_str(&format!(
+                "#EXT-X-PART:DURATION={:.3},URI=\"{}\"{}\r\n",
+                part.duration,
+                Self::media_filename(format, part.sequence, Some(part.index)),
+                if part.independent {
+                    ",INDEPENDENT=YES"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+
+    /// Get the media sequence number of the live window
     pub fn media_sequence(&self) -> u64 {
-        self.media_sequence
+        self.segments
+            .get(self.segments.len().saturating_sub(self.max_segments))
+            .map(|segment| segment.sequence)
+            .unwrap_or(self.next_sequence)
     }
 
-    /// Get the number of segments
+    /// Sequence of the oldest retained segment (DVR history included)
+    pub fn oldest_sequence(&self) -> u64 {
+        self.segments
+            .front()
+            .map(|segment| segment.sequence)
+            .unwrap_or(self.next_sequence)
+    }
+
+    /// Get the number of segments in the live window
     pub fn segment_count(&self) -> usize {
-        self.segments.len()
+        self.segments.len().min(self.max_segments)
     }
 
     /// Mark the stream as ended
@@ -192,6 +486,24 @@ impl M3u8Generator {
         format!("segment_{sequence}.ts")
     }
 
+    /// Segment (or part) filename in `format`: `segment_<sequence>[.<part>].<ts|m4s>`.
+    pub fn media_filename(format: HlsSegmentFormat, sequence: u64, part: Option<u32>) -> String {
+        let ext = format.extension();
+        match part {
+            Some(index) => format!("segment_{sequence}.{index}.{ext}"),
+            None => format!("segment_{sequence}.{ext}"),
+        }
+    }
+
+    /// Append `?token=` to `#EXT-X-KEY` URIs so key requests carry the viewer's
+    /// playback token; tokens with unexpected characters are not echoed.
+    pub fn with_key_token(playlist: String, token: &str) -> String {
+        if token.is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') {
+            return playlist;
+        }
+        playlist.replace(".key\"", &format!(".key?token={token}\""))
+    }
+
     /// Slot-based segment filename (legacy).
     pub fn slot_filename(sequence: u64, max_segments: usize) -> String {
         Self::segment_filename(sequence)
@@ -247,4 +559,136 @@ mod tests {
         assert!(pl.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1"));
         assert!(!pl.contains("#EXT-X-DISCONTINUITY\r\n"));
     }
+
+    #[test]
+    fn low_latency_playlist_lists_parts_and_preload_hint() {
+        let mut gen = M3u8Generator::new(1.0, 3).with_part_target(0.25);
+        let now = SystemTime::now();
+        gen.add_part(0, 0, 0.25, true);
+        gen.add_part(0, 1, 0.25, false);
+        assert!(gen.has_media(0, Some(1)));
+        assert!(!gen.has_media(0, Some(2)));
+        assert!(!gen.has_media(0, None));
+
+        gen.add_segment(0.5, 0, now, false);
+        gen.add_part(1, 0, 0.25, true);
+        let pl = gen.generate();
+        assert!(pl.contains("#EXT-X-VERSION:6"));
+        assert!(pl.contains("#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=0.750"));
+        assert!(pl.contains("#EXT-X-PART-INF:PART-TARGET=0.250"));
+        assert!(pl.contains("#EXT-X-PART:DURATION=0.250,URI=\"segment_0.0.ts\",INDEPENDENT=YES"));
+        assert!(pl.contains("#EXT-X-PART:DURATION=0.250,URI=\"segment_0.1.ts\"\r\n"));
+        assert!(pl.contains("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment_1.1.ts\""));
+        let part = pl.find("segment_0.1.ts").unwrap();
+        let extinf = pl.find("#EXTINF:0.500,").unwrap();
+        assert!(part < extinf, "parts precede their segment's EXTINF");
+        assert!(gen.has_media(0, Some(5)));
+    }
+
+    #[test]
+    fn fmp4_playlist_references_init_segment() {
+        let mut gen = M3u8Generator::new(1.0, 4).with_segment_format(HlsSegmentFormat::Fmp4);
+        assert!(!gen.has_format_media(HlsSegmentFormat::Fmp4));
+        let now = SystemTime::now();
+        gen.add_segment(1.0, 0, now, false);
+        gen.add_segment(1.0, 1, now, true);
+        let pl = gen.generate();
+        assert!(gen.has_format_media(HlsSegmentFormat::Fmp4));
+        assert!(pl.contains("#EXT-X-VERSION:7"));
+        assert!(pl.contains("#EXT-X-MAP:URI=\"init.mp4\""));
+        assert!(pl.contains("#EXT-X-MEDIA-SEQUENCE:0"));
+        assert!(pl.contains("segment_0.m4s"));
+        assert!(pl.contains("segment_1.m4s"));
+        assert!(!pl.contains(".ts"));
+    }
+
+    #[test]
+    fn fmp4_rendition_starts_at_first_fmp4_segment() {
+        let mut gen = M3u8Generator::new(1.0, 4);
+        let now = SystemTime::now();
+        gen.add_segment(1.0, 0, now, false);
+        gen.add_segment(1.0, 1, now, true);
+        assert!(!gen.has_format_media(HlsSegmentFormat::Fmp4));
+
+        gen.set_fmp4_from(2);
+        gen.add_segment(1.0, 2, now, false);
+        let pl = gen.generate_window(HlsSegmentFormat::Fmp4, PlaylistWindow::Live);
+        assert!(pl.contains("#EXT-X-VERSION:7"));
+        assert!(pl.contains("#EXT-X-MAP:URI=\"init.mp4\""));
+        assert!(pl.contains("#EXT-X-MEDIA-SEQUENCE:2"));
+        assert!(pl.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1"));
+        assert!(pl.contains("segment_2.m4s"));
+        assert!(!pl.contains("segment_1"));
+
+        let ts = gen.generate();
+        assert!(ts.contains("#EXT-X-MEDIA-SEQUENCE:0"));
+        assert!(ts.contains("segment_2.ts"));
+        assert!(!ts.contains("#EXT-X-MAP"));
+    }
+
+    #[test]
+    fn encrypted_playlist_rotates_keys() {
+        let mut gen = M3u8Generator::new(1.0, 4);
+        gen.set_encryption(HlsEncryptionMethod::Aes128, 2);
+        let now = SystemTime::now();
+        for seq in 0..5 {
+            gen.add_segment(1.0, seq, now, false);
+        }
+        let pl = gen.generate();
+        // Segments 1..=4 use keys 0, 1, 1, 2
+        assert_eq!(pl.matches("#EXT-X-KEY:").count(), 3);
+        assert!(pl.find("key_0.key").unwrap() < pl.find("segment_1.ts").unwrap());
+        let key1 = pl
+            .find("#EXT-X-KEY:METHOD=AES-128,URI=\"key_1.key\"")
+            .unwrap();
+        assert!(key1 < pl.find("segment_2.ts").unwrap());
+        let key2 = pl.find("URI=\"key_2.key\"").unwrap();
+        assert!(pl.find("segment_3.ts").unwrap() < key2);
+        assert!(key2 < pl.find("segment_4.ts").unwrap());
+
+        let signed = M3u8Generator::with_key_token(pl.clone(), "123.abc");
+        assert!(signed.contains("URI=\"key_1.key?token=123.abc\""));
+        assert_eq!(M3u8Generator::with_key_token(pl.clone(), "\"x"), pl);
+
+        gen.set_encryption(HlsEncryptionMethod::SampleAes, 2);
+        assert!(gen.generate().contains("#EXT-X-VERSION:5"));
+    }
+
+    #[test]
+    fn dvr_window_keeps_history_behind_short_live_playlist() {
+        let mut gen = M3u8Generator::new(1.0, 2).with_dvr_window(5.0);
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for seq in 0..10u64 {
+            let pdt = t0 + Duration::from_secs(seq);
+            gen.add_segment(1.0, seq, pdt, seq == 6);
+        }
+        assert_eq!(gen.oldest_sequence(), 5);
+        assert_eq!(gen.segment_count(), 2);
+
+        let live = gen.generate();
+        assert!(live.contains("#EXT-X-MEDIA-SEQUENCE:8"));
+        assert!(live.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1"));
+        assert!(!live.contains("#EXT-X-START"));
+        assert!(!live.contains("segment_7.ts"));
+
+        let shifted = gen.generate_window(HlsSegmentFormat::Ts, PlaylistWindow::Offset(3.0));
+        assert!(shifted.contains("#EXT-X-START:TIME-OFFSET=0,PRECISE=YES"));
+        assert!(shifted.contains("#EXT-X-MEDIA-SEQUENCE:7"));
+        assert!(shifted.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1"));
+        assert!(shifted.contains("segment_9.ts"));
+
+        let since = t0 + Duration::from_millis(6_500);
+        let shifted = gen.generate_window(HlsSegmentFormat::Ts, PlaylistWindow::Since(since));
+        assert!(shifted.contains("#EXT-X-MEDIA-SEQUENCE:6"));
+        assert!(!shifted.contains("#EXT-X-DISCONTINUITY-SEQUENCE"));
+        assert!(shifted.contains("#EXT-X-DISCONTINUITY\r\n"));
+        assert!(shifted.contains(&format!(
+            "#EXT-X-PROGRAM-DATE-TIME:{}",
+            M3u8Generator::format_program_date_time(t0 + Duration::from_secs(6))
+        )));
+
+        // Further back than the DVR history starts at the oldest retained segment
+        let oldest = gen.generate_window(HlsSegmentFormat::Ts, PlaylistWindow::Offset(3600.0));
+        assert!(oldest.contains("#EXT-X-MEDIA-SEQUENCE:5"));
+    }
 }
@@ -1,22 +1,39 @@
 /// HLS (HTTP Live Streaming) module
 /// Subscribes to streams and generates HLS segments (MPEG-TS) with M3U8 playlists.
+pub mod encryption;
+pub mod fmp4_muxer;
+pub mod id3;
 pub mod m3u8;
+pub mod mpd;
+pub mod puller;
+pub mod store;
 pub mod timing;
+pub mod ts_demuxer;
 pub mod ts_muxer;
 
 use anyhow::Result;
+use bytes::Bytes;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tokio::fs;
+use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
-use self::m3u8::M3u8Generator;
+use self::encryption::{
+    encrypt_segment, sequence_iv, HlsEncryptionConfig, HlsEncryptionMethod, SegmentKeys,
+};
+use self::fmp4_muxer::{emsg_box, AudioCodecConfig, Fmp4Muxer, VideoCodecConfig};
+use self::id3::{TimedMetadata, ID3_EMSG_SCHEME};
+use self::m3u8::{M3u8Generator, PlaylistWindow, INIT_SEGMENT_FILENAME};
+use self::mpd::{MpdGenerator, AUDIO_INIT_FILENAME, VIDEO_INIT_FILENAME};
+use self::store::{media_sequence, MemorySegmentStore};
 use self::ts_muxer::TsMuxer;
 use crate::core::dispatch::DispatchError;
-use crate::core::{CodecType, DispatchPolicy, MediaFrame, StreamManager, DEFAULT_HLS_DIR, MILLISECOND_CLOCK_RATE};
+use crate::core::{h265, CodecType, DispatchPolicy, MediaFrame, StreamManager, DEFAULT_HLS_DIR, MILLISECOND_CLOCK_RATE};
+use crate::process::analysis::AnalysisEvent;
 use crate::server::webrtc::{
     annex_b_with_config, h264_util::is_keyframe_annex_b, request_publisher_keyframe,
 };
@@ -27,16 +44,77 @@ use self::timing::{
 };
 /// PAT/PMT only; segments smaller than this are not committed.
 const MIN_SEGMENT_BYTES: usize = 512;
+/// LL-HLS part target (seconds) when not configured.
+pub const DEFAULT_PART_DURATION: f64 = 0.25;
+/// Memory store cap (MiB) when not configured.
+pub const DEFAULT_MEMORY_LIMIT_MB: u64 = 256;
+/// LL-HLS playlists keep at least this many segments so parts cover ~3 target durations.
+const LL_MIN_SEGMENTS: usize = 3;
+/// Timed metadata cues queued per stream before the oldest is dropped.
+const MAX_PENDING_METADATA: usize = 64;
 
 fn is_hls_video_keyframe(frame: &MediaFrame) -> bool {
-    matches!(frame.codec, CodecType::H264 | CodecType::H265)
-        && (frame.is_keyframe || is_keyframe_annex_b(&frame.data))
+    match frame.codec {
+        CodecType::H264 => frame.is_keyframe || is_keyframe_annex_b(&frame.data),
+        CodecType::H265 => frame.is_keyframe || h265::contains_irap_nalu(&frame.data),
+        _ => false,
+    }
 }
 
 fn audio_timestamp_delta_ms(prev: u64, curr: u64, clock_rate: Option<u32>) -> u64 {
     crate::core::media_timestamp_delta_ms_with_clock(prev, curr, clock_rate)
 }
 
+/// Container of HLS media segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HlsSegmentFormat {
+    /// MPEG-TS segments
+    #[default]
+    Ts,
+    /// CMAF fragmented MP4 (`#EXT-X-MAP` init segment + moof/mdat segments)
+    Fmp4,
+}
+
+impl HlsSegmentFormat {
+    /// Parse a config or query value: "ts", or "fmp4"/"cmaf"/"mp4".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "ts" | "mpegts" => Some(Self::Ts),
+            "fmp4" | "cmaf" | "mp4" => Some(Self::Fmp4),
+            _ => None,
+        }
+    }
+
+    /// Media segment file extension.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ts => "ts",
+            Self::Fmp4 => "m4s",
+        }
+    }
+}
+
+/// Where segment files are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HlsStorage {
+    /// Files under `output_dir/<stream_id>`
+    #[default]
+    Disk,
+    /// `Bytes` buffers in a shared store capped at `memory_limit_mb`
+    Memory,
+}
+
+impl HlsStorage {
+    /// Parse a config value: "disk" or "memory".
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "disk" | "file" => Some(Self::Disk),
+            "memory" | "mem" => Some(Self::Memory),
+            _ => None,
+        }
+    }
+}
+
 /// HLS configuration
 #[derive(Debug, Clone)]
 pub struct HlsConfig {
@@ -44,6 +122,37 @@ pub struct HlsConfig {
     pub segment_duration: f64,
     pub max_segments: usize,
     pub output_dir: String,
+    /// LL-HLS: publish `#EXT-X-PART`s and answer blocking playlist reloads.
+    pub low_latency: bool,
+    /// LL-HLS part target in seconds.
+    pub part_duration: f64,
+    /// Segment container; a TS stream also muxes an fMP4 rendition once a
+    /// playlist asks for `?format=fmp4`.
+    pub segment_format: HlsSegmentFormat,
+    /// Segment storage; memory mode does no disk I/O.
+    pub storage: HlsStorage,
+    /// Total memory cap across streams for `HlsStorage::Memory` (MiB).
+    pub memory_limit_mb: u64,
+    /// DVR history kept behind the live window for time-shift, in seconds (0 = off).
+    pub dvr_window: f64,
+    /// Per-stream DVR window overrides keyed by stream id.
+    pub dvr_stream_windows: HashMap<String, f64>,
+    /// Segment encryption for matching streams.
+    pub encryption: Option<HlsEncryptionConfig>,
+    /// Carry ID3 timed metadata (TS metadata PID, `emsg` in fMP4).
+    pub timed_metadata: bool,
+    /// Analysis event kinds forwarded as timed metadata.
+    pub metadata_events: Vec<String>,
+}
+
+impl HlsConfig {
+    /// DVR window of `stream_id` in seconds.
+    pub fn dvr_window_for(&self, stream_id: &str) -> f64 {
+        self.dvr_stream_windows
+            .get(stream_id)
+            .copied()
+            .unwrap_or(self.dvr_window)
+    }
 }
 
 impl Default for HlsConfig {
@@ -53,6 +162,16 @@ impl Default for HlsConfig {
             segment_duration: 1.0,
             max_segments: 1,
             output_dir: DEFAULT_HLS_DIR.to_string(),
+            low_latency: false,
+            part_duration: DEFAULT_PART_DURATION,
+            segment_format: HlsSegmentFormat::Ts,
+            storage: HlsStorage::Disk,
+            memory_limit_mb: DEFAULT_MEMORY_LIMIT_MB,
+            dvr_window: 0.0,
+            dvr_stream_windows: HashMap::new(),
+            encryption: None,
+            timed_metadata: false,
+            metadata_events: Vec::new(),
         }
     }
 }
@@ -62,8 +181,12 @@ struct HlsSession {
     stream_id: String,
     muxer: TsMuxer,
     playlist: M3u8Generator,
+    /// Container muxed for HLS (TS for encrypted streams)
+    segment_format: HlsSegmentFormat,
     /// Current segment buffer
     segment_buffer: Vec<u8>,
+    /// Whether the open segment has been started (TS: PAT/PMT written)
+    segment_open: bool,
     /// Current segment duration accumulator (from video timestamps)
     segment_duration_acc: f64,
     /// Output directory for this stream
@@ -100,9 +223,107 @@ struct HlsSession {
     session_pdt_anchor: Option<SystemTime>,
     /// Emit #EXT-X-DISCONTINUITY on the next committed segment (lag snap)
     pending_discontinuity: bool,
+    /// LL-HLS part target (ms); `None` when parts are disabled
+    part_target_ms: Option<u64>,
+    /// Media sequence of the open segment (parent of its parts)
+    part_sequence: u64,
+    /// Index of the open part within the open segment
+    part_index: u32,
+    /// Offset in `segment_buffer` where the open part starts
+    part_offset: usize,
+    /// Session mux ms when the open part started
+    part_open_mux_ms: u64,
+    /// Whether the open part starts with a keyframe
+    part_independent: bool,
+    /// Whether the open part holds media beyond PAT/PMT
+    part_has_media: bool,
+    /// Duration of the parts already cut from the open segment
+    parts_secs: f64,
+    /// Parts cut since the last `take_completed_parts`
+    completed_parts: Vec<CompletedPart>,
+    /// fMP4 segment muxer, started on the first keyframe with parameter sets
+    /// (on a TS stream: the fMP4 rendition, once requested)
+    fmp4: Option<Fmp4Muxer>,
+    /// Mux an fMP4 rendition of a TS stream (a `?format=fmp4` playlist request)
+    fmp4_requested: bool,
+    /// Open segment of the fMP4 rendition
+    fmp4_buffer: Vec<u8>,
+    /// Offset in `fmp4_buffer` where the open part starts
+    fmp4_part_offset: usize,
+    /// Mux per-track fMP4 for DASH (a manifest request)
+    dash_requested: bool,
+    /// DASH output, started on a segment boundary once requested
+    dash: Option<DashOutput>,
+    /// DASH manifest over committed DASH segments
+    mpd: Option<MpdGenerator>,
+    /// Init segments (filename, data) waiting to be written to disk
+    pending_inits: Vec<(&'static str, Vec<u8>)>,
+    /// AAC config of the stream's audio track (hub AAC carries no ADTS header)
+    audio_config: Option<AudioCodecConfig>,
+    /// Content keys when this stream is encrypted
+    encryption: Option<SegmentKeys>,
+    /// Timed metadata cues muxed with the next frame
+    pending_metadata: Vec<TimedMetadata>,
+    /// `emsg` id of the next cue
+    next_metadata_id: u32,
 }
 
-/// A completed TS segment ready to write to disk.
+/// Per-track fMP4 of the open segment for DASH adaptation sets.
+struct DashOutput {
+    video: Fmp4Muxer,
+    audio: Option<Fmp4Muxer>,
+    video_buffer: Vec<u8>,
+    audio_buffer: Vec<u8>,
+    /// Mux ms of the first video sample in the open segment
+    start_ms: Option<u64>,
+}
+
+impl DashOutput {
+    fn push(&mut self, mux_frame: &MediaFrame) {
+        if mux_frame.codec.is_video() {
+            self.start_ms.get_or_insert(mux_frame.timestamp);
+            if let Some(fragment) = self.video.push_frame(mux_frame) {
+                self.video_buffer.extend(fragment);
+            }
+        } else if let Some(audio) = self.audio.as_mut() {
+            if let Some(fragment) = audio.push_frame(mux_frame) {
+                self.audio_buffer.extend(fragment);
+            }
+        }
+    }
+
+    /// Close the open segment, which ends where the next one starts (`end_ms`).
+    fn take_segment(&mut self, end_ms: u64) -> Option<DashSegment> {
+        self.video_buffer
+            .extend(self.video.flush().unwrap_or_default());
+        if let Some(audio) = self.audio.as_mut() {
+            self.audio_buffer.extend(audio.flush().unwrap_or_default());
+        }
+        let video = std::mem::take(&mut self.video_buffer);
+        let audio = std::mem::take(&mut self.audio_buffer);
+        let start_ms = self.start_ms.take()?;
+        Some(DashSegment {
+            video,
+            audio: self.audio.is_some().then_some(audio),
+            start_ms,
+            duration_ms: end_ms.saturating_sub(start_ms),
+        })
+    }
+
+    fn discard(&mut self) {
+        let _ = self.take_segment(0);
+    }
+}
+
+/// A completed DASH segment (one file per track).
+struct DashSegment {
+    video: Vec<u8>,
+    audio: Option<Vec<u8>>,
+    start_ms: u64,
+    duration_ms: u64,
+}
+
+/// A completed TS or fMP4 segment ready to write to disk.
 struct CompletedSegment {
     data: Vec<u8>,
     filename: String,
@@ -110,6 +331,23 @@ struct CompletedSegment {
     seq: u64,
     pdt: SystemTime,
     discontinuity: bool,
+    /// fMP4 rendition of a TS segment
+    fmp4_data: Option<Vec<u8>>,
+    /// DASH per-track files of the same media
+    dash: Option<DashSegment>,
+    /// AES-128 key the segment is encrypted with before it is stored
+    aes_key: Option<[u8; 16]>,
+}
+
+/// A completed LL-HLS part ready to write to disk.
+struct CompletedPart {
+    data: Vec<u8>,
+    fmp4_data: Option<Vec<u8>>,
+    filename: String,
+    duration: f64,
+    seq: u64,
+    index: u32,
+    independent: bool,
 }
 
 impl HlsSession {
@@ -117,16 +355,75 @@ impl HlsSession {
         let output_dir = PathBuf::from(&config.output_dir).join(stream_id);
 
         // Remove stale segments from a previous server run
-        if output_dir.exists() {
-            let _ = std::fs::remove_dir_all(&output_dir);
+        if config.storage == HlsStorage::Disk {
+            if output_dir.exists() {
+                let _ = std::fs::remove_dir_all(&output_dir);
+            }
+            std::fs::create_dir_all(&output_dir)?;
+        }
+
+        let encryption = config
+            .encryption
+            .as_ref()
+            .filter(|encryption| encryption.applies_to(stream_id))
+            .map(|encryption| {
+                SegmentKeys::new(encryption.method, encryption.key_rotation_segments)
+            });
+        // AES-128 encrypts whole segments, so LL-HLS parts would go out in the clear.
+        let low_latency = config.low_latency
+            && encryption
+                .as_ref()
+                .is_none_or(|keys| keys.method() != HlsEncryptionMethod::Aes128);
+        if low_latency != config.low_latency {
+            warn!(
+                "[HLS] [{}] AES-128 encryption: LL-HLS parts disabled",
+                stream_id
+            );
+        }
+
+        // fMP4 segments are not encrypted, so encrypted streams are TS only.
+        let segment_format = match &encryption {
+            Some(_) if config.segment_format == HlsSegmentFormat::Fmp4 => {
+                warn!(
+                    "[HLS] [{}] Encrypted stream: muxing TS instead of fMP4",
+                    stream_id
+                );
+                HlsSegmentFormat::Ts
+            }
+            _ => config.segment_format,
+        };
+
+        let mut playlist = if low_latency {
+            M3u8Generator::new(
+                config.segment_duration,
+                config.max_segments.max(LL_MIN_SEGMENTS),
+            )
+            .with_part_target(config.part_duration)
+        } else {
+            M3u8Generator::new(config.segment_duration, config.max_segments)
+        }
+        .with_dvr_window(config.dvr_window_for(stream_id))
+        .with_segment_format(segment_format);
+        if let Some(keys) = &encryption {
+            info!(
+                "[HLS] [{}] Segments encrypted with {} (key every {} segments)",
+                stream_id,
+                keys.method().as_m3u8(),
+                keys.rotation()
+            );
+            playlist.set_encryption(keys.method(), keys.rotation());
         }
-        std::fs::create_dir_all(&output_dir)?;
+
+        let mut muxer = TsMuxer::new();
+        muxer.set_timed_metadata(config.timed_metadata);
 
         Ok(Self {
             stream_id: stream_id.to_string(),
-            muxer: TsMuxer::new(),
-            playlist: M3u8Generator::new(config.segment_duration, config.max_segments),
+            muxer,
+            playlist,
+            segment_format,
             segment_buffer: Vec::new(),
+            segment_open: false,
             segment_duration_acc: 0.0,
             output_dir,
             active: true,
@@ -145,12 +442,110 @@ impl HlsSession {
             wall_anchor: None,
             session_pdt_anchor: None,
             pending_discontinuity: false,
+            part_target_ms: low_latency
+                .then(|| (config.part_duration * 1000.0).round().max(1.0) as u64),
+            part_sequence: 0,
+            part_index: 0,
+            part_offset: 0,
+            part_open_mux_ms: 0,
+            part_independent: false,
+            part_has_media: false,
+            parts_secs: 0.0,
+            completed_parts: Vec::new(),
+            fmp4: None,
+            fmp4_requested: false,
+            fmp4_buffer: Vec::new(),
+            fmp4_part_offset: 0,
+            dash_requested: false,
+            dash: None,
+            mpd: None,
+            pending_inits: Vec::new(),
+            audio_config: None,
+            encryption,
+            pending_metadata: Vec::new(),
+            next_metadata_id: 0,
         })
     }
 
+    /// Queue a cue for the live edge; the oldest is dropped while nothing is muxed.
+    fn queue_metadata(&mut self, cue: TimedMetadata) {
+        if self.pending_metadata.len() >= MAX_PENDING_METADATA {
+            self.pending_metadata.remove(0);
+        }
+        self.pending_metadata.push(cue);
+    }
+
+    /// Mux queued cues at `mux_ms`: an ID3 PES on the TS metadata PID and an
+    /// `emsg` ahead of the next fMP4 fragment.
+    fn mux_pending_metadata(&mut self, mux_ms: u64) {
+        for cue in std::mem::take(&mut self.pending_metadata) {
+            let id3 = cue.id3_tag();
+            let emsg = self
+                .fmp4
+                .is_some()
+                .then(|| emsg_box(mux_ms, self.next_metadata_id, ID3_EMSG_SCHEME, &id3));
+            match self.segment_format {
+                HlsSegmentFormat::Ts => {
+                    self.segment_buffer
+                        .extend(self.muxer.metadata_to_ts(mux_ms, &id3));
+                    self.fmp4_buffer.extend(emsg.unwrap_or_default());
+                }
+                HlsSegmentFormat::Fmp4 => self.segment_buffer.extend(emsg.unwrap_or_default()),
+            }
+            self.next_metadata_id = self.next_metadata_id.wrapping_add(1);
+            debug!(
+                "[HLS] [{}] Timed metadata '{}' at mux_ms={} ({} bytes)",
+                self.stream_id,
+                cue.kind,
+                mux_ms,
+                id3.len()
+            );
+        }
+    }
+
+    /// AAC config of the stream, used by the fMP4 init segment and TS ADTS headers.
+    fn set_audio_config(&mut self, config: AudioCodecConfig) {
+        self.muxer.set_audio_config(config.clone());
+        self.audio_config = Some(config);
+    }
+
+    /// SAMPLE-AES: key the muxer for segment `seq` before its PAT/PMT is written.
+    fn arm_segment_key(&mut self, seq: u64) {
+        let Some(keys) = self.encryption.as_mut() else {
+            return;
+        };
+        if keys.method() != HlsEncryptionMethod::SampleAes {
+            return;
+        }
+        let key = keys.key_for_sequence(seq);
+        let asc = self.audio_config.clone().unwrap_or_default().asc;
+        self.muxer.set_sample_aes(key, sequence_iv(seq), asc);
+    }
+
+    /// SAMPLE-AES is defined for H.264 only; H.265 streams fall back to AES-128
+    /// (which rules out LL-HLS parts).
+    fn check_sample_aes_codec(&mut self, codec: CodecType) {
+        let Some(keys) = self.encryption.as_mut() else {
+            return;
+        };
+        if codec != CodecType::H265 || keys.method() != HlsEncryptionMethod::SampleAes {
+            return;
+        }
+        warn!(
+            "[HLS] [{}] SAMPLE-AES does not cover H.265, using AES-128 without LL-HLS parts",
+            self.stream_id
+        );
+        keys.set_method(HlsEncryptionMethod::Aes128);
+        self.playlist
+            .set_encryption(HlsEncryptionMethod::Aes128, keys.rotation());
+        self.playlist.disable_parts();
+        self.part_target_ms = None;
+    }
+
     /// Discard partial segment after falling behind; keep timeline + CC continuous.
     fn recover_from_lag(&mut self) {
         self.segment_buffer.clear();
+        self.segment_open = false;
         self.segment_duration_acc = 0.0;
         self.last_video_timestamp = 0;
         self.segment_open_mux_ms = self.session_video_mux_ms;
@@ -159,6 +554,199 @@ impl HlsSession {
         self.segment_last_idr_mux_ms = 0;
         self.segment_wall_start = None;
         self.pending_discontinuity = true;
+        self.completed_parts.clear();
+        self.playlist.discard_pending_parts();
+        if let Some(fmp4) = self.fmp4.as_mut() {
+            let _ = fmp4.flush();
+        }
+        self.fmp4_buffer.clear();
+        self.fmp4_part_offset = 0;
+        if let Some(dash) = self.dash.as_mut() {
+            dash.discard();
+        }
+    }
+
+    /// Start the fMP4 outputs (HLS segments or rendition, requested DASH
+    /// tracks) at the segment `seq` opened by keyframe `frame`. The audio track
+    /// comes from the stream's AAC track, or from AAC already seen on the session.
+    fn maybe_start_fmp4(&mut self, frame: &MediaFrame, seq: u64) {
+        let start_hls = (self.segment_format == HlsSegmentFormat::Fmp4 || self.fmp4_requested)
+            && self.fmp4.is_none();
+        let start_dash = self.dash_requested && self.dash.is_none();
+        if !start_hls && !start_dash {
+            return;
+        }
+        let Some(video) = VideoCodecConfig::from_keyframe(frame) else {
+            return;
+        };
+        let audio = self
+            .audio_config
+            .clone()
+            .or_else(|| (self.session_audio_frames > 0).then(AudioCodecConfig::default));
+
+        if start_hls {
+            let mut muxer = Fmp4Muxer::new();
+            muxer.set_video_config(video.clone());
+            if let Some(audio) = &audio {
+                muxer.set_audio_config(audio.clone());
+            }
+            info!(
+                "[HLS] [{}] fMP4 segments start at segment {}",
+                self.stream_id, seq
+            );
+            self.pending_inits
+                .push((INIT_SEGMENT_FILENAME, muxer.init_segment()));
+            self.fmp4 = Some(muxer);
+            if self.segment_format == HlsSegmentFormat::Ts {
+                self.playlist.set_fmp4_from(seq);
+            }
+        }
+
+        if start_dash {
+            let mut video_muxer = Fmp4Muxer::new();
+            video_muxer.set_video_config(video.clone());
+            self.pending_inits
+                .push((VIDEO_INIT_FILENAME, video_muxer.init_segment()));
+            let audio_muxer = audio.clone().map(|config| {
+                let mut muxer = Fmp4Muxer::new();
+                muxer.set_audio_config(config);
+                self.pending_inits
+                    .push((AUDIO_INIT_FILENAME, muxer.init_segment()));
+                muxer
+            });
+            info!(
+                "[HLS] [{}] DASH output starts at segment {} (audio={})",
+                self.stream_id,
+                seq,
+                audio_muxer.is_some()
+            );
+            self.dash = Some(DashOutput {
+                video: video_muxer,
+                audio: audio_muxer,
+                video_buffer: Vec::new(),
+                audio_buffer: Vec::new(),
+                start_ms: None,
+            });
+            self.mpd = Some(MpdGenerator::new(
+                self.session_pdt_anchor.unwrap_or_else(SystemTime::now),
+                self.playlist.target_duration(),
+                // Segment files are kept two segments past the playlist window.
+                self.playlist.max_segments() + 2,
+                video,
+                audio,
+            ));
+        }
+    }
+
+    /// Mux a frame into the segment buffer (TS or fMP4), the fMP4 rendition
+    /// and the DASH tracks; returns the bytes added to the segment buffer.
+    fn mux_media(&mut self, mux_frame: &MediaFrame) -> usize {
+        let before = self.segment_buffer.len();
+        let fragment = self
+            .fmp4
+            .as_mut()
+            .and_then(|fmp4| fmp4.push_frame(mux_frame))
+            .unwrap_or_default();
+        match self.segment_format {
+            HlsSegmentFormat::Ts => {
+                self.muxer.update_pcr(mux_frame.timestamp);
+                let ts_data = self.muxer.frame_to_ts(mux_frame);
+                self.segment_buffer.extend(ts_data);
+                self.fmp4_buffer.extend(fragment);
+            }
+            HlsSegmentFormat::Fmp4 => self.segment_buffer.extend(fragment),
+        }
+        if let Some(dash) = self.dash.as_mut() {
+            dash.push(mux_frame);
+        }
+        self.segment_buffer.len() - before
+    }
+
+    /// Close the open fMP4 fragment so the buffer ends on a part/segment boundary.
+    fn flush_fmp4(&mut self) {
+        if let Some(fragment) = self.fmp4.as_mut().and_then(|fmp4| fmp4.flush()) {
+            match self.segment_format {
+                HlsSegmentFormat::Ts => self.fmp4_buffer.extend(fragment),
+                HlsSegmentFormat::Fmp4 => self.segment_buffer.extend(fragment),
+            }
+        }
+    }
+
+    /// Bytes of the open segment, including fMP4 samples not yet fragmented.
+    fn segment_bytes(&self) -> usize {
+        let queued = match self.segment_format {
+            HlsSegmentFormat::Ts => 0,
+            HlsSegmentFormat::Fmp4 => self.fmp4.as_ref().map_or(0, |fmp4| fmp4.queued_bytes()),
+        };
+        self.segment_buffer.len() + queued
+    }
+
+    /// Whether a TS stream is also muxing its fMP4 rendition.
+    fn has_fmp4_rendition(&self) -> bool {
+        self.segment_format == HlsSegmentFormat::Ts && self.fmp4.is_some()
+    }
+
+    /// fMP4 init segments (filename, data) produced since the last call.
+    fn take_init_segments(&mut self) -> Vec<(&'static str, Vec<u8>)> {
+        std::mem::take(&mut self.pending_inits)
+    }
+
+    /// Start the first part of the segment with sequence `seq` at the current buffer end.
+    fn open_first_part(&mut self, seq: u64, mux_ms: u64) {
+        self.part_sequence = seq;
+        self.part_index = 0;
+        self.part_offset = 0;
+        self.fmp4_part_offset = 0;
+        self.part_open_mux_ms = mux_ms;
+        self.part_independent = true;
+        self.part_has_media = false;
+        self.parts_secs = 0.0;
+    }
+
+    /// Cut `segment_buffer[self.part_offset..]` as the open part and start the next one.
+    fn cut_part(&mut self, duration: f64) {
+        self.flush_fmp4();
+        let data = &self.segment_buffer;
+        if data.len() <= self.part_offset {
+            return;
+        }
+        let filename = M3u8Generator::media_filename(
+            self.segment_format,
+            self.part_sequence,
+            Some(self.part_index),
+        );
+        debug!(
+            "[HLS] [{}] Part {} ({:.3}s, {} bytes, independent={})",
+            self.stream_id,
+            filename,
+            duration,
+            data.len() - self.part_offset,
+            self.part_independent
+        );
+        let data = data[self.part_offset..].to_vec();
+        let fmp4_data = self.has_fmp4_rendition().then(|| {
+            let data = self.fmp4_buffer[self.fmp4_part_offset..].to_vec();
+            self.fmp4_part_offset = self.fmp4_buffer.len();
+            data
+        });
+        self.completed_parts.push(CompletedPart {
+            data,
+            fmp4_data,
+            filename,
+            duration,
+            seq: self.part_sequence,
+            index: self.part_index,
+            independent: self.part_independent,
+        });
+        self.parts_secs += duration;
+        self.part_index += 1;
+        self.part_offset = self.segment_buffer.len();
+        self.part_has_media = false;
+    }
+
+    /// LL-HLS parts cut since the last call, in order.
+    fn take_completed_parts(&mut self) -> Vec<CompletedPart> {
+        std::mem::take(&mut self.completed_parts)
     }
 
     fn segment_mux_secs(&self) -> f64 {
@@ -288,11 +876,18 @@ impl HlsSession {
             return Ok(None);
         }
 
-        // HLS muxer only supports AAC audio; WebRTC publishes Opus.
-        if matches!(frame.codec, CodecType::Opus | CodecType::G711) {
+        // HLS muxer only supports H.264/H.265 and AAC; WebRTC publishes Opus.
+        if matches!(
+            frame.codec,
+            CodecType::Opus | CodecType::G711 | CodecType::AV1 | CodecType::VP9
+        ) {
             return Ok(None);
         }
 
+        if frame.codec.is_video() {
+            self.muxer.set_video_codec(frame.codec);
+        }
+
         // Skip AAC sequence header / tiny config payloads
         if frame.codec == CodecType::AAC && frame.data.len() < 8 {
             debug!(
@@ -317,16 +912,25 @@ impl HlsSession {
                 self.stream_id,
                 frame.data.len()
             );
+            self.check_sample_aes_codec(frame.codec);
         }
 
-        // Initialize segment with PAT/PMT
-        if self.segment_buffer.is_empty() {
+        // Initialize segment (TS: PAT/PMT)
+        if !self.segment_open {
+            self.segment_open = true;
             self.segment_wall_start = Some(Instant::now());
             self.segment_open_mux_ms = self.session_video_mux_ms;
             self.segment_open_mux_ms_at_split = self.session_video_mux_ms;
             let has_audio = self.session_audio_frames > 0 || frame.codec == CodecType::AAC;
-            let pat_pmt = self.muxer.generate_pat_pmt(true, has_audio);
-            self.segment_buffer.extend(pat_pmt);
+            if self.segment_format == HlsSegmentFormat::Ts {
+                self.arm_segment_key(self.playlist.next_sequence());
+                let pat_pmt = self.muxer.generate_pat_pmt(true, has_audio);
+                self.segment_buffer.extend(pat_pmt);
+            }
+            self.open_first_part(self.playlist.next_sequence(), self.session_video_mux_ms);
+            if is_hls_video_keyframe(frame) {
+                self.maybe_start_fmp4(frame, self.playlist.next_sequence());
+            }
             debug!(
                 "[HLS] [{}] Open segment: open_mux_ms={} has_audio={} first_codec={:?} first_raw_ts={} keyframe={}",
                 self.stream_id,
@@ -376,7 +980,7 @@ impl HlsSession {
             && !is_hls_video_keyframe(frame)
             && mux_secs >= split_threshold * 0.5
             && self.segment_last_idr_mux_ms <= self.segment_open_mux_ms_at_split
-            && self.segment_buffer.len() > MIN_SEGMENT_BYTES
+            && self.segment_bytes() > MIN_SEGMENT_BYTES
         {
             request_publisher_keyframe(&self.stream_id);
         }
@@ -388,7 +992,7 @@ impl HlsSession {
             split_threshold,
             committed_segments: self.playlist.segment_count(),
             has_muxed_media: self.segment_last_mux_ms > self.segment_open_mux_ms_at_split,
-            buffer_len: self.segment_buffer.len(),
+            buffer_len: self.segment_bytes(),
             min_segment_bytes: MIN_SEGMENT_BYTES,
         };
         let should_split = should_split_segment(&split_eval);
@@ -414,14 +1018,27 @@ impl HlsSession {
 
         let mut completed: Option<CompletedSegment> = None;
 
-        if should_split && self.segment_buffer.len() > MIN_SEGMENT_BYTES {
+        if should_split && self.segment_bytes() > MIN_SEGMENT_BYTES {
             let seq = self.playlist.next_sequence();
-            let filename = M3u8Generator::segment_filename(seq);
+            let filename = M3u8Generator::media_filename(self.segment_format, seq, None);
             let duration = self.closed_segment_secs();
             let discontinuity = self.pending_discontinuity;
             self.pending_discontinuity = false;
             let pdt = live_pdt(duration, SystemTime::now());
+            if self.part_target_ms.is_some() {
+                let last_part_secs = (duration - self.parts_secs).max(0.001);
+                self.cut_part(last_part_secs);
+            }
+            self.flush_fmp4();
             let data = std::mem::take(&mut self.segment_buffer);
+            let fmp4_data = self
+                .has_fmp4_rendition()
+                .then(|| std::mem::take(&mut self.fmp4_buffer));
+            let aes_key = self
+                .encryption
+                .as_mut()
+                .filter(|keys| keys.method() == HlsEncryptionMethod::Aes128)
+                .map(|keys| keys.key_for_sequence(seq));
             debug!(
                 "[HLS] [{}] Closing segment: seq={} filename={} duration={:.3}s open_ms={} last_video_ms={} last_idr_ms={} publisher_secs={:.3} bytes={} discontinuity={}",
                 self.stream_id,
@@ -461,6 +1078,9 @@ impl HlsSession {
                 seq,
                 pdt,
                 discontinuity,
+                fmp4_data,
+                dash: None,
+                aes_key,
             });
 
             // New segment starts with the keyframe below; PAT/PMT (+ discontinuity if lag snap).
@@ -469,8 +1089,11 @@ impl HlsSession {
                 self.muxer.mark_segment_discontinuity();
             }
             let has_audio = self.session_audio_frames > 0;
-            let pat_pmt = self.muxer.generate_pat_pmt(true, has_audio);
-            self.segment_buffer.extend(pat_pmt);
+            if self.segment_format == HlsSegmentFormat::Ts {
+                self.arm_segment_key(seq + 1);
+                let pat_pmt = self.muxer.generate_pat_pmt(true, has_audio);
+                self.segment_buffer.extend(pat_pmt);
+            }
             self.segment_wall_start = Some(Instant::now());
             debug!(
                 "[HLS] [{}] Open next segment after split: seq={} open_mux_ms={} has_audio={} start_raw_ts={} keyframe={}",
@@ -485,41 +1108,63 @@ impl HlsSession {
             let mux_frame = self.prepare_frame_for_mux(frame);
             self.segment_last_mux_ms = mux_frame.timestamp;
             self.segment_last_idr_mux_ms = mux_frame.timestamp;
-            self.muxer.update_pcr(mux_frame.timestamp);
-            let ts_data = self.muxer.frame_to_ts(&mux_frame);
+            if let (Some(segment), Some(dash)) = (completed.as_mut(), self.dash.as_mut()) {
+                segment.dash = dash.take_segment(mux_frame.timestamp);
+            }
+            self.open_first_part(seq + 1, mux_frame.timestamp);
+            self.maybe_start_fmp4(frame, seq + 1);
+            self.mux_pending_metadata(mux_frame.timestamp);
+            let muxed_bytes = self.mux_media(&mux_frame);
             debug!(
-                "[HLS] [{}] Mux split keyframe: raw_ts={} mux_ms={} ts_bytes={} segment_last_video_ms={}",
+                "[HLS] [{}] Mux split keyframe: raw_ts={} mux_ms={} muxed_bytes={} segment_last_video_ms={}",
                 self.stream_id,
                 frame.timestamp,
                 mux_frame.timestamp,
-                ts_data.len(),
+                muxed_bytes,
                 self.segment_last_mux_ms
             );
-            self.segment_buffer.extend(ts_data);
+            self.part_has_media = true;
             return Ok(completed);
         }
 
         let mux_frame = self.prepare_frame_for_mux(frame);
+        if let Some(part_target_ms) = self.part_target_ms {
+            // Parts start on video access units; a keyframe always opens an independent part.
+            // Cut before the access unit whose frame interval would push the part past
+            // PART-TARGET, so advertised parts never exceed it.
+            let part_ms = mux_frame.timestamp.saturating_sub(self.part_open_mux_ms);
+            let frame_ms = mux_frame.timestamp.saturating_sub(self.segment_last_mux_ms);
+            let keyframe = is_hls_video_keyframe(frame);
+            if mux_frame.codec.is_video()
+                && self.part_has_media
+                && part_ms > 0
+                && (part_ms + frame_ms > part_target_ms || keyframe)
+            {
+                self.cut_part(part_ms as f64 / 1000.0);
+                self.part_open_mux_ms = mux_frame.timestamp;
+                self.part_independent = keyframe;
+            }
+        }
+        self.mux_pending_metadata(mux_frame.timestamp);
         if matches!(mux_frame.codec, CodecType::H264 | CodecType::H265) {
             self.segment_last_mux_ms = mux_frame.timestamp;
             if is_hls_video_keyframe(frame) {
                 self.segment_last_idr_mux_ms = mux_frame.timestamp;
             }
         }
-        self.muxer.update_pcr(mux_frame.timestamp);
-        let ts_data = self.muxer.frame_to_ts(&mux_frame);
+        let muxed_bytes = self.mux_media(&mux_frame);
         debug!(
-            "[HLS] [{}] Mux frame: codec={:?} raw_ts={} mux_ms={} keyframe={} ts_bytes={} segment_last_video_ms={} buffer_before={}",
+            "[HLS] [{}] Mux frame: codec={:?} raw_ts={} mux_ms={} keyframe={} muxed_bytes={} segment_last_video_ms={} buffer={}",
             self.stream_id,
             frame.codec,
             frame.timestamp,
             mux_frame.timestamp,
             is_hls_video_keyframe(frame),
-            ts_data.len(),
+            muxed_bytes,
             self.segment_last_mux_ms,
             self.segment_buffer.len()
         );
-        self.segment_buffer.extend(ts_data);
+        self.part_has_media = true;
 
         Ok(completed)
     }
@@ -552,13 +1197,21 @@ pub struct HlsServer {
     sessions: Arc<RwLock<HashMap<String, Arc<RwLock<HlsSession>>>>>,
     task_aborts: Arc<RwLock<HashMap<String, tokio::task::AbortHandle>>>,
     last_access: Arc<RwLock<HashMap<String, Instant>>>,
+    /// Segment store shared by all streams in `HlsStorage::Memory` mode
+    memory_store: Option<Arc<MemorySegmentStore>>,
 }
 
 impl HlsServer {
     pub fn new(stream_manager: Arc<StreamManager>, config: HlsConfig) -> Self {
+        let memory_store = (config.storage == HlsStorage::Memory).then(|| {
+            Arc::new(MemorySegmentStore::new(
+                config.memory_limit_mb.saturating_mul(1024 * 1024),
+            ))
+        });
         Self {
             stream_manager,
             config,
+            memory_store,
             sessions: Arc::new(RwLock::new(HashMap::new())),
             task_aborts: Arc::new(RwLock::new(HashMap::new())),
             last_access: Arc::new(RwLock::new(HashMap::new())),
@@ -644,6 +1297,13 @@ impl HlsServer {
         info!("[HLS] Starting HLS generation for stream: {}", stream_id);
 
         let session = HlsSession::new(stream_id, &self.config)?;
+        let target = match &self.memory_store {
+            Some(store) => {
+                store.remove_stream(stream_id);
+                SegmentTarget::Memory(store.clone(), stream_id.to_string())
+            }
+            None => SegmentTarget::Disk(session.output_dir.clone()),
+        };
         let session = Arc::new(RwLock::new(session));
 
         {
@@ -670,6 +1330,7 @@ impl HlsServer {
         let sessions_clone = self.sessions.clone();
         let stream_manager = self.stream_manager.clone();
         let task_aborts = self.task_aborts.clone();
+        let memory_store = self.memory_store.clone();
 
         let handle = tokio::spawn(async move {
             info!(
@@ -714,13 +1375,26 @@ impl HlsServer {
                 }
 
                 for mut frame in frames {
-                    if matches!(frame.codec, CodecType::Opus | CodecType::G711) {
+                    if matches!(
+                        frame.codec,
+                        CodecType::Opus | CodecType::G711 | CodecType::AV1 | CodecType::VP9
+                    ) {
                         continue;
                     }
-                    if frame.codec == CodecType::H264 && is_hls_video_keyframe(&frame) {
+                    let mut audio_config = None;
+                    if is_hls_video_keyframe(&frame) {
                         if let Some(stream) = stream_manager.get_stream(&stream_id_owned) {
-                            if let (Some(sps), Some(pps)) = (&stream.sps, &stream.pps) {
-                                let data = annex_b_with_config(sps, pps, &frame.data);
+                            audio_config = AudioCodecConfig::from_stream(&stream);
+                            let data = match (frame.codec, &stream.vps, &stream.sps, &stream.pps) {
+                                (CodecType::H264, _, Some(sps), Some(pps)) => {
+                                    Some(annex_b_with_config(sps, pps, &frame.data))
+                                }
+                                (CodecType::H265, Some(vps), Some(sps), Some(pps)) => {
+                                    Some(h265::annex_b_with_config(vps, sps, pps, &frame.data))
+                                }
+                                _ => None,
+                            };
+                            if let Some(data) = data {
                                 let clock_rate = frame.clock_rate;
                                 frame = MediaFrame::new(
                                     frame.stream_id,
@@ -738,6 +1412,8 @@ impl HlsServer {
                     frame_count += 1;
 
                     // Process frame synchronously (no await while holding lock)
+                    let init_to_write;
+                    let parts_to_write;
                     let segment_to_write = {
                         let session_guard = {
                             let sessions = sessions_clone.read();
@@ -746,51 +1422,124 @@ impl HlsServer {
 
                         if let Some(session) = session_guard {
                             let mut sess = session.write();
-                            match sess.on_frame(&frame) {
-                                Ok(Some(seg)) => Some((
-                                    seg.data,
-                                    seg.filename,
-                                    seg.duration,
-                                    seg.seq,
-                                    seg.pdt,
-                                    seg.discontinuity,
-                                    sess.output_dir.clone(),
-                                )),
+                            if let Some(config) = audio_config {
+                                sess.set_audio_config(config);
+                            }
+                            let segment = match sess.on_frame(&frame) {
+                                Ok(Some(seg)) => Some(seg),
                                 Ok(None) => None,
                                 Err(e) => {
                                     error!("[HLS] [{}] Frame error: {}", stream_id_owned, e);
                                     None
                                 }
-                            }
+                            };
+                            init_to_write = sess.take_init_segments();
+                            parts_to_write = sess.take_completed_parts();
+                            segment
                         } else {
                             info!("[HLS] [{}] Session removed, stopping", stream_id_owned);
                             break;
                         }
                     };
 
-                    // Write segment to disk outside of lock
-                    if let Some((data, filename, duration, seq, pdt, discontinuity, output_dir)) =
-                        segment_to_write
-                    {
-                        let filepath = output_dir.join(&filename);
-                        let tmp_path = output_dir.join(format!("{}.part", filename));
-                        let write_ok = if let Err(e) = fs::write(&tmp_path, &data).await {
-                            error!("[HLS] [{}] Failed to write segment: {}", stream_id_owned, e);
-                            false
-                        } else if let Err(e) = fs::rename(&tmp_path, &filepath).await {
+                    // Publish the fMP4 init segment and LL-HLS parts before the
+                    // segment they belong to
+                    for (filename, init) in init_to_write {
+                        if let Err(e) = target.write(filename, init).await {
+                            error!(
+                                "[HLS] [{}] Failed to write init segment {}: {}",
+                                stream_id_owned, filename, e
+                            );
+                        }
+                    }
+                    for part in parts_to_write {
+                        let mut written = target.write(&part.filename, part.data).await;
+                        if let (Ok(()), Some(fmp4_data)) = (&written, part.fmp4_data) {
+                            let filename = M3u8Generator::media_filename(
+                                HlsSegmentFormat::Fmp4,
+                                part.seq,
+                                Some(part.index),
+                            );
+                            written = target.write(&filename, fmp4_data).await;
+                        }
+                        if let Err(e) = written {
                             error!(
-                                "[HLS] [{}] Failed to finalize segment: {}",
+                                "[HLS] [{}] Failed to write part {}: {}",
+                                stream_id_owned, part.filename, e
+                            );
+                            continue;
+                        }
+                        if let Some(s) = sessions_clone.read().get(&stream_id_owned) {
+                            s.write().playlist.add_part(
+                                part.seq,
+                                part.index,
+                                part.duration,
+                                part.independent,
+                            );
+                        }
+                    }
+
+                    // Store segment outside of lock
+                    if let Some(segment) = segment_to_write {
+                        let CompletedSegment {
+                            data,
+                            filename,
+                            duration,
+                            seq,
+                            pdt,
+                            discontinuity,
+                            fmp4_data,
+                            dash,
+                            aes_key,
+                        } = segment;
+                        // Post-processing: AES-128 encrypts the muxed segment as a whole
+                        let data = match aes_key {
+                            Some(key) => encrypt_segment(&key, &sequence_iv(seq), &data),
+                            None => data,
+                        };
+                        let data_len = data.len();
+                        let fmp4_written = match fmp4_data {
+                            Some(fmp4_data) => {
+                                let fmp4_filename = M3u8Generator::media_filename(
+                                    HlsSegmentFormat::Fmp4,
+                                    seq,
+                                    None,
+                                );
+                                target.write(&fmp4_filename, fmp4_data).await
+                            }
+                            None => Ok(()),
+                        };
+                        // DASH files are moved into the store; keep the timeline entry
+                        let dash_timeline = dash
+                            .as_ref()
+                            .map(|d| (d.start_ms, d.duration_ms, d.video.len()));
+                        let dash_written = match dash {
+                            Some(dash) => {
+                                let mut written = target
+                                    .write(&mpd::video_segment_filename(seq), dash.video)
+                                    .await;
+                                if let (Ok(()), Some(audio)) = (&written, dash.audio) {
+                                    written = target
+                                        .write(&mpd::audio_segment_filename(seq), audio)
+                                        .await;
+                                }
+                                written
+                            }
+                            None => Ok(()),
+                        };
+                        let write_ok = if let Err(e) = fmp4_written.and(dash_written) {
+                            error!(
+                                "[HLS] [{}] Failed to write fMP4 segment: {}",
                                 stream_id_owned, e
                             );
-                            let _ = fs::remove_file(&tmp_path).await;
+                            false
+                        } else if let Err(e) = target.write(&filename, data).await {
+                            error!("[HLS] [{}] Failed to write segment: {}", stream_id_owned, e);
                             false
                         } else {
                             debug!(
                                 "[HLS] [{}] Wrote segment: {} ({:.2}s, {} bytes)",
-                                stream_id_owned,
-                                filename,
-                                duration,
-                                data.len()
+                                stream_id_owned, filename, duration, data_len
                             );
                             true
                         };
@@ -798,28 +1547,29 @@ impl HlsServer {
                         if write_ok {
                             info!(
                                 "[HLS] [{}] Committed segment {} ({:.2}s, {} bytes)",
-                                stream_id_owned,
-                                filename,
-                                duration,
-                                data.len()
+                                stream_id_owned, filename, duration, data_len
                             );
                             let playlist_update = {
                                 let sessions = sessions_clone.read();
                                 sessions.get(&stream_id_owned).map(|s| {
                                     let mut sess = s.write();
                                     sess.playlist.add_segment(duration, seq, pdt, discontinuity);
-                                    let prune =
-                                        seq.saturating_sub(sess.playlist.max_segments() as u64 + 2);
+                                    if let (Some(mpd), Some((start_ms, duration_ms, bytes))) =
+                                        (sess.mpd.as_mut(), dash_timeline)
+                                    {
+                                        mpd.add_segment(seq, start_ms, duration_ms, bytes);
+                                    }
+                                    // Keep files two segments past the oldest listed one
+                                    let prune = sess.playlist.oldest_sequence().saturating_sub(2);
+                                    if let Some(keys) = sess.encryption.as_mut() {
+                                        keys.prune(prune);
+                                    }
                                     (sess.get_playlist(), prune)
                                 })
                             };
                             if let Some((content, prune_before)) = playlist_update {
-                                let m3u8_path = output_dir.join("live.m3u8");
-                                let tmp = output_dir.join("live.m3u8.part");
-                                if fs::write(&tmp, &content).await.is_ok() {
-                                    let _ = fs::rename(&tmp, &m3u8_path).await;
-                                }
-                                prune_old_segments(&output_dir, prune_before).await;
+                                target.publish_playlist(&content).await;
+                                target.prune(prune_before).await;
                             }
                         }
                     }
@@ -838,6 +1588,9 @@ impl HlsServer {
                 stream_id_owned, frame_count
             );
 
+            if let Some(store) = &memory_store {
+                store.remove_stream(&stream_id_owned);
+            }
             let mut sessions = sessions_clone.write();
             if sessions.remove(&stream_id_owned).is_some() {
                 info!(
@@ -856,20 +1609,208 @@ impl HlsServer {
         Ok(())
     }
 
-    /// Get the M3U8 playlist (in-memory, only lists committed segments).
-    pub fn get_playlist(&self, stream_id: &str) -> Option<String> {
+    /// Get the M3U8 playlist (in-memory, only lists committed segments) of the
+    /// `format` rendition; `window` selects the live window or a time-shifted
+    /// start in the DVR history.
+    pub fn get_playlist(
+        &self,
+        stream_id: &str,
+        format: HlsSegmentFormat,
+        window: PlaylistWindow,
+    ) -> Option<String> {
         self.touch(stream_id);
         let sessions = self.sessions.read();
         let session = sessions.get(stream_id)?;
         let sess = session.read();
-        if sess.playlist.segment_count() == 0 {
+        if !sess.playlist.has_format_media(format) {
             return None;
         }
-        Some(sess.get_playlist())
+        Some(sess.playlist.generate_window(format, window))
+    }
+
+    /// Container to serve for a playlist request asking for `format` (the
+    /// configured one when `None`). A TS stream starts its fMP4 rendition on
+    /// the next segment boundary; encrypted streams stay on TS.
+    pub fn request_format(
+        &self,
+        stream_id: &str,
+        format: Option<HlsSegmentFormat>,
+    ) -> HlsSegmentFormat {
+        let sessions = self.sessions.read();
+        let Some(session) = sessions.get(stream_id) else {
+            return self.config.segment_format;
+        };
+        let mut sess = session.write();
+        match format {
+            Some(HlsSegmentFormat::Fmp4) if sess.encryption.is_none() => {
+                sess.fmp4_requested = true;
+                HlsSegmentFormat::Fmp4
+            }
+            _ => sess.segment_format,
+        }
+    }
+
+    /// DASH manifest; `None` until the first DASH segment is committed.
+    pub fn get_mpd(&self, stream_id: &str) -> Option<String> {
+        self.touch(stream_id);
+        let sessions = self.sessions.read();
+        let sess = sessions.get(stream_id)?.read();
+        let mpd = sess.mpd.as_ref().filter(|mpd| mpd.segment_count() > 0)?;
+        Some(mpd.generate(SystemTime::now()))
+    }
+
+    /// Make sure the session muxes per-track fMP4 for DASH from the next segment
+    /// boundary; `false` for encrypted streams, which are HLS TS only.
+    pub fn request_dash(&self, stream_id: &str) -> bool {
+        let sessions = self.sessions.read();
+        let Some(session) = sessions.get(stream_id) else {
+            return false;
+        };
+        let mut sess = session.write();
+        if sess.encryption.is_some() {
+            return false;
+        }
+        sess.dash_requested = true;
+        true
+    }
+
+    /// Content key `index` of an encrypted stream.
+    pub fn get_key(&self, stream_id: &str, index: u64) -> Option<[u8; 16]> {
+        self.touch(stream_id);
+        let sessions = self.sessions.read();
+        let sess = sessions.get(stream_id)?.read();
+        sess.encryption.as_ref()?.key(index)
+    }
+
+    /// Whether a key request for `stream_id` carries a valid playback token.
+    pub fn authorize_key(&self, stream_id: &str, token: Option<&str>) -> bool {
+        let Some(encryption) = self.config.encryption.as_ref() else {
+            return false;
+        };
+        token.is_some_and(|token| {
+            encryption::verify_playback_token(&encryption.token_secret, stream_id, token)
+        })
+    }
+
+    /// Queue a timed metadata cue on a packaged stream; false when timed
+    /// metadata is off or the stream has no HLS session.
+    pub fn push_metadata(&self, stream_id: &str, cue: TimedMetadata) -> bool {
+        if !self.config.timed_metadata {
+            return false;
+        }
+        match self.sessions.read().get(stream_id) {
+            Some(session) => {
+                session.write().queue_metadata(cue);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forward analysis events of the configured kinds as timed metadata.
+    pub fn start_metadata_events(self: &Arc<Self>, mut events: broadcast::Receiver<AnalysisEvent>) {
+        if !self.config.timed_metadata || self.config.metadata_events.is_empty() {
+            return;
+        }
+        info!(
+            "[HLS] Timed metadata for analysis events {:?}",
+            self.config.metadata_events
+        );
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if !server.config.metadata_events.contains(&event.kind) {
+                            continue;
+                        }
+                        let payload = serde_json::to_value(&event).unwrap_or_default();
+                        server.push_metadata(
+                            &event.stream_id,
+                            TimedMetadata::new(&event.kind, payload),
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("[HLS] Timed metadata missed {skipped} analysis events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Whether LL-HLS parts and blocking playlist reloads are enabled.
+    pub fn is_low_latency(&self) -> bool {
+        self.config.low_latency
+    }
+
+    /// How long a blocking request may wait: three target durations.
+    fn blocking_timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.config.segment_duration.max(1.0) * 3.0)
+    }
+
+    /// LL-HLS blocking reload: wait until segment `msn` (or its part `part`) is
+    /// published, then return the playlist. Returns the current playlist on timeout.
+    pub async fn blocking_playlist(
+        &self,
+        stream_id: &str,
+        format: HlsSegmentFormat,
+        window: PlaylistWindow,
+        msn: u64,
+        part: Option<u32>,
+    ) -> Option<String> {
+        let deadline = Instant::now() + self.blocking_timeout();
+        loop {
+            let ready = {
+                let sessions = self.sessions.read();
+                let session = sessions.get(stream_id)?;
+                let ready = session.read().playlist.has_media(msn, part);
+                ready
+            };
+            if ready || Instant::now() >= deadline {
+                if !ready {
+                    debug!(
+                        "[HLS] [{}] blocking reload msn={} part={:?} timed out",
+                        stream_id, msn, part
+                    );
+                }
+                return self.get_playlist(stream_id, format, window);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// Segment or part bytes; in LL-HLS mode waits for a part announced by
+    /// `#EXT-X-PRELOAD-HINT` that is still being muxed.
+    pub async fn wait_segment(&self, stream_id: &str, filename: &str) -> Option<Bytes> {
+        if let Some(data) = self.get_segment(stream_id, filename).await {
+            return Some(data);
+        }
+        if !self.is_low_latency() || !self.has_stream(stream_id) {
+            return None;
+        }
+        let deadline = Instant::now() + self.blocking_timeout();
+        while Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            if let Some(data) = self.get_segment(stream_id, filename).await {
+                return Some(data);
+            }
+        }
+        None
+    }
+
+    /// Segment, part or init segment bytes from the memory store or disk.
+    pub async fn get_segment(&self, stream_id: &str, filename: &str) -> Option<Bytes> {
+        if let Some(store) = &self.memory_store {
+            self.touch(stream_id);
+            return store.get(stream_id, filename);
+        }
+        let path = self.get_segment_path(stream_id, filename)?;
+        fs::read(path).await.ok().map(Bytes::from)
     }
 
-    /// Get the segment file path for a stream
-    pub fn get_segment_path(&self, stream_id: &str, filename: &str) -> Option<PathBuf> {
+    /// Segment file path for a stream in disk mode
+    fn get_segment_path(&self, stream_id: &str, filename: &str) -> Option<PathBuf> {
         self.touch(stream_id);
         let sessions = self.sessions.read();
         if let Some(s) = sessions.get(stream_id) {
@@ -912,6 +1853,9 @@ impl HlsServer {
             sessions.remove(stream_id)
         };
 
+        if let Some(store) = &self.memory_store {
+            store.remove_stream(stream_id);
+        }
         if session.is_some() {
             info!("[HLS] Stopped HLS for stream: {}", stream_id);
         }
@@ -925,6 +1869,56 @@ impl HlsServer {
     }
 }
 
+/// Where the frame loop puts finished segment files.
+enum SegmentTarget {
+    Disk(PathBuf),
+    Memory(Arc<MemorySegmentStore>, String),
+}
+
+impl SegmentTarget {
+    async fn write(&self, filename: &str, data: Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Self::Disk(dir) => write_hls_file(dir, filename, &data).await,
+            Self::Memory(store, stream_id) => {
+                store.insert(stream_id, filename, Bytes::from(data));
+                Ok(())
+            }
+        }
+    }
+
+    /// Mirror the playlist to `live.m3u8`; memory mode serves playlists from the session only.
+    async fn publish_playlist(&self, content: &str) {
+        if let Self::Disk(dir) = self {
+            let _ = write_hls_file(dir, "live.m3u8", content.as_bytes()).await;
+        }
+    }
+
+    async fn prune(&self, prune_before_seq: u64) {
+        match self {
+            Self::Disk(dir) => prune_old_segments(dir, prune_before_seq).await,
+            Self::Memory(store, stream_id) => {
+                store.prune(stream_id, prune_before_seq);
+                debug!(
+                    "[HLS] [{}] Memory store holds {} bytes",
+                    stream_id,
+                    store.total_bytes()
+                );
+            }
+        }
+    }
+}
+
+/// Write `data` as `output_dir/filename` via a `.part` rename so readers never see a partial file.
+async fn write_hls_file(output_dir: &Path, filename: &str, data: &[u8]) -> std::io::Result<()> {
+    let tmp_path = output_dir.join(format!("{}.part", filename));
+    fs::write(&tmp_path, data).await?;
+    if let Err(e) = fs::rename(&tmp_path, output_dir.join(filename)).await {
+        let _ = fs::remove_file(&tmp_path).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
 /// Remove segment files older than the sliding playlist window.
 async fn prune_old_segments(output_dir: &Path, prune_before_seq: u64) {
     if prune_before_seq == 0 {
@@ -935,11 +1929,7 @@ async fn prune_old_segments(output_dir: &Path, prune_before_seq: u64) {
     };
     while let Ok(Some(entry)) = entries.next_entry().await {
         let name = entry.file_name().to_string_lossy().into_owned();
-        let Some(seq) = name
-            .strip_prefix("segment_")
-            .and_then(|s| s.strip_suffix(".ts"))
-            .and_then(|s| s.parse::<u64>().ok())
-        else {
+        let Some(seq) = media_sequence(&name) else {
             continue;
         };
         if seq < prune_before_seq {
@@ -1005,6 +1995,7 @@ mod playback_tests {
             segment_duration: 1.0,
             max_segments: 6,
             output_dir: dir.to_string_lossy().into(),
+            ..Default::default()
         }
     }
 
@@ -1191,4 +2182,224 @@ mod playback_tests {
             last_closed_mux_ms
         );
     }
+
+    #[test]
+    fn low_latency_cuts_parts_that_add_up_to_the_segment() {
+        let config = HlsConfig {
+            low_latency: true,
+            // Not a multiple of the 40 ms frame interval
+            part_duration: 0.3,
+            ..temp_hls_config("ll_parts")
+        };
+        let mut session = HlsSession::new("t", &config).unwrap();
+        push_one_second_gop(&mut session, 0);
+        let seg = push_one_second_gop(&mut session, 1)
+            .into_iter()
+            .last()
+            .expect("two 1s GOPs should close one segment");
+        let parts: Vec<CompletedPart> = session
+            .take_completed_parts()
+            .into_iter()
+            .filter(|part| part.seq == seg.seq)
+            .collect();
+
+        assert!(
+            parts.len() >= 3,
+            "expected ~300ms parts, got {}",
+            parts.len()
+        );
+        assert!(parts[0].independent, "first part starts on the IDR");
+        assert!(parts.iter().skip(1).all(|part| !part.independent));
+        assert!(parts.iter().all(|part| part.duration <= 0.3 + 1e-9));
+        let total: f64 = parts.iter().map(|part| part.duration).sum();
+        assert!((total - seg.duration).abs() < 0.01);
+        let bytes: usize = parts.iter().map(|part| part.data.len()).sum();
+        assert_eq!(bytes, seg.data.len(), "parts concatenate to the segment");
+    }
+
+    #[test]
+    fn fmp4_format_muxes_only_fmp4_segments() {
+        let config = HlsConfig {
+            segment_format: HlsSegmentFormat::Fmp4,
+            ..temp_hls_config("fmp4")
+        };
+        let mut session = HlsSession::new("t", &config).unwrap();
+        let audio = AudioCodecConfig::aac_lc(48_000, 2);
+        session.audio_config = Some(audio.clone());
+        let idr_with_config = Bytes::from(
+            [
+                &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e][..],
+                &[0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80],
+                &annex_b_idr(),
+            ]
+            .concat(),
+        );
+        let mut segments = Vec::new();
+        for gop in 0..3u64 {
+            for f in 0..25u64 {
+                let ts = RTP_BASE + (gop * 25 + f) * FRAME_TICKS;
+                let mut frame = h264_frame(ts, f == 0);
+                if f == 0 {
+                    frame.data = idr_with_config.clone();
+                }
+                if let Ok(Some(seg)) = session.on_frame(&frame) {
+                    session.record_completed_segment(&seg);
+                    segments.push(seg);
+                }
+            }
+        }
+
+        let inits = session.take_init_segments();
+        let (filename, init) = inits.first().expect("init segment on the first keyframe");
+        assert_eq!(*filename, INIT_SEGMENT_FILENAME);
+        assert_eq!(&init[4..8], b"ftyp");
+        assert!(init.windows(2).any(|w| w == audio.asc.as_slice()));
+        assert!(!segments.is_empty());
+        for seg in &segments {
+            assert_eq!(&seg.data[4..8], b"moof", "no TS muxed alongside fMP4");
+            assert!(seg.filename.ends_with(".m4s"));
+            assert!(seg.fmp4_data.is_none());
+        }
+
+        let playlist = session.playlist.generate();
+        assert!(playlist.contains("#EXT-X-MAP:URI=\"init.mp4\""));
+        assert!(playlist.contains("segment_0.m4s"));
+        assert!(!playlist.contains(".ts"));
+    }
+
+    #[test]
+    fn fmp4_rendition_of_ts_stream_starts_on_request() {
+        let mut session = HlsSession::new("t", &temp_hls_config("fmp4-rendition")).unwrap();
+        let idr_with_config = Bytes::from(
+            [
+                &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e][..],
+                &[0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80],
+                &annex_b_idr(),
+            ]
+            .concat(),
+        );
+        let mut segments = Vec::new();
+        for gop in 0..4u64 {
+            if gop == 2 {
+                session.fmp4_requested = true;
+            }
+            for f in 0..25u64 {
+                let ts = RTP_BASE + (gop * 25 + f) * FRAME_TICKS;
+                let mut frame = h264_frame(ts, f == 0);
+                if f == 0 {
+                    frame.data = idr_with_config.clone();
+                }
+                if let Ok(Some(seg)) = session.on_frame(&frame) {
+                    session.record_completed_segment(&seg);
+                    segments.push(seg);
+                }
+            }
+        }
+
+        assert!(session
+            .take_init_segments()
+            .iter()
+            .any(|(name, _)| *name == INIT_SEGMENT_FILENAME));
+        assert!(
+            segments[0].fmp4_data.is_none(),
+            "no fMP4 muxed before a request"
+        );
+        let seg = segments.last().unwrap();
+        assert_eq!(seg.data[0], 0x47);
+        let fmp4 = seg
+            .fmp4_data
+            .as_ref()
+            .expect("fMP4 rendition after the request");
+        assert_eq!(&fmp4[4..8], b"moof");
+
+        let ts = session.playlist.generate();
+        assert!(ts.contains("segment_0.ts"));
+        assert!(!ts.contains("#EXT-X-MAP"));
+        let fmp4 = session
+            .playlist
+            .generate_window(HlsSegmentFormat::Fmp4, PlaylistWindow::Live);
+        assert!(fmp4.contains("#EXT-X-MAP:URI=\"init.mp4\""));
+        assert!(fmp4.contains(&format!("segment_{}.m4s", seg.seq)));
+        assert!(!fmp4.contains("segment_0."));
+    }
+
+    #[test]
+    fn timed_metadata_cue_is_muxed_on_metadata_pid() {
+        let config = HlsConfig {
+            timed_metadata: true,
+            ..temp_hls_config("id3")
+        };
+        let mut session = HlsSession::new("t", &config).unwrap();
+        push_one_second_gop(&mut session, 0);
+        session.queue_metadata(TimedMetadata::new(
+            "cue",
+            serde_json::json!({"title": "goal"}),
+        ));
+        // The cue rides the segment opened by the next keyframe
+        push_one_second_gop(&mut session, 1);
+        assert!(session.pending_metadata.is_empty());
+        let segment = push_one_second_gop(&mut session, 2)
+            .pop()
+            .expect("next keyframe should close the cue's segment");
+
+        // PMT declares stream_type 0x15 on PID 0x102 and the cue rides that PID
+        assert!(segment.data.windows(3).any(|w| w == [0x15, 0x01, 0x02]));
+        let cue_packet = segment
+            .data
+            .chunks(188)
+            .find(|p| p[1] == 0x41 && p[2] == 0x02)
+            .expect("metadata PES packet");
+        assert!(cue_packet.windows(4).any(|w| w == [0x00, 0x00, 0x01, 0xBD]));
+        let payload = br#"{"title":"goal"}"#;
+        assert!(segment.data.windows(payload.len()).any(|w| w == payload));
+    }
+
+    #[test]
+    fn dash_output_writes_per_track_segments_and_manifest() {
+        let mut session = HlsSession::new("t", &temp_hls_config("dash")).unwrap();
+        session.dash_requested = true;
+        let idr_with_config = Bytes::from(
+            [
+                &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1e][..],
+                &[0, 0, 0, 1, 0x68, 0xce, 0x3c, 0x80],
+                &annex_b_idr(),
+            ]
+            .concat(),
+        );
+        let mut segments = Vec::new();
+        for gop in 0..3u64 {
+            for f in 0..25u64 {
+                let ts = RTP_BASE + (gop * 25 + f) * FRAME_TICKS;
+                let mut frame = h264_frame(ts, f == 0);
+                if f == 0 {
+                    frame.data = idr_with_config.clone();
+                }
+                if let Ok(Some(seg)) = session.on_frame(&frame) {
+                    session.record_completed_segment(&seg);
+                    if let (Some(mpd), Some(dash)) = (session.mpd.as_mut(), &seg.dash) {
+                        mpd.add_segment(seg.seq, dash.start_ms, dash.duration_ms, dash.video.len());
+                    }
+                    segments.push(seg);
+                }
+            }
+        }
+
+        let inits = session.take_init_segments();
+        assert_eq!(inits.len(), 1, "video-only stream has no audio init");
+        assert_eq!(inits[0].0, VIDEO_INIT_FILENAME);
+        let dash = segments
+            .last()
+            .unwrap()
+            .dash
+            .as_ref()
+            .expect("DASH segment");
+        assert_eq!(&dash.video[4..8], b"moof");
+        assert!(dash.audio.is_none());
+        assert!((dash.duration_ms as i64 - 1000).abs() <= 1);
+
+        let manifest = session.mpd.as_ref().unwrap().generate(SystemTime::now());
+        assert!(manifest.contains("type=\"dynamic\""));
+        assert!(manifest.contains("video_$Number$.m4s"));
+        assert!(!manifest.contains("audio_$Number$.m4s"));
+    }
 }
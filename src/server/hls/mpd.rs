@@ -0,0 +1,236 @@
+//! MPEG-DASH dynamic manifest over the HLS segmenter's per-track fMP4 output.
+//!
+//! Segments share numbers and boundaries with the HLS playlist; video and audio
+//! are separate adaptation sets addressed by `SegmentTemplate` + `$Number$`.
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use super::fmp4_muxer::{AudioCodecConfig, VideoCodecConfig};
+use super::m3u8::M3u8Generator;
+use crate::core::h265;
+
+pub const VIDEO_INIT_FILENAME: &str = "video_init.mp4";
+pub const AUDIO_INIT_FILENAME: &str = "audio_init.mp4";
+
+/// Video track timescale (matches the fMP4 muxer).
+const VIDEO_TIMESCALE: u64 = 90_000;
+/// Advertised audio bandwidth; AAC bitrate is not tracked.
+const AUDIO_BANDWIDTH: u64 = 128_000;
+
+/// Video media segment filename (`$Number$` = HLS media sequence).
+pub fn video_segment_filename(number: u64) -> String {
+    format!("video_{number}.m4s")
+}
+
+/// Audio media segment filename (`$Number$` = HLS media sequence).
+pub fn audio_segment_filename(number: u64) -> String {
+    format!("audio_{number}.m4s")
+}
+
+#[derive(Debug, Clone)]
+struct MpdSegment {
+    number: u64,
+    /// Session mux ms of the first sample (fMP4 `tfdt`)
+    start_ms: u64,
+    duration_ms: u64,
+    video_bytes: usize,
+}
+
+/// Dynamic MPD generator with a sliding segment window.
+pub struct MpdGenerator {
+    /// Wall-clock time of session mux ms 0
+    availability_start: SystemTime,
+    target_duration: f64,
+    max_segments: usize,
+    video: VideoCodecConfig,
+    audio: Option<AudioCodecConfig>,
+    segments: VecDeque<MpdSegment>,
+}
+
+impl MpdGenerator {
+    pub fn new(
+        availability_start: SystemTime,
+        target_duration: f64,
+        max_segments: usize,
+        video: VideoCodecConfig,
+        audio: Option<AudioCodecConfig>,
+    ) -> Self {
+        Self {
+            availability_start,
+            target_duration,
+            max_segments: max_segments.max(1),
+            video,
+            audio,
+            segments: VecDeque::new(),
+        }
+    }
+
+    pub fn add_segment(
+        &mut self,
+        number: u64,
+        start_ms: u64,
+        duration_ms: u64,
+        video_bytes: usize,
+    ) {
+        self.segments.push_back(MpdSegment {
+            number,
+            start_ms,
+            duration_ms,
+            video_bytes,
+        });
+        while self.segments.len() > self.max_segments {
+            self.segments.pop_front();
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn video_bandwidth(&self) -> u64 {
+        self.segments
+            .iter()
+            .filter(|s| s.duration_ms > 0)
+            .map(|s| s.video_bytes as u64 * 8 * 1000 / s.duration_ms)
+            .max()
+            .unwrap_or(1_000_000)
+    }
+
+    fn segment_timeline(&self, timescale: u64) -> String {
+        let mut out = String::from("<SegmentTimeline>");
+        for segment in &self.segments {
+            out.push_str(&format!(
+                "<S t=\"{}\" d=\"{}\"/>",
+                segment.start_ms * timescale / 1000,
+                segment.duration_ms * timescale / 1000
+            ));
+        }
+        out.push_str("</SegmentTimeline>");
+        out
+    }
+
+    /// Generate the MPD; `now` is the publish time.
+    pub fn generate(&self, now: SystemTime) -> String {
+        let start_number = self.segments.front().map(|s| s.number).unwrap_or(0);
+        let window_secs: f64 =
+            self.segments.iter().map(|s| s.duration_ms).sum::<u64>() as f64 / 1000.0;
+        let max_segment_secs = self
+            .segments
+            .iter()
+            .map(|s| s.duration_ms as f64 / 1000.0)
+            .fold(self.target_duration, f64::max);
+        let (width, height) = self.video.dimensions();
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n");
+        out.push_str(&format!(
+            "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-live:2011\" type=\"dynamic\" availabilityStartTime=\"{}\" publishTime=\"{}\" minimumUpdatePeriod=\"PT{:.3}S\" minBufferTime=\"PT{:.3}S\" timeShiftBufferDepth=\"PT{:.3}S\" suggestedPresentationDelay=\"PT{:.3}S\" maxSegmentDuration=\"PT{:.3}S\">\r\n",
+            M3u8Generator::format_program_date_time(self.availability_start),
+            M3u8Generator::format_program_date_time(now),
+            self.target_duration,
+            self.target_duration,
+            window_secs.max(self.target_duration),
+            self.target_duration * 2.0,
+            max_segment_secs
+        ));
+        out.push_str("<Period id=\"0\" start=\"PT0S\">\r\n");
+
+        out.push_str("<AdaptationSet id=\"0\" contentType=\"video\" mimeType=\"video/mp4\" segmentAlignment=\"true\" startWithSAP=\"1\">\r\n");
+        out.push_str(&format!(
+            "<Representation id=\"video\" codecs=\"{}\" bandwidth=\"{}\" width=\"{}\" height=\"{}\">\r\n",
+            video_codec_string(&self.video),
+            self.video_bandwidth(),
+            width,
+            height
+        ));
+        out.push_str(&format!(
+            "<SegmentTemplate timescale=\"{}\" initialization=\"{}\" media=\"video_$Number$.m4s\" startNumber=\"{}\">{}</SegmentTemplate>\r\n",
+            VIDEO_TIMESCALE,
+            VIDEO_INIT_FILENAME,
+            start_number,
+            self.segment_timeline(VIDEO_TIMESCALE)
+        ));
+        out.push_str("</Representation>\r\n</AdaptationSet>\r\n");
+
+        if let Some(audio) = &self.audio {
+            let timescale = audio.sample_rate as u64;
+            out.push_str("<AdaptationSet id=\"1\" contentType=\"audio\" mimeType=\"audio/mp4\" lang=\"und\" segmentAlignment=\"true\" startWithSAP=\"1\">\r\n");
+            out.push_str(&format!(
+                "<Representation id=\"audio\" codecs=\"{}\" bandwidth=\"{}\" audioSamplingRate=\"{}\">\r\n",
+                audio_codec_string(audio),
+                AUDIO_BANDWIDTH,
+                audio.sample_rate
+            ));
+            out.push_str(&format!(
+                "<AudioChannelConfiguration schemeIdUri=\"urn:mpeg:dash:23003:3:audio_channel_configuration:2011\" value=\"{}\"/>\r\n",
+                audio.channels
+            ));
+            out.push_str(&format!(
+                "<SegmentTemplate timescale=\"{}\" initialization=\"{}\" media=\"audio_$Number$.m4s\" startNumber=\"{}\">{}</SegmentTemplate>\r\n",
+                timescale,
+                AUDIO_INIT_FILENAME,
+                start_number,
+                self.segment_timeline(timescale)
+            ));
+            out.push_str("</Representation>\r\n</AdaptationSet>\r\n");
+        }
+
+        out.push_str("</Period>\r\n");
+        out.push_str(&format!(
+            "<UTCTiming schemeIdUri=\"urn:mpeg:dash:utc:direct:2014\" value=\"{}\"/>\r\n",
+            M3u8Generator::format_program_date_time(now)
+        ));
+        out.push_str("</MPD>\r\n");
+        out
+    }
+}
+
+/// RFC 6381 codec string for the video sample entry.
+fn video_codec_string(config: &VideoCodecConfig) -> String {
+    match config {
+        VideoCodecConfig::Avc { sps, .. } if sps.len() >= 4 => {
+            format!("avc1.{:02x}{:02x}{:02x}", sps[1], sps[2], sps[3])
+        }
+        VideoCodecConfig::Avc { .. } => "avc1.42e01e".to_string(),
+        VideoCodecConfig::Hevc { sps, .. } => h265::codec_string(sps),
+    }
+}
+
+/// `mp4a.40.<audio object type>` from the AudioSpecificConfig.
+fn audio_codec_string(config: &AudioCodecConfig) -> String {
+    let object_type = config.asc.first().map(|b| b >> 3).unwrap_or(2);
+    format!("mp4a.40.{object_type}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn mpd_lists_contiguous_timeline_per_track() {
+        let video = VideoCodecConfig::Avc {
+            sps: vec![0x67, 0x42, 0xc0, 0x1e],
+            pps: vec![0x68, 0xce, 0x3c, 0x80],
+        };
+        let audio = AudioCodecConfig::aac_lc(48_000, 2);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut mpd = MpdGenerator::new(start, 1.0, 2, video, Some(audio));
+        mpd.add_segment(4, 0, 1000, 100_000);
+        mpd.add_segment(5, 1000, 960, 100_000);
+        mpd.add_segment(6, 1960, 1040, 125_000);
+
+        let xml = mpd.generate(start + Duration::from_secs(3));
+        assert!(xml.contains("type=\"dynamic\""));
+        assert!(xml.contains("availabilityStartTime=\"2023-11-14T22:13:20.000Z\""));
+        assert!(xml.contains("codecs=\"avc1.42c01e\""));
+        assert!(xml.contains("codecs=\"mp4a.40.2\""));
+        assert!(xml.contains("media=\"video_$Number$.m4s\" startNumber=\"5\""));
+        assert!(xml.contains(
+            "<SegmentTimeline><S t=\"90000\" d=\"86400\"/><S t=\"176400\" d=\"93600\"/></SegmentTimeline>"
+        ));
+        assert!(xml.contains("<S t=\"48000\" d=\"46080\"/>"));
+        assert!(xml.contains("bandwidth=\"961538\""));
+    }
+}
@@ -0,0 +1,803 @@
+/// HLS client puller: poll a remote live playlist, download new MPEG-TS
+/// segments over http/https and relay their A/V frames into StreamManager at
+/// their PTS pace.
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_rustls::rustls::{self, pki_types::ServerName, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use super::fmp4_muxer::AudioCodecConfig;
+use super::ts_demuxer::{demux_ts, normalize_annex_b, split_adts_frames, TsTrack};
+use crate::core::{
+    h265, CodecType, MediaFrame, StreamManager, StreamProtocol, StreamSourceMode, Track,
+    MILLISECOND_CLOCK_RATE,
+};
+use crate::server::webrtc::h264_util::iter_annex_b_nal_ranges;
+
+/// Segments fetched behind the live edge when joining (HOLD-BACK of three targets)
+const LIVE_EDGE_SEGMENTS: usize = 3;
+/// Playlist reload failures tolerated before the pull gives up
+const MAX_PLAYLIST_FAILURES: u32 = 5;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+/// Source timestamp steps larger than this are treated as a discontinuity
+const MAX_TIMESTAMP_JUMP_MS: i64 = 10_000;
+/// Gap inserted between the last frame and the first one after a discontinuity
+const DISCONTINUITY_GAP_MS: u64 = 40;
+/// Samples per AAC frame
+const AAC_FRAME_SAMPLES: u64 = 1024;
+/// Pacing falls back to the wall clock when publishing runs this far behind
+const MAX_PACING_LAG_MS: u64 = 2_000;
+
+/// Client TLS config for https sources (Mozilla root store).
+static TLS_CONNECTOR: Lazy<TlsConnector> = Lazy::new(|| {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("ring provider supports the default TLS versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+});
+
+#[derive(Debug, Clone, PartialEq)]
+struct RemoteSegment {
+    sequence: u64,
+    uri: String,
+    discontinuity: bool,
+}
+
+/// The parts of a remote m3u8 the puller acts on.
+#[derive(Debug, Default)]
+struct RemotePlaylist {
+    target_duration: f64,
+    segments: Vec<RemoteSegment>,
+    ended: bool,
+    /// Master playlist variants as (BANDWIDTH, uri)
+    variants: Vec<(u64, String)>,
+}
+
+impl RemotePlaylist {
+    fn parse(text: &str) -> Result<Self> {
+        if !text.trim_start().starts_with("#EXTM3U") {
+            return Err(anyhow!("Not an m3u8 playlist"));
+        }
+        let mut playlist = RemotePlaylist {
+            target_duration: 6.0,
+            ..Default::default()
+        };
+        let mut sequence = 0;
+        let mut discontinuity = false;
+        let mut variant_bandwidth = None;
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                playlist.target_duration = value.parse().unwrap_or(6.0);
+            } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+                sequence = value.parse().unwrap_or(0);
+            } else if line == "#EXT-X-DISCONTINUITY" {
+                discontinuity = true;
+            } else if line == "#EXT-X-ENDLIST" {
+                playlist.ended = true;
+            } else if line.starts_with("#EXT-X-MAP:") {
+                return Err(anyhow!("fMP4 segments are not supported, only MPEG-TS"));
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+                if !attrs.contains("METHOD=NONE") {
+                    return Err(anyhow!("Encrypted HLS sources are not supported"));
+                }
+            } else if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                variant_bandwidth = Some(
+                    attrs
+                        .split(',')
+                        .find_map(|a| a.strip_prefix("BANDWIDTH="))
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0),
+                );
+            } else if !line.starts_with('#') {
+                if let Some(bandwidth) = variant_bandwidth.take() {
+                    playlist.variants.push((bandwidth, line.to_string()));
+                } else {
+                    playlist.segments.push(RemoteSegment {
+                        sequence,
+                        uri: line.to_string(),
+                        discontinuity: std::mem::take(&mut discontinuity),
+                    });
+                    sequence += 1;
+                }
+            }
+        }
+        Ok(playlist)
+    }
+
+    /// Highest-bandwidth variant of a master playlist.
+    fn best_variant(&self) -> Option<&str> {
+        self.variants
+            .iter()
+            .max_by_key(|(bandwidth, _)| *bandwidth)
+            .map(|(_, uri)| uri.as_str())
+    }
+}
+
+/// Maps 90 kHz source timestamps onto one millisecond timeline that keeps
+/// running across `#EXT-X-DISCONTINUITY`, PTS wraps and encoder restarts.
+#[derive(Debug, Default)]
+struct ContinuousClock {
+    offset_ms: i64,
+    last_source_ms: Option<i64>,
+    last_output_ms: u64,
+    rebase_pending: bool,
+}
+
+impl ContinuousClock {
+    fn mark_discontinuity(&mut self) {
+        self.rebase_pending = true;
+    }
+
+    fn map(&mut self, timestamp_90k: u64) -> u64 {
+        let source_ms = (timestamp_90k / 90) as i64;
+        match self.last_source_ms {
+            None => self.offset_ms = -source_ms,
+            Some(last)
+                if self.rebase_pending || (source_ms - last).abs() > MAX_TIMESTAMP_JUMP_MS =>
+            {
+                debug!(
+                    "[HLS Puller] Timestamp rebase: source {}ms -> output {}ms",
+                    source_ms,
+                    self.last_output_ms + DISCONTINUITY_GAP_MS
+                );
+                self.offset_ms = (self.last_output_ms + DISCONTINUITY_GAP_MS) as i64 - source_ms;
+            }
+            Some(_) => {}
+        }
+        self.rebase_pending = false;
+        self.last_source_ms = Some(source_ms);
+        let output = (source_ms + self.offset_ms).max(0) as u64;
+        self.last_output_ms = self.last_output_ms.max(output);
+        output
+    }
+}
+
+/// Releases frames at the rate of their (continuous) timestamps, so a segment
+/// reaches the hub over its duration instead of in one burst.
+#[derive(Debug, Default)]
+struct Pacer {
+    /// Wall-clock instant and timestamp of the first paced frame
+    anchor: Option<(Instant, u64)>,
+}
+
+impl Pacer {
+    /// Time to wait before publishing a frame stamped `timestamp_ms`.
+    fn delay(&mut self, timestamp_ms: u64, now: Instant) -> Duration {
+        let (start, base) = *self.anchor.get_or_insert((now, timestamp_ms));
+        let due = start + Duration::from_millis(timestamp_ms.saturating_sub(base));
+        if now.saturating_duration_since(due) > Duration::from_millis(MAX_PACING_LAG_MS) {
+            // Downloads stalled: restart pacing here rather than bursting to catch up
+            self.anchor = Some((now, timestamp_ms));
+            return Duration::ZERO;
+        }
+        due.saturating_duration_since(now)
+    }
+}
+
+pub struct HlsPuller {
+    stream_manager: Arc<StreamManager>,
+}
+
+impl HlsPuller {
+    pub fn new(stream_manager: Arc<StreamManager>) -> Self {
+        Self { stream_manager }
+    }
+
+    pub async fn pull(&self, remote_url: &str, local_stream_id: &str) -> Result<()> {
+        let mut playlist_url =
+            Url::parse(remote_url).map_err(|e| anyhow!("Invalid HLS URL: {}", e))?;
+
+        info!("[HLS Puller] =========================================");
+        info!(
+            "[HLS Puller] Pull {} -> local stream '{}'",
+            remote_url, local_stream_id
+        );
+        info!("[HLS Puller] =========================================");
+
+        let mut playlist = fetch_playlist(&playlist_url).await?;
+        if let Some(variant) = playlist.best_variant() {
+            playlist_url = playlist_url.join(variant)?;
+            info!(
+                "[HLS Puller] Master playlist, using variant {}",
+                playlist_url
+            );
+            playlist = fetch_playlist(&playlist_url).await?;
+        }
+
+        self.stream_manager.create_stream(
+            local_stream_id,
+            StreamSourceMode::Pull,
+            StreamProtocol::HLS,
+            Some(remote_url.to_string()),
+        );
+        let _ = self.stream_manager.set_unpublished(local_stream_id);
+        self.stream_manager.set_stream_broadcast(local_stream_id);
+        let _ = self.stream_manager.set_publishing(local_stream_id);
+
+        let mut relay = SegmentRelay::new(self.stream_manager.clone(), local_stream_id);
+        let mut next_sequence = None;
+        let mut failures = 0;
+
+        loop {
+            if self.stream_manager.get_hub(local_stream_id).is_none() {
+                info!("[HLS Puller] Local stream '{}' removed", local_stream_id);
+                break;
+            }
+
+            // Join near the live edge, then take everything newer than the last fetch
+            let start = match next_sequence {
+                Some(next) => playlist
+                    .segments
+                    .iter()
+                    .position(|s| s.sequence >= next)
+                    .unwrap_or(playlist.segments.len()),
+                None if playlist.ended => 0,
+                None => playlist.segments.len().saturating_sub(LIVE_EDGE_SEGMENTS),
+            };
+            let new_segments = &playlist.segments[start..];
+            for segment in new_segments {
+                if next_sequence.is_some_and(|next| segment.sequence > next) {
+                    warn!(
+                        "[HLS Puller] Fell behind: segments {}..{} expired before download",
+                        next_sequence.unwrap_or_default(),
+                        segment.sequence
+                    );
+                    relay.clock.mark_discontinuity();
+                }
+                if segment.discontinuity {
+                    relay.clock.mark_discontinuity();
+                }
+                let segment_url = playlist_url.join(&segment.uri)?;
+                match http_get(&segment_url).await {
+                    Ok(data) => {
+                        relay.relay_segment(&data);
+                        relay.publish_paced().await;
+                    }
+                    Err(e) => {
+                        warn!("[HLS Puller] Segment {} failed: {}", segment_url, e);
+                        relay.clock.mark_discontinuity();
+                    }
+                }
+                next_sequence = Some(segment.sequence + 1);
+            }
+
+            if playlist.ended && start + new_segments.len() >= playlist.segments.len() {
+                info!("[HLS Puller] Remote playlist ended");
+                break;
+            }
+
+            // Pacing already spent the new segments' duration; when nothing
+            // changed, reload after half a target duration
+            if new_segments.is_empty() {
+                sleep(Duration::from_secs_f64(
+                    (playlist.target_duration / 2.0).max(0.5),
+                ))
+                .await;
+            }
+
+            match fetch_playlist(&playlist_url).await {
+                Ok(reloaded) => {
+                    failures = 0;
+                    playlist = reloaded;
+                }
+                Err(e) => {
+                    failures += 1;
+                    warn!(
+                        "[HLS Puller] Playlist reload failed ({}/{}): {}",
+                        failures, MAX_PLAYLIST_FAILURES, e
+                    );
+                    if failures >= MAX_PLAYLIST_FAILURES {
+                        let _ = self.stream_manager.set_stopped(local_stream_id);
+                        return Err(e);
+                    }
+                    playlist.segments.clear();
+                }
+            }
+        }
+
+        let _ = self.stream_manager.set_stopped(local_stream_id);
+        info!(
+            "[HLS Puller] Pull ended for stream '{}', total frames={}",
+            local_stream_id, relay.frames_relayed
+        );
+        Ok(())
+    }
+}
+
+/// Demuxes downloaded segments and publishes their frames on one timeline.
+struct SegmentRelay {
+    manager: Arc<StreamManager>,
+    stream_id: String,
+    clock: ContinuousClock,
+    tracks: Vec<Track>,
+    /// Frames of the current segment, in file order
+    pending: Vec<MediaFrame>,
+    pacer: Pacer,
+    frames_relayed: u64,
+}
+
+impl SegmentRelay {
+    fn new(manager: Arc<StreamManager>, stream_id: &str) -> Self {
+        Self {
+            manager,
+            stream_id: stream_id.to_string(),
+            clock: ContinuousClock::default(),
+            tracks: Vec::new(),
+            pending: Vec::new(),
+            pacer: Pacer::default(),
+            frames_relayed: 0,
+        }
+    }
+
+    fn relay_segment(&mut self, data: &[u8]) {
+        // Each segment is self-contained (PAT/PMT at its start)
+        let mut packets = Vec::new();
+        demux_ts(data, |pes| {
+            if let Some(pts) = pes.pts {
+                let dts = pes.dts.unwrap_or(pts);
+                packets.push((pes.offset, pes.track, pts, dts, pes.payload.to_vec()));
+            }
+        });
+        packets.sort_by_key(|(offset, ..)| *offset);
+        for (_, track, pts, dts, payload) in packets {
+            match track {
+                TsTrack::Video(codec) => self.relay_video(codec, dts, &payload),
+                TsTrack::Audio => self.relay_audio(pts, &payload),
+            }
+        }
+    }
+
+    fn relay_video(&mut self, codec: CodecType, dts: u64, payload: &[u8]) {
+        let data = normalize_annex_b(payload, codec);
+        if data.is_empty() {
+            return;
+        }
+        let nals: Vec<&[u8]> = iter_annex_b_nal_ranges(&data)
+            .into_iter()
+            .map(|(start, end)| &data[start..end])
+            .collect();
+        let is_keyframe = match codec {
+            CodecType::H265 => nals
+                .iter()
+                .any(|nal| h265::is_irap_type(h265::nal_type(nal[0]))),
+            _ => nals.iter().any(|nal| nal[0] & 0x1F == 5),
+        };
+        if is_keyframe {
+            for nal in &nals {
+                match codec {
+                    CodecType::H265 => self
+                        .manager
+                        .merge_stream_hevc_nalu_config(&self.stream_id, nal),
+                    _ => self.manager.merge_stream_nalu_config(&self.stream_id, nal),
+                }
+            }
+        }
+        self.ensure_track(Track::new(0, codec, 96, 90_000));
+        let timestamp = self.clock.map(dts);
+        self.pending.push(MediaFrame::new(
+            self.stream_id.clone(),
+            0,
+            timestamp,
+            Bytes::from(data),
+            is_keyframe,
+            codec,
+        ));
+    }
+
+    fn relay_audio(&mut self, pts: u64, payload: &[u8]) {
+        let frames = split_adts_frames(payload);
+        let Some(config) = frames.first().and_then(|f| AudioCodecConfig::from_adts(f)) else {
+            return;
+        };
+        let mut track = Track::new(1, CodecType::AAC, 97, config.sample_rate);
+        track.set_audio_specific_config(&config.asc);
+        self.ensure_track(track);
+        let timestamp = self.clock.map(pts);
+        for (i, frame) in frames.into_iter().enumerate() {
+            // protection_absent = 0 adds a 2-byte CRC to the header
+            let header_len = if frame[1] & 0x01 != 0 { 7 } else { 9 };
+            let Some(raw) = frame.get(header_len..).filter(|raw| !raw.is_empty()) else {
+                continue;
+            };
+            let offset_ms = i as u64 * AAC_FRAME_SAMPLES * 1000 / config.sample_rate as u64;
+            self.pending.push(MediaFrame::new(
+                self.stream_id.clone(),
+                1,
+                timestamp + offset_ms,
+                Bytes::copy_from_slice(raw),
+                false,
+                CodecType::AAC,
+            ));
+        }
+    }
+
+    /// Announce a newly seen (or changed) elementary stream as a stream track.
+    fn ensure_track(&mut self, track: Track) {
+        match self.tracks.iter_mut().find(|t| t.id == track.id) {
+            Some(existing)
+                if existing.codec == track.codec
+                    && existing.clock_rate == track.clock_rate
+                    && existing.extra_params == track.extra_params =>
+            {
+                return
+            }
+            Some(existing) => *existing = track,
+            None => self.tracks.push(track),
+        }
+        self.tracks.sort_by_key(|t| t.id);
+        info!(
+            "[HLS Puller] Stream '{}' tracks: {:?}",
+            self.stream_id,
+            self.tracks.iter().map(|t| t.codec).collect::<Vec<_>>()
+        );
+        self.manager
+            .set_stream_tracks(&self.stream_id, self.tracks.clone());
+    }
+
+    /// Publish the demuxed frames of the last segment at their timestamp pace.
+    async fn publish_paced(&mut self) {
+        for frame in std::mem::take(&mut self.pending) {
+            let delay = self.pacer.delay(frame.timestamp, Instant::now());
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            self.publish(frame);
+        }
+    }
+
+    fn publish(&mut self, frame: MediaFrame) {
+        self.frames_relayed += 1;
+        if self.frames_relayed == 1 {
+            info!(
+                "[HLS Puller] First relayed frame: codec={:?} keyframe={} ts={} size={}",
+                frame.codec,
+                frame.is_keyframe,
+                frame.timestamp,
+                frame.data.len()
+            );
+        }
+        self.manager
+            .publish_frame(frame.with_clock_rate(MILLISECOND_CLOCK_RATE));
+    }
+}
+
+async fn fetch_playlist(url: &Url) -> Result<RemotePlaylist> {
+    let body = http_get(url).await?;
+    RemotePlaylist::parse(&String::from_utf8_lossy(&body))
+}
+
+/// Minimal HTTP/1.1 GET over http or https, following redirects.
+async fn http_get(url: &Url) -> Result<Vec<u8>> {
+    let mut url = url.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        let (status, headers, body) = timeout(HTTP_TIMEOUT, http_request(&url))
+            .await
+            .map_err(|_| anyhow!("Timed out fetching {}", url))??;
+        match status {
+            200 => return Ok(body),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = header_value(&headers, "location")
+                    .ok_or_else(|| anyhow!("Redirect without Location from {}", url))?;
+                url = url.join(location)?;
+                debug!("[HLS Puller] Redirected to {}", url);
+            }
+            _ => return Err(anyhow!("HTTP {} from {}", status, url)),
+        }
+    }
+    Err(anyhow!("Too many redirects fetching {}", url))
+}
+
+async fn http_request(url: &Url) -> Result<(u16, String, Vec<u8>)> {
+    let tls = match url.scheme() {
+        "http" => false,
+        "https" => true,
+        scheme => {
+            return Err(anyhow!(
+                "Only http:// and https:// HLS sources are supported, got {}",
+                scheme
+            ))
+        }
+    };
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Missing host in HLS URL"))?;
+    let port = url
+        .port_or_known_default()
+        .unwrap_or(if tls { 443 } else { 80 });
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: vcp-media-server\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+
+    let tcp = TcpStream::connect((host, port)).await?;
+    let mut response = if tls {
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| anyhow!("Invalid TLS server name {}: {}", host, e))?;
+        let stream = TLS_CONNECTOR.connect(server_name, tcp).await?;
+        exchange(stream, &request).await?
+    } else {
+        exchange(tcp, &request).await?
+    };
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed HTTP response from {}", url))?;
+    let headers = String::from_utf8_lossy(&response[..header_end]).to_string();
+    let status = headers
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Malformed HTTP status line from {}", url))?;
+
+    let mut body = response.split_off(header_end + 4);
+    if header_value(&headers, "transfer-encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    {
+        body = decode_chunked(&body)?;
+    } else if let Some(len) = header_value(&headers, "content-length").and_then(|v| v.parse().ok())
+    {
+        body.truncate(len);
+    }
+    Ok((status, headers, body))
+}
+
+/// Send `request` and read the response until the server closes the connection.
+async fn exchange<S>(mut stream: S, request: &str) -> Result<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response).await {
+        Ok(_) => Ok(response),
+        // Servers often close TLS without close_notify once the body is complete
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => {
+            Ok(response)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn header_value<'a>(headers: &'a str, name: &str) -> Option<&'a str> {
+    headers.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("Truncated chunked body"))?;
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| anyhow!("Invalid chunk size '{}'", size_hex))?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if data.len() < size {
+            return Err(anyhow!("Truncated chunked body"));
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::hls::ts_muxer::TsMuxer;
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn parses_media_playlist_with_discontinuity() {
+        let playlist = RemotePlaylist::parse(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-MEDIA-SEQUENCE:7\n#EXTINF:4.0,\na.ts\n#EXT-X-DISCONTINUITY\n#EXTINF:4.0,\nb.ts\n#EXT-X-ENDLIST\n",
+        )
+        .unwrap();
+        assert_eq!(playlist.target_duration, 4.0);
+        assert!(playlist.ended);
+        assert_eq!(
+            playlist.segments,
+            vec![
+                RemoteSegment {
+                    sequence: 7,
+                    uri: "a.ts".into(),
+                    discontinuity: false
+                },
+                RemoteSegment {
+                    sequence: 8,
+                    uri: "b.ts".into(),
+                    discontinuity: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_highest_bandwidth_variant() {
+        let playlist = RemotePlaylist::parse(
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=800000\nlow.m3u8\n#EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\nhigh.m3u8\n",
+        )
+        .unwrap();
+        assert_eq!(playlist.best_variant(), Some("high.m3u8"));
+        assert!(playlist.segments.is_empty());
+
+        assert!(RemotePlaylist::parse("#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n").is_err());
+    }
+
+    #[test]
+    fn clock_stays_continuous_across_discontinuity() {
+        let mut clock = ContinuousClock::default();
+        assert_eq!(clock.map(900_000), 0);
+        assert_eq!(clock.map(990_000), 1000);
+        // Encoder restart without a tag: large backwards jump
+        assert_eq!(clock.map(0), 1040);
+        assert_eq!(clock.map(9_000), 1140);
+        // Tagged discontinuity with a small step still rebases
+        clock.mark_discontinuity();
+        assert_eq!(clock.map(4_500), 1180);
+    }
+
+    #[test]
+    fn pacer_follows_timestamps_and_reanchors_after_stall() {
+        let mut pacer = Pacer::default();
+        let start = Instant::now();
+        assert_eq!(pacer.delay(1_000, start), Duration::ZERO);
+        assert_eq!(pacer.delay(1_040, start), Duration::from_millis(40));
+        assert_eq!(
+            pacer.delay(1_080, start + Duration::from_millis(50)),
+            Duration::from_millis(30)
+        );
+        // Far behind schedule: re-anchor instead of bursting
+        let late = start + Duration::from_secs(10);
+        assert_eq!(pacer.delay(1_120, late), Duration::ZERO);
+        assert_eq!(pacer.delay(1_160, late), Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_scheme() {
+        let err = http_get(&Url::parse("ftp://example.com/live.m3u8").unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("https://"));
+    }
+
+    #[test]
+    fn decodes_chunked_body() {
+        assert_eq!(
+            decode_chunked(b"4\r\nWiki\r\n5;ext\r\npedia\r\n0\r\n\r\n").unwrap(),
+            b"Wikipedia"
+        );
+    }
+
+    fn segment(muxer: &mut TsMuxer, start_ms: u64) -> Vec<u8> {
+        let mut ts = muxer.generate_pat_pmt(true, true);
+        for i in 0..3u64 {
+            let keyframe = i == 0;
+            let nal: &[u8] = if keyframe {
+                &[
+                    0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xCE, 0, 0, 0, 1, 0x65, 0x88,
+                ]
+            } else {
+                &[0, 0, 0, 1, 0x41, 0x9A]
+            };
+            ts.extend(muxer.frame_to_ts(&MediaFrame::new(
+                "src".into(),
+                0,
+                start_ms + i * 40,
+                Bytes::copy_from_slice(nal),
+                keyframe,
+                CodecType::H264,
+            )));
+            ts.extend(muxer.frame_to_ts(&MediaFrame::new(
+                "src".into(),
+                1,
+                start_ms + i * 40,
+                Bytes::from(vec![0x21u8; 64]),
+                false,
+                CodecType::AAC,
+            )));
+        }
+        ts
+    }
+
+    /// Static file server for one request per connection.
+    async fn serve_static(files: HashMap<&'static str, Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 2048];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let response = match files.get(path) {
+                    Some(body) => {
+                        let mut r = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        r.extend_from_slice(body);
+                        r
+                    }
+                    None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                };
+                let _ = socket.write_all(&response).await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn pulls_static_playlist_into_hub() {
+        let mut muxer = TsMuxer::new();
+        let first = segment(&mut muxer, 5_000);
+        // Second segment restarts the source clock behind a discontinuity
+        let mut muxer = TsMuxer::new();
+        let second = segment(&mut muxer, 0);
+        let mut files = HashMap::new();
+        files.insert(
+            "/live/index.m3u8",
+            b"#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:0.12,\nseg0.ts\n#EXT-X-DISCONTINUITY\n#EXTINF:0.12,\nseg1.ts\n#EXT-X-ENDLIST\n".to_vec(),
+        );
+        files.insert("/live/seg0.ts", first);
+        files.insert("/live/seg1.ts", second);
+        let base = serve_static(files).await;
+
+        let manager = Arc::new(StreamManager::new());
+        HlsPuller::new(manager.clone())
+            .pull(&format!("{}/live/index.m3u8", base), "pulled")
+            .await
+            .unwrap();
+
+        let hub = manager.get_hub("pulled").unwrap();
+        let frames: Vec<MediaFrame> = (0..=hub.latest_seq()).filter_map(|s| hub.get(s)).collect();
+        let video: Vec<&MediaFrame> = frames
+            .iter()
+            .filter(|f| f.codec == CodecType::H264)
+            .collect();
+        let audio: Vec<&MediaFrame> = frames
+            .iter()
+            .filter(|f| f.codec == CodecType::AAC)
+            .collect();
+        assert_eq!(video.len(), 6);
+        assert_eq!(audio.len(), 6);
+        assert!(video[0].is_keyframe && video[3].is_keyframe);
+        assert!(video[0].data.starts_with(&[0, 0, 0, 1, 0x67]));
+        assert_eq!(audio[0].data.len(), 64);
+
+        let timestamps: Vec<u64> = video.iter().map(|f| f.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 40, 80, 120, 160, 200]);
+        let stream = manager.get_stream(&"pulled".to_string()).unwrap();
+        assert!(stream.sps.is_some() && stream.pps.is_some());
+        assert_eq!(stream.tracks.len(), 2);
+    }
+}
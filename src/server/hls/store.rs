@@ -0,0 +1,198 @@
+/// Memory-backed HLS segment store.
+/// Keeps segments, LL-HLS parts, DASH files and init segments of every stream in
+/// `Bytes` buffers instead of files; a total byte cap evicts the oldest media
+/// sequences across all streams.
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tracing::debug;
+
+/// Media sequence of a segment file name: `segment_<seq>.<ts|m4s>`, LL-HLS parts
+/// `segment_<seq>.<part>.<ts|m4s>` and DASH `video_<seq>.m4s` / `audio_<seq>.m4s`.
+/// Init segments and playlists have none.
+pub(super) fn media_sequence(filename: &str) -> Option<u64> {
+    ["segment_", "video_", "audio_"]
+        .iter()
+        .find_map(|prefix| filename.strip_prefix(prefix))
+        .and_then(|s| s.strip_suffix(".ts").or_else(|| s.strip_suffix(".m4s")))
+        .and_then(|s| s.split('.').next())
+        .and_then(|s| s.parse::<u64>().ok())
+}
+
+#[derive(Default)]
+struct StreamFiles {
+    /// Media files grouped by sequence (segment + its parts and renditions)
+    media: BTreeMap<u64, HashMap<String, Bytes>>,
+    /// Files without a sequence (init segments)
+    other: HashMap<String, Bytes>,
+}
+
+#[derive(Default)]
+struct StoreInner {
+    streams: HashMap<String, StreamFiles>,
+    /// (stream, sequence) in insertion order, oldest first
+    order: VecDeque<(String, u64)>,
+    total_bytes: u64,
+}
+
+pub struct MemorySegmentStore {
+    limit_bytes: u64,
+    inner: Mutex<StoreInner>,
+}
+
+impl MemorySegmentStore {
+    pub fn new(limit_bytes: u64) -> Self {
+        Self {
+            limit_bytes,
+            inner: Mutex::new(StoreInner::default()),
+        }
+    }
+
+    /// Store `filename` for `stream_id`, replacing an earlier copy, then evict the
+    /// oldest sequences (of any stream) while over the memory cap.
+    pub fn insert(&self, stream_id: &str, filename: &str, data: Bytes) {
+        let mut inner = self.inner.lock();
+        let seq = media_sequence(filename);
+        let added = data.len() as u64;
+        let stream = inner.streams.entry(stream_id.to_string()).or_default();
+        let (replaced, new_group) = match seq {
+            Some(seq) => {
+                let new_group = !stream.media.contains_key(&seq);
+                let files = stream.media.entry(seq).or_default();
+                (files.insert(filename.to_string(), data), new_group)
+            }
+            None => (stream.other.insert(filename.to_string(), data), false),
+        };
+        let replaced = replaced.map_or(0, |old| old.len() as u64);
+        inner.total_bytes = inner.total_bytes + added - replaced;
+        if let (Some(seq), true) = (seq, new_group) {
+            inner.order.push_back((stream_id.to_string(), seq));
+        }
+
+        while inner.total_bytes > self.limit_bytes {
+            let Some((oldest_stream, oldest_seq)) = inner.order.front().cloned() else {
+                break;
+            };
+            // Never evict what was just stored
+            if oldest_stream == stream_id && Some(oldest_seq) == seq {
+                break;
+            }
+            inner.order.pop_front();
+            let freed = inner
+                .streams
+                .get_mut(&oldest_stream)
+                .and_then(|s| s.media.remove(&oldest_seq))
+                .map_or(0, |files| files.values().map(|d| d.len() as u64).sum());
+            inner.total_bytes -= freed;
+            debug!(
+                "[HLS] [{}] Memory cap reached, evicted sequence {} ({} bytes)",
+                oldest_stream, oldest_seq, freed
+            );
+        }
+    }
+
+    pub fn get(&self, stream_id: &str, filename: &str) -> Option<Bytes> {
+        let inner = self.inner.lock();
+        let stream = inner.streams.get(stream_id)?;
+        match media_sequence(filename) {
+            Some(seq) => stream.media.get(&seq)?.get(filename).cloned(),
+            None => stream.other.get(filename).cloned(),
+        }
+    }
+
+    /// Drop media sequences of `stream_id` below `before_seq`.
+    pub fn prune(&self, stream_id: &str, before_seq: u64) {
+        let mut inner = self.inner.lock();
+        let Some(stream) = inner.streams.get_mut(stream_id) else {
+            return;
+        };
+        let kept = stream.media.split_off(&before_seq);
+        let removed = std::mem::replace(&mut stream.media, kept);
+        let freed: u64 = removed
+            .values()
+            .flat_map(|files| files.values())
+            .map(|d| d.len() as u64)
+            .sum();
+        inner.total_bytes -= freed;
+        inner
+            .order
+            .retain(|(s, seq)| s != stream_id || *seq >= before_seq);
+    }
+
+    /// Drop every file of `stream_id`.
+    pub fn remove_stream(&self, stream_id: &str) {
+        let mut inner = self.inner.lock();
+        let Some(stream) = inner.streams.remove(stream_id) else {
+            return;
+        };
+        let freed: u64 = stream
+            .media
+            .values()
+            .flat_map(|files| files.values())
+            .chain(stream.other.values())
+            .map(|d| d.len() as u64)
+            .sum();
+        inner.total_bytes -= freed;
+        inner.order.retain(|(s, _)| s != stream_id);
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.inner.lock().total_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(len: usize) -> Bytes {
+        Bytes::from(vec![0u8; len])
+    }
+
+    #[test]
+    fn parses_media_sequence_from_filenames() {
+        assert_eq!(media_sequence("segment_12.ts"), Some(12));
+        assert_eq!(media_sequence("segment_12.3.m4s"), Some(12));
+        assert_eq!(media_sequence("audio_7.m4s"), Some(7));
+        assert_eq!(media_sequence("init.mp4"), None);
+        assert_eq!(media_sequence("live.m3u8"), None);
+    }
+
+    #[test]
+    fn prune_drops_old_sequences_and_their_parts() {
+        let store = MemorySegmentStore::new(u64::MAX);
+        store.insert("a", "init.mp4", data(10));
+        store.insert("a", "segment_0.0.ts", data(100));
+        store.insert("a", "segment_0.ts", data(200));
+        store.insert("a", "segment_1.ts", data(200));
+        assert_eq!(store.total_bytes(), 510);
+
+        store.prune("a", 1);
+        assert!(store.get("a", "segment_0.0.ts").is_none());
+        assert!(store.get("a", "segment_0.ts").is_none());
+        assert_eq!(store.get("a", "segment_1.ts").unwrap().len(), 200);
+        assert!(store.get("a", "init.mp4").is_some());
+        assert_eq!(store.total_bytes(), 210);
+
+        store.remove_stream("a");
+        assert_eq!(store.total_bytes(), 0);
+    }
+
+    #[test]
+    fn memory_cap_evicts_oldest_sequences_across_streams() {
+        let store = MemorySegmentStore::new(1000);
+        store.insert("a", "segment_0.ts", data(400));
+        store.insert("b", "segment_0.ts", data(400));
+        store.insert("a", "segment_1.ts", data(400));
+
+        assert!(store.get("a", "segment_0.ts").is_none());
+        assert!(store.get("b", "segment_0.ts").is_some());
+        assert!(store.get("a", "segment_1.ts").is_some());
+        assert_eq!(store.total_bytes(), 800);
+
+        // A segment larger than the cap is still kept until the next one arrives
+        store.insert("b", "segment_1.ts", data(2000));
+        assert!(store.get("b", "segment_1.ts").is_some());
+        assert_eq!(store.total_bytes(), 2000);
+    }
+}
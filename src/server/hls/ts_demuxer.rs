@@ -0,0 +1,409 @@
+/// MPEG-TS demuxer shared by recording playback/recovery and HLS ingest:
+/// PAT/PMT lookup and PES reassembly for one program with H264/H265 video and
+/// ADTS AAC audio. Timestamps stay in the 90 kHz PES clock.
+use std::collections::BTreeMap;
+
+use crate::core::{h265, CodecType};
+use crate::server::webrtc::h264_util::is_keyframe_annex_b;
+
+pub const TS_PACKET_SIZE: usize = 188;
+pub const PTS_CLOCK_PER_MS: u64 = 90;
+
+const TS_SYNC_BYTE: u8 = 0x47;
+const STREAM_TYPE_AAC: u8 = 0x0F;
+const STREAM_TYPE_H264: u8 = 0x1B;
+const STREAM_TYPE_H265: u8 = 0x24;
+const PTS_MASK: u64 = (1 << 33) - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsTrack {
+    Video(CodecType),
+    Audio,
+}
+
+/// One reassembled PES packet.
+#[derive(Debug, Clone, Copy)]
+pub struct TsPes<'a> {
+    pub track: TsTrack,
+    /// Byte offset of the packet that starts this PES; gives file order.
+    pub offset: u64,
+    /// 90 kHz presentation timestamp.
+    pub pts: Option<u64>,
+    /// 90 kHz decoding timestamp, when it differs from the PTS.
+    pub dts: Option<u64>,
+    pub payload: &'a [u8],
+}
+
+impl TsPes<'_> {
+    pub fn is_keyframe(&self) -> bool {
+        match self.track {
+            TsTrack::Video(CodecType::H265) => h265::contains_irap_nalu(self.payload),
+            TsTrack::Video(_) => is_keyframe_annex_b(self.payload),
+            TsTrack::Audio => false,
+        }
+    }
+}
+
+/// Container facts gathered while demuxing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TsLayout {
+    /// Offset of the first non-PSI packet (size of the PAT/PMT header).
+    pub header_bytes: u64,
+    /// Length up to the last complete TS packet.
+    pub valid_bytes: u64,
+    pub has_video: bool,
+    pub has_audio: bool,
+}
+
+#[derive(Default)]
+struct Programs {
+    pmt_pid: Option<u16>,
+    video: Option<(u16, CodecType)>,
+    audio_pid: Option<u16>,
+}
+
+impl Programs {
+    fn track(&self, pid: u16) -> Option<TsTrack> {
+        match self.video {
+            Some((video_pid, codec)) if video_pid == pid => Some(TsTrack::Video(codec)),
+            _ if self.audio_pid == Some(pid) => Some(TsTrack::Audio),
+            _ => None,
+        }
+    }
+}
+
+/// Walk `data` and hand every complete PES to `on_pes`. PES are delivered when
+/// the next one on the same PID starts, so calls are only ordered per track;
+/// sort by [`TsPes::offset`] for file order. A trailing partial packet or an
+/// incomplete final PES is ignored.
+pub fn demux_ts(data: &[u8], mut on_pes: impl FnMut(TsPes<'_>)) -> TsLayout {
+    let valid_len = data.len() / TS_PACKET_SIZE * TS_PACKET_SIZE;
+    let mut layout = TsLayout {
+        valid_bytes: valid_len as u64,
+        ..Default::default()
+    };
+    let mut programs = Programs::default();
+    let mut header_done = false;
+    // PID → (start offset, buffered PES bytes)
+    let mut pes: BTreeMap<u16, (u64, Vec<u8>)> = BTreeMap::new();
+
+    for (index, packet) in data[..valid_len].chunks_exact(TS_PACKET_SIZE).enumerate() {
+        if packet[0] != TS_SYNC_BYTE {
+            continue;
+        }
+        let pid = (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2]);
+        let unit_start = packet[1] & 0x40 != 0;
+        let adaptation = (packet[3] >> 4) & 0x03;
+        let mut payload_start = 4;
+        if adaptation & 0x02 != 0 {
+            payload_start += 1 + packet[4] as usize;
+        }
+        if adaptation & 0x01 == 0 || payload_start >= TS_PACKET_SIZE {
+            continue;
+        }
+        let payload = &packet[payload_start..];
+        let offset = (index * TS_PACKET_SIZE) as u64;
+
+        if pid == 0 {
+            if let Some(section) = psi_section(payload, unit_start) {
+                programs.pmt_pid = parse_pat(section);
+            }
+            continue;
+        }
+        if Some(pid) == programs.pmt_pid {
+            if let Some(section) = psi_section(payload, unit_start) {
+                for (stream_type, es_pid) in parse_pmt(section) {
+                    match stream_type {
+                        STREAM_TYPE_H264 => programs.video = Some((es_pid, CodecType::H264)),
+                        STREAM_TYPE_H265 => programs.video = Some((es_pid, CodecType::H265)),
+                        STREAM_TYPE_AAC => programs.audio_pid = Some(es_pid),
+                        _ => {}
+                    }
+                }
+                layout.has_video = programs.video.is_some();
+                layout.has_audio = programs.audio_pid.is_some();
+            }
+            continue;
+        }
+        if !header_done {
+            layout.header_bytes = offset;
+            header_done = true;
+        }
+        let Some(track) = programs.track(pid) else {
+            continue;
+        };
+        if unit_start {
+            if let Some((start, buf)) = pes.insert(pid, (offset, payload.to_vec())) {
+                emit_pes(track, start, &buf, false, &mut on_pes);
+            }
+        } else if let Some((_, buf)) = pes.get_mut(&pid) {
+            buf.extend_from_slice(payload);
+        }
+    }
+    for (pid, (start, buf)) in pes {
+        if let Some(track) = programs.track(pid) {
+            emit_pes(track, start, &buf, true, &mut on_pes);
+        }
+    }
+    layout
+}
+
+fn emit_pes(
+    track: TsTrack,
+    offset: u64,
+    buf: &[u8],
+    final_pes: bool,
+    on_pes: &mut impl FnMut(TsPes<'_>),
+) {
+    if let Some((pts, dts, payload)) = parse_pes(buf, final_pes) {
+        on_pes(TsPes {
+            track,
+            offset,
+            pts,
+            dts,
+            payload,
+        });
+    }
+}
+
+/// Signed milliseconds from `from` to `to`, across a 33-bit PTS wrap.
+pub fn pts_diff_ms(from: u64, to: u64) -> i64 {
+    let delta = to.wrapping_sub(from) & PTS_MASK;
+    let delta = if delta > PTS_MASK / 2 {
+        delta as i64 - (PTS_MASK as i64 + 1)
+    } else {
+        delta as i64
+    };
+    delta / PTS_CLOCK_PER_MS as i64
+}
+
+/// Individual ADTS frames in a PES payload; stops at the first damaged header.
+pub fn split_adts_frames(data: &[u8]) -> Vec<&[u8]> {
+    let mut frames = Vec::new();
+    let mut i = 0;
+    while i + 7 <= data.len() && data[i] == 0xFF && data[i + 1] & 0xF0 == 0xF0 {
+        let frame_len = (usize::from(data[i + 3] & 0x03) << 11)
+            | (usize::from(data[i + 4]) << 3)
+            | (usize::from(data[i + 5]) >> 5);
+        if frame_len < 7 || i + frame_len > data.len() {
+            break;
+        }
+        frames.push(&data[i..i + frame_len]);
+        i += frame_len;
+    }
+    frames
+}
+
+fn psi_section(payload: &[u8], unit_start: bool) -> Option<&[u8]> {
+    if !unit_start {
+        return None;
+    }
+    let pointer = *payload.first()? as usize;
+    let section = payload.get(1 + pointer..)?;
+    let section_len = ((usize::from(section.get(1)? & 0x0F)) << 8) | usize::from(*section.get(2)?);
+    section.get(..3 + section_len)
+}
+
+fn parse_pat(section: &[u8]) -> Option<u16> {
+    // Program loop between the 8-byte header and the CRC.
+    let programs = section.get(8..section.len().checked_sub(4)?)?;
+    programs.chunks_exact(4).find_map(|program| {
+        let number = u16::from_be_bytes([program[0], program[1]]);
+        (number != 0).then(|| (u16::from(program[2] & 0x1F) << 8) | u16::from(program[3]))
+    })
+}
+
+fn parse_pmt(section: &[u8]) -> Vec<(u8, u16)> {
+    let mut streams = Vec::new();
+    let Some(end) = section.len().checked_sub(4) else {
+        return streams;
+    };
+    let Some(info_len) = section
+        .get(10..12)
+        .map(|b| (usize::from(b[0] & 0x0F) << 8) | usize::from(b[1]))
+    else {
+        return streams;
+    };
+    let mut i = 12 + info_len;
+    while i + 5 <= end {
+        let stream_type = section[i];
+        let pid = (u16::from(section[i + 1] & 0x1F) << 8) | u16::from(section[i + 2]);
+        let es_info_len = (usize::from(section[i + 3] & 0x0F) << 8) | usize::from(section[i + 4]);
+        streams.push((stream_type, pid));
+        i += 5 + es_info_len;
+    }
+    streams
+}
+
+/// PES header → (PTS, DTS in 90 kHz, elementary payload). A bounded PES shorter
+/// than its declared length, or any truncated header, yields `None`.
+fn parse_pes(buf: &[u8], final_pes: bool) -> Option<(Option<u64>, Option<u64>, &[u8])> {
+    if buf.len() < 9 || buf[..3] != [0, 0, 1] {
+        return None;
+    }
+    let declared = usize::from(u16::from_be_bytes([buf[4], buf[5]]));
+    let end = if declared > 0 {
+        if buf.len() < 6 + declared {
+            return None;
+        }
+        6 + declared
+    } else {
+        buf.len()
+    };
+    let payload_start = 9 + usize::from(buf[8]);
+    if payload_start > end {
+        return None;
+    }
+    let pts = (buf[7] & 0x80 != 0 && payload_start >= 14).then(|| read_timestamp(&buf[9..14]));
+    let dts = (buf[7] & 0xC0 == 0xC0 && payload_start >= 19).then(|| read_timestamp(&buf[14..19]));
+    let payload = &buf[payload_start..end];
+    // An unbounded final PES may be cut anywhere; keep it only if it carries data.
+    if final_pes && declared == 0 && payload.is_empty() {
+        return None;
+    }
+    Some((pts, dts, payload))
+}
+
+fn read_timestamp(bytes: &[u8]) -> u64 {
+    (u64::from(bytes[0] & 0x0E) << 29)
+        | (u64::from(bytes[1]) << 22)
+        | (u64::from(bytes[2] & 0xFE) << 14)
+        | (u64::from(bytes[3]) << 7)
+        | (u64::from(bytes[4]) >> 1)
+}
+
+/// Re-emit an access unit with 4-byte start codes and without access unit
+/// delimiters, as the other ingest paths publish it. Third-party TS commonly
+/// uses 3-byte start codes.
+pub fn normalize_annex_b(data: &[u8], codec: CodecType) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 16);
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0x00, 0x00, 0x01] {
+            starts.push(i);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    for (n, &start) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).copied().unwrap_or(data.len());
+        // A 4-byte start code leaves its leading zero on the previous NAL
+        if end < data.len() && end > start + 3 && data[end - 1] == 0x00 {
+            end -= 1;
+        }
+        let nal = &data[start + 3..end];
+        let is_aud = match (codec, nal.first()) {
+            (_, None) => true,
+            (CodecType::H265, Some(&header)) => h265::nal_type(header) == h265::NAL_AUD,
+            (_, Some(&header)) => header & 0x1F == 9,
+        };
+        if !is_aud {
+            out.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            out.extend_from_slice(nal);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::MediaFrame;
+    use crate::server::hls::ts_muxer::TsMuxer;
+    use bytes::Bytes;
+
+    #[test]
+    fn demuxes_muxer_output_back_into_frames() {
+        let mut muxer = TsMuxer::new();
+        let idr = vec![0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x65, 0x88, 0x84, 0x21];
+        let aac = vec![0x21u8; 300];
+        let mut ts = muxer.generate_pat_pmt(true, true);
+        ts.extend(muxer.frame_to_ts(&MediaFrame::new(
+            "s".into(),
+            0,
+            1000,
+            Bytes::from(idr.clone()),
+            true,
+            CodecType::H264,
+        )));
+        ts.extend(muxer.frame_to_ts(&MediaFrame::new(
+            "s".into(),
+            1,
+            1010,
+            Bytes::from(aac.clone()),
+            false,
+            CodecType::AAC,
+        )));
+
+        let mut pes = Vec::new();
+        let layout = demux_ts(&ts, |p| {
+            pes.push((
+                p.offset,
+                p.track,
+                p.pts,
+                p.is_keyframe(),
+                p.payload.to_vec(),
+            ))
+        });
+        assert!(layout.has_video && layout.has_audio);
+        pes.sort_by_key(|p| p.0);
+        assert_eq!(pes.len(), 2);
+
+        assert_eq!(pes[0].1, TsTrack::Video(CodecType::H264));
+        assert_eq!(pes[0].2, Some(90_000));
+        assert!(pes[0].3);
+        assert_eq!(normalize_annex_b(&pes[0].4, CodecType::H264), idr);
+
+        assert_eq!(pes[1].1, TsTrack::Audio);
+        assert_eq!(pes[1].2, Some(1010 * 90));
+        let frames = split_adts_frames(&pes[1].4);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(&frames[0][7..], &aac[..]);
+    }
+
+    #[test]
+    fn short_bounded_pes_is_dropped() {
+        let pes = [0, 0, 1, 0xE0, 0x00, 0x01, 0x80, 0x80, 0x05, 0x21];
+        assert!(parse_pes(&pes, false).is_none());
+    }
+
+    #[test]
+    fn normalizes_three_byte_start_codes_and_drops_aud() {
+        let au = [
+            0, 0, 1, 0x09, 0xF0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x65, 0x88,
+        ];
+        assert_eq!(
+            normalize_annex_b(&au, CodecType::H264),
+            vec![0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x65, 0x88]
+        );
+    }
+
+    #[test]
+    fn pts_diff_crosses_33_bit_wrap() {
+        assert_eq!(pts_diff_ms(900, 1_800), 10);
+        assert_eq!(pts_diff_ms(1_800, 900), -10);
+        assert_eq!(pts_diff_ms(PTS_MASK - 89, 90 * 2), 3);
+    }
+
+    #[test]
+    fn splits_back_to_back_adts_frames() {
+        let frame = |len: usize| {
+            let mut f = vec![0xFF, 0xF1, 0x50, 0x80, 0, 0x1F, 0xFC];
+            f[3] |= ((len >> 11) & 0x03) as u8;
+            f[4] = ((len >> 3) & 0xFF) as u8;
+            f[5] = (((len & 0x07) << 5) as u8) | 0x1F;
+            f.resize(len, 0xAA);
+            f
+        };
+        let mut data = frame(10);
+        data.extend(frame(12));
+        data.extend_from_slice(&[0xFF, 0xF1]);
+        let frames = split_adts_frames(&data);
+        assert_eq!(
+            frames.iter().map(|f| f.len()).collect::<Vec<_>>(),
+            vec![10, 12]
+        );
+    }
+}
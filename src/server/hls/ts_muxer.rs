@@ -2,12 +2,10 @@
 /// Generates PAT, PMT, and PES packets for HLS segments.
 use bytes::Bytes;
 
+use super::encryption::{sample_aes_adts, sample_aes_h264};
+use super::fmp4_muxer::AudioCodecConfig;
 use crate::core::{CodecType, MediaFrame};
 
-/// AAC sample rate index (44100 Hz)
-const AAC_SAMPLE_RATE_INDEX: u8 = 4;
-const AAC_CHANNELS: u8 = 2;
-
 /// Standard TS packet size
 const TS_PACKET_SIZE: usize = 188;
 /// Sync byte for TS packets
@@ -20,6 +18,8 @@ const PMT_PID: u16 = 0x1000;
 const VIDEO_PID: u16 = 0x100;
 /// Audio PID (AAC)
 const AUDIO_PID: u16 = 0x101;
+/// Timed metadata PID (ID3)
+const METADATA_PID: u16 = 0x102;
 
 /// CRC32 table for MPEG-TS (ISO 13818-1)
 const CRC32_TABLE: [u32; 256] = {
@@ -51,6 +51,14 @@ fn crc32(data: &[u8]) -> u32 {
     crc
 }
 
+/// SAMPLE-AES key of the open segment plus the AudioSpecificConfig advertised
+/// in the PMT `audio_setup_information`.
+struct SampleAes {
+    key: [u8; 16],
+    iv: [u8; 16],
+    audio_asc: Vec<u8>,
+}
+
 /// MPEG-TS adaptation-field flags
 const AF_DISCONTINUITY: u8 = 0x80;
 const AF_PCR: u8 = 0x10;
@@ -59,12 +67,21 @@ const AF_PCR: u8 = 0x10;
 pub struct TsMuxer {
     continuity_counter_video: u8,
     continuity_counter_audio: u8,
+    continuity_counter_metadata: u8,
     continuity_counter_pat: u8,
     continuity_counter_pmt: u8,
     /// PCR in 27MHz units
     pcr_clock: u64,
     /// Set on the first PAT packet of a segment (resets demuxer CC/PCR state).
     segment_discontinuity: bool,
+    /// Video elementary stream codec advertised in the PMT.
+    video_codec: CodecType,
+    /// Encrypt H.264/AAC payloads (SAMPLE-AES) when set.
+    sample_aes: Option<SampleAes>,
+    /// Declare the ID3 timed-metadata PID in the PMT.
+    timed_metadata: bool,
+    /// AAC config written into the ADTS headers of raw hub frames.
+    audio_config: AudioCodecConfig,
 }
 
 impl TsMuxer {
@@ -72,10 +89,38 @@ impl TsMuxer {
         Self {
             continuity_counter_video: 0,
             continuity_counter_audio: 0,
+            continuity_counter_metadata: 0,
             continuity_counter_pat: 0,
             continuity_counter_pmt: 0,
             pcr_clock: 0,
             segment_discontinuity: false,
+            video_codec: CodecType::H264,
+            sample_aes: None,
+            timed_metadata: false,
+            audio_config: AudioCodecConfig::default(),
+        }
+    }
+
+    /// AAC sample rate/channels for the ADTS headers of following audio frames.
+    pub fn set_audio_config(&mut self, config: AudioCodecConfig) {
+        self.audio_config = config;
+    }
+
+    /// Declare an ID3 timed-metadata stream (stream_type 0x15) in following PMTs.
+    pub fn set_timed_metadata(&mut self, enabled: bool) {
+        self.timed_metadata = enabled;
+    }
+
+    /// SAMPLE-AES key and IV for the following PMT and frames; `audio_asc` is the
+    /// AAC AudioSpecificConfig. Applies to H.264 and AAC only.
+    pub fn set_sample_aes(&mut self, key: [u8; 16], iv: [u8; 16], audio_asc: Vec<u8>) {
+        self.sample_aes = Some(SampleAes { key, iv, audio_asc });
+    }
+
+    /// Select the PMT video stream_type (0x1B H264, 0x24 H265).
+    pub fn set_video_codec(&mut self, codec: CodecType) {
+        if codec.is_video() {
+            self.video_codec = codec;
         }
     }
 
@@ -91,7 +136,15 @@ impl TsMuxer {
 
     /// Full reset after lag snap (true timeline discontinuity).
     pub fn hard_reset(&mut self) {
+        let video_codec = self.video_codec;
+        let sample_aes = self.sample_aes.take();
+        let timed_metadata = self.timed_metadata;
+        let audio_config = std::mem::take(&mut self.audio_config);
         *self = Self::new();
+        self.video_codec = video_codec;
+        self.sample_aes = sample_aes;
+        self.timed_metadata = timed_metadata;
+        self.audio_config = audio_config;
     }
 
     /// Generate a PAT (Program Association Table) packet
@@ -129,14 +182,60 @@ impl TsMuxer {
         payload.push(0x00); // Pointer field
         payload.push(0x02); // Table ID
 
+        // SAMPLE-AES: encrypted stream types and their private data descriptors
+        let mut video_es_info = Vec::new();
+        let mut audio_es_info = Vec::new();
+        let mut video_type = match self.video_codec {
+            CodecType::H265 => 0x24, // HEVC
+            _ => 0x1B,               // H264
+        };
+        let mut audio_type = 0x0F; // AAC
+        if let Some(sample_aes) = &self.sample_aes {
+            if video_type == 0x1B {
+                video_type = 0xDB;
+                video_es_info.extend_from_slice(&[0x0F, 4]);
+                video_es_info.extend_from_slice(b"zavc");
+            }
+            audio_type = 0xCF;
+            audio_es_info.extend_from_slice(&[0x0F, 4]);
+            audio_es_info.extend_from_slice(b"aacd");
+            // registration_descriptor carrying audio_setup_information
+            let setup_len = sample_aes.audio_asc.len();
+            audio_es_info.extend_from_slice(&[0x05, (4 + 4 + 2 + 1 + 1 + setup_len) as u8]);
+            audio_es_info.extend_from_slice(b"apad");
+            audio_es_info.extend_from_slice(b"zaac");
+            audio_es_info.extend_from_slice(&[0x00, 0x00, 0x01, setup_len as u8]);
+            audio_es_info.extend_from_slice(&sample_aes.audio_asc);
+        }
+
+        // metadata_descriptor: ID3 in PES (application format 0xFFFF, format 0xFF + 'ID3 ')
+        let mut metadata_es_info = vec![0x26, 13, 0xFF, 0xFF];
+        metadata_es_info.extend_from_slice(b"ID3 ");
+        metadata_es_info.push(0xFF);
+        metadata_es_info.extend_from_slice(b"ID3 ");
+        metadata_es_info.extend_from_slice(&[0x00, 0x0F]);
+
+        // metadata_pointer_descriptor: the ID3 metadata service belongs to program 1
+        let mut program_info = Vec::new();
+        if self.timed_metadata {
+            program_info.extend_from_slice(&[0x25, 15, 0xFF, 0xFF]);
+            program_info.extend_from_slice(b"ID3 ");
+            program_info.push(0xFF);
+            program_info.extend_from_slice(b"ID3 ");
+            program_info.extend_from_slice(&[0x00, 0x1F, 0x00, 0x01]);
+        }
+
         let mut stream_info_len: u16 = 0;
         if has_video {
-            stream_info_len += 5;
+            stream_info_len += 5 + video_es_info.len() as u16;
         }
         if has_audio {
-            stream_info_len += 5;
+            stream_info_len += 5 + audio_es_info.len() as u16;
         }
-        let section_length: u16 = 5 + 4 + stream_info_len + 4;
+        if self.timed_metadata {
+            stream_info_len += 5 + metadata_es_info.len() as u16;
+        }
+        let section_length: u16 = 5 + 4 + program_info.len() as u16 + stream_info_len + 4;
 
         payload.push(0xB0 | ((section_length >> 8) as u8 & 0x0F));
         payload.push((section_length & 0xFF) as u8);
@@ -155,19 +254,29 @@ impl TsMuxer {
         } else {
             payload.extend_from_slice(&[0x1F, 0xFF]);
         }
-        payload.extend_from_slice(&[0xF0, 0x00]); // Program info length = 0
+        payload.extend_from_slice(&[0xF0, program_info.len() as u8]);
+        payload.extend_from_slice(&program_info);
 
         if has_video {
-            payload.push(0x1B); // H264
+            payload.push(video_type);
             payload.push(((VIDEO_PID >> 8) as u8) & 0x1F);
             payload.push((VIDEO_PID & 0xFF) as u8);
-            payload.extend_from_slice(&[0xF0, 0x00]);
+            payload.extend_from_slice(&[0xF0, video_es_info.len() as u8]);
+            payload.extend_from_slice(&video_es_info);
         }
         if has_audio {
-            payload.push(0x0F); // AAC
+            payload.push(audio_type);
             payload.push(((AUDIO_PID >> 8) as u8) & 0x1F);
             payload.push((AUDIO_PID & 0xFF) as u8);
-            payload.extend_from_slice(&[0xF0, 0x00]);
+            payload.extend_from_slice(&[0xF0, audio_es_info.len() as u8]);
+            payload.extend_from_slice(&audio_es_info);
+        }
+        if self.timed_metadata {
+            payload.push(0x15); // Metadata in PES
+            payload.push(((METADATA_PID >> 8) as u8) & 0x1F);
+            payload.push((METADATA_PID & 0xFF) as u8);
+            payload.extend_from_slice(&[0xF0, metadata_es_info.len() as u8]);
+            payload.extend_from_slice(&metadata_es_info);
         }
 
         let crc = crc32(&payload[1..]);
@@ -199,7 +308,9 @@ impl TsMuxer {
 
         let is_video = matches!(frame.codec, CodecType::H264 | CodecType::H265);
         let payload = if matches!(frame.codec, CodecType::AAC) {
-            wrap_aac_adts(&frame.data)
+            wrap_aac_adts(&self.audio_config, &frame.data)
+        } else if frame.codec == CodecType::H265 {
+            prepare_h265_au_for_ts(&frame.data)
         } else if is_video {
             prepare_h264_au_for_ts(&frame.data)
         } else {
@@ -208,6 +319,11 @@ impl TsMuxer {
         if payload.is_empty() {
             return Vec::new();
         }
+        let payload = match (&self.sample_aes, frame.codec) {
+            (Some(aes), CodecType::H264) => sample_aes_h264(&aes.key, &aes.iv, &payload),
+            (Some(aes), CodecType::AAC) => sample_aes_adts(&aes.key, &aes.iv, &payload),
+            _ => payload,
+        };
         let frame_for_pes = MediaFrame::new(
             frame.stream_id.clone(),
             frame.track_id,
@@ -227,6 +343,28 @@ impl TsMuxer {
         pes_to_ts_packets(&pes, pid, is_video, cc, self.pcr_clock)
     }
 
+    /// Wrap an ID3 tag into a private_stream_1 PES on the metadata PID at `timestamp_ms`.
+    pub fn metadata_to_ts(&mut self, timestamp_ms: u64, id3: &[u8]) -> Vec<u8> {
+        if !self.timed_metadata || id3.is_empty() {
+            return Vec::new();
+        }
+        let mut pes = vec![0x00, 0x00, 0x01, 0xBD];
+        let pes_body_len = 3 + 5 + id3.len();
+        pes.extend_from_slice(&(pes_body_len.min(0xFFFF) as u16).to_be_bytes());
+        pes.push(0x84); // '10' + data_alignment_indicator
+        pes.push(0x80); // PTS only
+        pes.push(5);
+        write_pes_timestamp(&mut pes, 0x20, timestamp_ms);
+        pes.extend_from_slice(id3);
+        pes_to_ts_packets(
+            &pes,
+            METADATA_PID,
+            false,
+            &mut self.continuity_counter_metadata,
+            self.pcr_clock,
+        )
+    }
+
     fn take_segment_discontinuity(&mut self) -> bool {
         std::mem::take(&mut self.segment_discontinuity)
     }
@@ -262,6 +400,22 @@ fn prepare_h264_au_for_ts(data: &[u8]) -> Vec<u8> {
     out
 }
 
+/// Prepend H265 AUD (NAL type 35) if missing.
+fn prepare_h265_au_for_ts(data: &[u8]) -> Vec<u8> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+    if data.starts_with(&[0x00, 0x00, 0x00, 0x01, 0x46])
+        || data.starts_with(&[0x00, 0x00, 0x01, 0x46])
+    {
+        return data.to_vec();
+    }
+    let mut out = Vec::with_capacity(7 + data.len());
+    out.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x46, 0x01, 0x50]);
+    out.extend_from_slice(data);
+    out
+}
+
 fn starts_with_aud(data: &[u8]) -> bool {
     if data.starts_with(&[0x00, 0x00, 0x00, 0x01, 0x09]) {
         return true;
@@ -498,7 +652,7 @@ fn ts_packet(
 }
 
 /// Wrap raw AAC (RTMP-style, no ADTS) in a 7-byte ADTS header for MPEG-TS.
-fn wrap_aac_adts(aac_raw: &[u8]) -> Vec<u8> {
+fn wrap_aac_adts(config: &AudioCodecConfig, aac_raw: &[u8]) -> Vec<u8> {
     if aac_raw.is_empty() {
         return Vec::new();
     }
@@ -507,15 +661,8 @@ fn wrap_aac_adts(aac_raw: &[u8]) -> Vec<u8> {
         return aac_raw.to_vec();
     }
 
-    let frame_len = aac_raw.len() + 7;
-    let mut adts = Vec::with_capacity(frame_len);
-    adts.push(0xFF);
-    adts.push(0xF1); // MPEG-4, layer 0, no CRC
-    adts.push((0 << 6) | ((AAC_SAMPLE_RATE_INDEX & 0x0F) << 2) | ((AAC_CHANNELS >> 2) & 0x01));
-    adts.push(((AAC_CHANNELS & 0x03) << 6) | ((frame_len >> 11) as u8));
-    adts.push(((frame_len >> 3) & 0xFF) as u8);
-    adts.push((((frame_len & 0x07) as u8) << 5) | 0x1F);
-    adts.push(0xFC);
+    let mut adts = Vec::with_capacity(aac_raw.len() + 7);
+    adts.extend_from_slice(&config.adts_header(aac_raw.len()));
     adts.extend_from_slice(aac_raw);
     adts
 }
@@ -556,6 +703,78 @@ mod tests {
         assert_eq!(&pes[14..], &payload[..]);
     }
 
+    #[test]
+    fn pmt_advertises_hevc_stream_type() {
+        let mut muxer = TsMuxer::new();
+        muxer.set_video_codec(CodecType::H265);
+        muxer.hard_reset();
+        let pmt = muxer.generate_pmt(true, false);
+        // Section is right-aligned after stuffing: 13 header bytes, one ES entry, CRC.
+        assert_eq!(pmt[TS_PACKET_SIZE - 5 - 4], 0x24);
+
+        let au = prepare_h265_au_for_ts(&[0, 0, 0, 1, 0x26, 0x01, 0xAF]);
+        assert_eq!(&au[..7], &[0, 0, 0, 1, 0x46, 0x01, 0x50]);
+    }
+
+    #[test]
+    fn pmt_advertises_sample_aes_stream_types() {
+        let mut muxer = TsMuxer::new();
+        muxer.set_sample_aes([1; 16], [0; 16], vec![0x12, 0x10]);
+        let pmt = muxer.generate_pmt(true, true);
+        let contains = |needle: &[u8]| pmt.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"\xdb\x01\x00\xf0\x06\x0f\x04zavc"));
+        assert!(contains(
+            b"\xcf\x01\x01\xf0\x16\x0f\x04aacd\x05\x0eapadzaac"
+        ));
+    }
+
+    #[test]
+    fn adts_header_follows_audio_config() {
+        let mut muxer = TsMuxer::new();
+        muxer.set_audio_config(AudioCodecConfig::aac_lc(48_000, 1));
+        muxer.hard_reset();
+        let adts = wrap_aac_adts(&muxer.audio_config, &[0x21; 10]);
+        assert_eq!(adts.len(), 17);
+        // profile LC (1), frequency index 3 (48 kHz), one channel
+        assert_eq!(adts[2], (1 << 6) | (3 << 2));
+        assert_eq!(adts[3] >> 6, 1);
+    }
+
+    #[test]
+    fn timed_metadata_pid_declared_and_muxed() {
+        let mut muxer = TsMuxer::new();
+        assert!(muxer.metadata_to_ts(1000, b"ID3").is_empty());
+        muxer.set_timed_metadata(true);
+        let pmt = muxer.generate_pmt(true, true);
+        assert!(pmt.windows(3).any(|w| w == [0x15, 0x01, 0x02]));
+        assert!(pmt.windows(4).any(|w| w == b"ID3 "));
+        // Section after the adaptation field stuffing and pointer field
+        let payload_start = if pmt[3] & 0x20 != 0 {
+            5 + pmt[4] as usize
+        } else {
+            4
+        };
+        let section = &pmt[payload_start + 1..];
+        assert_eq!(section[0], 0x02);
+        let section_length = (((section[1] & 0x0F) as usize) << 8) | section[2] as usize;
+        assert_eq!(section.len(), 3 + section_length);
+        let program_info_length = (((section[10] & 0x0F) as usize) << 8) | section[11] as usize;
+        assert_eq!(program_info_length, 17);
+        let program_info = &section[12..12 + program_info_length];
+        assert_eq!(&program_info[..4], &[0x25, 15, 0xFF, 0xFF]);
+        assert_eq!(&program_info[4..8], b"ID3 ");
+        assert_eq!(&program_info[13..], &[0x00, 0x1F, 0x00, 0x01]);
+        let crc_at = section.len() - 4;
+        assert_eq!(crc32(&section[..crc_at]).to_be_bytes(), section[crc_at..]);
+
+        let ts = muxer.metadata_to_ts(1000, b"ID3\x04\x00\x00\x00\x00\x00\x00");
+        assert_eq!(ts.len(), TS_PACKET_SIZE);
+        assert_eq!(ts[1], 0x40 | 0x01); // PUSI + PID 0x102 high bits
+        assert_eq!(ts[2], 0x02);
+        let pes = ts.windows(4).position(|w| w == [0x00, 0x00, 0x01, 0xBD]);
+        assert!(pes.is_some());
+    }
+
     #[test]
     fn pat_first_packet_sets_discontinuity_indicator() {
         let mut muxer = TsMuxer::new();
@@ -1,53 +1,113 @@
 use anyhow::Result;
 use serde_json::json;
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::{sleep, Duration, Instant};
 use tracing::{error, info, warn};
 
+use crate::core::live_play::prepare_video_play_frame;
 use crate::core::{
-    is_idr_frame, prime_live_play, CodecType, DispatchError, DispatchPolicy, StreamManager,
-    StreamProtocol, StreamSourceMode, Track, WallclockMsTimeline,
+    is_idr_frame, job_runner, prime_live_play, CodecType, DispatchError, DispatchPolicy, JobKind,
+    JobSupervisor, MediaFrame, Stream, StreamManager, StreamProtocol, StreamSourceMode, Track,
+    WallclockMsTimeline,
 };
 use crate::process::analysis::{AnalysisManager, StartAnalysisRequest, StopAnalysisRequest};
-use crate::process::record::{RecorderManager, StartRecordRequest, StopRecordRequest};
+use crate::process::record::{
+    build_vod_playlist, open_vod_segment, parse_vod_segment_filename, resolve_byte_range,
+    vod_content_type, ExportRequest, PacedReader, RecordFormat, RecorderManager,
+    StartRecordRequest, StopRecordRequest, TriggerRecordRequest,
+};
 use crate::process::snapshot::{CaptureSnapshotRequest, SnapshotManager};
-use crate::server::hls::HlsServer;
-use crate::server::http_flv::{format_chunk, HttpFlvServer, HttpFlvSession};
-use crate::server::rtmp::RtmpPuller;
+use crate::server::hls::encryption::parse_key_filename;
+use crate::server::hls::id3::TimedMetadata;
+use crate::server::hls::m3u8::{M3u8Generator, PlaylistWindow};
+use crate::server::hls::puller::HlsPuller;
+use crate::server::hls::{HlsSegmentFormat, HlsServer};
+use crate::server::http_flv::{
+    format_chunk, websocket_key, FlvTransport, HttpFlvServer, HttpFlvSession,
+};
+use crate::server::http_mp4::HttpMp4Session;
+use crate::server::http_ts::HttpTsSession;
+use crate::server::rtmp::puller::parse_rtmp_url;
+use crate::server::rtmp::session::StreamMetadata;
+use crate::server::rtmp::{RtmpPuller, RtmpPusher};
 use crate::server::rtsp::{RtspPuller, RtspPusher};
 use crate::server::webrtc::request_publisher_keyframe;
 
+/// Single-response live muxers fed from the hub (`/ts/<id>`, `/mp4/<id>`).
+enum LiveBodySession {
+    Ts(HttpTsSession),
+    Mp4(HttpMp4Session),
+}
+
+impl LiveBodySession {
+    /// `(stream_id, log label)` when `route` is a live body route.
+    fn route(route: &str) -> Option<(&str, &'static str)> {
+        if let Some(rest) = route.strip_prefix("/ts/") {
+            return Some((
+                rest.trim_end_matches('/').trim_end_matches(".ts"),
+                "HTTP-TS",
+            ));
+        }
+        let rest = route.strip_prefix("/mp4/")?;
+        Some((
+            rest.trim_end_matches('/').trim_end_matches(".mp4"),
+            "HTTP-MP4",
+        ))
+    }
+
+    fn new(label: &str, stream: &Stream) -> Self {
+        match label {
+            "HTTP-MP4" => Self::Mp4(HttpMp4Session::new(stream)),
+            _ => Self::Ts(HttpTsSession::new(stream)),
+        }
+    }
+
+    fn http_headers(label: &str) -> String {
+        match label {
+            "HTTP-MP4" => HttpMp4Session::generate_http_headers(),
+            _ => HttpTsSession::generate_http_headers(),
+        }
+    }
+
+    fn wait_for_keyframe(&mut self) {
+        match self {
+            Self::Ts(session) => session.wait_for_keyframe(),
+            Self::Mp4(session) => session.wait_for_keyframe(),
+        }
+    }
+
+    fn mux(&mut self, frame: &MediaFrame) -> Vec<u8> {
+        match self {
+            Self::Ts(session) => session.frame_to_ts(frame),
+            Self::Mp4(session) => session.frame_to_mp4(frame),
+        }
+    }
+}
+
+/// Shared handles every HTTP connection works with; optional services are
+/// `None` when disabled in the config.
+#[derive(Clone)]
+pub struct HttpContext {
+    pub stream_manager: Arc<StreamManager>,
+    pub hls_server: Option<Arc<HlsServer>>,
+    pub http_flv_server: Option<Arc<HttpFlvServer>>,
+    pub recorder: Option<Arc<RecorderManager>>,
+    pub analysis: Option<Arc<AnalysisManager>>,
+    pub snapshot: Option<Arc<SnapshotManager>>,
+    pub jobs: Arc<JobSupervisor>,
+}
+
 pub struct HttpServer {
-    stream_manager: Arc<StreamManager>,
     port: u16,
-    hls_server: Option<Arc<HlsServer>>,
-    http_flv_server: Option<Arc<HttpFlvServer>>,
-    recorder: Option<Arc<RecorderManager>>,
-    analysis: Option<Arc<AnalysisManager>>,
-    snapshot: Option<Arc<SnapshotManager>>,
+    ctx: HttpContext,
 }
 
 impl HttpServer {
-    pub fn new(
-        stream_manager: Arc<StreamManager>,
-        port: u16,
-        hls_server: Option<Arc<HlsServer>>,
-        http_flv_server: Option<Arc<HttpFlvServer>>,
-        recorder: Option<Arc<RecorderManager>>,
-        analysis: Option<Arc<AnalysisManager>>,
-        snapshot: Option<Arc<SnapshotManager>>,
-    ) -> Self {
-        Self {
-            stream_manager,
-            port,
-            hls_server,
-            http_flv_server,
-            recorder,
-            analysis,
-            snapshot,
-        }
+    pub fn new(port: u16, ctx: HttpContext) -> Self {
+        Self { port, ctx }
     }
 
     pub async fn start(&self) -> Result<()> {
@@ -66,47 +126,67 @@ impl HttpServer {
         info!("[HTTP]   POST /api/rtsp/pull      - RTSP pull from remote URL");
         info!("[HTTP]   POST /api/rtsp/push      - RTSP push to remote URL");
         info!("[HTTP]   POST /api/rtmp/pull      - RTMP pull from remote URL");
-        if self.recorder.is_some() {
+        info!("[HTTP]   POST /api/rtmp/push      - RTMP push to remote URL");
+        info!("[HTTP]   GET  /api/pulls          - List supervised pull jobs");
+        info!("[HTTP]   GET  /api/pushes         - List supervised push jobs");
+        info!("[HTTP]   DELETE /api/pulls/<id>   - Stop a pull job (same for /api/pushes/<id>)");
+        info!("[HTTP]   POST /api/pulls/<id>/restart - Reconnect a job now (same for pushes)");
+        info!("[HTTP]   POST /api/hls/pull       - HLS pull from remote playlist URL");
+        if self.ctx.recorder.is_some() {
             info!("[HTTP]   POST /api/record/start  - Start DVR recording");
             info!("[HTTP]   POST /api/record/stop   - Stop DVR recording");
+            info!("[HTTP]   POST /api/record/trigger - Event clip with pre-roll");
             info!("[HTTP]   GET  /api/recordings    - List recordings");
+            info!("[HTTP]   GET  /api/record/events - Recorder events (retention deletions)");
+            info!("[HTTP]   GET  /api/recordings/<stream_id>/vod.m3u8?start=&end= - Recording VOD playlist");
+            info!("[HTTP]   POST /api/recordings/export - Export a time range as MP4");
+            info!("[HTTP]   GET  /api/recordings/exports/<id> - Export job status (<id>.mp4 downloads)");
         }
-        if self.analysis.is_some() {
+        if self.ctx.analysis.is_some() {
             info!("[HTTP]   POST /api/analysis/start - Start video analysis");
             info!("[HTTP]   POST /api/analysis/stop  - Stop video analysis");
             info!("[HTTP]   GET  /api/analysis/<stream_id>/metrics - Analysis metrics");
             info!("[HTTP]   GET  /api/analysis/<stream_id>/events  - Analysis events");
         }
-        if self.snapshot.is_some() {
+        if self.ctx.snapshot.is_some() {
             info!("[HTTP]   POST /api/snapshot      - Capture stream snapshot");
             info!("[HTTP]   GET  /api/snapshots     - List snapshots");
             info!("[HTTP]   GET  /api/snapshots/<id>.jpg - Read snapshot image");
         }
         info!("[HTTP]   GET  /webrtc/webrtc-test.html - WebRTC test page");
-        if self.hls_server.is_some() {
+        if self.ctx.hls_server.is_some() {
             info!("[HTTP]   GET  /hls/<stream_id>/live.m3u8 - HLS playlist");
-            info!("[HTTP]   GET  /hls/<stream_id>/<segment>.ts - HLS segment");
+            info!("[HTTP]   GET  /hls/<stream_id>/<segment>.ts - HLS segment (.m4s + init.mp4 for fMP4)");
+            info!("[HTTP]   GET  /hls/<stream_id>/live.m3u8?format=fmp4 - CMAF HLS playlist (init.mp4, .m4s)");
+            info!("[HTTP]   GET  /hls/<stream_id>/live.m3u8?offset=<secs> - HLS time-shift (DVR window)");
+            info!("[HTTP]   GET  /hls/<stream_id>/key_<n>.key?token= - HLS encryption key");
+            info!("[HTTP]   GET  /dash/<stream_id>/manifest.mpd - MPEG-DASH manifest");
+            info!("[HTTP]   POST /api/stream/<id>/metadata - HLS timed metadata (ID3) cue");
+            if self
+                .ctx
+                .hls_server
+                .as_ref()
+                .is_some_and(|hls| hls.is_low_latency())
+            {
+                info!("[HTTP]   GET  /hls/<stream_id>/live.m3u8?_HLS_msn=&_HLS_part= - LL-HLS blocking reload");
+            }
         }
-        if self.http_flv_server.is_some() {
-            info!("[HTTP]   GET  /flv/<stream_id>  - HTTP-FLV live stream");
+        info!("[HTTP]   GET  /ts/<stream_id>     - HTTP-TS live stream");
+        info!("[HTTP]   GET  /mp4/<stream_id>    - HTTP fMP4 live stream (MSE)");
+        if self.ctx.http_flv_server.is_some() {
+            info!("[HTTP]   GET  /flv/<stream_id>  - HTTP-FLV live stream (WebSocket upgrade for WS-FLV)");
+            if self.ctx.recorder.is_some() {
+                info!("[HTTP]   GET  /flv/record/<stream_id>?start=<ms> - HTTP-FLV recording playback");
+            }
         }
 
         loop {
             match listener.accept().await {
                 Ok((socket, peer_addr)) => {
                     info!("[HTTP] New request from {}", peer_addr);
-                    let manager = self.stream_manager.clone();
-                    let hls = self.hls_server.clone();
-                    let flv = self.http_flv_server.clone();
-                    let recorder = self.recorder.clone();
-                    let analysis = self.analysis.clone();
-                    let snapshot = self.snapshot.clone();
+                    let ctx = self.ctx.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(
-                            socket, manager, hls, flv, recorder, analysis, snapshot,
-                        )
-                        .await
-                        {
+                        if let Err(e) = Self::handle_connection(socket, ctx).await {
                             error!("[HTTP] Connection error from {}: {}", peer_addr, e);
                         }
                     });
@@ -118,15 +198,15 @@ impl HttpServer {
         }
     }
 
-    async fn handle_connection(
-        socket: TcpStream,
-        manager: Arc<StreamManager>,
-        hls_server: Option<Arc<HlsServer>>,
-        flv_server: Option<Arc<HttpFlvServer>>,
-        recorder: Option<Arc<RecorderManager>>,
-        analysis: Option<Arc<AnalysisManager>>,
-        snapshot: Option<Arc<SnapshotManager>>,
-    ) -> Result<()> {
+    async fn handle_connection(socket: TcpStream, ctx: HttpContext) -> Result<()> {
+        let HttpContext {
+            stream_manager: manager,
+            hls_server,
+            http_flv_server: flv_server,
+            recorder,
+            snapshot,
+            ..
+        } = ctx.clone();
         let mut buffer = vec![0u8; 8192];
         let mut socket = socket;
 
@@ -144,9 +224,10 @@ impl HttpServer {
             let path = parts[1];
 
             // HLS playlist request
-            if path.starts_with("/hls/") && path.ends_with(".m3u8") {
+            let (route, query) = path.split_once('?').unwrap_or((path, ""));
+            if route.starts_with("/hls/") && route.ends_with(".m3u8") {
                 if let Some(ref hls) = hls_server {
-                    let stream_id = path
+                    let stream_id = route
                         .trim_start_matches("/hls/")
                         .trim_end_matches("/live.m3u8");
                     if manager.get_stream(&stream_id.to_string()).is_none() {
@@ -158,15 +239,34 @@ impl HttpServer {
                     manager.ensure_stream_broadcast(stream_id);
                     let _ = hls.ensure_stream(stream_id, false).await;
                     request_publisher_keyframe(stream_id);
+                    let format =
+                        Self::query_param(query, "format").and_then(HlsSegmentFormat::parse);
+                    let format = hls.request_format(stream_id, format);
+                    let window = Self::playlist_window(query);
+
+                    // LL-HLS blocking playlist reload
+                    let msn = Self::query_param(query, "_HLS_msn").and_then(|v| v.parse().ok());
+                    let mut playlist = match msn {
+                        Some(msn) if hls.is_low_latency() => {
+                            let part =
+                                Self::query_param(query, "_HLS_part").and_then(|v| v.parse().ok());
+                            hls.blocking_playlist(stream_id, format, window, msn, part)
+                                .await
+                        }
+                        _ => hls.get_playlist(stream_id, format, window),
+                    };
 
                     let deadline = Instant::now() + Duration::from_secs(3);
-                    let mut playlist = hls.get_playlist(stream_id);
                     while playlist.is_none() && Instant::now() < deadline {
                         request_publisher_keyframe(stream_id);
                         sleep(Duration::from_millis(50)).await;
-                        playlist = hls.get_playlist(stream_id);
+                        playlist = hls.get_playlist(stream_id, format, window);
                     }
                     let playlist = playlist.unwrap_or_else(|| hls.empty_playlist());
+                    let playlist = match Self::query_param(query, "token") {
+                        Some(token) => M3u8Generator::with_key_token(playlist, token),
+                        None => playlist,
+                    };
                     let response = format!(
                         "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.apple.mpegurl\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache, no-store, must-revalidate\r\nConnection: close\r\n\r\n{}",
                         playlist.len(), playlist
@@ -181,25 +281,157 @@ impl HttpServer {
                 return Ok(());
             }
 
-            // HLS segment request
-            if path.starts_with("/hls/") && path.ends_with(".ts") {
+            // DASH manifest request (fed by the HLS segmenter)
+            if route.starts_with("/dash/") && route.ends_with("/manifest.mpd") {
                 if let Some(ref hls) = hls_server {
-                    let path_parts: Vec<&str> =
-                        path.trim_start_matches("/hls/").split('/').collect();
+                    let stream_id = route
+                        .trim_start_matches("/dash/")
+                        .trim_end_matches("/manifest.mpd");
+                    if manager.get_stream(&stream_id.to_string()).is_none() {
+                        let response = Self::http_response(404, "Not Found", "Stream not found");
+                        socket.write_all(response.as_bytes()).await?;
+                        socket.flush().await?;
+                        return Ok(());
+                    }
+                    manager.ensure_stream_broadcast(stream_id);
+                    let _ = hls.ensure_stream(stream_id, false).await;
+                    request_publisher_keyframe(stream_id);
+                    if !hls.request_dash(stream_id) {
+                        let response = Self::http_response(
+                            403,
+                            "Forbidden",
+                            "DASH unavailable for encrypted streams",
+                        );
+                        socket.write_all(response.as_bytes()).await?;
+                        socket.flush().await?;
+                        return Ok(());
+                    }
+
+                    // The first DASH segment completes one segment after the request.
+                    let mut manifest = hls.get_mpd(stream_id);
+                    let deadline = Instant::now() + Duration::from_secs(3);
+                    while manifest.is_none() && Instant::now() < deadline {
+                        request_publisher_keyframe(stream_id);
+                        sleep(Duration::from_millis(50)).await;
+                        manifest = hls.get_mpd(stream_id);
+                    }
+                    let response = match manifest {
+                        Some(manifest) => format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/dash+xml\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache, no-store, must-revalidate\r\nConnection: close\r\n\r\n{}",
+                            manifest.len(), manifest
+                        ),
+                        None => Self::http_response(503, "Service Unavailable", "DASH segments not ready"),
+                    };
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.shutdown().await?;
+                    return Ok(());
+                }
+                let response = Self::http_response(404, "Not Found", "");
+                socket.write_all(response.as_bytes()).await?;
+                socket.flush().await?;
+                return Ok(());
+            }
+
+            // Timed metadata cue for a packaged stream (ID3 in TS, emsg in fMP4)
+            let metadata_stream = route
+                .strip_prefix("/api/stream/")
+                .and_then(|rest| rest.strip_suffix("/metadata"));
+            if let (Some(stream_id), Some(hls), true) =
+                (metadata_stream, &hls_server, parts[0] == "POST")
+            {
+                let body = request
+                    .find("\r\n\r\n")
+                    .map(|i| &request[i + 4..])
+                    .unwrap_or("");
+                let response = match serde_json::from_str::<serde_json::Value>(body) {
+                    Ok(payload) => {
+                        let kind = payload
+                            .get("kind")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("api")
+                            .to_string();
+                        if hls.push_metadata(stream_id, TimedMetadata::new(&kind, payload)) {
+                            let body = json!({
+                                "stream_id": stream_id,
+                                "kind": kind,
+                                "message": "Metadata queued"
+                            })
+                            .to_string();
+                            Self::http_response(202, "Accepted", &body)
+                        } else {
+                            Self::http_response(
+                                404,
+                                "Not Found",
+                                "{\"error\":\"Stream is not packaged with timed metadata\"}",
+                            )
+                        }
+                    }
+                    Err(_) => {
+                        Self::http_response(400, "Bad Request", "{\"error\":\"Invalid JSON body\"}")
+                    }
+                };
+                socket.write_all(response.as_bytes()).await?;
+                socket.flush().await?;
+                return Ok(());
+            }
+
+            // HLS encryption key request (playback token checked)
+            let key_request = route
+                .strip_prefix("/hls/")
+                .and_then(|rest| rest.split_once('/'))
+                .and_then(|(stream_id, file)| Some((stream_id, parse_key_filename(file)?)));
+            if let (Some((stream_id, index)), Some(hls)) = (key_request, &hls_server) {
+                if !hls.authorize_key(stream_id, Self::query_param(query, "token")) {
+                    let response = Self::http_response(403, "Forbidden", "Invalid playback token");
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.flush().await?;
+                    return Ok(());
+                }
+                match hls.get_key(stream_id, index) {
+                    Some(key) => {
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+                            key.len()
+                        );
+                        socket.write_all(response.as_bytes()).await?;
+                        socket.write_all(&key).await?;
+                        socket.shutdown().await?;
+                    }
+                    None => {
+                        let response = Self::http_response(404, "Not Found", "");
+                        socket.write_all(response.as_bytes()).await?;
+                        socket.flush().await?;
+                    }
+                }
+                return Ok(());
+            }
+
+            // HLS segment (or LL-HLS part / fMP4 init segment) or DASH segment request
+            let segment_type = match route.rsplit_once('.').map(|(_, ext)| ext) {
+                Some("ts") => Some("video/mp2t"),
+                Some("m4s") => Some("video/iso.segment"),
+                Some("mp4") => Some("video/mp4"),
+                _ => None,
+            };
+            let segment_route = route
+                .strip_prefix("/hls/")
+                .or_else(|| route.strip_prefix("/dash/"));
+            if let (Some(segment_route), Some(content_type)) = (segment_route, segment_type) {
+                if let Some(ref hls) = hls_server {
+                    let path_parts: Vec<&str> = segment_route.split('/').collect();
                     if path_parts.len() >= 2 {
                         let stream_id = path_parts[0];
                         let filename = path_parts[1];
-                        if let Some(seg_path) = hls.get_segment_path(stream_id, filename) {
-                            if let Ok(data) = tokio::fs::read(&seg_path).await {
-                                let response = format!(
-                                    "HTTP/1.1 200 OK\r\nContent-Type: video/mp2t\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache, no-store\r\nConnection: close\r\n\r\n",
-                                    data.len()
-                                );
-                                socket.write_all(response.as_bytes()).await?;
-                                socket.write_all(&data).await?;
-                                socket.shutdown().await?;
-                                return Ok(());
-                            }
+                        if let Some(data) = hls.wait_segment(stream_id, filename).await {
+                            let response = format!(
+                                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache, no-store\r\nConnection: close\r\n\r\n",
+                                content_type,
+                                data.len()
+                            );
+                            socket.write_all(response.as_bytes()).await?;
+                            socket.write_all(&data).await?;
+                            socket.shutdown().await?;
+                            return Ok(());
                         }
                     }
                 }
@@ -222,10 +454,73 @@ impl HttpServer {
                 return Ok(());
             }
 
-            // HTTP-FLV request
+            // HTTP-FLV playback of recordings
+            if let (Some(rest), Some(_)) = (path.strip_prefix("/flv/record/"), &flv_server) {
+                let (stream_id, query) = rest.split_once('?').unwrap_or((rest, ""));
+                let stream_id = stream_id.trim_end_matches('/');
+                let start_ms = Self::query_param(query, "start")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .or_else(|| {
+                        recorder
+                            .as_ref()
+                            .and_then(|recorder| recorder.recorded_span(stream_id))
+                            .map(|(first_ms, _)| first_ms)
+                    });
+                let reader = match (&recorder, start_ms) {
+                    (Some(recorder), Some(start_ms)) => {
+                        recorder.open_playback(stream_id, start_ms).await.ok()
+                    }
+                    _ => None,
+                };
+                let Some(reader) = reader else {
+                    let response = Self::http_response(404, "Not Found", "Recording not found");
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.flush().await?;
+                    return Ok(());
+                };
+                info!(
+                    "[HTTP-FLV] [{}] recorded play from {} (requested {:?})",
+                    stream_id,
+                    reader.start_ms(),
+                    start_ms
+                );
+                let mut session = HttpFlvSession::new(stream_id);
+                socket
+                    .write_all(HttpFlvSession::generate_http_headers().as_bytes())
+                    .await?;
+                let initial_data = session.generate_recording_initial_data(reader.info());
+                socket.write_all(&format_chunk(&initial_data)).await?;
+
+                let mut paced = PacedReader::new(reader, 1.0);
+                let mut frames_sent = 0u64;
+                while let Some((frame, offset_ms)) = paced.next_frame().await {
+                    let tag = session.recorded_frame_to_flv(&frame, offset_ms as u32);
+                    if tag.is_empty() {
+                        continue;
+                    }
+                    if socket.write_all(&format_chunk(&tag)).await.is_err() {
+                        info!(
+                            "[HTTP-FLV] [{}] recorded play client disconnected after {} frames",
+                            stream_id, frames_sent
+                        );
+                        return Ok(());
+                    }
+                    frames_sent += 1;
+                }
+                info!(
+                    "[HTTP-FLV] [{}] recorded play reached end after {} frames",
+                    stream_id, frames_sent
+                );
+                socket.write_all(b"0\r\n\r\n").await?;
+                socket.shutdown().await?;
+                return Ok(());
+            }
+
+            // HTTP-FLV / WebSocket-FLV request
             if path.starts_with("/flv/") {
                 if let Some(ref flv) = flv_server {
                     let stream_id = path.trim_start_matches("/flv/").trim_end_matches('/');
+                    let websocket_key = websocket_key(&request);
                     if let Some((mut session, mut stream)) = flv.create_session(stream_id) {
                         let play_started_at = Instant::now();
                         let stream_id_owned = stream_id.to_string();
@@ -287,19 +582,27 @@ impl HttpServer {
                             stream.tracks.len()
                         );
 
-                        let http_headers = HttpFlvSession::generate_http_headers();
-                        socket.write_all(http_headers.as_bytes()).await?;
+                        let client_addr = socket
+                            .peer_addr()
+                            .map(|addr| addr.to_string())
+                            .unwrap_or_default();
+                        let mut transport = match websocket_key {
+                            Some(key) => FlvTransport::websocket(socket, &key).await?,
+                            None => FlvTransport::chunked(socket).await?,
+                        };
 
+                        session.set_metadata(
+                            StreamMetadata::from_stream(&stream).with_hub_rates(reader.hub()),
+                        );
                         let initial_data = session.generate_initial_data(&stream);
                         if !initial_data.is_empty() {
-                            let chunk = format_chunk(&initial_data);
-                            socket.write_all(&chunk).await?;
+                            transport.send(&initial_data).await?;
                             info!(
-                                "[HTTP-FLV] [{}] sent initial FLV headers after={}ms data_bytes={} chunk_bytes={}",
+                                "[HTTP-FLV] [{}] sent initial FLV headers after={}ms data_bytes={} websocket={}",
                                 stream_id,
                                 play_started_at.elapsed().as_millis(),
                                 initial_data.len(),
-                                chunk.len()
+                                transport.is_websocket()
                             );
                         }
 
@@ -317,15 +620,29 @@ impl HttpServer {
                         let mut frames_sent = 0u64;
                         let mut wallclock = WallclockMsTimeline::default();
                         let send_started_at = Instant::now();
+                        let receiver = manager.create_pull_receiver(
+                            stream_id,
+                            StreamProtocol::HTTP,
+                            &client_addr,
+                        );
 
-                        loop {
+                        'play: loop {
                             let frames = if let Some(frame) = pending_idr.take() {
                                 vec![frame]
                             } else {
-                                match reader.recv_batch().await {
-                                    Ok(frames) if !frames.is_empty() => frames,
-                                    Ok(_) => continue,
-                                    Err(DispatchError::Closed) => break,
+                                tokio::select! {
+                                    batch = reader.recv_batch() => match batch {
+                                        Ok(frames) if !frames.is_empty() => frames,
+                                        Ok(_) => continue,
+                                        Err(DispatchError::Closed) => break,
+                                    },
+                                    _ = transport.closed() => {
+                                        info!(
+                                            "[HTTP-FLV] [{}] WebSocket client closed after {} frames",
+                                            stream_id, frames_sent
+                                        );
+                                        break;
+                                    }
                                 }
                             };
                             if reader.take_muxer_resync() {
@@ -344,9 +661,7 @@ impl HttpServer {
                                 {
                                     continue;
                                 }
-                                if matches!(frame.codec, CodecType::H264 | CodecType::H265)
-                                    && !video_streaming
-                                {
+                                if frame.codec.is_video() && !video_streaming {
                                     if !is_idr_frame(&frame) {
                                         let requested =
                                             request_publisher_keyframe(&stream_id_owned);
@@ -376,11 +691,9 @@ impl HttpServer {
                                         flv.stream_manager().get_stream(&stream_id_owned)
                                     {
                                         let more = session.generate_initial_data(&stream);
-                                        if !more.is_empty() {
-                                            let chunk = format_chunk(&more);
-                                            if socket.write_all(&chunk).await.is_err() {
-                                                break;
-                                            }
+                                        if !more.is_empty() && transport.send(&more).await.is_err()
+                                        {
+                                            break 'play;
                                         }
                                     }
                                     if session.needs_sequence_headers() {
@@ -391,13 +704,12 @@ impl HttpServer {
                                 let (flv_data, tag_ts) =
                                     session.frame_to_flv_with_wallclock(&frame, &mut wallclock);
                                 if !flv_data.is_empty() {
-                                    let chunk = format_chunk(&flv_data);
-                                    if socket.write_all(&chunk).await.is_err() {
-                                        break;
+                                    if transport.send(&flv_data).await.is_err() {
+                                        break 'play;
                                     }
                                     if frames_sent == 0 {
                                         info!(
-                                        "[HTTP-FLV] [{}] >>> SENT first frame after={}ms codec={:?} keyframe={} raw_ts={} tag_ts={} send_elapsed_ms={} tag_bytes={} chunk_bytes={} cursor={} latest_seq={}",
+                                        "[HTTP-FLV] [{}] >>> SENT first frame after={}ms codec={:?} keyframe={} raw_ts={} tag_ts={} send_elapsed_ms={} tag_bytes={} cursor={} latest_seq={}",
                                         stream_id,
                                         play_started_at.elapsed().as_millis(),
                                         frame.codec,
@@ -406,7 +718,6 @@ impl HttpServer {
                                         tag_ts,
                                         send_started_at.elapsed().as_millis(),
                                         flv_data.len(),
-                                        chunk.len(),
                                         reader.cursor(),
                                         reader.hub().latest_seq()
                                     );
@@ -431,7 +742,8 @@ impl HttpServer {
                                 }
                             }
                         }
-                        socket.flush().await?;
+                        manager.remove_receiver(&receiver.id);
+                        transport.finish().await;
                         return Ok(());
                     }
                 }
@@ -441,6 +753,199 @@ impl HttpServer {
                 return Ok(());
             }
 
+            // HTTP-TS / HTTP fMP4 live stream
+            if let Some((stream_id, label)) = LiveBodySession::route(route) {
+                let reader = manager
+                    .get_stream(&stream_id.to_string())
+                    .zip(manager.dispatch_subscribe(stream_id, DispatchPolicy::LiveCoalesce));
+                let Some((stream, mut reader)) = reader else {
+                    let response = Self::http_response(404, "Not Found", "Stream not found");
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.flush().await?;
+                    return Ok(());
+                };
+                let client_addr = socket
+                    .peer_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default();
+                info!(
+                    "[{}] [{}] play request from {}",
+                    label, stream_id, client_addr
+                );
+                socket
+                    .write_all(LiveBodySession::http_headers(label).as_bytes())
+                    .await?;
+
+                let mut pending_idr =
+                    prime_live_play(&mut reader, &manager, stream_id, label).await;
+                // Codec config (e.g. the AAC ASC) is known once the publisher's first
+                // keyframe has arrived.
+                let stream = manager.get_stream(&stream_id.to_string()).unwrap_or(stream);
+                let mut session = LiveBodySession::new(label, &stream);
+                let receiver =
+                    manager.create_pull_receiver(stream_id, StreamProtocol::HTTP, &client_addr);
+                let mut bytes_sent = 0u64;
+                'play: loop {
+                    let frames = if let Some(frame) = pending_idr.take() {
+                        vec![frame]
+                    } else {
+                        match reader.recv_batch().await {
+                            Ok(frames) if !frames.is_empty() => frames,
+                            Ok(_) => continue,
+                            Err(DispatchError::Closed) => break,
+                        }
+                    };
+                    if reader.take_muxer_resync() {
+                        session.wait_for_keyframe();
+                        reader.clear_video_catchup();
+                        request_publisher_keyframe(stream_id);
+                    }
+                    for frame in frames {
+                        let frame = if frame.codec.is_video() && is_idr_frame(&frame) {
+                            prepare_video_play_frame(&manager, stream_id, frame)
+                        } else {
+                            frame
+                        };
+                        let data = session.mux(&frame);
+                        if data.is_empty() {
+                            continue;
+                        }
+                        if socket.write_all(&data).await.is_err() {
+                            break 'play;
+                        }
+                        bytes_sent += data.len() as u64;
+                    }
+                }
+                info!(
+                    "[{}] [{}] play ended for {} after {} bytes",
+                    label, stream_id, client_addr, bytes_sent
+                );
+                manager.remove_receiver(&receiver.id);
+                let _ = socket.shutdown().await;
+                return Ok(());
+            }
+
+            // Exported MP4 download
+            if path.starts_with("/api/recordings/exports/") && path.ends_with(".mp4") {
+                let id = path
+                    .trim_start_matches("/api/recordings/exports/")
+                    .trim_end_matches(".mp4");
+                let export_path = recorder
+                    .as_ref()
+                    .and_then(|recorder| recorder.export_job(id))
+                    .filter(|job| job.status == "completed")
+                    .and_then(|job| job.path);
+                if let Some(export_path) = export_path {
+                    if let Ok(mut file) = tokio::fs::File::open(&export_path).await {
+                        let len = file.metadata().await.map(|m| m.len()).unwrap_or_default();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: {}\r\nContent-Disposition: attachment; filename=\"{}.mp4\"\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+                            len, id
+                        );
+                        socket.write_all(response.as_bytes()).await?;
+                        tokio::io::copy(&mut file, &mut socket).await?;
+                        socket.shutdown().await?;
+                        return Ok(());
+                    }
+                }
+                let response =
+                    Self::http_response(404, "Not Found", "{\"error\":\"export not found\"}");
+                socket.write_all(response.as_bytes()).await?;
+                socket.flush().await?;
+                return Ok(());
+            }
+
+            // Recording VOD playlist / segments
+            if path.starts_with("/api/recordings/")
+                && (path.contains("/vod.m3u8") || path.contains("/segments/"))
+            {
+                let Some(ref recorder) = recorder else {
+                    let response = Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    );
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.flush().await?;
+                    return Ok(());
+                };
+                let (route, query) = path.split_once('?').unwrap_or((path, ""));
+                let rest = route.trim_start_matches("/api/recordings/");
+                if let Some(stream_id) = rest.strip_suffix("/vod.m3u8") {
+                    let start_ms = Self::query_param(query, "start").and_then(|v| v.parse().ok());
+                    let end_ms = Self::query_param(query, "end").and_then(|v| v.parse().ok());
+                    let entries =
+                        recorder.completed_recordings_in_range(stream_id, start_ms, end_ms);
+                    if entries.is_empty() {
+                        let response = Self::http_response(
+                            404,
+                            "Not Found",
+                            "{\"error\":\"no recordings in range\"}",
+                        );
+                        socket.write_all(response.as_bytes()).await?;
+                        socket.flush().await?;
+                        return Ok(());
+                    }
+                    let playlist = build_vod_playlist(&entries, start_ms, end_ms);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.apple.mpegurl\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+                        playlist.len(), playlist
+                    );
+                    socket.write_all(response.as_bytes()).await?;
+                    socket.shutdown().await?;
+                    return Ok(());
+                }
+                if let Some((stream_id, filename)) = rest.split_once("/segments/") {
+                    let entry = parse_vod_segment_filename(filename).and_then(|(id, format)| {
+                        recorder
+                            .find_recording(id)
+                            .filter(|entry| entry.stream_id == stream_id && entry.format == format)
+                    });
+                    if let Some(entry) = entry {
+                        let range = Self::request_header(&request, "range");
+                        let Some((start, count)) = resolve_byte_range(range, entry.bytes) else {
+                            let response = format!(
+                                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+                                entry.bytes
+                            );
+                            socket.write_all(response.as_bytes()).await?;
+                            socket.shutdown().await?;
+                            return Ok(());
+                        };
+                        if let Ok(mut reader) = open_vod_segment(&entry, start, count).await {
+                            let (status, content_range) = match range {
+                                Some(_) => (
+                                    "206 Partial Content",
+                                    format!(
+                                        "Content-Range: bytes {}-{}/{}\r\n",
+                                        start,
+                                        start + count - 1,
+                                        entry.bytes
+                                    ),
+                                ),
+                                None => ("200 OK", String::new()),
+                            };
+                            let response = format!(
+                                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Accept-Ranges: bytes\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: max-age=3600\r\nConnection: close\r\n\r\n",
+                                status,
+                                vod_content_type(entry.format),
+                                count,
+                                content_range
+                            );
+                            socket.write_all(response.as_bytes()).await?;
+                            tokio::io::copy(&mut reader, &mut socket).await?;
+                            socket.shutdown().await?;
+                            return Ok(());
+                        }
+                    }
+                }
+                let response =
+                    Self::http_response(404, "Not Found", "{\"error\":\"recording not found\"}");
+                socket.write_all(response.as_bytes()).await?;
+                socket.flush().await?;
+                return Ok(());
+            }
+
             if path.starts_with("/api/snapshots/") && path.ends_with(".jpg") {
                 let Some(ref snapshot) = snapshot else {
                     let response = Self::http_response(
@@ -480,21 +985,22 @@ impl HttpServer {
         }
 
         // Regular API request
-        let response =
-            Self::process_request(&request, manager.clone(), recorder, analysis, snapshot).await?;
+        let response = Self::process_request(&request, ctx).await?;
         socket.write_all(response.as_bytes()).await?;
         socket.flush().await?;
 
         Ok(())
     }
 
-    async fn process_request(
-        request: &str,
-        manager: Arc<StreamManager>,
-        recorder: Option<Arc<RecorderManager>>,
-        analysis: Option<Arc<AnalysisManager>>,
-        snapshot: Option<Arc<SnapshotManager>>,
-    ) -> Result<String> {
+    async fn process_request(request: &str, ctx: HttpContext) -> Result<String> {
+        let HttpContext {
+            stream_manager: manager,
+            recorder,
+            analysis,
+            snapshot,
+            jobs,
+            ..
+        } = ctx;
         let lines: Vec<&str> = request.lines().collect();
         if lines.is_empty() {
             return Ok(Self::http_response(400, "Bad Request", ""));
@@ -608,6 +1114,7 @@ impl HttpServer {
                             "RTMP" => StreamProtocol::RTMP,
                             "WEBRTC" => StreamProtocol::WebRTC,
                             "HTTP" => StreamProtocol::HTTP,
+                            "HLS" => StreamProtocol::HLS,
                             _ => StreamProtocol::Unknown,
                         },
                     );
@@ -675,18 +1182,28 @@ impl HttpServer {
                     remote_url, local_stream_id
                 );
 
-                let manager_clone = manager.clone();
-                let remote_url_clone = remote_url.clone();
-                let local_stream_id_clone = local_stream_id.clone();
-
-                tokio::spawn(async move {
-                    let puller = RtspPuller::new(manager_clone);
-                    if let Err(e) = puller.pull(&remote_url_clone, &local_stream_id_clone).await {
-                        error!("[RTSP Puller] Failed to pull stream: {}", e);
-                    }
-                });
+                let runner = {
+                    let (manager, remote_url, local_stream_id) =
+                        (manager.clone(), remote_url.clone(), local_stream_id.clone());
+                    job_runner(move |_| {
+                        let puller = RtspPuller::new(manager.clone());
+                        let (remote_url, local_stream_id) =
+                            (remote_url.clone(), local_stream_id.clone());
+                        async move { puller.pull(&remote_url, &local_stream_id).await }
+                    })
+                };
+                let job = jobs
+                    .spawn(
+                        JobKind::Pull,
+                        &local_stream_id,
+                        StreamProtocol::RTSP,
+                        &remote_url,
+                        runner,
+                    )
+                    .await;
 
                 let body = json!({
+                    "job_id": job.id,
                     "stream_id": local_stream_id,
                     "remote_url": remote_url,
                     "message": "RTSP pull started"
@@ -729,22 +1246,86 @@ impl HttpServer {
                     remote_url, local_stream_id
                 );
 
+                let runner = {
+                    let (manager, remote_url, local_stream_id) =
+                        (manager.clone(), remote_url.clone(), local_stream_id.clone());
+                    job_runner(move |_| {
+                        let puller = RtmpPuller::new(manager.clone());
+                        let (remote_url, local_stream_id) =
+                            (remote_url.clone(), local_stream_id.clone());
+                        async move { puller.pull(&remote_url, &local_stream_id).await }
+                    })
+                };
+                let job = jobs
+                    .spawn(
+                        JobKind::Pull,
+                        &local_stream_id,
+                        StreamProtocol::RTMP,
+                        &remote_url,
+                        runner,
+                    )
+                    .await;
+
+                let body = json!({
+                    "job_id": job.id,
+                    "stream_id": local_stream_id,
+                    "remote_url": remote_url,
+                    "play_url": format!("rtmp://127.0.0.1:1935/live/{}", local_stream_id),
+                    "message": "RTMP pull started"
+                })
+                .to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
+            ("POST", "/api/hls/pull") => {
+                let body_start = request
+                    .find("\r\n\r\n")
+                    .map(|i| &request[i + 4..])
+                    .unwrap_or("");
+                let parse_result = serde_json::from_str::<serde_json::Value>(body_start);
+
+                let (remote_url, local_stream_id) = if let Ok(json) = &parse_result {
+                    let url = json.get("url").and_then(|v| v.as_str()).unwrap_or("");
+                    let stream_id = json
+                        .get("stream_id")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("hls_pulled");
+                    (url.to_string(), stream_id.to_string())
+                } else {
+                    return Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        "{\"error\":\"Invalid JSON body\"}",
+                    ));
+                };
+
+                if remote_url.is_empty() {
+                    return Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        "{\"error\":\"Missing 'url' parameter\"}",
+                    ));
+                }
+
+                info!(
+                    "[HTTP] Starting HLS pull from {} to stream {}",
+                    remote_url, local_stream_id
+                );
+
                 let manager_clone = manager.clone();
                 let remote_url_clone = remote_url.clone();
                 let local_stream_id_clone = local_stream_id.clone();
 
                 tokio::spawn(async move {
-                    let puller = RtmpPuller::new(manager_clone);
+                    let puller = HlsPuller::new(manager_clone);
                     if let Err(e) = puller.pull(&remote_url_clone, &local_stream_id_clone).await {
-                        error!("[RTMP Puller] Failed to pull stream: {}", e);
+                        error!("[HLS Puller] Failed to pull stream: {}", e);
                     }
                 });
 
                 let body = json!({
                     "stream_id": local_stream_id,
                     "remote_url": remote_url,
-                    "play_url": format!("rtmp://127.0.0.1:1935/live/{}", local_stream_id),
-                    "message": "RTMP pull started"
+                    "message": "HLS pull started"
                 })
                 .to_string();
                 Ok(Self::http_response(200, "OK", &body))
@@ -809,20 +1390,31 @@ impl HttpServer {
                     ));
                 }
 
-                let manager_clone = manager.clone();
-                let stream_id_clone = stream_id.clone();
-                let remote_url_clone = remote_url.clone();
-
-                tokio::spawn(async move {
-                    let mut pusher =
-                        RtspPusher::new(manager_clone, &remote_url_clone, &stream_id_clone);
-                    pusher.set_tracks(tracks);
-                    if let Err(e) = pusher.start().await {
-                        error!("[RTSP Pusher] Failed to push stream: {}", e);
-                    }
-                });
+                let runner = {
+                    let (manager, remote_url, stream_id) =
+                        (manager.clone(), remote_url.clone(), stream_id.clone());
+                    job_runner(move |ctx| {
+                        let mut pusher = RtspPusher::new(manager.clone(), &remote_url, &stream_id);
+                        pusher.set_tracks(tracks.clone());
+                        async move {
+                            pusher.start().await?;
+                            ctx.set_running();
+                            pusher.wait().await
+                        }
+                    })
+                };
+                let job = jobs
+                    .spawn(
+                        JobKind::Push,
+                        &stream_id,
+                        StreamProtocol::RTSP,
+                        &remote_url,
+                        runner,
+                    )
+                    .await;
 
                 let body = json!({
+                    "job_id": job.id,
                     "stream_id": stream_id,
                     "remote_url": remote_url,
                     "message": "RTSP push started"
@@ -830,6 +1422,91 @@ impl HttpServer {
                 .to_string();
                 Ok(Self::http_response(200, "OK", &body))
             }
+            ("POST", "/api/rtmp/push") => {
+                let body_start = request
+                    .find("\r\n\r\n")
+                    .map(|i| &request[i + 4..])
+                    .unwrap_or("");
+                let parse_result = serde_json::from_str::<serde_json::Value>(body_start);
+
+                let (stream_id, remote_url) = if let Ok(json) = &parse_result {
+                    let id = json.get("stream_id").and_then(|v| v.as_str()).unwrap_or("");
+                    let url = json.get("url").and_then(|v| v.as_str()).unwrap_or("");
+                    (id.to_string(), url.to_string())
+                } else {
+                    return Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        "{\"error\":\"Invalid JSON body\"}",
+                    ));
+                };
+
+                if stream_id.is_empty() {
+                    return Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        "{\"error\":\"Missing 'stream_id' parameter\"}",
+                    ));
+                }
+
+                if remote_url.is_empty() {
+                    return Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        "{\"error\":\"Missing 'url' parameter\"}",
+                    ));
+                }
+
+                if let Err(e) = parse_rtmp_url(&remote_url) {
+                    let body = json!({ "error": e.to_string() }).to_string();
+                    return Ok(Self::http_response(400, "Bad Request", &body));
+                }
+
+                if manager.get_stream(&stream_id).is_none() {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"Stream not found\"}",
+                    ));
+                }
+
+                info!(
+                    "[HTTP] Starting RTMP push from stream {} to {}",
+                    stream_id, remote_url
+                );
+
+                let runner = {
+                    let (manager, remote_url, stream_id) =
+                        (manager.clone(), remote_url.clone(), stream_id.clone());
+                    job_runner(move |ctx| {
+                        let mut pusher = RtmpPusher::new(manager.clone(), &remote_url, &stream_id)
+                            .with_id(ctx.job_id());
+                        async move {
+                            pusher.start().await?;
+                            ctx.set_running();
+                            pusher.wait().await
+                        }
+                    })
+                };
+                let job = jobs
+                    .spawn(
+                        JobKind::Push,
+                        &stream_id,
+                        StreamProtocol::RTMP,
+                        &remote_url,
+                        runner,
+                    )
+                    .await;
+
+                let body = json!({
+                    "job_id": job.id,
+                    "stream_id": stream_id,
+                    "remote_url": remote_url,
+                    "message": "RTMP push started"
+                })
+                .to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
             ("POST", "/api/record/start") => {
                 let Some(recorder) = recorder else {
                     return Ok(Self::http_response(
@@ -862,6 +1539,38 @@ impl HttpServer {
                     )),
                 }
             }
+            ("POST", "/api/record/trigger") => {
+                let Some(recorder) = recorder else {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    ));
+                };
+                let body = Self::json_body(request);
+                let req = match serde_json::from_str::<TriggerRecordRequest>(body) {
+                    Ok(req) => req,
+                    Err(_) => {
+                        return Ok(Self::http_response(
+                            400,
+                            "Bad Request",
+                            "{\"error\":\"Invalid JSON body\"}",
+                        ));
+                    }
+                };
+                match recorder.trigger(req) {
+                    Ok(info) => Ok(Self::http_response(
+                        200,
+                        "OK",
+                        &json!({"recording": info, "message": "event clip triggered"}).to_string(),
+                    )),
+                    Err(err) => Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        &json!({"error": err.to_string()}).to_string(),
+                    )),
+                }
+            }
             ("POST", "/api/record/stop") => {
                 let Some(recorder) = recorder else {
                     return Ok(Self::http_response(
@@ -894,6 +1603,17 @@ impl HttpServer {
                     )),
                 }
             }
+            ("GET", "/api/record/events") => {
+                let Some(recorder) = recorder else {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    ));
+                };
+                let body = json!({ "events": recorder.events(None) }).to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
             ("GET", "/api/recordings") => {
                 let Some(recorder) = recorder else {
                     return Ok(Self::http_response(
@@ -909,6 +1629,74 @@ impl HttpServer {
                 .to_string();
                 Ok(Self::http_response(200, "OK", &body))
             }
+            ("POST", "/api/recordings/export") => {
+                let Some(recorder) = recorder else {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    ));
+                };
+                let body = Self::json_body(request);
+                let req = match serde_json::from_str::<ExportRequest>(body) {
+                    Ok(req) => req,
+                    Err(_) => {
+                        return Ok(Self::http_response(
+                            400,
+                            "Bad Request",
+                            "{\"error\":\"Invalid JSON body\"}",
+                        ));
+                    }
+                };
+                match recorder.export(req) {
+                    Ok(job) => Ok(Self::http_response(
+                        202,
+                        "Accepted",
+                        &json!({"export": job, "message": "export job accepted"}).to_string(),
+                    )),
+                    Err(err) => Ok(Self::http_response(
+                        400,
+                        "Bad Request",
+                        &json!({"error": err.to_string()}).to_string(),
+                    )),
+                }
+            }
+            ("GET", "/api/recordings/exports") => {
+                let Some(recorder) = recorder else {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    ));
+                };
+                let body = json!({"exports": recorder.list_exports()}).to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
+            ("GET", path) if path.starts_with("/api/recordings/exports/") => {
+                let Some(recorder) = recorder else {
+                    return Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"recording API disabled\"}",
+                    ));
+                };
+                let id = path
+                    .trim_start_matches("/api/recordings/exports/")
+                    .trim_end_matches('/');
+                if let Some(job) = recorder.export_job(id) {
+                    Ok(Self::http_response(
+                        200,
+                        "OK",
+                        &json!({"export": job}).to_string(),
+                    ))
+                } else {
+                    Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"export not found\"}",
+                    ))
+                }
+            }
             ("GET", path)
                 if path.starts_with("/api/recordings/") && path.ends_with("/playback") =>
             {
@@ -928,10 +1716,16 @@ impl HttpServer {
                     .into_iter()
                     .find(|entry| entry.id == id);
                 if let Some(recording) = recording {
+                    let content_type = match recording.format {
+                        RecordFormat::Ts => "video/mp2t",
+                        RecordFormat::Mp4 => "video/mp4",
+                    };
                     let body = json!({
                         "id": recording.id,
                         "path": recording.path,
-                        "content_type": "video/mp2t"
+                        "content_type": content_type,
+                        "vod_url": format!("/api/recordings/{}/vod.m3u8?start={}&end={}",
+                            recording.stream_id, recording.started_at_ms, recording.ended_at_ms)
                     })
                     .to_string();
                     Ok(Self::http_response(200, "OK", &body))
@@ -1131,6 +1925,55 @@ impl HttpServer {
                     ))
                 }
             }
+            ("GET", "/api/pulls") => {
+                let body = json!({ "pulls": jobs.list(JobKind::Pull) }).to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
+            ("GET", "/api/pushes") => {
+                let body = json!({ "pushes": jobs.list(JobKind::Push) }).to_string();
+                Ok(Self::http_response(200, "OK", &body))
+            }
+            ("GET", path) if Self::job_path(path).is_some() => {
+                let (kind, id) = Self::job_path(path).unwrap();
+                match jobs.get(kind, id) {
+                    Some(job) => Ok(Self::http_response(200, "OK", &json!(job).to_string())),
+                    None => Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"job not found\"}",
+                    )),
+                }
+            }
+            ("DELETE", path) if Self::job_path(path).is_some() => {
+                let (kind, id) = Self::job_path(path).unwrap();
+                match jobs.stop(kind, id).await {
+                    Some(job) => Ok(Self::http_response(200, "OK", &json!(job).to_string())),
+                    None => Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"job not found\"}",
+                    )),
+                }
+            }
+            ("POST", path)
+                if path
+                    .strip_suffix("/restart")
+                    .and_then(Self::job_path)
+                    .is_some() =>
+            {
+                let (kind, id) = path
+                    .strip_suffix("/restart")
+                    .and_then(Self::job_path)
+                    .unwrap();
+                match jobs.restart(kind, id) {
+                    Some(job) => Ok(Self::http_response(200, "OK", &json!(job).to_string())),
+                    None => Ok(Self::http_response(
+                        404,
+                        "Not Found",
+                        "{\"error\":\"job not found\"}",
+                    )),
+                }
+            }
             ("DELETE", path) if path.starts_with("/api/stream/") => {
                 let stream_id = path.trim_start_matches("/api/stream/");
                 if manager.remove_stream(&stream_id.to_string()).is_some() {
@@ -1160,10 +2003,42 @@ impl HttpServer {
                     "POST /api/rtmp/pull".to_string(),
                     json!("Start RTMP pull from remote URL"),
                 );
+                endpoints.insert(
+                    "POST /api/hls/pull".to_string(),
+                    json!("Start HLS pull from remote playlist URL"),
+                );
                 endpoints.insert(
                     "POST /api/rtsp/push".to_string(),
                     json!("Start RTSP push to remote URL"),
                 );
+                endpoints.insert(
+                    "POST /api/rtmp/push".to_string(),
+                    json!("Start RTMP push to remote URL"),
+                );
+                endpoints.insert(
+                    "GET /api/pulls".to_string(),
+                    json!("List supervised pull jobs"),
+                );
+                endpoints.insert(
+                    "GET /api/pushes".to_string(),
+                    json!("List supervised push jobs"),
+                );
+                endpoints.insert(
+                    "DELETE /api/pulls/<id>".to_string(),
+                    json!("Stop a pull job"),
+                );
+                endpoints.insert(
+                    "DELETE /api/pushes/<id>".to_string(),
+                    json!("Stop a push job"),
+                );
+                endpoints.insert(
+                    "POST /api/pulls/<id>/restart".to_string(),
+                    json!("Reconnect a pull job now"),
+                );
+                endpoints.insert(
+                    "POST /api/pushes/<id>/restart".to_string(),
+                    json!("Reconnect a push job now"),
+                );
                 endpoints.insert(
                     "POST /api/record/start".to_string(),
                     json!("Start DVR recording"),
@@ -1172,7 +2047,35 @@ impl HttpServer {
                     "POST /api/record/stop".to_string(),
                     json!("Stop DVR recording"),
                 );
+                endpoints.insert(
+                    "POST /api/record/trigger".to_string(),
+                    json!("Record an event clip with pre-roll and post-roll"),
+                );
                 endpoints.insert("GET /api/recordings".to_string(), json!("List recordings"));
+                endpoints.insert(
+                    "GET /api/record/events".to_string(),
+                    json!("Recorder events (retention deletions)"),
+                );
+                endpoints.insert(
+                    "GET /api/recordings/<stream_id>/vod.m3u8?start=<ms>&end=<ms>".to_string(),
+                    json!("HLS VOD playlist over recordings"),
+                );
+                endpoints.insert(
+                    "POST /api/recordings/export".to_string(),
+                    json!("Export a time range of recordings as one MP4 (async job)"),
+                );
+                endpoints.insert(
+                    "GET /api/recordings/exports".to_string(),
+                    json!("List export jobs"),
+                );
+                endpoints.insert(
+                    "GET /api/recordings/exports/<id>".to_string(),
+                    json!("Export job status"),
+                );
+                endpoints.insert(
+                    "GET /api/recordings/exports/<id>.mp4".to_string(),
+                    json!("Download exported MP4"),
+                );
                 endpoints.insert(
                     "POST /api/analysis/start".to_string(),
                     json!("Start video analysis"),
@@ -1208,11 +2111,45 @@ impl HttpServer {
                 );
                 endpoints.insert(
                     "GET /hls/<stream_id>/<segment>.ts".to_string(),
-                    json!("HLS segment"),
+                    json!("HLS segment (.m4s + init.mp4 with segment_format = \"fmp4\")"),
+                );
+                endpoints.insert(
+                    "GET /hls/<stream_id>/live.m3u8?format=fmp4".to_string(),
+                    json!("CMAF HLS playlist (init.mp4 + .m4s segments)"),
+                );
+                endpoints.insert(
+                    "GET /hls/<stream_id>/live.m3u8?offset=<secs>".to_string(),
+                    json!(
+                        "HLS time-shift playlist from the DVR window (or _HLS_start=<unix secs>)"
+                    ),
+                );
+                endpoints.insert(
+                    "GET /hls/<stream_id>/key_<n>.key?token=<token>".to_string(),
+                    json!("HLS AES-128/SAMPLE-AES key (playback token checked)"),
+                );
+                endpoints.insert(
+                    "GET /dash/<stream_id>/manifest.mpd".to_string(),
+                    json!("MPEG-DASH manifest (video_/audio_ init + .m4s segments)"),
+                );
+                endpoints.insert(
+                    "POST /api/stream/<id>/metadata".to_string(),
+                    json!("Queue a JSON timed metadata cue (ID3 in TS, emsg in fMP4)"),
                 );
                 endpoints.insert(
                     "GET /flv/<stream_id>".to_string(),
-                    json!("HTTP-FLV live stream"),
+                    json!("HTTP-FLV live stream (WebSocket-FLV on upgrade)"),
+                );
+                endpoints.insert(
+                    "GET /ts/<stream_id>".to_string(),
+                    json!("HTTP-TS continuous live stream"),
+                );
+                endpoints.insert(
+                    "GET /mp4/<stream_id>".to_string(),
+                    json!("HTTP fragmented MP4 live stream for MSE"),
+                );
+                endpoints.insert(
+                    "GET /flv/record/<stream_id>?start=<ms>".to_string(),
+                    json!("HTTP-FLV playback of recordings"),
                 );
                 endpoints.insert("GET /health".to_string(), json!("Health check"));
 
@@ -1224,7 +2161,11 @@ impl HttpServer {
                         "RTMP": "rtmp://localhost:1935",
                         "RTSP": "rtsp://localhost:554",
                         "HLS": "http://localhost:8081/hls/<stream_id>/live.m3u8",
+                        "DASH": "http://localhost:8081/dash/<stream_id>/manifest.mpd",
                         "HTTP-FLV": "http://localhost:8081/flv/<stream_id>",
+                        "WS-FLV": "ws://localhost:8081/flv/<stream_id>",
+                        "HTTP-TS": "http://localhost:8081/ts/<stream_id>",
+                        "HTTP-MP4": "http://localhost:8081/mp4/<stream_id>",
                         "WebRTC": "ws://localhost:9080"
                     }
                 })
@@ -1235,6 +2176,16 @@ impl HttpServer {
         }
     }
 
+    /// `/api/pulls/<id>` or `/api/pushes/<id>` -> job kind and id.
+    fn job_path(path: &str) -> Option<(JobKind, &str)> {
+        let (kind, id) = if let Some(id) = path.strip_prefix("/api/pulls/") {
+            (JobKind::Pull, id)
+        } else {
+            (JobKind::Push, path.strip_prefix("/api/pushes/")?)
+        };
+        (!id.is_empty() && !id.contains('/')).then_some((kind, id))
+    }
+
     fn http_response(code: u32, reason: &str, body: &str) -> String {
         format!(
             "HTTP/1.1 {} {}\r\n\
@@ -1251,6 +2202,40 @@ impl HttpServer {
         )
     }
 
+    /// Value of header `name` (case-insensitive) in a raw HTTP request.
+    fn request_header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+
+    /// Value of `key` in a raw query string (`a=1&b=2`).
+    fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// HLS time-shift window: `offset=<secs>` behind live, or `_HLS_start=<unix secs>`.
+    fn playlist_window(query: &str) -> PlaylistWindow {
+        if let Some(start) = Self::query_param(query, "_HLS_start")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v >= 0.0)
+        {
+            return PlaylistWindow::Since(UNIX_EPOCH + Duration::from_secs_f64(start));
+        }
+        match Self::query_param(query, "offset").and_then(|v| v.parse::<f64>().ok()) {
+            Some(offset) if offset.is_finite() && offset > 0.0 => PlaylistWindow::Offset(offset),
+            _ => PlaylistWindow::Live,
+        }
+    }
+
     fn json_body(request: &str) -> &str {
         request
             .find("\r\n\r\n")
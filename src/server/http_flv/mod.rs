@@ -1,15 +1,29 @@
 use anyhow::Result;
+use base64::Engine;
 use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
 use parking_lot::RwLock;
+use sha1::{Digest, Sha1};
 /// HTTP-FLV streaming module
-/// Delivers live streams via HTTP with FLV container format using chunked transfer encoding.
+/// Delivers live streams via HTTP with FLV container format using chunked transfer encoding,
+/// or as binary WebSocket frames (WebSocket-FLV) for flv.js / mpegts.js behind proxies.
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 use tracing::{debug, error, info, warn};
 
 use crate::core::{
     CodecType, DispatchPolicy, DispatchReader, FlvPlayTimeline, MediaFrame, Stream, StreamManager,
 };
-use crate::server::rtmp::session::{frame_to_rtmp_audio, frame_to_rtmp_video};
+use crate::process::record::RecordingMediaInfo;
+use crate::server::rtmp::session::{
+    build_aac_sequence_header, build_enhanced_sequence_start, build_hevc_sequence_header,
+    build_metadata, build_recording_sequence_headers, frame_to_rtmp_audio, frame_to_rtmp_video,
+    recorded_frame_to_rtmp, StreamMetadata,
+};
 
 /// FLV file header (9 bytes)
 fn generate_flv_header(has_video: bool, has_audio: bool) -> Vec<u8> {
@@ -102,19 +116,14 @@ fn generate_avc_sequence_header(sps: &[u8], pps: &[u8]) -> Vec<u8> {
     generate_flv_tag(0x09, 0, &data)
 }
 
-/// Generate AAC sequence header
-fn generate_aac_sequence_header() -> Vec<u8> {
-    let mut data = Vec::new();
-    // Sound format + rate + size + type: AAC(10) = 0xAF
-    data.push(0xAF);
-    // AAC packet type: sequence header = 0x00
-    data.push(0x00);
-    // AudioSpecificConfig: AAC-LC, 44100Hz, mono
-    // 0x1210 = AAC-LC 44100Hz stereo
-    data.push(0x12);
-    data.push(0x10);
+/// Generate HEVC sequence header (VPS/SPS/PPS as hvcC, FLV codec id 12)
+fn generate_hevc_sequence_header(vps: &[u8], sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    generate_flv_tag(0x09, 0, &build_hevc_sequence_header(vps, sps, pps))
+}
 
-    generate_flv_tag(0x08, 0, &data)
+/// Generate AAC sequence header carrying the stream's AudioSpecificConfig
+fn generate_aac_sequence_header(stream: &Stream) -> Option<Vec<u8>> {
+    build_aac_sequence_header(stream).map(|data| generate_flv_tag(0x08, 0, &data))
 }
 
 fn stream_has_codec(stream: &Stream, codec: CodecType) -> bool {
@@ -122,48 +131,8 @@ fn stream_has_codec(stream: &Stream, codec: CodecType) -> bool {
 }
 
 /// Generate onMetaData script tag
-fn generate_metadata_tag(stream_id: &str, has_video: bool, has_audio: bool) -> Vec<u8> {
-    let mut data = Vec::new();
-
-    // AMF0 string: "onMetaData"
-    data.push(0x02); // String type
-    let name = "onMetaData";
-    data.extend_from_slice(&(name.len() as u16).to_be_bytes());
-    data.extend_from_slice(name.as_bytes());
-
-    // ECMA array
-    data.push(0x08); // ECMA array type
-    let prop_count = 3u32;
-    data.extend_from_slice(&prop_count.to_be_bytes());
-
-    // Property: width
-    let key = "width";
-    data.extend_from_slice(&(key.len() as u16).to_be_bytes());
-    data.extend_from_slice(key.as_bytes());
-    data.push(0x00); // Number type
-    data.extend_from_slice(&0u64.to_be_bytes()); // placeholder
-
-    // Property: height
-    let key = "height";
-    data.extend_from_slice(&(key.len() as u16).to_be_bytes());
-    data.extend_from_slice(key.as_bytes());
-    data.push(0x00);
-    data.extend_from_slice(&0u64.to_be_bytes());
-
-    // Property: streamName
-    let key = "streamName";
-    data.extend_from_slice(&(key.len() as u16).to_be_bytes());
-    data.extend_from_slice(key.as_bytes());
-    data.push(0x02); // String type
-    data.extend_from_slice(&(stream_id.len() as u16).to_be_bytes());
-    data.extend_from_slice(stream_id.as_bytes());
-
-    // End marker
-    data.push(0x00);
-    data.push(0x00);
-    data.push(0x09);
-
-    generate_flv_tag(0x12, 0, &data)
+fn generate_metadata_tag(stream_id: &str, metadata: &StreamMetadata) -> Vec<u8> {
+    generate_flv_tag(0x12, 0, &build_metadata(metadata, stream_id))
 }
 
 /// Convert a MediaFrame to FLV video tag data (Annex B → AVCC, same as RTMP play path).
@@ -187,6 +156,8 @@ pub struct HttpFlvSession {
     header_sent: bool,
     metadata_sent: bool,
     sequence_header_sent: bool,
+    /// onMetaData to send instead of the stream-derived default
+    metadata: Option<StreamMetadata>,
     /// Monotonic FLV tag timeline (mux order).
     timeline: FlvPlayTimeline,
 }
@@ -198,10 +169,16 @@ impl HttpFlvSession {
             header_sent: false,
             metadata_sent: false,
             sequence_header_sent: false,
+            metadata: None,
             timeline: FlvPlayTimeline::default(),
         }
     }
 
+    /// Use `metadata` (e.g. with hub data rates) for the onMetaData tag.
+    pub fn set_metadata(&mut self, metadata: StreamMetadata) {
+        self.metadata = Some(metadata);
+    }
+
     /// Map publisher timestamps to a continuous session-local FLV timeline.
     fn tag_timestamp_ms(&mut self, frame: &MediaFrame) -> u32 {
         self.timeline.map(frame)
@@ -229,8 +206,7 @@ impl HttpFlvSession {
     /// Generate the initial FLV data (header + metadata + sequence headers)
     pub fn generate_initial_data(&mut self, stream: &crate::core::Stream) -> Vec<u8> {
         let mut data = Vec::new();
-        let has_video =
-            stream_has_codec(stream, CodecType::H264) || stream_has_codec(stream, CodecType::H265);
+        let has_video = stream.tracks.iter().any(|track| track.codec.is_video());
         let has_audio = stream_has_codec(stream, CodecType::AAC);
 
         // FLV header
@@ -241,18 +217,35 @@ impl HttpFlvSession {
 
         // Metadata
         if !self.metadata_sent {
-            data.extend(generate_metadata_tag(&self.stream_id, has_video, has_audio));
+            let metadata = self
+                .metadata
+                .take()
+                .unwrap_or_else(|| StreamMetadata::from_stream(stream));
+            data.extend(generate_metadata_tag(&self.stream_id, &metadata));
             self.metadata_sent = true;
         }
 
-        // AVC sequence header (SPS/PPS)
+        // AVC/HEVC sequence header (parameter sets), AV1/VP9 configuration record
         if !self.sequence_header_sent {
-            if let (Some(ref sps), Some(ref pps)) = (&stream.sps, &stream.pps) {
+            let video_header = match (stream.video_codec(), &stream.vps, &stream.sps, &stream.pps) {
+                (CodecType::H265, Some(vps), Some(sps), Some(pps)) => {
+                    Some(generate_hevc_sequence_header(vps, sps, pps))
+                }
+                (CodecType::H265, ..) => None,
+                (codec @ (CodecType::AV1 | CodecType::VP9), ..) => {
+                    stream.video_config_record().map(|record| {
+                        generate_flv_tag(0x09, 0, &build_enhanced_sequence_start(codec, &record))
+                    })
+                }
+                (_, _, Some(sps), Some(pps)) => Some(generate_avc_sequence_header(sps, pps)),
+                _ => None,
+            };
+            if let Some(video_header) = video_header {
                 if has_video {
-                    data.extend(generate_avc_sequence_header(sps, pps));
+                    data.extend(video_header);
                 }
-                if has_audio {
-                    data.extend(generate_aac_sequence_header());
+                if let Some(audio_header) = generate_aac_sequence_header(stream) {
+                    data.extend(audio_header);
                 }
                 self.sequence_header_sent = true;
             }
@@ -261,11 +254,37 @@ impl HttpFlvSession {
         data
     }
 
+    /// Initial FLV data for recorded playback, with sequence headers built from
+    /// the parameter sets and AudioSpecificConfig found in the recording.
+    pub fn generate_recording_initial_data(&mut self, info: &RecordingMediaInfo) -> Vec<u8> {
+        let has_video = info.video.is_some();
+        let has_audio = info.audio.is_some();
+        let mut data = generate_flv_header(has_video, has_audio);
+        data.extend(generate_metadata_tag(
+            &self.stream_id,
+            &StreamMetadata::from_recording(info),
+        ));
+        for (tag_type, payload) in build_recording_sequence_headers(info) {
+            data.extend(generate_flv_tag(tag_type, 0, &payload));
+        }
+        self.header_sent = true;
+        self.metadata_sent = true;
+        self.sequence_header_sent = true;
+        data
+    }
+
+    /// Convert a recorded frame (ADTS audio) to an FLV tag at `timestamp`.
+    pub fn recorded_frame_to_flv(&self, frame: &MediaFrame, timestamp: u32) -> Vec<u8> {
+        recorded_frame_to_rtmp(frame)
+            .map(|(tag_type, data)| generate_flv_tag(tag_type, timestamp, &data))
+            .unwrap_or_default()
+    }
+
     /// Convert a media frame to FLV tag and return the session-local tag timestamp.
     pub fn frame_to_flv_with_timestamp(&mut self, frame: &MediaFrame) -> (Vec<u8>, u32) {
         let timestamp = self.tag_timestamp_ms(frame);
         let data = match frame.codec {
-            CodecType::H264 | CodecType::H265 => frame_to_flv_video(frame, timestamp),
+            codec if codec.is_video() => frame_to_flv_video(frame, timestamp),
             CodecType::AAC | CodecType::Opus | CodecType::G711 => {
                 frame_to_flv_audio(frame, timestamp)
             }
@@ -282,7 +301,7 @@ impl HttpFlvSession {
     ) -> (Vec<u8>, u32) {
         let timestamp = timeline.map_ms();
         let data = match frame.codec {
-            CodecType::H264 | CodecType::H265 => frame_to_flv_video(frame, timestamp),
+            codec if codec.is_video() => frame_to_flv_video(frame, timestamp),
             CodecType::AAC | CodecType::Opus | CodecType::G711 => {
                 frame_to_flv_audio(frame, timestamp)
             }
@@ -301,6 +320,102 @@ pub fn format_chunk(data: &[u8]) -> Vec<u8> {
     chunk
 }
 
+/// RFC 6455 handshake GUID
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// `Sec-WebSocket-Key` of a WebSocket upgrade request, if the request is one.
+pub fn websocket_key(request: &str) -> Option<String> {
+    let mut upgrade = false;
+    let mut key = None;
+    for line in request.lines().skip(1) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("upgrade") {
+            upgrade = value.eq_ignore_ascii_case("websocket");
+        } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+            key = Some(value.to_string());
+        }
+    }
+    key.filter(|_| upgrade)
+}
+
+/// `Sec-WebSocket-Accept` value for a client key.
+fn websocket_accept(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(sha1.finalize())
+}
+
+/// Live FLV byte stream transport: chunked HTTP body or WebSocket binary frames.
+pub enum FlvTransport {
+    Chunked(TcpStream),
+    WebSocket(Box<WebSocketStream<TcpStream>>),
+}
+
+impl FlvTransport {
+    /// Send chunked HTTP response headers.
+    pub async fn chunked(mut socket: TcpStream) -> Result<Self> {
+        socket
+            .write_all(HttpFlvSession::generate_http_headers().as_bytes())
+            .await?;
+        Ok(Self::Chunked(socket))
+    }
+
+    /// Complete the WebSocket upgrade for an already-read handshake request.
+    pub async fn websocket(mut socket: TcpStream, key: &str) -> Result<Self> {
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            websocket_accept(key)
+        );
+        socket.write_all(response.as_bytes()).await?;
+        let ws = WebSocketStream::from_raw_socket(socket, Role::Server, None).await;
+        Ok(Self::WebSocket(Box::new(ws)))
+    }
+
+    pub fn is_websocket(&self) -> bool {
+        matches!(self, Self::WebSocket(_))
+    }
+
+    /// Send FLV bytes (header and/or tags) as one chunk or binary frame.
+    pub async fn send(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            Self::Chunked(socket) => socket.write_all(&format_chunk(data)).await?,
+            Self::WebSocket(ws) => ws.send(Message::Binary(data.to_vec())).await?,
+        }
+        Ok(())
+    }
+
+    /// Resolves when the WebSocket client closes; chunked clients are only
+    /// detected by failed writes, so this never resolves for them.
+    pub async fn closed(&mut self) {
+        match self {
+            Self::Chunked(_) => std::future::pending().await,
+            Self::WebSocket(ws) => loop {
+                match ws.next().await {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                }
+            },
+        }
+    }
+
+    /// End the stream (terminating chunk or close frame).
+    pub async fn finish(&mut self) {
+        match self {
+            Self::Chunked(socket) => {
+                let _ = socket.write_all(b"0\r\n\r\n").await;
+                let _ = socket.flush().await;
+            }
+            Self::WebSocket(ws) => {
+                let _ = (**ws).close(None).await;
+            }
+        }
+    }
+}
+
 /// HTTP-FLV streaming handler
 pub struct HttpFlvServer {
     stream_manager: Arc<StreamManager>,
@@ -357,6 +472,7 @@ mod tests {
             source: StreamSourceMode::Push,
             protocol: StreamProtocol::WebRTC,
             pull_url: None,
+            vps: None,
             sps: Some(vec![0x67, 0x42, 0x00, 0x1e]),
             pps: Some(vec![0x68, 0xce, 0x1f, 0x20]),
         }
@@ -395,4 +511,133 @@ mod tests {
             "AAC sequence tag should be present when stream has AAC"
         );
     }
+
+    #[test]
+    fn initial_data_uses_stream_audio_config_and_metadata() {
+        let mut audio = Track::new(1, CodecType::AAC, 97, 48_000);
+        audio.set_audio_specific_config(&[0x11, 0x90]);
+        let stream =
+            publishing_stream_with_tracks(vec![Track::new(0, CodecType::H264, 96, 90_000), audio]);
+        let mut session = HttpFlvSession::new("webrtc_test");
+        session.set_metadata(StreamMetadata::from_stream(&stream));
+
+        let data = session.generate_initial_data(&stream);
+
+        assert!(data.windows(4).any(|w| w == [0xaf, 0x00, 0x11, 0x90]));
+        assert!(data.windows(10).any(|w| w == b"onMetaData"));
+        let rate_key = b"audiosamplerate";
+        let pos = data
+            .windows(rate_key.len())
+            .position(|w| w == rate_key)
+            .expect("audiosamplerate in onMetaData");
+        let value = &data[pos + rate_key.len()..pos + rate_key.len() + 9];
+        assert_eq!(value[0], 0x00, "AMF0 number");
+        assert_eq!(
+            f64::from_be_bytes(value[1..9].try_into().unwrap()),
+            48_000.0
+        );
+    }
+
+    #[test]
+    fn initial_data_for_hevc_stream_sends_hvcc_sequence_header() {
+        let mut stream =
+            publishing_stream_with_tracks(vec![Track::new(0, CodecType::H265, 98, 90_000)]);
+        stream.vps = Some(vec![0x40, 0x01, 0x0C]);
+        stream.sps = Some(vec![0x42, 0x01, 0x01]);
+        stream.pps = Some(vec![0x44, 0x01, 0xC1]);
+        let mut session = HttpFlvSession::new("webrtc_test");
+
+        let data = session.generate_initial_data(&stream);
+
+        assert!(!session.needs_sequence_headers());
+        assert!(
+            data.windows(3).any(|w| w == [0x1C, 0x00, 0x00]),
+            "HEVC keyframe sequence header (codec id 12) should be present"
+        );
+        assert!(!data.windows(2).any(|w| w == [0x17, 0x00]));
+    }
+
+    #[test]
+    fn hevc_frame_uses_codec_id_12() {
+        let frame = MediaFrame::new(
+            "webrtc_test".into(),
+            0,
+            0,
+            Bytes::from_static(&[0, 0, 0, 1, 0x26, 0x01, 0xAF]),
+            false,
+            CodecType::H265,
+        );
+        let tag = frame_to_flv_video(&frame, 0);
+        assert_eq!(tag[11], 0x1C, "keyframe + HEVC");
+    }
+
+    #[test]
+    fn recording_initial_data_uses_recorded_audio_config() {
+        use crate::server::hls::fmp4_muxer::{AudioCodecConfig, VideoCodecConfig};
+        let info = RecordingMediaInfo {
+            video: Some(VideoCodecConfig::Avc {
+                sps: vec![0x67, 0x42, 0x00, 0x1e],
+                pps: vec![0x68, 0xce, 0x1f, 0x20],
+            }),
+            audio: Some(AudioCodecConfig::aac_lc(48_000, 2)),
+        };
+        let mut session = HttpFlvSession::new("cam1");
+
+        let data = session.generate_recording_initial_data(&info);
+
+        assert_eq!(
+            data[4] & 0x05,
+            0x05,
+            "FLV audio and video flags should be set"
+        );
+        let asc = &info.audio.as_ref().unwrap().asc;
+        let mut aac_header = vec![0xaf, 0x00];
+        aac_header.extend_from_slice(asc);
+        assert!(data.windows(aac_header.len()).any(|w| w == aac_header));
+        assert!(!session.needs_sequence_headers());
+    }
+
+    #[test]
+    fn websocket_upgrade_key_and_accept() {
+        let request = "GET /flv/cam1 HTTP/1.1\r\nHost: x\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        let key = websocket_key(request).expect("upgrade request");
+        assert_eq!(websocket_accept(&key), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert!(websocket_key("GET /flv/cam1 HTTP/1.1\r\nHost: x\r\n\r\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn websocket_transport_sends_binary_frames_and_sees_close() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            let key = websocket_key(&request).unwrap();
+            let mut transport = FlvTransport::websocket(socket, &key).await.unwrap();
+            transport
+                .send(&generate_flv_header(true, false))
+                .await
+                .unwrap();
+            transport.closed().await;
+        });
+
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let (mut ws, _) =
+            tokio_tungstenite::client_async(format!("ws://{}/flv/cam1", addr), socket)
+                .await
+                .unwrap();
+        match ws.next().await {
+            Some(Ok(Message::Binary(data))) => assert_eq!(&data[..3], b"FLV"),
+            other => panic!("expected binary FLV frame, got {:?}", other),
+        }
+        ws.close(None).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(2), server)
+            .await
+            .expect("server should observe close")
+            .unwrap();
+    }
 }
@@ -0,0 +1,187 @@
+/// HTTP fMP4 streaming module
+/// Delivers live streams as one progressive fragmented MP4 (init segment, then one
+/// moof/mdat per video frame) that browsers can append straight into Media Source Extensions.
+use tracing::info;
+
+use crate::core::{
+    is_idr_frame, CodecType, FlvPlayTimeline, MediaFrame, Stream, MILLISECOND_CLOCK_RATE,
+};
+use crate::server::hls::fmp4_muxer::{AudioCodecConfig, Fmp4Muxer, VideoCodecConfig};
+
+/// Per-client fMP4 session; the muxer is created on the first IDR that carries
+/// its parameter sets.
+pub struct HttpMp4Session {
+    stream_id: String,
+    muxer: Option<Fmp4Muxer>,
+    timeline: FlvPlayTimeline,
+    has_audio: bool,
+    audio_config: Option<AudioCodecConfig>,
+    waiting_for_keyframe: bool,
+}
+
+impl HttpMp4Session {
+    pub fn new(stream: &Stream) -> Self {
+        Self {
+            stream_id: stream.id.clone(),
+            muxer: None,
+            timeline: FlvPlayTimeline::default(),
+            has_audio: stream.tracks.iter().any(|t| t.codec == CodecType::AAC),
+            // Hub AAC is raw, so the sample rate/channels come from the track's ASC.
+            audio_config: AudioCodecConfig::from_stream(stream),
+            waiting_for_keyframe: true,
+        }
+    }
+
+    /// HTTP response headers for an endless fMP4 body (no length, closed by either side).
+    pub fn generate_http_headers() -> String {
+        let mut headers = String::new();
+        headers.push_str("HTTP/1.1 200 OK\r\n");
+        headers.push_str("Content-Type: video/mp4\r\n");
+        headers.push_str("Connection: close\r\n");
+        headers.push_str("Access-Control-Allow-Origin: *\r\n");
+        headers.push_str("Cache-Control: no-cache\r\n");
+        headers.push_str("X-Accel-Buffering: no\r\n");
+        headers.push_str("\r\n");
+        headers
+    }
+
+    /// Drop frames until the next IDR (after a ring gap).
+    pub fn wait_for_keyframe(&mut self) {
+        self.waiting_for_keyframe = true;
+    }
+
+    /// Mux one hub frame; the first output is the init segment followed by the
+    /// fragments. Video keyframes must already carry SPS/PPS in-band.
+    pub fn frame_to_mp4(&mut self, frame: &MediaFrame) -> Vec<u8> {
+        // fMP4 output only carries H.264/H.265 video and AAC audio
+        if matches!(
+            frame.codec,
+            CodecType::Opus | CodecType::G711 | CodecType::AV1 | CodecType::VP9
+        ) {
+            return Vec::new();
+        }
+        if frame.codec == CodecType::AAC {
+            if frame.data.len() < 8 {
+                return Vec::new();
+            }
+            self.has_audio = true;
+        }
+
+        let mut out = Vec::new();
+        if self.waiting_for_keyframe {
+            if !(frame.codec.is_video() && is_idr_frame(frame)) {
+                return Vec::new();
+            }
+            if self.muxer.is_none() {
+                let Some(video) = VideoCodecConfig::from_keyframe(frame) else {
+                    return Vec::new();
+                };
+                let mut muxer = Fmp4Muxer::new();
+                muxer.set_fragment_per_frame(true);
+                muxer.set_video_config(video);
+                if self.has_audio {
+                    muxer.set_audio_config(self.audio_config.clone().unwrap_or_default());
+                }
+                out.extend(muxer.init_segment());
+                info!(
+                    "[HTTP-MP4] [{}] Start on video IDR (audio={})",
+                    self.stream_id, self.has_audio
+                );
+                self.muxer = Some(muxer);
+            }
+            self.waiting_for_keyframe = false;
+        }
+        let Some(muxer) = self.muxer.as_mut() else {
+            return Vec::new();
+        };
+
+        let mux_frame = MediaFrame::new(
+            frame.stream_id.clone(),
+            frame.track_id,
+            self.timeline.map(frame) as u64,
+            frame.data.clone(),
+            frame.is_keyframe,
+            frame.codec,
+        )
+        .with_clock_rate(MILLISECOND_CLOCK_RATE);
+        if let Some(fragment) = muxer.push_frame(&mux_frame) {
+            out.extend(fragment);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{PlaybackStatus, StreamProtocol, StreamSourceMode, StreamStatus, Track};
+    use bytes::Bytes;
+
+    fn stream() -> Stream {
+        Stream {
+            id: "cam1".to_string(),
+            tracks: vec![Track::new(0, CodecType::H264, 96, 90_000)],
+            status: StreamStatus::Publishing,
+            playback_status: PlaybackStatus::Idle,
+            source: StreamSourceMode::Push,
+            protocol: StreamProtocol::RTMP,
+            pull_url: None,
+            vps: None,
+            sps: None,
+            pps: None,
+        }
+    }
+
+    fn video(ts: u64, nals: &[&[u8]], idr: bool) -> MediaFrame {
+        let mut data = Vec::new();
+        for nal in nals {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+            data.extend_from_slice(nal);
+        }
+        MediaFrame::new(
+            "cam1".to_string(),
+            0,
+            ts,
+            Bytes::from(data),
+            idr,
+            CodecType::H264,
+        )
+        .with_clock_rate(MILLISECOND_CLOCK_RATE)
+    }
+
+    #[test]
+    fn init_segment_then_one_fragment_per_frame() {
+        let sps: &[u8] = &[0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+        let pps: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+        let mut session = HttpMp4Session::new(&stream());
+        assert!(session
+            .frame_to_mp4(&video(0, &[&[0x41, 0x9A]], false))
+            .is_empty());
+
+        let init = session.frame_to_mp4(&video(40, &[sps, pps, &[0x65, 0x88]], true));
+        assert_eq!(&init[4..8], b"ftyp");
+        assert!(!init.windows(4).any(|w| w == b"moof"));
+
+        let fragment = session.frame_to_mp4(&video(80, &[&[0x41, 0x9A]], false));
+        assert_eq!(&fragment[4..8], b"moof");
+        assert!(fragment.windows(4).any(|w| w == b"mdat"));
+    }
+    #[test]
+    fn init_segment_uses_stream_audio_config() {
+        let mut stream = stream();
+        let mut audio = Track::new(1, CodecType::AAC, 97, 48_000);
+        audio.set_audio_specific_config(&[0x11, 0x90]);
+        stream.add_track(audio);
+        let mut session = HttpMp4Session::new(&stream);
+
+        let sps: &[u8] = &[0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+        let pps: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+        let init = session.frame_to_mp4(&video(0, &[sps, pps, &[0x65, 0x88]], true));
+        assert!(init.windows(4).any(|w| w == b"esds"));
+        assert!(init.windows(2).any(|w| w == [0x11, 0x90]));
+        // mp4a sample rate field is 16.16 fixed point
+        assert!(init
+            .windows(4)
+            .any(|w| w == (48_000u32 << 16).to_be_bytes()));
+    }
+}
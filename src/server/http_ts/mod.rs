@@ -0,0 +1,172 @@
+/// HTTP-TS streaming module
+/// Delivers live streams as one endless MPEG-TS byte stream over HTTP, for set-top boxes,
+/// VLC and ffmpeg consumers that prefer a single connection to HLS playlist polling.
+use tracing::info;
+
+use crate::core::{
+    is_idr_frame, CodecType, FlvPlayTimeline, MediaFrame, Stream, MILLISECOND_CLOCK_RATE,
+};
+use crate::server::hls::fmp4_muxer::AudioCodecConfig;
+use crate::server::hls::ts_muxer::TsMuxer;
+
+/// Repeat PAT/PMT at least this often so late-probing demuxers can lock on.
+const PSI_INTERVAL_MS: u64 = 500;
+
+/// Per-client HTTP-TS session: one TsMuxer with continuous counters and timeline.
+pub struct HttpTsSession {
+    stream_id: String,
+    muxer: TsMuxer,
+    timeline: FlvPlayTimeline,
+    has_audio: bool,
+    started: bool,
+    last_psi_ms: Option<u64>,
+}
+
+impl HttpTsSession {
+    pub fn new(stream: &Stream) -> Self {
+        let mut muxer = TsMuxer::new();
+        // Hub AAC is raw, so the ADTS sample rate/channels come from the track's ASC.
+        if let Some(config) = AudioCodecConfig::from_stream(stream) {
+            muxer.set_audio_config(config);
+        }
+        Self {
+            stream_id: stream.id.clone(),
+            muxer,
+            timeline: FlvPlayTimeline::default(),
+            has_audio: stream.tracks.iter().any(|t| t.codec == CodecType::AAC),
+            started: false,
+            last_psi_ms: None,
+        }
+    }
+
+    /// HTTP response headers for an endless TS body (no length, closed by either side).
+    pub fn generate_http_headers() -> String {
+        let mut headers = String::new();
+        headers.push_str("HTTP/1.1 200 OK\r\n");
+        headers.push_str("Content-Type: video/mp2t\r\n");
+        headers.push_str("Connection: close\r\n");
+        headers.push_str("Access-Control-Allow-Origin: *\r\n");
+        headers.push_str("Cache-Control: no-cache\r\n");
+        headers.push_str("X-Accel-Buffering: no\r\n");
+        headers.push_str("\r\n");
+        headers
+    }
+
+    /// Drop frames until the next IDR (after a ring gap).
+    pub fn wait_for_keyframe(&mut self) {
+        self.started = false;
+    }
+
+    /// Mux one hub frame; empty until the first IDR. Video keyframes must already
+    /// carry SPS/PPS in-band.
+    pub fn frame_to_ts(&mut self, frame: &MediaFrame) -> Vec<u8> {
+        // TS output only carries H.264/H.265 video and AAC audio
+        if matches!(
+            frame.codec,
+            CodecType::Opus | CodecType::G711 | CodecType::AV1 | CodecType::VP9
+        ) {
+            return Vec::new();
+        }
+        if frame.codec == CodecType::AAC && frame.data.len() < 8 {
+            return Vec::new();
+        }
+        let keyframe = frame.codec.is_video() && is_idr_frame(frame);
+        if !self.started {
+            if !keyframe {
+                return Vec::new();
+            }
+            self.started = true;
+            info!("[HTTP-TS] [{}] Start on video IDR", self.stream_id);
+        }
+        if frame.codec.is_video() {
+            self.muxer.set_video_codec(frame.codec);
+        }
+
+        let mux_ms = self.timeline.map(frame) as u64;
+        let mut out = Vec::new();
+        let audio_appeared = frame.codec == CodecType::AAC && !self.has_audio;
+        let psi_due = self
+            .last_psi_ms
+            .is_none_or(|last| mux_ms.saturating_sub(last) >= PSI_INTERVAL_MS);
+        if keyframe || audio_appeared || psi_due {
+            self.has_audio |= frame.codec == CodecType::AAC;
+            out.extend(self.muxer.generate_pat_pmt(true, self.has_audio));
+            self.last_psi_ms = Some(mux_ms);
+        }
+
+        let mux_frame = MediaFrame::new(
+            frame.stream_id.clone(),
+            frame.track_id,
+            mux_ms,
+            frame.data.clone(),
+            frame.is_keyframe,
+            frame.codec,
+        )
+        .with_clock_rate(MILLISECOND_CLOCK_RATE);
+        self.muxer.update_pcr(mux_ms);
+        out.extend(self.muxer.frame_to_ts(&mux_frame));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{PlaybackStatus, StreamProtocol, StreamSourceMode, StreamStatus, Track};
+    use bytes::Bytes;
+
+    fn stream() -> Stream {
+        Stream {
+            id: "cam1".to_string(),
+            tracks: vec![Track::new(0, CodecType::H264, 96, 90_000)],
+            status: StreamStatus::Publishing,
+            playback_status: PlaybackStatus::Idle,
+            source: StreamSourceMode::Push,
+            protocol: StreamProtocol::RTMP,
+            pull_url: None,
+            vps: None,
+            sps: None,
+            pps: None,
+        }
+    }
+
+    fn video(ts: u64, idr: bool) -> MediaFrame {
+        let nal = if idr { 0x65 } else { 0x41 };
+        MediaFrame::new(
+            "cam1".to_string(),
+            0,
+            ts,
+            Bytes::from(vec![0, 0, 0, 1, nal, 0x88, 0x84, 0x00]),
+            idr,
+            CodecType::H264,
+        )
+        .with_clock_rate(MILLISECOND_CLOCK_RATE)
+    }
+
+    fn pids(ts: &[u8]) -> Vec<u16> {
+        assert_eq!(ts.len() % 188, 0);
+        ts.chunks(188)
+            .map(|p| (((p[1] & 0x1F) as u16) << 8) | p[2] as u16)
+            .collect()
+    }
+
+    #[test]
+    fn starts_on_idr_and_repeats_pat_pmt() {
+        let mut session = HttpTsSession::new(&stream());
+        assert!(session.frame_to_ts(&video(0, false)).is_empty());
+
+        let first = pids(&session.frame_to_ts(&video(40, true)));
+        assert_eq!(
+            &first[..2],
+            &[0x0000, 0x1000],
+            "PAT then PMT before the IDR"
+        );
+        assert!(first.contains(&0x100));
+
+        let next = pids(&session.frame_to_ts(&video(80, false)));
+        assert!(!next.contains(&0x0000), "no PSI between keyframes");
+
+        let later = pids(&session.frame_to_ts(&video(80 + PSI_INTERVAL_MS, false)));
+        assert_eq!(later[0], 0x0000, "PSI repeats after the interval");
+    }
+}
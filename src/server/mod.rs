@@ -1,6 +1,8 @@
 pub mod hls;
 pub mod http;
 pub mod http_flv;
+pub mod http_mp4;
+pub mod http_ts;
 pub mod rtmp;
 pub mod rtsp;
 pub mod webrtc;
@@ -12,6 +12,7 @@ pub enum Amf0Value {
     Null,
     Undefined,
     EcmaArray(HashMap<String, Amf0Value>),
+    StrictArray(Vec<Amf0Value>),
 }
 
 impl Amf0Value {
@@ -42,6 +43,13 @@ impl Amf0Value {
             _ => None,
         }
     }
+
+    pub fn as_array(&self) -> Option<&[Amf0Value]> {
+        match self {
+            Amf0Value::StrictArray(items) => Some(items),
+            _ => None,
+        }
+    }
 }
 
 /// AMF0 type markers
@@ -53,6 +61,7 @@ const AMF0_NULL: u8 = 0x05;
 const AMF0_UNDEFINED: u8 = 0x06;
 const AMF0_ECMA_ARRAY: u8 = 0x08;
 const AMF0_OBJECT_END: u8 = 0x09;
+const AMF0_STRICT_ARRAY: u8 = 0x0A;
 
 /// Decode AMF0 values from a byte slice, returns (values, bytes_consumed)
 pub fn decode(data: &[u8]) -> Result<(Vec<Amf0Value>, usize), String> {
@@ -122,6 +131,18 @@ fn decode_value(data: &[u8], offset: &mut usize) -> Result<Amf0Value, String> {
             *offset += 4;
             decode_object_inner(data, offset, true)
         }
+        AMF0_STRICT_ARRAY => {
+            if *offset + 4 > data.len() {
+                return Err("Not enough data for strict array count".to_string());
+            }
+            let count = u32::from_be_bytes(data[*offset..*offset + 4].try_into().unwrap());
+            *offset += 4;
+            let mut items = Vec::new();
+            for _ in 0..count {
+                items.push(decode_value(data, offset)?);
+            }
+            Ok(Amf0Value::StrictArray(items))
+        }
         _ => Err(format!(
             "Unsupported AMF0 type: 0x{:02X} at offset {}",
             marker,
@@ -263,6 +284,13 @@ pub fn encode_value(buf: &mut Vec<u8>, val: &Amf0Value) {
             buf.push(0x00);
             buf.push(AMF0_OBJECT_END);
         }
+        Amf0Value::StrictArray(items) => {
+            buf.push(AMF0_STRICT_ARRAY);
+            buf.extend_from_slice(&(items.len() as u32).to_be_bytes());
+            for item in items {
+                encode_value(buf, item);
+            }
+        }
     }
 }
 
@@ -348,6 +376,22 @@ mod tests {
         assert!(matches!(&decoded[0], Amf0Value::Object(_)));
     }
 
+    #[test]
+    fn test_decode_strict_array_inside_object() {
+        let mut map = HashMap::new();
+        map.insert(
+            "fourCcList".to_string(),
+            Amf0Value::StrictArray(vec![
+                Amf0Value::String("av01".to_string()),
+                Amf0Value::String("hvc1".to_string()),
+            ]),
+        );
+        map.insert("app".to_string(), Amf0Value::String("live".to_string()));
+        let encoded = encode(&[Amf0Value::Object(map.clone())]);
+        let (decoded, _) = decode(&encoded).unwrap();
+        assert_eq!(decoded[0], Amf0Value::Object(map));
+    }
+
     #[test]
     fn test_parse_command() {
         let values = vec![
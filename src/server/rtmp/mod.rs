@@ -1,9 +1,12 @@
 pub mod amf0;
 pub mod chunk;
 pub mod puller;
+pub mod pusher;
+mod record_play;
 pub mod session;
 
 pub use puller::RtmpPuller;
+pub use pusher::RtmpPusher;
 
 use anyhow::Result;
 use bytes::{Buf, Bytes, BytesMut};
@@ -16,12 +19,15 @@ use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
 use crate::core::{
-    default_live_tracks, is_idr_frame, prime_live_play, CodecType, DispatchError, DispatchPolicy,
-    MediaFrame, StreamManager, StreamProtocol, StreamSourceMode, StreamStatus,
+    default_live_tracks, h265, is_idr_frame, prime_live_play, CodecType, DispatchError,
+    DispatchPolicy, MediaFrame, StreamManager, StreamProtocol, StreamSourceMode, StreamStatus,
     MILLISECOND_CLOCK_RATE,
 };
+use crate::process::record::RecorderManager;
+use crate::server::hls::fmp4_muxer::AudioCodecConfig;
 use crate::server::webrtc::{annex_b_with_config, request_publisher_keyframe};
 use chunk::RtmpMessage;
+use record_play::{RecordPlay, RecordPlayTask};
 use session::{RtmpSession, SessionState};
 
 /// 将 RTMP 消息类型 ID 转为可读名称
@@ -59,14 +65,74 @@ fn video_frame_type(b: u8) -> &'static str {
 }
 
 /// 将视频编解码器转为可读名称
-fn video_codec_name(b: u8) -> &'static str {
-    match b & 0x0F {
-        0x07 => "H264",
-        0x0C => "H265",
+fn video_codec_name(codec: CodecType) -> &'static str {
+    match codec {
+        CodecType::H264 => "H264",
+        CodecType::H265 => "H265",
+        CodecType::AV1 => "AV1",
+        CodecType::VP9 => "VP9",
         _ => "Unknown",
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VideoTagKind {
+    SequenceHeader,
+    Nalus,
+    Other(u8),
+}
+
+/// 视频 tag 头解析结果（传统 codec id 或 Enhanced RTMP ExVideoTagHeader）
+struct VideoTag<'a> {
+    codec: CodecType,
+    is_keyframe: bool,
+    kind: VideoTagKind,
+    body: &'a [u8],
+}
+
+/// 解析视频 tag 头：codec id 7/12，或 Enhanced RTMP `hvc1` / `av01` / `vp09` FourCC
+fn parse_video_tag(data: &[u8]) -> Option<VideoTag<'_>> {
+    if data.len() < 5 {
+        return None;
+    }
+    if data[0] & 0x80 != 0 {
+        // [IsExHeader | FrameType(3) | PacketType(4)] + FourCC
+        let codec = session::codec_from_fourcc(&data[1..5])?;
+        let frame_type = (data[0] >> 4) & 0x07;
+        let (kind, body) = match data[0] & 0x0F {
+            // FrameType 5 is a command frame with no video payload
+            t if frame_type == 5 => (VideoTagKind::Other(t), &data[5..]),
+            0 => (VideoTagKind::SequenceHeader, &data[5..]),
+            // HEVC CodedFrames carries a SI24 composition time; CodedFramesX does not.
+            1 if codec == CodecType::H265 => (VideoTagKind::Nalus, data.get(8..)?),
+            1 | 3 => (VideoTagKind::Nalus, &data[5..]),
+            t => (VideoTagKind::Other(t), &data[5..]),
+        };
+        return Some(VideoTag {
+            codec,
+            is_keyframe: frame_type == 1,
+            kind,
+            body,
+        });
+    }
+    let codec = match data[0] & 0x0F {
+        0x07 => CodecType::H264,
+        0x0C => CodecType::H265,
+        _ => return None,
+    };
+    let kind = match data[1] {
+        0 => VideoTagKind::SequenceHeader,
+        1 => VideoTagKind::Nalus,
+        t => VideoTagKind::Other(t),
+    };
+    Some(VideoTag {
+        codec,
+        is_keyframe: (data[0] & 0xF0) == 0x10,
+        kind,
+        body: &data[5..],
+    })
+}
+
 fn prepend_rtmp_video_config(
     manager: &StreamManager,
     stream_id: &str,
@@ -78,24 +144,24 @@ fn prepend_rtmp_video_config(
     let Some(stream) = manager.get_stream(&stream_id.to_string()) else {
         return frame.clone();
     };
-    let (Some(sps), Some(pps)) = (&stream.sps, &stream.pps) else {
-        return frame.clone();
+    let data = match (frame.codec, &stream.vps, &stream.sps, &stream.pps) {
+        (CodecType::H265, Some(vps), Some(sps), Some(pps)) => {
+            h265::annex_b_with_config(vps, sps, pps, &frame.data)
+        }
+        (CodecType::H264, _, Some(sps), Some(pps)) => annex_b_with_config(sps, pps, &frame.data),
+        _ => return frame.clone(),
     };
     MediaFrame::new(
         frame.stream_id.clone(),
         frame.track_id,
         frame.timestamp,
-        bytes::Bytes::from(annex_b_with_config(sps, pps, &frame.data)),
+        bytes::Bytes::from(data),
         frame.is_keyframe,
         frame.codec,
     )
     .with_optional_clock_rate(frame.clock_rate)
 }
 
-fn stream_has_codec(stream: &crate::core::Stream, codec: CodecType) -> bool {
-    stream.tracks.iter().any(|track| track.codec == codec)
-}
-
 /// 将 AMF0 值格式化为可读字符串
 fn fmt_amf0(val: &amf0::Amf0Value) -> String {
     match val {
@@ -111,6 +177,10 @@ fn fmt_amf0(val: &amf0::Amf0Value) -> String {
                 .collect();
             format!("{{{}}}", pairs.join(", "))
         }
+        amf0::Amf0Value::StrictArray(items) => {
+            let items: Vec<String> = items.iter().map(fmt_amf0).collect();
+            format!("[{}]", items.join(", "))
+        }
     }
 }
 
@@ -127,6 +197,9 @@ struct RtmpConnection {
     stream_id: String,
     publisher_id: Option<String>,
     play_abort: Option<tokio::task::AbortHandle>,
+    recorder: Option<Arc<RecorderManager>>,
+    record: Option<RecordPlay>,
+    record_task: Option<RecordPlayTask>,
 }
 
 impl RtmpConnection {
@@ -135,6 +208,7 @@ impl RtmpConnection {
         hls_server: Option<Arc<crate::server::hls::HlsServer>>,
         writer: Arc<Mutex<tokio::net::tcp::OwnedWriteHalf>>,
         peer_addr: &str,
+        recorder: Option<Arc<RecorderManager>>,
     ) -> Self {
         Self {
             stream_manager,
@@ -149,6 +223,9 @@ impl RtmpConnection {
             stream_id: String::new(),
             publisher_id: None,
             play_abort: None,
+            recorder,
+            record: None,
+            record_task: None,
         }
     }
 }
@@ -157,6 +234,7 @@ pub struct RtmpServer {
     stream_manager: Arc<StreamManager>,
     port: u16,
     hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+    recorder: Option<Arc<RecorderManager>>,
 }
 
 impl RtmpServer {
@@ -164,11 +242,13 @@ impl RtmpServer {
         stream_manager: Arc<StreamManager>,
         port: u16,
         hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+        recorder: Option<Arc<RecorderManager>>,
     ) -> Self {
         Self {
             stream_manager,
             port,
             hls_server,
+            recorder,
         }
     }
 
@@ -198,9 +278,10 @@ impl RtmpServer {
                     info!("[RTMP] Connection ID: {:?}", socket.peer_addr());
                     let manager = self.stream_manager.clone();
                     let hls = self.hls_server.clone();
+                    let recorder = self.recorder.clone();
                     tokio::spawn(async move {
                         if let Err(e) =
-                            Self::handle_connection(socket, manager, hls, peer_addr).await
+                            Self::handle_connection(socket, manager, hls, recorder, peer_addr).await
                         {
                             error!("[RTMP] Connection error from {}: {}", peer_addr, e);
                         }
@@ -218,6 +299,7 @@ impl RtmpServer {
         socket: TcpStream,
         manager: Arc<StreamManager>,
         hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+        recorder: Option<Arc<RecorderManager>>,
         peer_addr: std::net::SocketAddr,
     ) -> Result<()> {
         let (mut reader, writer) = socket.into_split();
@@ -228,6 +310,7 @@ impl RtmpServer {
             hls_server,
             writer.clone(),
             &peer_addr.to_string(),
+            recorder,
         );
         let mut chunk_size: usize = 128;
         let mut bytes_received = 0;
@@ -254,6 +337,9 @@ impl RtmpServer {
                 if let Some(handle) = conn.play_abort.take() {
                     handle.abort();
                 }
+                if let Some(task) = conn.record_task.take() {
+                    task.stop().await;
+                }
                 Self::release_publish_connection(&mut conn);
                 break;
             }
@@ -433,72 +519,123 @@ impl RtmpServer {
             // Video data
             0x09 => {
                 let data = &msg.payload;
-                if data.is_empty() {
+                let Some(tag) = parse_video_tag(data) else {
+                    debug!(
+                        "[RTMP] [{}] <<< VIDEO unsupported tag header ({}bytes)",
+                        peer_addr,
+                        data.len()
+                    );
                     return Ok(None);
-                }
-                let frame_type_str = video_frame_type(data[0]);
-                let codec_str = video_codec_name(data[0]);
-                let avc_packet_type = if data.len() > 1 { data[1] } else { 0xFF };
+                };
+                let frame_type_str = video_frame_type(data[0] & 0x7F);
+                let codec_str = video_codec_name(tag.codec);
 
-                match avc_packet_type {
-                    0x00 => {
+                match tag.kind {
+                    VideoTagKind::SequenceHeader
+                        if matches!(tag.codec, CodecType::AV1 | CodecType::VP9) =>
+                    {
+                        // AV1 / VP9 sequence start (av1C / vpcC configuration record)
+                        info!(
+                            "[RTMP] [{}] <<< VIDEO {} SequenceStart ({}bytes)",
+                            peer_addr,
+                            codec_str,
+                            tag.body.len()
+                        );
+                        if tag.body.is_empty() {
+                            warn!(
+                                "[RTMP] [{}] Empty {} configuration record",
+                                peer_addr, codec_str
+                            );
+                        } else {
+                            conn.stream_manager.set_stream_video_config_record(
+                                &conn.stream_id,
+                                tag.codec,
+                                tag.body,
+                            );
+                        }
+                    }
+                    VideoTagKind::SequenceHeader if tag.codec == CodecType::H265 => {
+                        // HEVC sequence header (hvcC: VPS/SPS/PPS)
+                        info!(
+                            "[RTMP] [{}] <<< VIDEO HEVC SequenceHeader ({}+{})",
+                            peer_addr, frame_type_str, codec_str
+                        );
+                        match h265::parse_hvcc(tag.body) {
+                            Some((vps, sps, pps)) => {
+                                conn.stream_manager
+                                    .set_stream_video_codec(&conn.stream_id, CodecType::H265);
+                                conn.stream_manager.set_stream_hevc_config(
+                                    &conn.stream_id,
+                                    vps,
+                                    sps,
+                                    pps,
+                                );
+                            }
+                            None => warn!(
+                                "[RTMP] [{}] Invalid hvcC record ({}bytes)",
+                                peer_addr,
+                                tag.body.len()
+                            ),
+                        }
+                    }
+                    VideoTagKind::SequenceHeader => {
                         // AVC sequence header (SPS/PPS)
                         info!(
                             "[RTMP] [{}] <<< VIDEO AVC SequenceHeader ({}+{})",
                             peer_addr, frame_type_str, codec_str
                         );
-                        if data.len() > 13 {
-                            let config_data = &data[5..]; // skip frame_type + avc_type + composition_time
-                            if config_data.len() > 7 {
-                                let num_sps = (config_data[5] & 0x1F) as usize;
-                                let mut offset = 6;
-                                let mut sps_data = Vec::new();
-                                for _ in 0..num_sps {
+                        conn.stream_manager
+                            .set_stream_video_codec(&conn.stream_id, CodecType::H264);
+                        let config_data = tag.body;
+                        if config_data.len() > 8 {
+                            let num_sps = (config_data[5] & 0x1F) as usize;
+                            let mut offset = 6;
+                            let mut sps_data = Vec::new();
+                            for _ in 0..num_sps {
+                                if offset + 2 > config_data.len() {
+                                    break;
+                                }
+                                let sps_len = ((config_data[offset] as usize) << 8)
+                                    | config_data[offset + 1] as usize;
+                                offset += 2;
+                                if offset + sps_len > config_data.len() {
+                                    break;
+                                }
+                                sps_data = config_data[offset..offset + sps_len].to_vec();
+                                offset += sps_len;
+                            }
+                            let mut pps_data = Vec::new();
+                            if offset < config_data.len() {
+                                let num_pps = config_data[offset] & 0x1F;
+                                offset += 1;
+                                for _ in 0..num_pps as usize {
                                     if offset + 2 > config_data.len() {
                                         break;
                                     }
-                                    let sps_len = ((config_data[offset] as usize) << 8)
+                                    let pps_len = ((config_data[offset] as usize) << 8)
                                         | config_data[offset + 1] as usize;
                                     offset += 2;
-                                    if offset + sps_len > config_data.len() {
+                                    if offset + pps_len > config_data.len() {
                                         break;
                                     }
-                                    sps_data = config_data[offset..offset + sps_len].to_vec();
-                                    offset += sps_len;
-                                }
-                                let mut pps_data = Vec::new();
-                                if offset < config_data.len() {
-                                    let num_pps = config_data[offset] & 0x1F;
-                                    offset += 1;
-                                    for _ in 0..num_pps as usize {
-                                        if offset + 2 > config_data.len() {
-                                            break;
-                                        }
-                                        let pps_len = ((config_data[offset] as usize) << 8)
-                                            | config_data[offset + 1] as usize;
-                                        offset += 2;
-                                        if offset + pps_len > config_data.len() {
-                                            break;
-                                        }
-                                        pps_data = config_data[offset..offset + pps_len].to_vec();
-                                        offset += pps_len;
-                                    }
-                                }
-                                if !sps_data.is_empty() {
-                                    conn.stream_manager.set_stream_sps_pps(
-                                        &conn.stream_id,
-                                        sps_data,
-                                        pps_data,
-                                    );
+                                    pps_data = config_data[offset..offset + pps_len].to_vec();
+                                    offset += pps_len;
                                 }
                             }
+                            if !sps_data.is_empty() {
+                                conn.stream_manager.set_stream_sps_pps(
+                                    &conn.stream_id,
+                                    sps_data,
+                                    pps_data,
+                                );
+                            }
                         }
                     }
-                    0x01 => {
-                        // Video NALU (AVC)
+                    VideoTagKind::Nalus => {
+                        // Video NALUs (AVC / HEVC, length-prefixed)
                         conn.frames_received += 1;
-                        let is_keyframe = (data[0] & 0xF0) == 0x10;
-                        let avcc_payload = &data[5..];
+                        let is_keyframe = tag.is_keyframe;
+                        let avcc_payload = tag.body;
 
                         if conn.frames_received <= 3
                             || is_keyframe && conn.frames_received % 30 == 0
@@ -513,7 +650,21 @@ impl RtmpServer {
                                 conn.frames_received
                             );
                         }
-                        if conn.is_publishing {
+                        if conn.is_publishing && !tag.codec.is_nal_video() {
+                            // AV1 OBUs / VP9 frames are relayed as-is
+                            if !avcc_payload.is_empty() {
+                                let frame = MediaFrame::new(
+                                    conn.stream_id.clone(),
+                                    0,
+                                    msg.timestamp as u64,
+                                    Bytes::copy_from_slice(avcc_payload),
+                                    is_keyframe,
+                                    tag.codec,
+                                )
+                                .with_clock_rate(MILLISECOND_CLOCK_RATE);
+                                conn.stream_manager.publish_frame(frame);
+                            }
+                        } else if conn.is_publishing {
                             // Convert AVCC format to Annex B (add start codes)
                             let mut annex_b = Vec::with_capacity(avcc_payload.len());
                             let mut offset = 0;
@@ -538,18 +689,18 @@ impl RtmpServer {
                                     msg.timestamp as u64,
                                     Bytes::from(annex_b),
                                     is_keyframe,
-                                    CodecType::H264,
+                                    tag.codec,
                                 )
                                 .with_clock_rate(MILLISECOND_CLOCK_RATE);
                                 conn.stream_manager.publish_frame(frame);
                             }
                         }
                     }
-                    _ => {
+                    VideoTagKind::Other(packet_type) => {
                         debug!(
-                            "[RTMP] [{}] <<< VIDEO unknown avc_packet_type=0x{:02X} ({}bytes)",
+                            "[RTMP] [{}] <<< VIDEO unknown packet_type=0x{:02X} ({}bytes)",
                             peer_addr,
-                            avc_packet_type,
+                            packet_type,
                             data.len()
                         );
                     }
@@ -585,6 +736,20 @@ impl RtmpServer {
                         if sound_size == 1 { 16 } else { 8 },
                         if sound_type == 1 { "stereo" } else { "mono" }
                     );
+                    if conn.is_publishing && sound_format == 0x0A {
+                        match AudioCodecConfig::from_asc(&data[2..]) {
+                            Some(config) => conn.stream_manager.set_stream_audio_config(
+                                &conn.stream_id,
+                                &config.asc,
+                                config.sample_rate,
+                            ),
+                            None => warn!(
+                                "[RTMP] [{}] Invalid AudioSpecificConfig ({} bytes)",
+                                peer_addr,
+                                data.len() - 2
+                            ),
+                        }
+                    }
                 } else {
                     debug!(
                         "[RTMP] [{}] <<< AUDIO ts={} size={} frame#{}",
@@ -695,7 +860,16 @@ impl RtmpServer {
                 w.write_all(&cs).await?;
 
                 // Send _result using new chunk_size (SetChunkSize takes effect immediately for sender)
-                let result = session::build_result_response(conn.session.transaction_id, "Connect");
+                if !conn.session.fourcc_list.is_empty() {
+                    info!(
+                        "[RTMP] [{}] --- Enhanced RTMP fourCcList={:?}",
+                        peer_addr, conn.session.fourcc_list
+                    );
+                }
+                let result = session::build_connect_result(
+                    conn.session.transaction_id,
+                    &conn.session.fourcc_list,
+                );
                 let response =
                     chunk::encode_message(0x14, 0, 0, &result, conn.session.chunk_size, 3);
                 w.write_all(&response).await?;
@@ -847,6 +1021,12 @@ impl RtmpServer {
                     peer_addr, play_stream_id
                 );
 
+                if let Some((record_stream, start_ms)) =
+                    record_play::parse_record_play(&conn.session.app, &play_stream_id)
+                {
+                    return Self::play_recording(conn, record_stream, start_ms, peer_addr).await;
+                }
+
                 if conn.stream_manager.get_stream(&play_stream_id).is_none() {
                     warn!(
                         "[RTMP] [{}] Stream '{}' does not exist",
@@ -903,10 +1083,14 @@ impl RtmpServer {
                     let writer_clone = conn.writer.clone();
                     let chunk_size_val = conn.session.chunk_size;
                     let server_stream_id = conn.session.server_stream_id;
+                    let enhanced_hevc = conn.session.accepts_enhanced(CodecType::H265);
                     let play_started_at = Instant::now();
                     if let Some(handle) = conn.play_abort.take() {
                         handle.abort();
                     }
+                    if let Some(task) = conn.record_task.take() {
+                        task.stop().await;
+                    }
                     let initial_keyframe_requested = request_publisher_keyframe(&play_stream_id);
                     info!(
                         "[RTMP] [{}] PLAY start stream='{}' requested_keyframe_before_response={} started_at=0ms",
@@ -943,22 +1127,47 @@ impl RtmpServer {
                         );
                         w.write_all(&response).await?;
 
-                        if let (Some(ref sps), Some(ref pps)) = (&stream.sps, &stream.pps) {
-                            let avc_header = session::build_avc_sequence_header(sps, pps);
+                        let metadata = session::StreamMetadata::from_stream(&stream)
+                            .with_hub_rates(reader.hub());
+                        let metadata_msg = chunk::encode_message(
+                            0x12,
+                            0,
+                            conn.session.server_stream_id,
+                            &session::build_metadata(&metadata, &play_stream_id),
+                            conn.session.chunk_size,
+                            chunk::CSID_COMMAND,
+                        );
+                        w.write_all(&metadata_msg).await?;
+                        info!(
+                            "[RTMP] [{}] Sent onMetaData {}x{} fps={:?} audio={:?}",
+                            peer_addr,
+                            metadata.width,
+                            metadata.height,
+                            metadata.framerate,
+                            metadata.audio.as_ref().map(|a| (a.sample_rate, a.channels))
+                        );
+
+                        let video_header =
+                            session::build_video_sequence_header(&stream, enhanced_hevc);
+                        if let Some(ref video_header) = video_header {
                             let header_msg = chunk::encode_message(
                                 0x09,
                                 0,
                                 conn.session.server_stream_id,
-                                &avc_header,
+                                video_header,
                                 conn.session.chunk_size,
                                 chunk::CSID_VIDEO,
                             );
                             w.write_all(&header_msg).await?;
-                            info!("[RTMP] [{}] Sent AVC sequence header", peer_addr);
+                            info!(
+                                "[RTMP] [{}] Sent {:?} sequence header",
+                                peer_addr,
+                                stream.video_codec()
+                            );
                         }
-                        let has_aac = stream_has_codec(&stream, CodecType::AAC);
-                        if has_aac {
-                            let aac_header = session::build_aac_sequence_header();
+                        let aac_header = session::build_aac_sequence_header(&stream);
+                        let has_aac = aac_header.is_some();
+                        if let Some(aac_header) = aac_header {
                             let aac_msg = chunk::encode_message(
                                 0x08,
                                 0,
@@ -972,11 +1181,11 @@ impl RtmpServer {
                         }
 
                         info!(
-                            "[RTMP] [{}] >>> SENT play response after={}ms stream='{}' avc_header={} aac_header={}",
+                            "[RTMP] [{}] >>> SENT play response after={}ms stream='{}' video_header={} aac_header={}",
                             peer_addr,
                             play_started_at.elapsed().as_millis(),
                             play_stream_id,
-                            stream.sps.is_some() && stream.pps.is_some(),
+                            video_header.is_some(),
                             has_aac
                         );
 
@@ -1046,9 +1255,7 @@ impl RtmpServer {
                                     {
                                         continue;
                                     }
-                                    if matches!(frame.codec, CodecType::H264 | CodecType::H265)
-                                        && !video_streaming
-                                    {
+                                    if frame.codec.is_video() && !video_streaming {
                                         if !is_idr_frame(&frame) {
                                             continue;
                                         }
@@ -1082,8 +1289,8 @@ impl RtmpServer {
                                     );
                                     }
                                     let data = match frame.codec {
-                                        CodecType::H264 | CodecType::H265 => {
-                                            session::frame_to_rtmp_video(
+                                        CodecType::H265 if enhanced_hevc => {
+                                            session::frame_to_enhanced_rtmp_video(
                                                 &prepend_rtmp_video_config(
                                                     &manager_for_play,
                                                     &stream_id_for_play,
@@ -1091,17 +1298,23 @@ impl RtmpServer {
                                                 ),
                                             )
                                         }
+                                        codec if codec.is_video() => session::frame_to_rtmp_video(
+                                            &prepend_rtmp_video_config(
+                                                &manager_for_play,
+                                                &stream_id_for_play,
+                                                &frame,
+                                            ),
+                                        ),
                                         CodecType::AAC => session::frame_to_rtmp_audio(&frame),
                                         _ => continue,
                                     };
                                     if data.is_empty() {
                                         continue;
                                     }
-                                    let (msg_type, csid) = match frame.codec {
-                                        CodecType::H264 | CodecType::H265 => {
-                                            (0x09u8, chunk::CSID_VIDEO)
-                                        }
-                                        _ => (0x08u8, chunk::CSID_AUDIO),
+                                    let (msg_type, csid) = if frame.codec.is_video() {
+                                        (0x09u8, chunk::CSID_VIDEO)
+                                    } else {
+                                        (0x08u8, chunk::CSID_AUDIO)
                                     };
                                     let rtmp_ts = clock.map_wallclock();
                                     let rtmp_msg = chunk::encode_message(
@@ -1180,6 +1393,49 @@ impl RtmpServer {
                     }
                 }
             }
+            "seek" if conn.record.is_some() => {
+                let offset_ms = args.get(2).and_then(|v| v.as_f64()).unwrap_or(0.0).max(0.0);
+                let record = conn.record.clone().unwrap();
+                info!(
+                    "[RTMP] [{}] --- seek '{}' to {}ms",
+                    peer_addr, record.stream_id, offset_ms
+                );
+                Self::start_record_play(
+                    conn,
+                    record.origin_ms + offset_ms as u64,
+                    &["NetStream.Seek.Notify", "NetStream.Play.Start"],
+                    "NetStream.Seek.InvalidTime",
+                    peer_addr,
+                )
+                .await?;
+            }
+            "pause" if conn.record.is_some() => {
+                let pause = args.get(2).and_then(|v| v.as_bool()).unwrap_or(true);
+                let record = conn.record.clone().unwrap();
+                info!(
+                    "[RTMP] [{}] --- pause={} '{}' at {}",
+                    peer_addr,
+                    pause,
+                    record.stream_id,
+                    record.position_ms()
+                );
+                if pause {
+                    if let Some(task) = conn.record_task.take() {
+                        task.stop().await;
+                    }
+                    Self::send_on_status(conn, "NetStream.Pause.Notify", "Paused.", "status")
+                        .await?;
+                } else {
+                    Self::start_record_play(
+                        conn,
+                        record.position_ms(),
+                        &["NetStream.Unpause.Notify"],
+                        "NetStream.Play.StreamNotFound",
+                        peer_addr,
+                    )
+                    .await?;
+                }
+            }
             "deleteStream" => {
                 info!("[RTMP] [{}] --- deleteStream", peer_addr);
                 Self::release_publish_connection(conn);
@@ -1200,6 +1456,120 @@ impl RtmpServer {
         Ok(())
     }
 
+    async fn send_on_status(
+        conn: &RtmpConnection,
+        code: &str,
+        description: &str,
+        level: &str,
+    ) -> Result<()> {
+        let status = session::build_on_status(code, description, level);
+        let response = chunk::encode_message(
+            0x14,
+            0,
+            conn.session.server_stream_id,
+            &status,
+            conn.session.chunk_size,
+            chunk::CSID_COMMAND,
+        );
+        let mut w = conn.writer.lock().await;
+        w.write_all(&response).await?;
+        Ok(())
+    }
+
+    /// `play` of a recording: RTMP timestamp 0 is `start_ms` (or the oldest
+    /// recording) and `seek` offsets count from there.
+    async fn play_recording(
+        conn: &mut RtmpConnection,
+        stream_id: String,
+        start_ms: Option<u64>,
+        peer_addr: std::net::SocketAddr,
+    ) -> Result<()> {
+        let span = conn
+            .recorder
+            .as_ref()
+            .and_then(|recorder| recorder.recorded_span(&stream_id));
+        let Some((first_ms, last_ms)) = span else {
+            warn!("[RTMP] [{}] no recordings for '{}'", peer_addr, stream_id);
+            return Self::send_on_status(
+                conn,
+                "NetStream.Play.StreamNotFound",
+                "Recording not found",
+                "error",
+            )
+            .await;
+        };
+        let origin_ms = start_ms.unwrap_or(first_ms).clamp(first_ms, last_ms);
+        conn.record = Some(RecordPlay::new(stream_id, origin_ms));
+        Self::start_record_play(
+            conn,
+            origin_ms,
+            &["NetStream.Play.Reset", "NetStream.Play.Start"],
+            "NetStream.Play.StreamNotFound",
+            peer_addr,
+        )
+        .await
+    }
+
+    /// (Re)open the recording at `position_ms` and restart the play task,
+    /// announcing `codes` on success or `fail_code` when nothing is recorded there.
+    async fn start_record_play(
+        conn: &mut RtmpConnection,
+        position_ms: u64,
+        codes: &[&str],
+        fail_code: &str,
+        peer_addr: std::net::SocketAddr,
+    ) -> Result<()> {
+        let (Some(recorder), Some(record)) = (conn.recorder.clone(), conn.record.clone()) else {
+            return Ok(());
+        };
+        if let Some(task) = conn.record_task.take() {
+            task.stop().await;
+        }
+        let reader = match recorder.open_playback(&record.stream_id, position_ms).await {
+            Ok(reader) => reader,
+            Err(err) => {
+                warn!(
+                    "[RTMP] [{}] cannot play recording '{}' at {}: {}",
+                    peer_addr, record.stream_id, position_ms, err
+                );
+                return Self::send_on_status(conn, fail_code, &err.to_string(), "error").await;
+            }
+        };
+        info!(
+            "[RTMP] [{}] recorded play '{}' from {} (requested {})",
+            peer_addr,
+            record.stream_id,
+            reader.start_ms(),
+            position_ms
+        );
+        let server_stream_id = conn.session.server_stream_id;
+        {
+            let stream_begin = session::build_user_control_stream_begin(server_stream_id);
+            let msg = chunk::encode_message(
+                0x04,
+                0,
+                0,
+                &stream_begin,
+                conn.session.chunk_size,
+                chunk::CSID_PROTOCOL,
+            );
+            let mut w = conn.writer.lock().await;
+            w.write_all(&msg).await?;
+        }
+        for code in codes {
+            Self::send_on_status(conn, code, "Recorded playback.", "status").await?;
+        }
+        conn.record_task = Some(record_play::spawn_record_play(
+            conn.writer.clone(),
+            conn.session.chunk_size,
+            server_stream_id,
+            reader,
+            record,
+            peer_addr.to_string(),
+        ));
+        Ok(())
+    }
+
     // Legacy methods kept for backward compatibility
     #[allow(dead_code)]
     fn parse_amf0_command(
@@ -1442,4 +1812,100 @@ mod tests {
         assert_eq!(prepared.timestamp, frame.timestamp);
         assert!(prepared.is_keyframe);
     }
+
+    #[test]
+    fn parse_enhanced_video_tags() {
+        // av01 SequenceStart (keyframe) + av1C record
+        let tag = parse_video_tag(&[0x90, b'a', b'v', b'0', b'1', 0x81, 0x00]).unwrap();
+        assert_eq!(tag.codec, CodecType::AV1);
+        assert_eq!(tag.kind, VideoTagKind::SequenceHeader);
+        assert_eq!(tag.body, &[0x81, 0x00]);
+
+        // vp09 CodedFrames (inter frame): no composition time
+        let tag = parse_video_tag(&[0xA1, b'v', b'p', b'0', b'9', 0xAB, 0xCD]).unwrap();
+        assert_eq!(tag.codec, CodecType::VP9);
+        assert_eq!(tag.kind, VideoTagKind::Nalus);
+        assert!(!tag.is_keyframe);
+        assert_eq!(tag.body, &[0xAB, 0xCD]);
+
+        // hvc1 CodedFrames skips the SI24 composition time
+        let tag = parse_video_tag(&[0x91, b'h', b'v', b'c', b'1', 0, 0, 0, 0, 0, 0, 2]).unwrap();
+        assert_eq!(tag.codec, CodecType::H265);
+        assert!(tag.is_keyframe);
+        assert_eq!(tag.body, &[0, 0, 0, 2]);
+
+        // command frames carry no video
+        let tag = parse_video_tag(&[0xD1, b'a', b'v', b'0', b'1', 0x00]).unwrap();
+        assert_eq!(tag.kind, VideoTagKind::Other(1));
+        assert!(parse_video_tag(&[0x91, b'x', b'x', b'x', b'x', 0x00]).is_none());
+    }
+
+    #[test]
+    fn connect_negotiates_fourcc_list() {
+        let mut command = HashMap::new();
+        command.insert("app".to_string(), amf0::Amf0Value::String("live".into()));
+        command.insert(
+            "fourCcList".to_string(),
+            amf0::Amf0Value::StrictArray(vec![
+                amf0::Amf0Value::String("av01".into()),
+                amf0::Amf0Value::String("hvc1".into()),
+                amf0::Amf0Value::String("avc3".into()),
+            ]),
+        );
+        let mut session = RtmpSession::new("peer");
+        session.handle_connect(&[
+            amf0::Amf0Value::Number(1.0),
+            amf0::Amf0Value::Object(command),
+        ]);
+
+        assert_eq!(session.app, "live");
+        assert!(session.accepts_enhanced(CodecType::H265));
+        assert!(!session.accepts_enhanced(CodecType::VP9));
+
+        let result = session::build_connect_result(1.0, &session.fourcc_list);
+        let (values, _) = amf0::decode(&result).unwrap();
+        let echoed = values[2].as_object().unwrap()["fourCcList"]
+            .as_array()
+            .unwrap();
+        assert_eq!(
+            echoed.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>(),
+            ["av01", "hvc1"]
+        );
+    }
+
+    #[test]
+    fn av1_stream_plays_as_enhanced_rtmp() {
+        let manager = StreamManager::new();
+        manager.create_stream("s", StreamSourceMode::Push, StreamProtocol::RTMP, None);
+        manager.set_stream_tracks("s", default_live_tracks());
+        let record = [0x81, 0x00, 0x0C, 0x00];
+        manager.set_stream_video_config_record("s", CodecType::AV1, &record);
+        let stream = manager.get_stream(&"s".to_string()).unwrap();
+        assert_eq!(stream.video_codec(), CodecType::AV1);
+
+        let header = session::build_video_sequence_header(&stream, false).unwrap();
+        assert_eq!(&header[..5], &[0x90, b'a', b'v', b'0', b'1']);
+        assert_eq!(&header[5..], &record);
+
+        let obus = Bytes::from_static(&[0x32, 0x02, 0xAA, 0xBB]);
+        let frame = MediaFrame::new("s".into(), 0, 40, obus.clone(), false, CodecType::AV1);
+        let tag = session::frame_to_rtmp_video(&frame);
+        assert_eq!(&tag[..5], &[0xA1, b'a', b'v', b'0', b'1']);
+        assert_eq!(&tag[5..], &obus[..]);
+    }
+
+    #[test]
+    fn hevc_enhanced_play_uses_coded_frames_x() {
+        let frame = MediaFrame::new(
+            "s".into(),
+            0,
+            0,
+            Bytes::from_static(&[0, 0, 0, 1, 0x26, 0x01, 0xAF]),
+            true,
+            CodecType::H265,
+        );
+        let tag = session::frame_to_enhanced_rtmp_video(&frame);
+        assert_eq!(&tag[..5], &[0x93, b'h', b'v', b'c', b'1']);
+        assert_eq!(&tag[5..], &[0, 0, 0, 3, 0x26, 0x01, 0xAF]);
+    }
 }
@@ -13,6 +13,7 @@ use super::chunk::{self, ChunkAssembler, ChunkHeader};
 use crate::core::{
     CodecType, MediaFrame, StreamManager, StreamProtocol, StreamSourceMode, MILLISECOND_CLOCK_RATE,
 };
+use crate::server::hls::fmp4_muxer::AudioCodecConfig;
 
 #[derive(Debug, Clone)]
 pub struct RtmpUrl {
@@ -93,7 +94,7 @@ impl RtmpPuller {
         let mut server_stream_id: u32 = 1;
 
         // Step 1: connect
-        let connect_msg = build_connect_command(tx_id, &rtmp_url.app, &rtmp_url.tc_url);
+        let connect_msg = build_connect_command(tx_id, &rtmp_url.app, &rtmp_url.tc_url, &[]);
         write_half.write_all(&connect_msg).await?;
         write_half.flush().await?;
         info!("[RTMP Puller] Sent connect (tx={})", tx_id);
@@ -213,6 +214,8 @@ impl RtmpPuller {
                     manager.publish_frame(frame);
                 } else if msg.msg_type == 0x09 {
                     extract_and_store_sps_pps(&msg.payload, &manager, &local_id);
+                } else if msg.msg_type == 0x08 {
+                    store_audio_config(&msg.payload, &manager, &local_id);
                 }
             }
         }
@@ -226,7 +229,7 @@ impl RtmpPuller {
     }
 }
 
-async fn client_handshake(stream: &mut TcpStream) -> Result<()> {
+pub(super) async fn client_handshake(stream: &mut TcpStream) -> Result<()> {
     let mut c0c1 = vec![0x03u8];
     c0c1.extend(vec![0u8; 1536]);
     stream.write_all(&c0c1).await?;
@@ -244,7 +247,13 @@ async fn client_handshake(stream: &mut TcpStream) -> Result<()> {
 
 const CLIENT_CHUNK_SIZE: usize = 128;
 
-fn build_connect_command(tx_id: f64, app: &str, tc_url: &str) -> Vec<u8> {
+/// `connect` command; a non-empty `fourcc_list` advertises Enhanced RTMP codecs.
+pub(super) fn build_connect_command(
+    tx_id: f64,
+    app: &str,
+    tc_url: &str,
+    fourcc_list: &[&str],
+) -> Vec<u8> {
     let mut props = HashMap::new();
     props.insert("app".to_string(), Amf0Value::String(app.to_string()));
     props.insert(
@@ -257,6 +266,17 @@ fn build_connect_command(tx_id: f64, app: &str, tc_url: &str) -> Vec<u8> {
     props.insert("audioCodecs".to_string(), Amf0Value::Number(4071.0));
     props.insert("videoCodecs".to_string(), Amf0Value::Number(252.0));
     props.insert("videoFunction".to_string(), Amf0Value::Number(1.0));
+    if !fourcc_list.is_empty() {
+        props.insert(
+            "fourCcList".to_string(),
+            Amf0Value::StrictArray(
+                fourcc_list
+                    .iter()
+                    .map(|fourcc| Amf0Value::String(fourcc.to_string()))
+                    .collect(),
+            ),
+        );
+    }
 
     let payload = amf0::encode(&[
         Amf0Value::String("connect".to_string()),
@@ -266,7 +286,7 @@ fn build_connect_command(tx_id: f64, app: &str, tc_url: &str) -> Vec<u8> {
     chunk::encode_message(0x14, 0, 0, &payload, CLIENT_CHUNK_SIZE, 3)
 }
 
-fn build_create_stream_command(tx_id: f64) -> Vec<u8> {
+pub(super) fn build_create_stream_command(tx_id: f64) -> Vec<u8> {
     let payload = amf0::encode(&[
         Amf0Value::String("createStream".to_string()),
         Amf0Value::Number(tx_id),
@@ -286,7 +306,7 @@ fn build_play_command(tx_id: f64, stream_name: &str, msg_stream_id: u32) -> Vec<
     chunk::encode_message(0x14, 0, msg_stream_id, &payload, CLIENT_CHUNK_SIZE, 3)
 }
 
-fn handle_control_message(payload: &[u8], msg_type: u8) -> Option<usize> {
+pub(super) fn handle_control_message(payload: &[u8], msg_type: u8) -> Option<usize> {
     match msg_type {
         0x01 if payload.len() >= 4 => {
             let new_size = ((payload[0] as usize) << 24)
@@ -299,7 +319,7 @@ fn handle_control_message(payload: &[u8], msg_type: u8) -> Option<usize> {
     }
 }
 
-async fn read_more(
+pub(super) async fn read_more(
     read_half: &mut tokio::net::tcp::OwnedReadHalf,
     buf: &mut BytesMut,
 ) -> Result<bool> {
@@ -365,7 +385,7 @@ async fn wait_for_play_start(
     Ok(())
 }
 
-async fn wait_for_result(
+pub(super) async fn wait_for_result(
     read_half: &mut tokio::net::tcp::OwnedReadHalf,
     buf: &mut BytesMut,
     chunk_states: &mut HashMap<u32, ChunkAssembler>,
@@ -373,9 +393,9 @@ async fn wait_for_result(
     chunk_size: &mut usize,
     tx_id: f64,
     phase: &str,
-) -> Result<()> {
+) -> Result<Vec<Amf0Value>> {
     for _ in 0..200 {
-        let mut found = false;
+        let mut found = None;
 
         while let Some(msg) = chunk::parse_chunks(buf, chunk_states, chunk_headers, *chunk_size) {
             if let Some(new_size) = handle_control_message(&msg.payload, msg.msg_type) {
@@ -388,7 +408,7 @@ async fn wait_for_result(
                         if let Some(Amf0Value::Number(id)) = args.first() {
                             if (*id - tx_id).abs() < 0.001 {
                                 info!("[RTMP Puller] {} _result OK", phase);
-                                found = true;
+                                found = Some(args);
                                 break;
                             }
                         }
@@ -407,8 +427,8 @@ async fn wait_for_result(
             }
         }
 
-        if found {
-            return Ok(());
+        if let Some(args) = found {
+            return Ok(args);
         }
 
         if !read_more(read_half, buf).await? {
@@ -418,7 +438,7 @@ async fn wait_for_result(
     Err(anyhow!("Timeout waiting for {} _result", phase))
 }
 
-async fn wait_for_create_stream(
+pub(super) async fn wait_for_create_stream(
     read_half: &mut tokio::net::tcp::OwnedReadHalf,
     buf: &mut BytesMut,
     chunk_states: &mut HashMap<u32, ChunkAssembler>,
@@ -514,6 +534,26 @@ fn extract_and_store_sps_pps(data: &[u8], manager: &StreamManager, stream_id: &s
     }
 }
 
+fn store_audio_config(data: &[u8], manager: &StreamManager, stream_id: &str) {
+    // AAC sequence header: 0xAF 0x00 + AudioSpecificConfig
+    if data.len() < 4 || (data[0] >> 4) != 0x0A || data[1] != 0x00 {
+        return;
+    }
+    match AudioCodecConfig::from_asc(&data[2..]) {
+        Some(config) => {
+            info!(
+                "[RTMP Puller] Stored AAC config ({} Hz, {} ch) for stream {}",
+                config.sample_rate, config.channels, stream_id
+            );
+            manager.set_stream_audio_config(stream_id, &config.asc, config.sample_rate);
+        }
+        None => warn!(
+            "[RTMP Puller] Invalid AudioSpecificConfig for stream {}",
+            stream_id
+        ),
+    }
+}
+
 fn rtmp_message_to_frame(msg: &chunk::RtmpMessage, stream_id: &str) -> Option<MediaFrame> {
     match msg.msg_type {
         0x09 => video_payload_to_frame(&msg.payload, stream_id, msg.timestamp),
@@ -0,0 +1,705 @@
+/// RTMP client pusher: connect to a remote RTMP server, publish a local
+/// stream and forward hub frames as FLV video/audio messages.
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use super::amf0::{self, Amf0Value};
+use super::chunk::{self, ChunkAssembler, ChunkHeader};
+use super::prepend_rtmp_video_config;
+use super::puller::{
+    build_connect_command, build_create_stream_command, client_handshake, handle_control_message,
+    parse_rtmp_url, read_more, wait_for_create_stream, wait_for_result,
+};
+use super::session::{self, StreamMetadata};
+use crate::core::{
+    is_idr_frame, prime_live_play, CodecType, DispatchError, DispatchPolicy, PusherId,
+    PusherStatus, Stream, StreamManager, StreamProtocol, StreamPusher,
+};
+use crate::server::webrtc::request_publisher_keyframe;
+
+/// Chunk size announced to the remote server once `connect` succeeds.
+const PUSH_CHUNK_SIZE: usize = 4096;
+/// Enhanced RTMP codecs offered in `connect`; H.265 falls back to the legacy
+/// codec id 12 tags unless the server echoes `hvc1`.
+const PUSH_FOURCC_LIST: &[&str] = &["hvc1"];
+
+/// What the remote server accepted for this publish.
+struct PublishTarget {
+    stream_name: String,
+    msg_stream_id: u32,
+    /// The server echoed `hvc1` in its connect `fourCcList`.
+    enhanced_hevc: bool,
+}
+
+pub struct RtmpPusher {
+    id: PusherId,
+    stream_manager: Arc<StreamManager>,
+    remote_url: String,
+    stream_id: String,
+    status: Arc<RwLock<PusherStatus>>,
+    paused: Arc<RwLock<bool>>,
+    session: Option<JoinHandle<Result<()>>>,
+}
+
+impl RtmpPusher {
+    pub fn new(stream_manager: Arc<StreamManager>, remote_url: &str, stream_id: &str) -> Self {
+        let id = format!("pusher_rtmp_{}_{}", stream_id, uuid::Uuid::new_v4());
+        Self {
+            id,
+            stream_manager,
+            remote_url: remote_url.to_string(),
+            stream_id: stream_id.to_string(),
+            status: Arc::new(RwLock::new(PusherStatus::Idle)),
+            paused: Arc::new(RwLock::new(false)),
+            session: None,
+        }
+    }
+
+    /// Use `id` (e.g. the supervising push job id) instead of a generated one.
+    pub fn with_id(mut self, id: PusherId) -> Self {
+        self.id = id;
+        self
+    }
+
+    fn set_status(&self, status: PusherStatus) {
+        set_status(&self.status, status);
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        info!("[RTMP Pusher] =========================================");
+        info!(
+            "[RTMP Pusher] Starting RTMP Pusher {} for stream: {}",
+            self.id, self.stream_id
+        );
+        info!("[RTMP Pusher] Target URL: {}", self.remote_url);
+        info!("[RTMP Pusher] =========================================");
+
+        self.set_status(PusherStatus::Starting);
+
+        let (read_half, write_half, target) = match self.connect_and_publish().await {
+            Ok(session) => session,
+            Err(e) => {
+                self.set_status(PusherStatus::Error(e.to_string()));
+                return Err(e);
+            }
+        };
+
+        self.set_status(PusherStatus::Running);
+        info!("[RTMP Pusher] =========================================");
+        info!(
+            "[RTMP Pusher] SUCCESS: RTMP Pusher started for stream {}",
+            self.stream_id
+        );
+        info!("[RTMP Pusher] Remote URL: {}", self.remote_url);
+        info!("[RTMP Pusher] Message stream ID: {}", target.msg_stream_id);
+        info!("[RTMP Pusher] =========================================");
+
+        let manager = self.stream_manager.clone();
+        let stream_id = self.stream_id.clone();
+        let status = Arc::clone(&self.status);
+        let paused = Arc::clone(&self.paused);
+
+        self.session = Some(tokio::spawn(async move {
+            let result = tokio::select! {
+                r = Self::send_loop(write_half, manager, stream_id.clone(), target, Arc::clone(&status), paused) => r,
+                r = Self::monitor_connection(read_half) => r,
+            };
+            match &result {
+                Ok(()) => info!("[RTMP Pusher] Push ended for stream {}", stream_id),
+                Err(e) => {
+                    error!("[RTMP Pusher] Push failed for stream {}: {}", stream_id, e);
+                    if !status.read().is_terminated() {
+                        set_status(&status, PusherStatus::Error(e.to_string()));
+                    }
+                }
+            }
+            result
+        }));
+
+        Ok(())
+    }
+
+    /// Wait for the publish session spawned by `start()` to end.
+    pub async fn wait(&mut self) -> Result<()> {
+        match self.session.as_mut() {
+            Some(session) => session.await?,
+            None => Ok(()),
+        }
+    }
+
+    /// Handshake, then `connect` / `releaseStream` / `FCPublish` / `createStream` /
+    /// `publish` until the server answers `NetStream.Publish.Start`.
+    async fn connect_and_publish(&self) -> Result<(OwnedReadHalf, OwnedWriteHalf, PublishTarget)> {
+        let rtmp_url = parse_rtmp_url(&self.remote_url)?;
+        if self.stream_manager.get_stream(&self.stream_id).is_none() {
+            return Err(anyhow!("Stream {} not found", self.stream_id));
+        }
+
+        info!("[RTMP Pusher] [Step 1/5] Connecting...");
+        let addr = format!("{}:{}", rtmp_url.host, rtmp_url.port);
+        let mut stream = TcpStream::connect(&addr).await?;
+        stream.set_nodelay(true)?;
+        client_handshake(&mut stream).await?;
+        info!("[RTMP Pusher] Handshake with {} completed", addr);
+
+        let (mut read_half, mut write_half) = stream.into_split();
+        let mut buf = BytesMut::with_capacity(8192);
+        let mut chunk_states: HashMap<u32, ChunkAssembler> = HashMap::new();
+        let mut chunk_headers: HashMap<u32, ChunkHeader> = HashMap::new();
+        let mut chunk_size: usize = 128;
+        let mut tx_id = 1.0_f64;
+
+        info!(
+            "[RTMP Pusher] [Step 2/5] Sending connect app={}",
+            rtmp_url.app
+        );
+        write_half
+            .write_all(&build_connect_command(
+                tx_id,
+                &rtmp_url.app,
+                &rtmp_url.tc_url,
+                PUSH_FOURCC_LIST,
+            ))
+            .await?;
+        let connect_result = wait_for_result(
+            &mut read_half,
+            &mut buf,
+            &mut chunk_states,
+            &mut chunk_headers,
+            &mut chunk_size,
+            tx_id,
+            "connect",
+        )
+        .await?;
+        let enhanced_hevc = echoes_fourcc(&connect_result, "hvc1");
+        info!(
+            "[RTMP Pusher] Server {} Enhanced RTMP hvc1",
+            if enhanced_hevc {
+                "accepts"
+            } else {
+                "did not echo"
+            }
+        );
+        tx_id += 1.0;
+
+        write_half
+            .write_all(&chunk::encode_set_chunk_size(PUSH_CHUNK_SIZE as u32))
+            .await?;
+
+        info!(
+            "[RTMP Pusher] [Step 3/5] Sending releaseStream/FCPublish stream={}",
+            rtmp_url.stream_name
+        );
+        for command in ["releaseStream", "FCPublish"] {
+            let msg = build_stream_name_command(command, tx_id, &rtmp_url.stream_name);
+            write_half.write_all(&msg).await?;
+            tx_id += 1.0;
+        }
+
+        info!("[RTMP Pusher] [Step 4/5] Sending createStream...");
+        write_half
+            .write_all(&build_create_stream_command(tx_id))
+            .await?;
+        let msg_stream_id = wait_for_create_stream(
+            &mut read_half,
+            &mut buf,
+            &mut chunk_states,
+            &mut chunk_headers,
+            &mut chunk_size,
+            tx_id,
+        )
+        .await? as u32;
+        tx_id += 1.0;
+
+        info!(
+            "[RTMP Pusher] [Step 5/5] Sending publish stream={} msg_stream_id={}",
+            rtmp_url.stream_name, msg_stream_id
+        );
+        write_half
+            .write_all(&build_publish_command(
+                tx_id,
+                &rtmp_url.stream_name,
+                msg_stream_id,
+            ))
+            .await?;
+        write_half.flush().await?;
+        wait_for_publish_start(
+            &mut read_half,
+            &mut buf,
+            &mut chunk_states,
+            &mut chunk_headers,
+            &mut chunk_size,
+        )
+        .await?;
+
+        let target = PublishTarget {
+            stream_name: rtmp_url.stream_name,
+            msg_stream_id,
+            enhanced_hevc,
+        };
+        Ok((read_half, write_half, target))
+    }
+
+    async fn send_loop(
+        mut writer: OwnedWriteHalf,
+        manager: Arc<StreamManager>,
+        stream_id: String,
+        target: PublishTarget,
+        status: Arc<RwLock<PusherStatus>>,
+        paused: Arc<RwLock<bool>>,
+    ) -> Result<()> {
+        let mut reader = manager
+            .dispatch_subscribe(&stream_id, DispatchPolicy::LiveCoalesce)
+            .ok_or_else(|| anyhow!("Failed to subscribe to stream {}", stream_id))?;
+        let mut pending = prime_live_play(&mut reader, &manager, &stream_id, "RTMP Pusher").await;
+
+        // Sequence headers are built after priming so the first IDR has filled in SPS/PPS.
+        let stream = manager
+            .get_stream(&stream_id)
+            .ok_or_else(|| anyhow!("Stream {} disappeared", stream_id))?;
+        let metadata = StreamMetadata::from_stream(&stream).with_hub_rates(reader.hub());
+        for (msg_type, csid, payload) in publish_header_messages(&stream, &metadata, &target) {
+            let msg = chunk::encode_message(
+                msg_type,
+                0,
+                target.msg_stream_id,
+                &payload,
+                PUSH_CHUNK_SIZE,
+                csid,
+            );
+            writer.write_all(&msg).await?;
+        }
+        info!(
+            "[RTMP Pusher] Sent onMetaData and sequence headers for stream {} ({:?} {}x{})",
+            stream_id,
+            stream.video_codec(),
+            metadata.width,
+            metadata.height
+        );
+
+        let mut video_streaming = pending.is_some();
+        let mut clock = session::RtmpPlayClock::default();
+        let mut frames_sent: u64 = 0;
+        let mut bytes_sent: u64 = 0;
+        let mut last_log_time = Instant::now();
+
+        loop {
+            let frames = if let Some(frame) = pending.take() {
+                vec![frame]
+            } else {
+                match reader.recv_batch().await {
+                    Ok(frames) if !frames.is_empty() => frames,
+                    Ok(_) => continue,
+                    Err(DispatchError::Closed) => break,
+                }
+            };
+            if status.read().is_terminated() {
+                info!("[RTMP Pusher] Pusher stopped for stream {}", stream_id);
+                return Ok(());
+            }
+            if reader.take_muxer_resync() {
+                video_streaming = false;
+                reader.clear_video_catchup();
+                request_publisher_keyframe(&stream_id);
+                info!(
+                    "[RTMP Pusher] ring gap — wait for fresh IDR stream='{}'",
+                    stream_id
+                );
+            }
+            for frame in frames {
+                if *paused.read() {
+                    // Resume on a keyframe so the remote decoder is not fed broken references.
+                    video_streaming = false;
+                    continue;
+                }
+                if frame.codec.is_video() && !video_streaming {
+                    if !is_idr_frame(&frame) {
+                        continue;
+                    }
+                    video_streaming = true;
+                }
+                let data = match frame.codec {
+                    CodecType::H265 if target.enhanced_hevc => {
+                        session::frame_to_enhanced_rtmp_video(&prepend_rtmp_video_config(
+                            &manager, &stream_id, &frame,
+                        ))
+                    }
+                    codec if codec.is_video() => session::frame_to_rtmp_video(
+                        &prepend_rtmp_video_config(&manager, &stream_id, &frame),
+                    ),
+                    CodecType::AAC if frame.data.len() >= 8 => session::frame_to_rtmp_audio(&frame),
+                    _ => continue,
+                };
+                if data.is_empty() {
+                    continue;
+                }
+                let (msg_type, csid) = if frame.codec.is_video() {
+                    (0x09u8, chunk::CSID_VIDEO)
+                } else {
+                    (0x08u8, chunk::CSID_AUDIO)
+                };
+                let msg = chunk::encode_message(
+                    msg_type,
+                    clock.map_wallclock(),
+                    target.msg_stream_id,
+                    &data,
+                    PUSH_CHUNK_SIZE,
+                    csid,
+                );
+                writer.write_all(&msg).await?;
+                frames_sent += 1;
+                bytes_sent += msg.len() as u64;
+
+                if frames_sent == 1 {
+                    info!(
+                        "[RTMP Pusher] First frame sent: codec={:?} keyframe={} size={}",
+                        frame.codec,
+                        frame.is_keyframe,
+                        frame.data.len()
+                    );
+                }
+                let elapsed = last_log_time.elapsed();
+                if elapsed >= Duration::from_secs(10) {
+                    info!(
+                        "[RTMP Pusher] Stats - Frames: {}, Bytes: {}, BPS: {:.2} KB/s",
+                        frames_sent,
+                        bytes_sent,
+                        bytes_sent as f64 / elapsed.as_secs_f64() / 1024.0
+                    );
+                    last_log_time = Instant::now();
+                }
+            }
+        }
+
+        info!(
+            "[RTMP Pusher] Source stream {} closed after {} frames",
+            stream_id, frames_sent
+        );
+        Ok(())
+    }
+
+    /// Drain server-to-client traffic; an `onStatus` error or EOF ends the push.
+    async fn monitor_connection(mut reader: OwnedReadHalf) -> Result<()> {
+        let mut buf = BytesMut::with_capacity(4096);
+        let mut chunk_states: HashMap<u32, ChunkAssembler> = HashMap::new();
+        let mut chunk_headers: HashMap<u32, ChunkHeader> = HashMap::new();
+        let mut chunk_size: usize = 128;
+
+        loop {
+            if !read_more(&mut reader, &mut buf).await? {
+                return Err(anyhow!("Connection closed by remote server"));
+            }
+            while let Some(msg) =
+                chunk::parse_chunks(&mut buf, &mut chunk_states, &mut chunk_headers, chunk_size)
+            {
+                if let Some(new_size) = handle_control_message(&msg.payload, msg.msg_type) {
+                    chunk_size = new_size;
+                }
+                if msg.msg_type != 0x14 {
+                    continue;
+                }
+                if let Ok((cmd, args)) = amf0::parse_command(&msg.payload) {
+                    debug!("[RTMP Pusher] [Monitor] recv cmd={}", cmd);
+                    if let Some(code) = on_status_error(&cmd, &args) {
+                        return Err(anyhow!("Remote server reported {}", code));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn set_status(current: &RwLock<PusherStatus>, status: PusherStatus) {
+    let mut s = current.write();
+    info!(
+        "[RTMP Pusher] Status changed: {} -> {}",
+        s.as_str(),
+        status.as_str()
+    );
+    *s = status;
+}
+
+/// `releaseStream` / `FCPublish`: commands whose only argument is the stream name.
+fn build_stream_name_command(command: &str, tx_id: f64, stream_name: &str) -> Vec<u8> {
+    let payload = amf0::encode(&[
+        Amf0Value::String(command.to_string()),
+        Amf0Value::Number(tx_id),
+        Amf0Value::Null,
+        Amf0Value::String(stream_name.to_string()),
+    ]);
+    chunk::encode_message(0x14, 0, 0, &payload, PUSH_CHUNK_SIZE, chunk::CSID_COMMAND)
+}
+
+fn build_publish_command(tx_id: f64, stream_name: &str, msg_stream_id: u32) -> Vec<u8> {
+    let payload = amf0::encode(&[
+        Amf0Value::String("publish".to_string()),
+        Amf0Value::Number(tx_id),
+        Amf0Value::Null,
+        Amf0Value::String(stream_name.to_string()),
+        Amf0Value::String("live".to_string()),
+    ]);
+    chunk::encode_message(
+        0x14,
+        0,
+        msg_stream_id,
+        &payload,
+        PUSH_CHUNK_SIZE,
+        chunk::CSID_COMMAND,
+    )
+}
+
+/// `@setDataFrame(onMetaData)` followed by the video and AAC sequence headers,
+/// as `(msg_type, csid, payload)`. H.265 goes out as Enhanced RTMP (`hvc1`) when
+/// the server accepted it, else with the legacy codec id 12.
+fn publish_header_messages(
+    stream: &Stream,
+    metadata: &StreamMetadata,
+    target: &PublishTarget,
+) -> Vec<(u8, u32, Vec<u8>)> {
+    let mut data_frame = amf0::encode(&[Amf0Value::String("@setDataFrame".to_string())]);
+    data_frame.extend(session::build_metadata(metadata, &target.stream_name));
+    let mut messages = vec![(0x12, chunk::CSID_COMMAND, data_frame)];
+    if let Some(header) = session::build_video_sequence_header(stream, target.enhanced_hevc) {
+        messages.push((0x09, chunk::CSID_VIDEO, header));
+    }
+    if let Some(header) = session::build_aac_sequence_header(stream) {
+        messages.push((0x08, chunk::CSID_AUDIO, header));
+    }
+    messages
+}
+
+/// Whether the connect `_result` lists `fourcc` in its `fourCcList`.
+fn echoes_fourcc(connect_result: &[Amf0Value], fourcc: &str) -> bool {
+    connect_result
+        .iter()
+        .filter_map(|value| value.as_object()?.get("fourCcList")?.as_array())
+        .flatten()
+        .any(|value| value.as_str() == Some(fourcc))
+}
+
+fn on_status_error(cmd: &str, args: &[Amf0Value]) -> Option<String> {
+    if cmd == "_error" {
+        return Some("_error".to_string());
+    }
+    if cmd != "onStatus" {
+        return None;
+    }
+    let code = args.get(2)?.as_object()?.get("code")?.as_str()?;
+    let failed = code.contains("Failed") || code.contains("BadName") || code.contains("Rejected");
+    failed.then(|| code.to_string())
+}
+
+async fn wait_for_publish_start(
+    read_half: &mut OwnedReadHalf,
+    buf: &mut BytesMut,
+    chunk_states: &mut HashMap<u32, ChunkAssembler>,
+    chunk_headers: &mut HashMap<u32, ChunkHeader>,
+    chunk_size: &mut usize,
+) -> Result<()> {
+    for _ in 0..200 {
+        while let Some(msg) = chunk::parse_chunks(buf, chunk_states, chunk_headers, *chunk_size) {
+            if let Some(new_size) = handle_control_message(&msg.payload, msg.msg_type) {
+                *chunk_size = new_size;
+            }
+            if msg.msg_type != 0x14 {
+                continue;
+            }
+            let Ok((cmd, args)) = amf0::parse_command(&msg.payload) else {
+                continue;
+            };
+            if let Some(code) = on_status_error(&cmd, &args) {
+                return Err(anyhow!("Publish failed: {}", code));
+            }
+            if cmd != "onStatus" {
+                debug!("[RTMP Pusher] recv cmd={} during publish", cmd);
+                continue;
+            }
+            let code = args
+                .get(2)
+                .and_then(|info| amf0::get_string_prop(info, "code"))
+                .unwrap_or_default();
+            info!("[RTMP Pusher] onStatus: {}", code);
+            if code == "NetStream.Publish.Start" {
+                return Ok(());
+            }
+        }
+
+        if !read_more(read_half, buf).await? {
+            return Err(anyhow!("Connection closed waiting for publish start"));
+        }
+    }
+    warn!("[RTMP Pusher] No explicit Publish.Start, continuing anyway");
+    Ok(())
+}
+
+impl StreamPusher for RtmpPusher {
+    fn id(&self) -> &PusherId {
+        &self.id
+    }
+
+    fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
+    fn protocol(&self) -> StreamProtocol {
+        StreamProtocol::RTMP
+    }
+
+    fn remote_url(&self) -> &str {
+        &self.remote_url
+    }
+
+    fn status(&self) -> PusherStatus {
+        self.status.read().clone()
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        Self::start(self).await
+    }
+
+    async fn pause(&mut self) -> Result<()> {
+        info!("[RTMP Pusher] Pausing pusher for stream {}", self.stream_id);
+        *self.paused.write() = true;
+        if self.status.read().is_running() {
+            self.set_status(PusherStatus::Paused);
+        }
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> Result<()> {
+        info!(
+            "[RTMP Pusher] Resuming pusher for stream {}",
+            self.stream_id
+        );
+        *self.paused.write() = false;
+        if self.status.read().is_paused() {
+            request_publisher_keyframe(&self.stream_id);
+            self.set_status(PusherStatus::Running);
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        info!(
+            "[RTMP Pusher] Stopping pusher for stream {}",
+            self.stream_id
+        );
+        self.set_status(PusherStatus::Stopped);
+        if let Some(session) = self.session.take() {
+            session.abort();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RtmpPusher {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{default_live_tracks, StreamSourceMode, Track};
+
+    fn decode_command(encoded: &[u8]) -> (String, Vec<Amf0Value>) {
+        let mut buf = BytesMut::from(encoded);
+        let msg = chunk::parse_chunks(
+            &mut buf,
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+            PUSH_CHUNK_SIZE,
+        )
+        .unwrap();
+        amf0::parse_command(&msg.payload).unwrap()
+    }
+
+    #[test]
+    fn publish_commands_carry_stream_name() {
+        let (cmd, args) = decode_command(&build_stream_name_command("FCPublish", 3.0, "key"));
+        assert_eq!(cmd, "FCPublish");
+        assert_eq!(args[0].as_f64(), Some(3.0));
+        assert_eq!(args[2].as_str(), Some("key"));
+
+        let (cmd, args) = decode_command(&build_publish_command(5.0, "key", 1));
+        assert_eq!(cmd, "publish");
+        assert_eq!(args[2].as_str(), Some("key"));
+        assert_eq!(args[3].as_str(), Some("live"));
+    }
+
+    #[test]
+    fn publish_headers_start_with_set_data_frame() {
+        let manager = StreamManager::new();
+        manager.create_stream("s", StreamSourceMode::Push, StreamProtocol::RTMP, None);
+        manager.set_stream_tracks("s", default_live_tracks());
+        manager.set_stream_sps_pps("s", vec![0x67, 0x42, 0x00, 0x1f], vec![0x68, 0xce]);
+        manager.set_stream_audio_config("s", &[0x11, 0x90], 48_000);
+        let stream = manager.get_stream(&"s".to_string()).unwrap();
+
+        let target = PublishTarget {
+            stream_name: "key".to_string(),
+            msg_stream_id: 1,
+            enhanced_hevc: false,
+        };
+        let messages =
+            publish_header_messages(&stream, &StreamMetadata::from_stream(&stream), &target);
+
+        let types: Vec<u8> = messages.iter().map(|(t, _, _)| *t).collect();
+        assert_eq!(types, vec![0x12, 0x09, 0x08]);
+        let (values, _) = amf0::decode(&messages[0].2).unwrap();
+        assert_eq!(values[0].as_str(), Some("@setDataFrame"));
+        assert_eq!(values[1].as_str(), Some("onMetaData"));
+        assert_eq!(&messages[1].2[..2], &[0x17, 0x00]);
+        assert_eq!(&messages[2].2[..4], &[0xAF, 0x00, 0x11, 0x90]);
+    }
+
+    #[test]
+    fn hevc_uses_enhanced_tags_only_when_server_echoes_hvc1() {
+        let mut buf = BytesMut::from(
+            &build_connect_command(1.0, "live", "rtmp://host/live", PUSH_FOURCC_LIST)[..],
+        );
+        // connect goes out before the chunk size is raised
+        let msg =
+            chunk::parse_chunks(&mut buf, &mut HashMap::new(), &mut HashMap::new(), 128).unwrap();
+        let (cmd, args) = amf0::parse_command(&msg.payload).unwrap();
+        assert_eq!(cmd, "connect");
+        assert!(echoes_fourcc(&args, "hvc1"));
+
+        let mut props = HashMap::new();
+        props.insert(
+            "fourCcList".to_string(),
+            Amf0Value::StrictArray(vec![Amf0Value::String("av01".into())]),
+        );
+        let result = vec![Amf0Value::Number(1.0), Amf0Value::Object(props)];
+        assert!(!echoes_fourcc(&result, "hvc1"));
+
+        let manager = StreamManager::new();
+        manager.create_stream("s", StreamSourceMode::Push, StreamProtocol::RTMP, None);
+        manager.set_stream_tracks("s", vec![Track::new(0, CodecType::H265, 96, 90_000)]);
+        manager.set_stream_hevc_config("s", vec![0x40, 0x01], vec![0x42, 0x01], vec![0x44, 0x01]);
+        let stream = manager.get_stream(&"s".to_string()).unwrap();
+        let metadata = StreamMetadata::from_stream(&stream);
+        let mut target = PublishTarget {
+            stream_name: "key".to_string(),
+            msg_stream_id: 1,
+            enhanced_hevc: false,
+        };
+        let legacy = publish_header_messages(&stream, &metadata, &target);
+        assert_eq!(legacy[1].2[0], 0x1C);
+        target.enhanced_hevc = true;
+        let enhanced = publish_header_messages(&stream, &metadata, &target);
+        assert_eq!(&enhanced[1].2[1..5], b"hvc1");
+    }
+}
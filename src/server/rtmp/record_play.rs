@@ -0,0 +1,166 @@
+/// RTMP playback of recordings: `play` of `record/<stream>[?start=<ms>]` (or any
+/// stream under the `record` app), with `seek` and `pause` handled by reopening
+/// the recording at the new position.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use super::{chunk, session};
+use crate::process::record::{PacedReader, RecordingReader};
+
+/// App name, or stream name prefix, that selects recorded playback.
+pub const RECORD_APP: &str = "record";
+
+/// `(stream_id, start_ms)` when `app`/`stream_name` address a recording.
+pub fn parse_record_play(app: &str, stream_name: &str) -> Option<(String, Option<u64>)> {
+    let name = if app.trim_matches('/') == RECORD_APP {
+        stream_name
+    } else {
+        stream_name.strip_prefix(RECORD_APP)?.strip_prefix('/')?
+    };
+    let (stream_id, query) = name.split_once('?').unwrap_or((name, ""));
+    if stream_id.is_empty() {
+        return None;
+    }
+    let start_ms = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "start")
+        .and_then(|(_, value)| value.parse().ok());
+    Some((stream_id.to_string(), start_ms))
+}
+
+/// Recorded playback bound to one RTMP connection.
+#[derive(Clone)]
+pub struct RecordPlay {
+    pub stream_id: String,
+    /// Wall-clock time of RTMP timestamp 0; `seek` offsets are relative to it.
+    pub origin_ms: u64,
+    /// Timestamp of the last frame sent; unpause resumes here.
+    pub position: Arc<AtomicU64>,
+}
+
+impl RecordPlay {
+    pub fn new(stream_id: String, origin_ms: u64) -> Self {
+        Self {
+            stream_id,
+            origin_ms,
+            position: Arc::new(AtomicU64::new(origin_ms)),
+        }
+    }
+
+    pub fn position_ms(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+}
+
+/// Running recorded playback. The task is stopped between messages, never
+/// inside a chunked write, so the client's chunk stream stays in sync.
+pub struct RecordPlayTask {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl RecordPlayTask {
+    /// Stop sending and wait until the task has let go of the writer.
+    pub async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Send sequence headers, then the recording paced at media rate. Message
+/// timestamps are milliseconds since `record.origin_ms`.
+pub fn spawn_record_play(
+    writer: Arc<Mutex<tokio::net::tcp::OwnedWriteHalf>>,
+    chunk_size: usize,
+    server_stream_id: u32,
+    reader: RecordingReader,
+    record: RecordPlay,
+    peer_log: String,
+) -> RecordPlayTask {
+    let headers = session::build_recording_sequence_headers(reader.info());
+    let base_ts = reader.start_ms().saturating_sub(record.origin_ms);
+    let (stop, mut stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        let mut paced = PacedReader::new(reader, 1.0);
+        let mut frames_sent = 0u64;
+        {
+            let mut w = writer.lock().await;
+            for (msg_type, payload) in &headers {
+                let csid = if *msg_type == 0x09 {
+                    chunk::CSID_VIDEO
+                } else {
+                    chunk::CSID_AUDIO
+                };
+                let msg = chunk::encode_message(
+                    *msg_type,
+                    base_ts as u32,
+                    server_stream_id,
+                    payload,
+                    chunk_size,
+                    csid,
+                );
+                if w.write_all(&msg).await.is_err() {
+                    return;
+                }
+            }
+        }
+        loop {
+            // Only the wait for the next frame is cancelled; writes always complete.
+            let next = tokio::select! {
+                biased;
+                _ = &mut stopped => return,
+                next = paced.next_frame() => next,
+            };
+            let Some((frame, offset_ms)) = next else {
+                break;
+            };
+            let Some((msg_type, data)) = session::recorded_frame_to_rtmp(&frame) else {
+                continue;
+            };
+            let csid = if msg_type == 0x09 {
+                chunk::CSID_VIDEO
+            } else {
+                chunk::CSID_AUDIO
+            };
+            let rtmp_ts = (base_ts + offset_ms as u64) as u32;
+            let msg =
+                chunk::encode_message(msg_type, rtmp_ts, server_stream_id, &data, chunk_size, csid);
+            if writer.lock().await.write_all(&msg).await.is_err() {
+                info!("[RTMP] [{}] Play client disconnected", peer_log);
+                return;
+            }
+            frames_sent += 1;
+            record.position.store(frame.timestamp, Ordering::Relaxed);
+        }
+        info!(
+            "[RTMP] [{}] recorded playback of '{}' reached end after {} frames",
+            peer_log, record.stream_id, frames_sent
+        );
+    });
+    RecordPlayTask { stop, task }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_play_accepts_app_or_stream_prefix() {
+        assert_eq!(
+            parse_record_play("record", "cam1?start=1700000000000"),
+            Some(("cam1".to_string(), Some(1_700_000_000_000)))
+        );
+        assert_eq!(
+            parse_record_play("live", "record/cam1"),
+            Some(("cam1".to_string(), None))
+        );
+        assert_eq!(parse_record_play("live", "cam1"), None);
+        assert_eq!(parse_record_play("live", "recordings"), None);
+    }
+}
@@ -5,8 +5,14 @@ use std::collections::HashMap;
 use tracing::{debug, info, Level};
 
 use super::amf0::{self, Amf0Value};
-use crate::core::{CodecType, MediaFrame, StreamProtocol, StreamSourceMode};
-use crate::server::webrtc::h264_util::is_keyframe_annex_b;
+use crate::core::{
+    h265, media_frame_timestamp_delta_ms, CodecType, MediaFrame, Stream, StreamHub, StreamProtocol,
+    StreamSourceMode,
+};
+use crate::process::record::RecordingMediaInfo;
+use crate::server::hls::fmp4_muxer::{AudioCodecConfig, VideoCodecConfig};
+use crate::server::rtsp::common::aac_payload_without_adts;
+use crate::server::webrtc::h264_util::{build_avcc, is_keyframe_annex_b, sps_info};
 
 /// RTMP session state
 #[derive(Debug, Clone, PartialEq)]
@@ -51,6 +57,8 @@ pub struct RtmpSession {
     pub aac_header_sent: bool,
     /// Whether we've sent onMetaData
     pub metadata_sent: bool,
+    /// Video FourCCs the client announced in connect (`fourCcList`, Enhanced RTMP)
+    pub fourcc_list: Vec<String>,
     /// Transaction ID counter for commands
     pub transaction_id: f64,
     /// Peer address string for logging
@@ -79,6 +87,7 @@ impl RtmpSession {
             avc_header_sent: false,
             aac_header_sent: false,
             metadata_sent: false,
+            fourcc_list: Vec::new(),
             transaction_id: 1.0,
             peer_addr: peer_addr.to_string(),
         }
@@ -93,6 +102,13 @@ impl RtmpSession {
                     self.app = app.to_string();
                     // info!("[RTMP] [{}] App: {}", self.peer_addr, self.app);
                 }
+                if let Some(list) = map.get("fourCcList").and_then(|v| v.as_array()) {
+                    self.fourcc_list = list
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .map(str::to_string)
+                        .collect();
+                }
             }
         }
         self.state = SessionState::Connected;
@@ -100,6 +116,16 @@ impl RtmpSession {
         self.app.clone()
     }
 
+    /// Whether the client negotiated Enhanced RTMP tags for this video codec
+    pub fn accepts_enhanced(&self, codec: CodecType) -> bool {
+        let Some(fourcc) = video_fourcc(codec) else {
+            return false;
+        };
+        self.fourcc_list
+            .iter()
+            .any(|c| c == "*" || c.as_bytes() == fourcc)
+    }
+
     /// Handle createStream command
     pub fn handle_create_stream(&mut self, args: &[Amf0Value]) -> u32 {
         self.server_stream_id += 1;
@@ -182,6 +208,28 @@ impl RtmpSession {
 
 /// Build _result response for connect/createStream
 pub fn build_result_response(transaction_id: f64, command: &str) -> Vec<u8> {
+    build_result_response_with_props(transaction_id, command, HashMap::new())
+}
+
+/// Build connect _result, echoing the client's `fourCcList` entries we can relay
+pub fn build_connect_result(transaction_id: f64, client_fourccs: &[String]) -> Vec<u8> {
+    let mut extra = HashMap::new();
+    if !client_fourccs.is_empty() {
+        let supported = client_fourccs
+            .iter()
+            .filter(|c| *c == "*" || codec_from_fourcc(c.as_bytes()).is_some())
+            .map(|c| Amf0Value::String(c.clone()))
+            .collect();
+        extra.insert("fourCcList".to_string(), Amf0Value::StrictArray(supported));
+    }
+    build_result_response_with_props(transaction_id, "Connect", extra)
+}
+
+fn build_result_response_with_props(
+    transaction_id: f64,
+    command: &str,
+    extra_props: HashMap<String, Amf0Value>,
+) -> Vec<u8> {
     let mut values = vec![
         Amf0Value::String("_result".to_string()), // Response command should be _result
         Amf0Value::Number(transaction_id),
@@ -195,6 +243,7 @@ pub fn build_result_response(transaction_id: f64, command: &str) -> Vec<u8> {
     );
     props.insert("capabilities".to_string(), Amf0Value::Number(31.0));
     props.insert("objectEncoding".to_string(), Amf0Value::Number(0.0)); // AMF0 encoding
+    props.extend(extra_props);
     values.push(Amf0Value::Object(props));
 
     // Information object
@@ -262,62 +311,257 @@ pub fn build_avc_sequence_header(sps: &[u8], pps: &[u8]) -> Vec<u8> {
     data.extend_from_slice(&[0x00, 0x00, 0x00]);
 
     // AVCDecoderConfigurationRecord
-    data.push(0x01); // configurationVersion
-    if sps.len() >= 4 {
-        data.push(sps[1]); // AVCProfileIndication
-        data.push(sps[2]); // profile_compatibility
-        data.push(sps[3]); // AVCLevelIndication
-    } else {
-        data.push(0x42);
-        data.push(0x00);
-        data.push(0x1F);
+    data.extend_from_slice(&build_avcc(sps, pps));
+
+    data
+}
+
+/// Build HEVC sequence header (legacy FLV codec id 12 + hvcC) as RTMP video message payload
+pub fn build_hevc_sequence_header(vps: &[u8], sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x1C, 0x00, 0x00, 0x00, 0x00];
+    data.extend_from_slice(&h265::build_hvcc(vps, sps, pps));
+    data
+}
+
+/// Enhanced RTMP video packet types (ExVideoTagHeader)
+const EX_PACKET_SEQUENCE_START: u8 = 0;
+const EX_PACKET_CODED_FRAMES: u8 = 1;
+const EX_PACKET_CODED_FRAMES_X: u8 = 3;
+
+/// Enhanced RTMP FourCC of a video codec
+pub fn video_fourcc(codec: CodecType) -> Option<&'static [u8; 4]> {
+    match codec {
+        CodecType::H265 => Some(b"hvc1"),
+        CodecType::AV1 => Some(b"av01"),
+        CodecType::VP9 => Some(b"vp09"),
+        _ => None,
     }
-    data.push(0xFF); // lengthSizeMinusOne = 3
-    data.push(0xE1); // numOfSPS = 1
-
-    // SPS
-    let sps_len = sps.len() as u16;
-    data.push((sps_len >> 8) as u8);
-    data.push((sps_len & 0xFF) as u8);
-    data.extend_from_slice(sps);
-
-    // PPS
-    data.push(0x01); // numOfPPS = 1
-    let pps_len = pps.len() as u16;
-    data.push((pps_len >> 8) as u8);
-    data.push((pps_len & 0xFF) as u8);
-    data.extend_from_slice(pps);
+}
+
+/// Video codec of an Enhanced RTMP FourCC
+pub fn codec_from_fourcc(fourcc: &[u8]) -> Option<CodecType> {
+    match fourcc {
+        b"hvc1" => Some(CodecType::H265),
+        b"av01" => Some(CodecType::AV1),
+        b"vp09" => Some(CodecType::VP9),
+        _ => None,
+    }
+}
 
+/// Enhanced RTMP tag header: [IsExHeader | FrameType(3) | PacketType(4)] + FourCC
+fn enhanced_video_header(codec: CodecType, keyframe: bool, packet_type: u8) -> Vec<u8> {
+    let frame_type = if keyframe { 1 } else { 2 };
+    let mut data = vec![0x80 | (frame_type << 4) | packet_type];
+    data.extend_from_slice(video_fourcc(codec).unwrap_or(b"avc1"));
     data
 }
 
-/// Build AAC sequence header as RTMP audio message payload
-pub fn build_aac_sequence_header() -> Vec<u8> {
-    let mut data = Vec::new();
+/// Build Enhanced RTMP sequence start carrying the codec configuration record
+/// (hvcC / av1C / vpcC)
+pub fn build_enhanced_sequence_start(codec: CodecType, record: &[u8]) -> Vec<u8> {
+    let mut data = enhanced_video_header(codec, true, EX_PACKET_SEQUENCE_START);
+    data.extend_from_slice(record);
+    data
+}
+
+/// Sequence header for the stream's video codec, once its parameter sets are known.
+/// HEVC uses the Enhanced RTMP form when `enhanced`, else legacy codec id 12;
+/// AV1/VP9 only exist as Enhanced RTMP.
+pub fn build_video_sequence_header(stream: &Stream, enhanced: bool) -> Option<Vec<u8>> {
+    match (stream.video_codec(), &stream.vps, &stream.sps, &stream.pps) {
+        (CodecType::H265, Some(vps), Some(sps), Some(pps)) if enhanced => Some(
+            build_enhanced_sequence_start(CodecType::H265, &h265::build_hvcc(vps, sps, pps)),
+        ),
+        (CodecType::H265, Some(vps), Some(sps), Some(pps)) => {
+            Some(build_hevc_sequence_header(vps, sps, pps))
+        }
+        (CodecType::H265, ..) => None,
+        (codec @ (CodecType::AV1 | CodecType::VP9), ..) => stream
+            .video_config_record()
+            .map(|record| build_enhanced_sequence_start(codec, &record)),
+        (_, _, Some(sps), Some(pps)) => Some(build_avc_sequence_header(sps, pps)),
+        _ => None,
+    }
+}
+
+/// Build AAC sequence header for the stream's audio track (announced ASC, else AAC-LC
+/// at the track rate)
+pub fn build_aac_sequence_header(stream: &Stream) -> Option<Vec<u8>> {
+    AudioCodecConfig::from_stream(stream)
+        .map(|config| build_aac_sequence_header_with_config(&config.asc))
+}
+
+/// Build AAC sequence header carrying the given AudioSpecificConfig
+pub fn build_aac_sequence_header_with_config(asc: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(2 + asc.len());
     data.push(0xAF); // AAC 44kHz 16bit stereo
     data.push(0x00); // Sequence header
-                     // AudioSpecificConfig: AAC-LC, 44100Hz, stereo
-    data.push(0x12);
-    data.push(0x10);
+    data.extend_from_slice(asc);
     data
 }
 
-/// Build onMetaData script message
-pub fn build_metadata(width: u32, height: u32, stream_name: &str) -> Vec<u8> {
+/// Sequence headers for recorded playback as (message type, payload), video first
+pub fn build_recording_sequence_headers(info: &RecordingMediaInfo) -> Vec<(u8, Vec<u8>)> {
+    let mut headers = Vec::new();
+    match &info.video {
+        Some(VideoCodecConfig::Avc { sps, pps }) => {
+            headers.push((0x09, build_avc_sequence_header(sps, pps)))
+        }
+        Some(VideoCodecConfig::Hevc { vps, sps, pps }) => {
+            headers.push((0x09, build_hevc_sequence_header(vps, sps, pps)))
+        }
+        None => {}
+    }
+    if let Some(audio) = &info.audio {
+        headers.push((0x08, build_aac_sequence_header_with_config(&audio.asc)));
+    }
+    headers
+}
+
+/// Message type and payload for a recorded frame (parameter sets are in-band)
+pub fn recorded_frame_to_rtmp(frame: &MediaFrame) -> Option<(u8, Vec<u8>)> {
+    let (msg_type, data) = match frame.codec {
+        CodecType::H264 | CodecType::H265 => (0x09, frame_to_rtmp_video(frame)),
+        CodecType::AAC => (0x08, frame_to_rtmp_audio(frame)),
+        _ => return None,
+    };
+    (data.len() > 5).then_some((msg_type, data))
+}
+
+/// Frames sampled from the hub ring when estimating live data rates.
+const METADATA_RATE_WINDOW_FRAMES: u64 = 300;
+
+/// onMetaData properties for live play.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamMetadata {
+    /// FLV video codec id (7 = AVC, 12 = HEVC; the FourCC value for AV1/VP9)
+    pub video_codec_id: Option<u32>,
+    pub width: u32,
+    pub height: u32,
+    pub framerate: Option<f64>,
+    pub audio: Option<AudioCodecConfig>,
+    pub video_kbps: Option<f64>,
+    pub audio_kbps: Option<f64>,
+}
+
+impl StreamMetadata {
+    /// Codec ids, picture size and SPS frame rate, plus the AAC config.
+    pub fn from_stream(stream: &Stream) -> Self {
+        let mut meta = Self {
+            audio: AudioCodecConfig::from_stream(stream),
+            ..Self::default()
+        };
+        if !stream.tracks.iter().any(|t| t.codec.is_video()) {
+            return meta;
+        }
+        let sps = stream.sps.as_deref().unwrap_or_default();
+        match stream.video_codec() {
+            CodecType::H265 => {
+                meta.video_codec_id = Some(0x0C);
+                (meta.width, meta.height) = h265::sps_dimensions(sps).unwrap_or_default();
+            }
+            codec @ (CodecType::AV1 | CodecType::VP9) => {
+                meta.video_codec_id = video_fourcc(codec).map(|fourcc| u32::from_be_bytes(*fourcc));
+            }
+            _ => {
+                meta.video_codec_id = Some(0x07);
+                if let Some(info) = sps_info(sps) {
+                    (meta.width, meta.height) = (info.width, info.height);
+                    meta.framerate = info.framerate;
+                }
+            }
+        }
+        meta
+    }
+
+    /// Codec ids, picture size and AAC config found in a recording.
+    pub fn from_recording(info: &RecordingMediaInfo) -> Self {
+        let video_codec_id = info.video.as_ref().map(|video| match video {
+            VideoCodecConfig::Avc { .. } => 0x07,
+            VideoCodecConfig::Hevc { .. } => 0x0C,
+        });
+        let (width, height) = info
+            .video
+            .as_ref()
+            .map(VideoCodecConfig::dimensions)
+            .unwrap_or_default();
+        Self {
+            video_codec_id,
+            width,
+            height,
+            audio: info.audio.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Data rates (and frame rate when the SPS has none) from the hub's recent frames.
+    pub fn with_hub_rates(mut self, hub: &StreamHub) -> Self {
+        let latest = hub.latest_seq();
+        let frames = hub.frames_from(latest.saturating_sub(METADATA_RATE_WINDOW_FRAMES), latest);
+        let rate = |video: bool| {
+            let frames: Vec<_> = frames
+                .iter()
+                .filter(|f| f.codec.is_video() == video && f.codec != CodecType::Unknown)
+                .collect();
+            let (first, last) = (frames.first()?, frames.last()?);
+            let span_ms = media_frame_timestamp_delta_ms(first, last);
+            if span_ms == 0 {
+                return None;
+            }
+            let bytes: usize = frames[1..].iter().map(|f| f.data.len()).sum();
+            let kbps = bytes as f64 * 8.0 / span_ms as f64;
+            let fps = (frames.len() - 1) as f64 * 1000.0 / span_ms as f64;
+            Some((kbps, fps))
+        };
+        if let Some((kbps, fps)) = rate(true) {
+            self.video_kbps = Some(kbps);
+            self.framerate = self.framerate.or(Some(fps));
+        }
+        if self.audio.is_some() {
+            self.audio_kbps = rate(false).map(|(kbps, _)| kbps);
+        }
+        self
+    }
+}
+
+/// Build the onMetaData script data (AMF0 name + ECMA array)
+pub fn build_metadata(meta: &StreamMetadata, stream_name: &str) -> Vec<u8> {
     let mut values = vec![Amf0Value::String("onMetaData".to_string())];
 
-    let mut meta = HashMap::new();
-    meta.insert("width".to_string(), Amf0Value::Number(width as f64));
-    meta.insert("height".to_string(), Amf0Value::Number(height as f64));
-    meta.insert(
+    let mut props = HashMap::new();
+    let mut number = |key: &str, value: f64| {
+        props.insert(key.to_string(), Amf0Value::Number(value));
+    };
+    if let Some(codec_id) = meta.video_codec_id {
+        number("videocodecid", codec_id as f64);
+        number("width", meta.width as f64);
+        number("height", meta.height as f64);
+        if let Some(framerate) = meta.framerate {
+            number("framerate", (framerate * 100.0).round() / 100.0);
+        }
+        if let Some(kbps) = meta.video_kbps {
+            number("videodatarate", kbps.round());
+        }
+    }
+    if let Some(audio) = &meta.audio {
+        number("audiocodecid", 10.0);
+        number("audiosamplerate", audio.sample_rate as f64);
+        number("audiosamplesize", 16.0);
+        number("audiochannels", audio.channels as f64);
+        if let Some(kbps) = meta.audio_kbps {
+            number("audiodatarate", kbps.round());
+        }
+        props.insert("stereo".to_string(), Amf0Value::Boolean(audio.channels > 1));
+    }
+    props.insert(
         "streamName".to_string(),
         Amf0Value::String(stream_name.to_string()),
     );
-    meta.insert(
+    props.insert(
         "encoder".to_string(),
         Amf0Value::String("Rust-Media-Server".to_string()),
     );
-    values.push(Amf0Value::EcmaArray(meta));
+    values.push(Amf0Value::EcmaArray(props));
 
     amf0::encode(&values)
 }
@@ -325,23 +569,56 @@ pub fn build_metadata(width: u32, height: u32, stream_name: &str) -> Vec<u8> {
 /// Convert a MediaFrame to RTMP video data
 /// frame.data is in Annex B format: [00 00 00 01][NALU][00 00 00 01][NALU]...
 /// Output is in AVCC format: [frame_type|codec][avc_packet_type][composition_time][length(4B)][NALU]...
+/// AV1/VP9 frames have no legacy codec id and always use Enhanced RTMP tags.
 pub fn frame_to_rtmp_video(frame: &MediaFrame) -> Vec<u8> {
+    if matches!(frame.codec, CodecType::AV1 | CodecType::VP9) {
+        return frame_to_enhanced_rtmp_video(frame);
+    }
     let mut data = Vec::new();
 
-    // Frame type + codec
-    let frame_type = if frame.is_keyframe || is_keyframe_annex_b(&frame.data) {
+    // Frame type + codec (AVC = 7, HEVC = 12)
+    let (is_keyframe, codec_id) = match frame.codec {
+        CodecType::H265 => (h265::contains_irap_nalu(&frame.data), 0x0C),
+        _ => (is_keyframe_annex_b(&frame.data), 0x07),
+    };
+    let frame_type = if frame.is_keyframe || is_keyframe {
         0x10
     } else {
         0x20
     };
-    data.push(frame_type | 0x07); // AVC
-                                  // AVC packet type: NALU = 0x01
+    data.push(frame_type | codec_id);
+    // AVC packet type: NALU = 0x01
     data.push(0x01);
     // Composition time offset (3 bytes)
     data.extend_from_slice(&[0x00, 0x00, 0x00]);
 
-    // Convert Annex B to AVCC: find start codes and write length-prefixed NALUs
-    let annex_b = &frame.data;
+    annex_b_to_length_prefixed(&frame.data, &mut data);
+    data
+}
+
+/// Convert a MediaFrame to an Enhanced RTMP video tag: HEVC as CodedFramesX with
+/// length-prefixed NALUs, AV1 (OBUs) / VP9 frames as CodedFrames. AVC stays legacy.
+pub fn frame_to_enhanced_rtmp_video(frame: &MediaFrame) -> Vec<u8> {
+    match frame.codec {
+        CodecType::H265 => {
+            let keyframe = frame.is_keyframe || h265::contains_irap_nalu(&frame.data);
+            let mut data =
+                enhanced_video_header(CodecType::H265, keyframe, EX_PACKET_CODED_FRAMES_X);
+            annex_b_to_length_prefixed(&frame.data, &mut data);
+            data
+        }
+        CodecType::AV1 | CodecType::VP9 => {
+            let mut data =
+                enhanced_video_header(frame.codec, frame.is_keyframe, EX_PACKET_CODED_FRAMES);
+            data.extend_from_slice(&frame.data);
+            data
+        }
+        _ => frame_to_rtmp_video(frame),
+    }
+}
+
+/// Convert Annex B to AVCC: find start codes and write length-prefixed NALUs
+fn annex_b_to_length_prefixed(annex_b: &[u8], data: &mut Vec<u8>) {
     let mut i = 0;
     while i < annex_b.len() {
         // Find next start code (0x000001 or 0x00000001)
@@ -375,16 +652,14 @@ pub fn frame_to_rtmp_video(frame: &MediaFrame) -> Vec<u8> {
         data.extend_from_slice(&annex_b[start..end]);
         i = end;
     }
-
-    data
 }
 
-/// Convert a MediaFrame to RTMP audio data
+/// Convert a MediaFrame to RTMP audio data (raw AAC; an ADTS header is dropped)
 pub fn frame_to_rtmp_audio(frame: &MediaFrame) -> Vec<u8> {
     let mut data = Vec::new();
     data.push(0xAF); // AAC 44kHz 16bit stereo
     data.push(0x01); // AAC raw
-    data.extend_from_slice(&frame.data);
+    data.extend_from_slice(aac_payload_without_adts(&frame.data));
     data
 }
 
@@ -6,7 +6,7 @@ use tokio::net::UdpSocket;
 use tracing::{debug, info, warn};
 
 use super::messages::RtspRequest;
-use crate::core::{CodecType, Track};
+use crate::core::{CodecType, Track, AUDIO_CONFIG_PARAM};
 
 /// Format RTSP message for human-readable logging (CRLF → LF, trim trailing blank line).
 pub fn format_rtsp_message(message: &str) -> String {
@@ -269,6 +269,7 @@ impl RtspCommon {
 
                     let mut codec = CodecType::H264;
                     let mut clock_rate = 90000;
+                    let mut extra_params = std::collections::HashMap::new();
 
                     while i < lines.len() {
                         let next_line = lines[i];
@@ -291,6 +292,11 @@ impl RtspCommon {
                                 }
                             }
                             i += 1;
+                        } else if next_line.starts_with("a=fmtp:") && codec == CodecType::AAC {
+                            if let Some(config) = Self::aac_fmtp_config(next_line) {
+                                extra_params.insert(AUDIO_CONFIG_PARAM.to_string(), config);
+                            }
+                            i += 1;
                         } else if next_line.starts_with("m=") || next_line.starts_with("s=") {
                             break;
                         } else {
@@ -304,7 +310,7 @@ impl RtspCommon {
                         codec,
                         payload_type,
                         clock_rate,
-                        extra_params: std::collections::HashMap::new(),
+                        extra_params,
                     });
                 }
             }
@@ -408,6 +414,7 @@ impl RtspCommon {
                     let payload_type: u8 = parts[3].parse().unwrap_or(96);
                     let mut codec = CodecType::H264;
                     let mut clock_rate = 90000;
+                    let mut extra_params = std::collections::HashMap::new();
 
                     i += 1;
                     while i < lines.len() {
@@ -431,6 +438,11 @@ impl RtspCommon {
                                 }
                             }
                             i += 1;
+                        } else if next_line.starts_with("a=fmtp:") && codec == CodecType::AAC {
+                            if let Some(config) = Self::aac_fmtp_config(next_line) {
+                                extra_params.insert(AUDIO_CONFIG_PARAM.to_string(), config);
+                            }
+                            i += 1;
                         } else if next_line.starts_with("a=fmtp:") && sps.is_none() {
                             // Extract SPS/PPS from fmtp line for H264
                             debug!(
@@ -488,7 +500,7 @@ impl RtspCommon {
                         codec,
                         payload_type,
                         clock_rate,
-                        extra_params: std::collections::HashMap::new(),
+                        extra_params,
                     });
                     continue;
                 }
@@ -530,8 +542,25 @@ impl RtspCommon {
 
             if track.codec == CodecType::H264 {
                 sdp.push_str("a=fmtp:96 packetization-mode=1;profile-level-id=42E01F;sprop-parameter-sets=Z0LAHukBQBbsAAADAAQAAAMABAAAAwHNgYI=\r\n");
+            } else if track.codec == CodecType::H265 {
+                let sprop = ["sprop-vps", "sprop-sps", "sprop-pps"]
+                    .iter()
+                    .filter_map(|k| track.extra_params.get(*k).map(|v| format!("{}={}", k, v)))
+                    .collect::<Vec<_>>();
+                if sprop.len() == 3 {
+                    sdp.push_str(&format!(
+                        "a=fmtp:{} {}\r\n",
+                        track.payload_type,
+                        sprop.join(";")
+                    ));
+                }
             } else if track.codec == CodecType::AAC {
-                sdp.push_str("a=fmtp:97 profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;\r\n");
+                let config = track
+                    .extra_params
+                    .get(AUDIO_CONFIG_PARAM)
+                    .map(|c| format!("config={};", c))
+                    .unwrap_or_default();
+                sdp.push_str(&format!("a=fmtp:97 profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;{}\r\n", config));
             }
         }
 
@@ -1026,6 +1055,130 @@ impl RtspCommon {
         }
         packets
     }
+
+    /// Packetize one H.265 NAL into one or more RTP packets (single NAL or FU, RFC 7798).
+    pub fn packetize_h265_nal_for_rtp(
+        nal: &[u8],
+        payload_type: u8,
+        seq: &mut u16,
+        ts: u32,
+        ssrc: u32,
+        marker: bool,
+    ) -> Vec<Vec<u8>> {
+        if nal.len() < 3 {
+            return Vec::new();
+        }
+
+        if nal.len() <= Self::UDP_RTP_MAX_PAYLOAD {
+            let pkt = Self::build_rtp_packet(payload_type, *seq, ts, ssrc, marker, nal);
+            *seq = seq.wrapping_add(1);
+            return vec![pkt];
+        }
+
+        let nal_type = (nal[0] >> 1) & 0x3F;
+        let payload_header = [(nal[0] & 0x81) | (49 << 1), nal[1]];
+        let data = &nal[2..];
+        let chunk_max = Self::UDP_RTP_MAX_PAYLOAD - 3;
+        let mut packets = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let chunk_size = (data.len() - offset).min(chunk_max);
+            let is_start = offset == 0;
+            let is_end = offset + chunk_size >= data.len();
+            let fu_header =
+                (if is_start { 0x80 } else { 0 }) | (if is_end { 0x40 } else { 0 }) | nal_type;
+            let mut payload = vec![payload_header[0], payload_header[1], fu_header];
+            payload.extend_from_slice(&data[offset..offset + chunk_size]);
+            packets.push(Self::build_rtp_packet(
+                payload_type,
+                *seq,
+                ts,
+                ssrc,
+                marker && is_end,
+                &payload,
+            ));
+            *seq = seq.wrapping_add(1);
+            offset += chunk_size;
+        }
+
+        packets
+    }
+
+    /// Packetize an Annex-B H.265 access unit for RTP egress.
+    pub fn packetize_h265_access_unit_for_rtp(
+        annex_b: &[u8],
+        payload_type: u8,
+        seq: &mut u16,
+        ts: u32,
+        ssrc: u32,
+    ) -> Vec<Vec<u8>> {
+        use crate::server::webrtc::h264_util::iter_annex_b_nal_ranges;
+        let ranges = iter_annex_b_nal_ranges(annex_b);
+        let mut packets = Vec::new();
+        for (i, (start, end)) in ranges.iter().enumerate() {
+            let marker = i + 1 == ranges.len();
+            packets.extend(Self::packetize_h265_nal_for_rtp(
+                &annex_b[*start..*end],
+                payload_type,
+                seq,
+                ts,
+                ssrc,
+                marker,
+            ));
+        }
+        packets
+    }
+
+    /// AudioSpecificConfig hex from an AAC fmtp line (RFC 3640 `config=`).
+    pub fn aac_fmtp_config(line: &str) -> Option<String> {
+        let params = line.split_once(' ').map(|(_, p)| p)?;
+        params
+            .split(';')
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(key, _)| key.eq_ignore_ascii_case("config"))
+            .map(|(_, value)| value.trim().to_ascii_lowercase())
+            .filter(|hex| hex.len() >= 4 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+    }
+
+    /// Parse `sprop-vps` / `sprop-sps` / `sprop-pps` from an H265 fmtp line (RFC 7798).
+    pub fn parse_sdp_hevc_parameter_sets(sdp: &str) -> Option<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        use base64::Engine;
+        let mut vps = None;
+        let mut sps = None;
+        let mut pps = None;
+        for line in sdp.lines().filter(|l| l.starts_with("a=fmtp:")) {
+            let params = line.split_once(' ').map(|(_, p)| p).unwrap_or("");
+            for param in params.split(';').map(str::trim) {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                // Multiple sets are comma-separated; the first one is enough for decoders.
+                let first = value.split(',').next().unwrap_or("");
+                let decoded = base64::engine::general_purpose::STANDARD.decode(first).ok();
+                match key {
+                    "sprop-vps" => vps = decoded,
+                    "sprop-sps" => sps = decoded,
+                    "sprop-pps" => pps = decoded,
+                    _ => {}
+                }
+            }
+        }
+        Some((vps?, sps?, pps?))
+    }
+
+    /// H265 `a=fmtp` line carrying VPS/SPS/PPS (RFC 7798).
+    pub fn hevc_fmtp_line(payload_type: u8, vps: &[u8], sps: &[u8], pps: &[u8]) -> String {
+        use base64::Engine;
+        let b64 = |d: &[u8]| base64::engine::general_purpose::STANDARD.encode(d);
+        format!(
+            "a=fmtp:{} sprop-vps={};sprop-sps={};sprop-pps={}\r\n",
+            payload_type,
+            b64(vps),
+            b64(sps),
+            b64(pps)
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1203,4 +1356,21 @@ mod aac_tests {
         assert_eq!(stripped.len(), au_size);
         assert!(stripped.iter().all(|&b| b == 0xAB));
     }
+
+    #[test]
+    fn sdp_aac_track_keeps_fmtp_config() {
+        let sdp = "v=0\r\nm=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n\
+                   a=fmtp:96 packetization-mode=1\r\nm=audio 0 RTP/AVP 97\r\n\
+                   a=rtpmap:97 MPEG4-GENERIC/48000/2\r\n\
+                   a=fmtp:97 streamtype=5;profile-level-id=15;mode=AAC-hbr;config=1190;sizeLength=13\r\n";
+        for tracks in [
+            RtspCommon::parse_sdp_tracks(sdp),
+            RtspCommon::parse_sdp_with_sps_pps(sdp).0,
+        ] {
+            assert_eq!(tracks.len(), 2);
+            assert_eq!(tracks[0].audio_specific_config(), None);
+            assert_eq!(tracks[1].clock_rate, 48_000);
+            assert_eq!(tracks[1].audio_specific_config(), Some(vec![0x11, 0x90]));
+        }
+    }
 }
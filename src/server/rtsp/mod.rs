@@ -15,6 +15,8 @@ pub mod messages;
 pub mod play_egress;
 mod puller;
 pub mod pusher;
+mod record_play;
+pub mod rtp_h265;
 pub mod server_session;
 pub mod session;
 
@@ -35,6 +37,7 @@ pub struct RtspServer {
     stream_manager: Arc<StreamManager>,
     port: u16,
     hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+    recorder: Option<Arc<crate::process::record::RecorderManager>>,
 }
 
 impl RtspServer {
@@ -42,11 +45,13 @@ impl RtspServer {
         stream_manager: Arc<StreamManager>,
         port: u16,
         hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+        recorder: Option<Arc<crate::process::record::RecorderManager>>,
     ) -> Self {
         Self {
             stream_manager,
             port,
             hls_server,
+            recorder,
         }
     }
 
@@ -63,8 +68,9 @@ impl RtspServer {
                     info!("[RTSP] New connection from {}", peer_addr);
                     let manager = self.stream_manager.clone();
                     let hls = self.hls_server.clone();
+                    let recorder = self.recorder.clone();
                     tokio::spawn(async move {
-                        let session = RtspServerSession::new(socket, manager, hls);
+                        let session = RtspServerSession::new(socket, manager, hls, recorder);
                         session.start().await;
                     });
                 }
@@ -348,6 +354,13 @@ impl RtspServer {
                         peer_addr, stream_id
                     );
                 }
+                if let Some((vps, sps, pps)) = RtspCommon::parse_sdp_hevc_parameter_sets(body) {
+                    manager.set_stream_hevc_config(&stream_id, vps, sps, pps);
+                    info!(
+                        "[RTSP] [{}] ANNOUNCE VPS/SPS/PPS saved to stream {}",
+                        peer_addr, stream_id
+                    );
+                }
 
                 info!(
                     "[RTSP] [{}] ANNOUNCE added {} tracks to stream",
@@ -554,11 +567,11 @@ impl RtspServer {
             stream_id
         );
 
-        // Get SPS/PPS from stream if available
-        let (sps, pps) = if let Some(stream) = manager.get_stream(&stream_id.to_string()) {
-            (stream.sps.clone(), stream.pps.clone())
+        // Get VPS/SPS/PPS from stream if available
+        let (vps, sps, pps) = if let Some(stream) = manager.get_stream(&stream_id.to_string()) {
+            (stream.vps.clone(), stream.sps.clone(), stream.pps.clone())
         } else {
-            (None, None)
+            (None, None, None)
         };
 
         let h264_fmtp = |payload_type: u8| {
@@ -614,6 +627,15 @@ impl RtspServer {
 
                 if track.codec == CodecType::H264 {
                     sdp.push_str(&h264_fmtp(track.payload_type));
+                } else if track.codec == CodecType::H265 {
+                    if let (Some(vps), Some(sps), Some(pps)) = (&vps, &sps, &pps) {
+                        sdp.push_str(&RtspCommon::hevc_fmtp_line(
+                            track.payload_type,
+                            vps,
+                            sps,
+                            pps,
+                        ));
+                    }
                 } else if track.codec == CodecType::AAC {
                     sdp.push_str(&format!("a=fmtp:{} profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;\r\n", track.payload_type));
                 }
@@ -741,6 +763,24 @@ mod tests {
         assert!(!sdp.contains("a=fmtp:96 "));
     }
 
+    #[test]
+    fn build_sdp_advertises_hevc_parameter_sets() {
+        let manager = StreamManager::new();
+        manager.create_stream("cam", StreamSourceMode::Push, StreamProtocol::RTSP, None);
+        manager.set_stream_tracks("cam", vec![Track::new(0, CodecType::H265, 98, 90_000)]);
+        manager.set_stream_hevc_config("cam", vec![0x40, 0x01], vec![0x42, 0x01], vec![0x44, 0x01]);
+
+        let sdp = RtspServer::build_sdp("cam", &manager);
+
+        assert!(sdp.contains("a=rtpmap:98 H265/90000"));
+        assert!(sdp.contains("a=fmtp:98 sprop-vps=QAE=;sprop-sps=QgE=;sprop-pps=RAE="));
+        let (vps, sps, pps) = RtspCommon::parse_sdp_hevc_parameter_sets(&sdp).unwrap();
+        assert_eq!(
+            (vps, sps, pps),
+            (vec![0x40, 0x01], vec![0x42, 0x01], vec![0x44, 0x01])
+        );
+    }
+
     #[test]
     fn build_rtp_info_only_lists_existing_tracks() {
         let manager = StreamManager::new();
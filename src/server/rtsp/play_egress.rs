@@ -46,8 +46,8 @@ impl PlayRtpTimeline {
     }
 }
 
-fn prepend_h264_config(manager: &StreamManager, stream_id: &str, frame: &MediaFrame) -> Vec<u8> {
-    crate::core::live_play::prepend_h264_config(manager, stream_id, frame)
+fn prepend_video_config(manager: &StreamManager, stream_id: &str, frame: &MediaFrame) -> Vec<u8> {
+    crate::core::live_play::prepend_video_config(manager, stream_id, frame)
 }
 
 pub fn is_idr(frame: &MediaFrame) -> bool {
@@ -78,15 +78,35 @@ pub fn egress_rtp_packets(
     let ts = timeline.map_wallclock();
 
     match frame.codec {
+        CodecType::H264 | CodecType::H265 => {
+            let annex = prepend_video_config(manager, stream_id, frame);
+            packetize_frame(frame.codec, &annex, payload_type, ts, seq, ssrc)
+        }
+        _ => packetize_frame(frame.codec, &frame.data, payload_type, ts, seq, ssrc),
+    }
+}
+
+/// Packetize one access unit (Annex B video or ADTS/raw AAC) into RTP packets.
+pub fn packetize_frame(
+    codec: CodecType,
+    data: &[u8],
+    payload_type: u8,
+    ts: u32,
+    seq: &mut u16,
+    ssrc: u32,
+) -> Vec<Vec<u8>> {
+    match codec {
         CodecType::H264 => {
-            let annex = prepend_h264_config(manager, stream_id, frame);
-            RtspCommon::packetize_h264_access_unit_for_rtp(&annex, payload_type, seq, ts, ssrc)
+            RtspCommon::packetize_h264_access_unit_for_rtp(data, payload_type, seq, ts, ssrc)
+        }
+        CodecType::H265 => {
+            RtspCommon::packetize_h265_access_unit_for_rtp(data, payload_type, seq, ts, ssrc)
         }
         CodecType::AAC => {
-            if frame.data.len() < 4 {
+            if data.len() < 4 {
                 return Vec::new();
             }
-            let payload = wrap_mpeg4_generic_aac_hbr(&frame.data);
+            let payload = wrap_mpeg4_generic_aac_hbr(data);
             if payload.is_empty() {
                 return Vec::new();
             }
@@ -98,6 +118,17 @@ pub fn egress_rtp_packets(
     }
 }
 
+/// Payload type used when the stream does not declare one.
+pub fn default_payload_type(codec: CodecType) -> u8 {
+    match codec {
+        CodecType::H264 => 96,
+        CodecType::AAC => 97,
+        CodecType::Opus => 109,
+        CodecType::H265 => 98,
+        _ => 96,
+    }
+}
+
 fn rtp_payload_type_for_codec(manager: &StreamManager, stream_id: &str, codec: CodecType) -> u8 {
     manager
         .get_stream(&stream_id.to_string())
@@ -108,13 +139,7 @@ fn rtp_payload_type_for_codec(manager: &StreamManager, stream_id: &str, codec: C
                 .find(|track| track.codec == codec)
                 .map(|track| track.payload_type)
         })
-        .unwrap_or(match codec {
-            CodecType::H264 => 96,
-            CodecType::AAC => 97,
-            CodecType::Opus => 109,
-            CodecType::H265 => 98,
-            _ => 96,
-        })
+        .unwrap_or_else(|| default_payload_type(codec))
 }
 
 #[cfg(test)]
@@ -1,19 +1,24 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bytes::BytesMut;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
 use tokio::time::Duration;
 use tracing::{debug, error, info, warn};
 
 use super::client_session::RtspClientSession;
 use super::common::RtspCommon;
+use super::rtp_h265::VideoRtpIngest;
 use super::RtspRequest;
 use crate::core::{
     CodecType, MediaFrame, StreamManager, StreamProtocol, StreamSourceMode, Track,
     AAC_DEFAULT_CLOCK_RATE,
 };
-use crate::server::webrtc::H264RtpIngest;
+
+/// A UDP pull with no RTP on any track for this long is treated as dead.
+const RTP_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct RtspPuller {
     stream_manager: Arc<StreamManager>,
@@ -95,6 +100,8 @@ impl RtspPuller {
             StreamProtocol::RTSP,
             Some(remote_url.to_string()),
         );
+        self.stream_manager
+            .set_stream_tracks(local_stream_id, tracks_to_create.clone());
         let _ = self.stream_manager.set_unpublished(local_stream_id);
         self.stream_manager.ensure_stream_broadcast(local_stream_id);
 
@@ -111,6 +118,17 @@ impl RtspPuller {
             self.stream_manager
                 .set_stream_sps_pps(local_stream_id, sps, pps);
         }
+        if let Some((vps, sps, pps)) = RtspCommon::parse_sdp_hevc_parameter_sets(sdp) {
+            info!(
+                "[RTSP Puller] Primed VPS/SPS/PPS from SDP stream='{}' vps={} sps={} pps={}",
+                local_stream_id,
+                vps.len(),
+                sps.len(),
+                pps.len()
+            );
+            self.stream_manager
+                .set_stream_hevc_config(local_stream_id, vps, sps, pps);
+        }
 
         info!(
             "[RTSP Puller] [Step 3/4] Setting up {} tracks...",
@@ -157,21 +175,22 @@ impl RtspPuller {
             if use_udp { "UDP" } else { "TCP" }
         );
 
-        tokio::spawn(async move {
-            if use_udp {
-                tokio::select! {
-                    _ = Self::udp_receive_loop(udp_sockets, manager_clone.clone(), stream_id_clone.clone(), receive_tracks.clone()) => (),
-                    _ = Self::send_keepalive(writer, session_clone, remote_url_clone) => (),
-                }
-            } else {
-                tokio::select! {
-                    _ = Self::rtp_receive_loop(reader, manager_clone.clone(), stream_id_clone.clone(), receive_tracks.clone()) => (),
-                    _ = Self::send_keepalive(writer, session_clone, remote_url_clone) => (),
-                }
+        // Run the session inline so the caller (and its supervisor) sees when it ends.
+        let result = if use_udp {
+            tokio::select! {
+                r = Self::udp_receive_loop(udp_sockets, manager_clone.clone(), stream_id_clone.clone(), receive_tracks.clone()) => r,
+                _ = Self::send_keepalive(writer, session_clone, remote_url_clone) => Ok(()),
             }
-        });
+        } else {
+            tokio::select! {
+                _ = Self::rtp_receive_loop(reader, manager_clone.clone(), stream_id_clone.clone(), receive_tracks.clone()) => Ok(()),
+                _ = Self::send_keepalive(writer, session_clone, remote_url_clone) => Ok(()),
+            }
+        };
 
-        Ok(())
+        let _ = self.stream_manager.set_stopped(local_stream_id);
+        info!("[RTSP Puller] Pull ended for stream {}", local_stream_id);
+        result
     }
 
     async fn rtp_receive_loop(
@@ -184,7 +203,8 @@ impl RtspPuller {
         let mut frame_count: u64 = 0;
         let mut bytes_received: u64 = 0;
         let mut last_log_time = std::time::Instant::now();
-        let mut h264_ingest = H264RtpIngest::new(manager.clone(), stream_id.clone(), "RTSP-Pull");
+        let mut video_ingest =
+            VideoRtpIngest::for_tracks(&tracks, manager.clone(), stream_id.clone(), "RTSP-Pull");
 
         info!(
             "[RTSP Puller] [RTP Loop] Starting RTP receive loop for stream {}",
@@ -218,7 +238,7 @@ impl RtspPuller {
                 let track_id = channel / 2;
 
                 if track_id == 0 && rtp_payload.len() >= 12 {
-                    if h264_ingest.ingest_rtp_packet(&rtp_payload) {
+                    if video_ingest.ingest_rtp_packet(&rtp_payload) {
                         frame_count += 1;
                     }
                 } else if rtp_payload.len() >= 12 {
@@ -278,7 +298,7 @@ impl RtspPuller {
             }
         }
 
-        h264_ingest.flush_remaining();
+        video_ingest.flush_remaining();
         let _ = manager.set_unpublished(&stream_id);
 
         info!(
@@ -287,28 +307,35 @@ impl RtspPuller {
         );
     }
 
+    /// One receiver task per track, owned by this future: dropping it (pull
+    /// stopped or restarted) aborts them. Ends with an error when a socket
+    /// fails or no RTP arrives for [`RTP_INACTIVITY_TIMEOUT`].
     async fn udp_receive_loop(
         tracks: Vec<(usize, Arc<UdpSocket>)>,
         manager: Arc<StreamManager>,
         stream_id: String,
         sdp_tracks: Vec<Track>,
-    ) {
+    ) -> Result<()> {
         info!(
             "[RTSP Puller] [UDP Loop] Starting for stream {} ({} tracks)",
             stream_id,
             tracks.len()
         );
 
+        let packets_received = Arc::new(AtomicU64::new(0));
+        let mut receivers = JoinSet::new();
         for (track_id, socket) in tracks {
             let manager = Arc::clone(&manager);
             let sid = stream_id.clone();
             let sdp_tracks = sdp_tracks.clone();
+            let packets_received = Arc::clone(&packets_received);
 
-            tokio::spawn(async move {
+            receivers.spawn(async move {
                 let mut buffer = vec![0u8; 65535];
                 let mut frame_count: u64 = 0;
-                let mut h264_ingest = if track_id == 0 {
-                    Some(H264RtpIngest::new(
+                let mut video_ingest = if track_id == 0 {
+                    Some(VideoRtpIngest::for_tracks(
+                        &sdp_tracks,
                         manager.clone(),
                         sid.clone(),
                         "RTSP-Pull-UDP",
@@ -323,9 +350,10 @@ impl RtspPuller {
                             if len < 12 || RtspCommon::is_rtcp_packet(&buffer[..len]) {
                                 continue;
                             }
+                            packets_received.fetch_add(1, Ordering::Relaxed);
 
                             if track_id == 0 {
-                                if let Some(ingest) = &mut h264_ingest {
+                                if let Some(ingest) = &mut video_ingest {
                                     if ingest.ingest_rtp_packet(&buffer[..len]) {
                                         frame_count += 1;
                                     }
@@ -370,18 +398,41 @@ impl RtspPuller {
                     }
                 }
 
-                if let Some(mut ingest) = h264_ingest {
+                if let Some(mut ingest) = video_ingest {
                     ingest.flush_remaining();
                 }
                 info!(
                     "[RTSP Puller] [UDP Loop] track={} ended, frames={}",
                     track_id, frame_count
                 );
+                Err::<(), _>(anyhow!("UDP receive failed on track {}", track_id))
             });
         }
 
-        // Keep task alive while UDP receivers run.
-        std::future::pending::<()>().await;
+        let mut last_count = 0;
+        loop {
+            tokio::select! {
+                joined = receivers.join_next() => match joined {
+                    Some(Ok(result)) => return result,
+                    Some(Err(e)) => return Err(anyhow!("UDP receiver task failed: {}", e)),
+                    None => return Ok(()),
+                },
+                _ = tokio::time::sleep(RTP_INACTIVITY_TIMEOUT) => {
+                    let count = packets_received.load(Ordering::Relaxed);
+                    if count == last_count {
+                        warn!(
+                            "[RTSP Puller] [UDP Loop] No RTP for {:?} on stream {}",
+                            RTP_INACTIVITY_TIMEOUT, stream_id
+                        );
+                        return Err(anyhow!(
+                            "No RTP received for {}s",
+                            RTP_INACTIVITY_TIMEOUT.as_secs()
+                        ));
+                    }
+                    last_count = count;
+                }
+            }
+        }
     }
 
     async fn send_keepalive(
@@ -6,6 +6,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
 use tracing::{debug, error, info, warn};
 use url;
@@ -13,8 +14,8 @@ use url;
 use super::client_session::RtspClientSession;
 use super::common::RtspCommon;
 use crate::core::{
-    CodecType, MediaFrame, PusherId, PusherStatus, StreamManager, StreamProtocol, StreamPusher,
-    Track,
+    CodecType, MediaFrame, PusherId, PusherStatus, Stream, StreamManager, StreamProtocol,
+    StreamPusher, Track,
 };
 
 pub struct RtspPusher {
@@ -28,6 +29,7 @@ pub struct RtspPusher {
     // H264 codec parameters cache
     sps_cache: Arc<parking_lot::RwLock<Option<Vec<u8>>>>,
     pps_cache: Arc<parking_lot::RwLock<Option<Vec<u8>>>>,
+    session: Option<JoinHandle<()>>,
 }
 
 impl RtspPusher {
@@ -43,6 +45,7 @@ impl RtspPusher {
             paused: Arc::new(RwLock::new(false)),
             sps_cache: Arc::new(parking_lot::RwLock::new(None)),
             pps_cache: Arc::new(parking_lot::RwLock::new(None)),
+            session: None,
         }
     }
 
@@ -55,6 +58,32 @@ impl RtspPusher {
         );
     }
 
+    /// Attach `sprop-vps/sps/pps` to H265 tracks so ANNOUNCE carries the stream's config.
+    fn tracks_with_hevc_sprop(&self, tracks: &[Track]) -> Vec<Track> {
+        use base64::Engine;
+        let stream = self.stream_manager.get_stream(&self.stream_id);
+        tracks
+            .iter()
+            .cloned()
+            .map(|track| {
+                let Some(Stream {
+                    vps: Some(vps),
+                    sps: Some(sps),
+                    pps: Some(pps),
+                    ..
+                }) = stream.as_ref().filter(|_| track.codec == CodecType::H265)
+                else {
+                    return track;
+                };
+                let b64 = |d: &[u8]| base64::engine::general_purpose::STANDARD.encode(d);
+                track
+                    .with_extra_params("sprop-vps", &b64(vps))
+                    .with_extra_params("sprop-sps", &b64(sps))
+                    .with_extra_params("sprop-pps", &b64(pps))
+            })
+            .collect()
+    }
+
     fn set_status(&self, status: PusherStatus) {
         let mut s = self.status.write();
         info!(
@@ -113,7 +142,8 @@ impl RtspPusher {
             return Err(anyhow::anyhow!("OPTIONS failed: {}", response));
         }
 
-        let sdp = RtspClientSession::build_sdp(tracks);
+        let sdp_tracks = self.tracks_with_hevc_sprop(tracks);
+        let sdp = RtspClientSession::build_sdp(&sdp_tracks);
         info!("[RTSP Pusher] [Step 3/5] Sending ANNOUNCE...");
         let response = session
             .send_announce(&mut writer, &mut reader, &sdp)
@@ -182,16 +212,24 @@ impl RtspPusher {
             if use_udp { "UDP" } else { "TCP" }
         );
 
-        tokio::spawn(async move {
+        self.session = Some(tokio::spawn(async move {
             tokio::select! {
                 _ = Self::rtp_send_loop(writer, manager_clone, stream_id_clone, tracks_clone, paused_clone, sps_cache_clone, pps_cache_clone, use_udp, udp_tracks) => (),
                 _ = Self::monitor_connection(reader) => (),
             }
-        });
+        }));
 
         Ok(())
     }
 
+    /// Wait for the RTP session spawned by `start()` to end.
+    pub async fn wait(&mut self) -> Result<()> {
+        if let Some(session) = self.session.as_mut() {
+            session.await?;
+        }
+        Ok(())
+    }
+
     async fn rtp_send_loop(
         mut writer: tokio::net::tcp::OwnedWriteHalf,
         manager: Arc<StreamManager>,
@@ -364,55 +402,66 @@ impl RtspPusher {
                     }
                 }
 
-                let seq = sequences[track.id as usize];
-
-                // Build RTP packet
-                let rtp_payload = if frame.codec == CodecType::H264 {
-                    // Add length prefix for proper RTP payload format
-                    let mut payload = Vec::with_capacity(4 + frame.data.len());
-                    payload.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
-                    payload.extend_from_slice(&frame.data);
-                    payload
+                let packets = if frame.codec == CodecType::H265 {
+                    let annex_b =
+                        crate::core::live_play::prepend_video_config(&manager, &stream_id, &frame);
+                    RtspCommon::packetize_h265_access_unit_for_rtp(
+                        &annex_b,
+                        track.payload_type,
+                        &mut sequences[track.id as usize],
+                        frame.timestamp as u32,
+                        0x12345678,
+                    )
                 } else {
-                    frame.data.to_vec()
+                    // Build RTP packet
+                    let rtp_payload = if frame.codec == CodecType::H264 {
+                        // Add length prefix for proper RTP payload format
+                        let mut payload = Vec::with_capacity(4 + frame.data.len());
+                        payload.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
+                        payload.extend_from_slice(&frame.data);
+                        payload
+                    } else {
+                        frame.data.to_vec()
+                    };
+
+                    let seq = sequences[track.id as usize];
+                    sequences[track.id as usize] = seq.wrapping_add(1);
+                    vec![RtspClientSession::build_rtp_packet(
+                        track.payload_type,
+                        seq,
+                        frame.timestamp as u32,
+                        0x12345678,
+                        frame.is_keyframe,
+                        &rtp_payload,
+                    )]
                 };
 
-                let rtp_packet = RtspClientSession::build_rtp_packet(
-                    track.payload_type,
-                    seq,
-                    frame.timestamp as u32,
-                    0x12345678,
-                    frame.is_keyframe,
-                    &rtp_payload,
-                );
-
-                if use_udp {
-                    bytes_sent += rtp_packet.len() as u64;
+                let mut send_failed = false;
+                for rtp_packet in &packets {
+                    if let Err(e) = send_rtp_packet(
+                        &mut writer,
+                        use_udp,
+                        &udp_tracks,
+                        track.id as usize,
+                        channel,
+                        rtp_packet,
+                    )
+                    .await
+                    {
+                        error!(
+                            "[RTSP Pusher] [RTP Loop] Frame #{:>6} - Failed to send RTP packet: {}",
+                            frame_count, e
+                        );
+                        send_failed = true;
+                        break;
+                    }
+                    // Interleaved TCP adds a 4-byte `$` header per packet.
+                    bytes_sent += (rtp_packet.len() + if use_udp { 0 } else { 4 }) as u64;
                 }
-
-                if let Err(e) = send_rtp_packet(
-                    &mut writer,
-                    use_udp,
-                    &udp_tracks,
-                    track.id as usize,
-                    channel,
-                    &rtp_packet,
-                )
-                .await
-                {
-                    error!(
-                        "[RTSP Pusher] [RTP Loop] Frame #{:>6} - Failed to send RTP packet: {}",
-                        frame_count, e
-                    );
+                if send_failed {
                     break;
                 }
 
-                if !use_udp {
-                    bytes_sent += (4 + rtp_packet.len()) as u64;
-                }
-
-                sequences[track.id as usize] = sequences[track.id as usize].wrapping_add(1);
-
                 let elapsed = last_log_time.elapsed();
                 if elapsed >= Duration::from_secs(10) {
                     let fps = frame_count as f64 / elapsed.as_secs_f64();
@@ -464,6 +513,9 @@ impl RtspPusher {
 
     pub fn stop(&mut self) {
         self.set_status(PusherStatus::Stopped);
+        if let Some(session) = self.session.take() {
+            session.abort();
+        }
     }
 
     pub fn is_running(&self) -> bool {
@@ -524,7 +576,15 @@ impl StreamPusher for RtspPusher {
             "[RTSP Pusher] Stopping pusher for stream {}",
             self.stream_id
         );
-        self.set_status(PusherStatus::Stopped);
+        Self::stop(self);
         Ok(())
     }
 }
+
+impl Drop for RtspPusher {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.abort();
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! RTSP playback of recordings: `rtsp://host/record/<stream>?start=...`.
+//! Range (`npt=` relative to the playback origin, `clock=` absolute UTC),
+//! Scale (keyframes only above 1.0) and PAUSE/PLAY resume.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+use super::common::RtspCommon;
+use super::play_egress::default_payload_type;
+use crate::core::{CodecType, AAC_DEFAULT_CLOCK_RATE, VIDEO_RTP_CLOCK_RATE};
+use crate::process::record::RecordingMediaInfo;
+use crate::server::hls::fmp4_muxer::VideoCodecConfig;
+
+/// First path component that selects recorded playback.
+pub const RECORD_PATH_PREFIX: &str = "record";
+pub const VIDEO_TRACK_ID: u8 = 0;
+pub const AUDIO_TRACK_ID: u8 = 1;
+
+/// `(stream_id, start_ms)` for a `/record/<stream>[?start=<ms|clock>]` URL.
+/// Tolerates a control suffix appended after the query (`...?start=1/trackID=0`).
+pub fn parse_record_url(url: &str) -> Option<(String, Option<u64>)> {
+    let parsed = url::Url::parse(url).ok()?;
+    let mut parts = parsed.path().split('/').filter(|part| !part.is_empty());
+    if parts.next()? != RECORD_PATH_PREFIX {
+        return None;
+    }
+    let stream_id = parts.next()?;
+    if stream_id.to_lowercase().starts_with("trackid=") {
+        return None;
+    }
+    let start_ms = parsed
+        .query_pairs()
+        .find(|(key, _)| key == "start")
+        .and_then(|(_, value)| {
+            let value = value.split('/').next().unwrap_or_default().trim();
+            value.parse::<u64>().ok().or_else(|| parse_clock(value))
+        });
+    Some((stream_id.to_string(), start_ms))
+}
+
+/// Case-insensitive request header lookup.
+pub fn request_header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayRange {
+    /// Seconds from the playback origin; `None` is "now"/open.
+    Npt {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
+    /// Unix milliseconds.
+    Clock {
+        start: Option<u64>,
+        end: Option<u64>,
+    },
+}
+
+pub fn parse_range(value: &str) -> Option<PlayRange> {
+    let value = value.split(';').next()?.trim();
+    if let Some(spec) = value.strip_prefix("npt=") {
+        let (start, end) = spec.split_once('-')?;
+        Some(PlayRange::Npt {
+            start: parse_npt(start),
+            end: parse_npt(end),
+        })
+    } else if let Some(spec) = value.strip_prefix("clock=") {
+        let (start, end) = spec.split_once('-')?;
+        Some(PlayRange::Clock {
+            start: parse_clock(start),
+            end: parse_clock(end),
+        })
+    } else {
+        None
+    }
+}
+
+/// `npt-sec` (`12.5`) or `npt-hhmmss` (`0:00:12.5`); `now` and empty are open.
+fn parse_npt(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let secs = if value.contains(':') {
+        let mut fields = value.rsplit(':');
+        let secs: f64 = fields.next()?.parse().ok()?;
+        let mins: f64 = fields.next()?.parse().ok()?;
+        let hours: f64 = fields.next().unwrap_or("0").parse().ok()?;
+        hours * 3600.0 + mins * 60.0 + secs
+    } else {
+        value.parse().ok()?
+    };
+    (secs.is_finite() && secs >= 0.0).then_some(secs)
+}
+
+/// RFC 2326 absolute time `YYYYMMDDThhmmss[.fraction]Z` to Unix milliseconds.
+pub fn parse_clock(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    let (date, time) = value.split_once('T')?;
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if date.len() != 8 || time.len() != 6 || !digits(date) || !digits(time) || !digits(fraction) {
+        return None;
+    }
+    let field = |s: &str| s.parse::<u8>().ok();
+    let date = Date::from_calendar_date(
+        date[..4].parse().ok()?,
+        Month::try_from(field(&date[4..6])?).ok()?,
+        field(&date[6..8])?,
+    )
+    .ok()?;
+    let time = Time::from_hms(field(&time[..2])?, field(&time[2..4])?, field(&time[4..6])?).ok()?;
+    let secs = PrimitiveDateTime::new(date, time)
+        .assume_utc()
+        .unix_timestamp();
+    let millis = format!("{fraction:0<3}")[..3].parse::<u64>().ok()?;
+    Some(u64::try_from(secs).ok()? * 1_000 + millis)
+}
+
+pub fn format_clock(ms: u64) -> String {
+    let dt = OffsetDateTime::from_unix_timestamp_nanos(i128::from(ms) * 1_000_000)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z",
+        dt.year(),
+        u8::from(dt.month()),
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        dt.second(),
+        ms % 1_000
+    )
+}
+
+/// Requested playback speed; reverse and invalid values fall back to 1.0.
+pub fn parse_scale(value: &str) -> f64 {
+    match value.trim().parse::<f64>() {
+        Ok(scale) if scale.is_finite() && scale > 0.0 => scale,
+        _ => 1.0,
+    }
+}
+
+/// RTP clock rate of a recorded track.
+pub fn track_clock_rate(info: &RecordingMediaInfo, codec: CodecType) -> u32 {
+    match (codec, &info.audio) {
+        (CodecType::AAC, Some(audio)) => audio.sample_rate,
+        (CodecType::AAC, None) => AAC_DEFAULT_CLOCK_RATE,
+        _ => VIDEO_RTP_CLOCK_RATE,
+    }
+}
+
+/// Track ids present in a recording, in SDP order.
+pub fn record_track_ids(info: &RecordingMediaInfo) -> Vec<u8> {
+    let mut ids = Vec::new();
+    if info.video.is_some() {
+        ids.push(VIDEO_TRACK_ID);
+    }
+    if info.audio.is_some() {
+        ids.push(AUDIO_TRACK_ID);
+    }
+    ids
+}
+
+/// SDP for a recording, built from the parameter sets found in the file.
+pub fn build_record_sdp(stream_id: &str, info: &RecordingMediaInfo, duration_ms: u64) -> String {
+    use base64::Engine;
+    let b64 = |data: &[u8]| base64::engine::general_purpose::STANDARD.encode(data);
+
+    let mut sdp = format!(
+        "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=MediaServer Recording: {}\r\nt=0 0\r\na=range:npt=0-{:.3}\r\n",
+        stream_id,
+        duration_ms as f64 / 1000.0
+    );
+    match &info.video {
+        Some(VideoCodecConfig::Avc { sps, pps }) => {
+            let pt = default_payload_type(CodecType::H264);
+            let profile_level_id = sps
+                .get(1..4)
+                .map(|p| format!("{:02X}{:02X}{:02X}", p[0], p[1], p[2]))
+                .unwrap_or_else(|| "42E01F".to_string());
+            sdp.push_str(&format!("m=video 0 RTP/AVP {pt}\r\nc=IN IP4 0.0.0.0\r\n"));
+            sdp.push_str(&format!("a=rtpmap:{pt} H264/{VIDEO_RTP_CLOCK_RATE}\r\n"));
+            sdp.push_str(&format!("a=control:trackID={VIDEO_TRACK_ID}\r\n"));
+            sdp.push_str(&format!(
+                "a=fmtp:{} packetization-mode=1;profile-level-id={};sprop-parameter-sets={},{}\r\n",
+                pt,
+                profile_level_id,
+                b64(sps),
+                b64(pps)
+            ));
+        }
+        Some(VideoCodecConfig::Hevc { vps, sps, pps }) => {
+            let pt = default_payload_type(CodecType::H265);
+            sdp.push_str(&format!("m=video 0 RTP/AVP {pt}\r\nc=IN IP4 0.0.0.0\r\n"));
+            sdp.push_str(&format!("a=rtpmap:{pt} H265/{VIDEO_RTP_CLOCK_RATE}\r\n"));
+            sdp.push_str(&format!("a=control:trackID={VIDEO_TRACK_ID}\r\n"));
+            sdp.push_str(&RtspCommon::hevc_fmtp_line(pt, vps, sps, pps));
+        }
+        None => {}
+    }
+    if let Some(audio) = &info.audio {
+        let pt = default_payload_type(CodecType::AAC);
+        let config: String = audio.asc.iter().map(|b| format!("{b:02X}")).collect();
+        sdp.push_str(&format!("m=audio 0 RTP/AVP {pt}\r\nc=IN IP4 0.0.0.0\r\n"));
+        sdp.push_str(&format!(
+            "a=rtpmap:{} mpeg4-generic/{}/{}\r\n",
+            pt, audio.sample_rate, audio.channels
+        ));
+        sdp.push_str(&format!("a=control:trackID={AUDIO_TRACK_ID}\r\n"));
+        sdp.push_str(&format!(
+            "a=fmtp:{pt} streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config={config}\r\n"
+        ));
+    }
+    sdp
+}
+
+/// Recorded playback bound to one RTSP session (DESCRIBE until TEARDOWN).
+#[derive(Clone)]
+pub struct RecordPlayback {
+    pub stream_id: String,
+    /// Wall-clock time that `npt=0` refers to.
+    pub origin_ms: u64,
+    pub info: RecordingMediaInfo,
+    /// Answer Range in `clock=` units (the client asked that way last).
+    pub use_clock: bool,
+    /// Timestamp of the last frame sent; PLAY without Range resumes here.
+    pub position: Arc<AtomicU64>,
+    /// Next RTP sequence number per track, kept across PAUSE/PLAY.
+    pub rtp_seq: Arc<Mutex<HashMap<u8, u16>>>,
+}
+
+impl RecordPlayback {
+    pub fn new(stream_id: String, origin_ms: u64, info: RecordingMediaInfo) -> Self {
+        Self {
+            stream_id,
+            origin_ms,
+            info,
+            use_clock: false,
+            position: Arc::new(AtomicU64::new(origin_ms)),
+            rtp_seq: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn position_ms(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    /// Wall-clock `(start, end)` of a PLAY; missing bounds stay `None`.
+    pub fn resolve_range(&self, range: PlayRange) -> (Option<u64>, Option<u64>) {
+        match range {
+            PlayRange::Npt { start, end } => {
+                let to_ms = |secs: f64| self.origin_ms + (secs * 1000.0) as u64;
+                (start.map(to_ms), end.map(to_ms))
+            }
+            PlayRange::Clock { start, end } => (start, end),
+        }
+    }
+
+    pub fn format_range(&self, start_ms: u64, end_ms: u64) -> String {
+        if self.use_clock {
+            format!("clock={}-{}", format_clock(start_ms), format_clock(end_ms))
+        } else {
+            let npt = |ms: u64| ms.saturating_sub(self.origin_ms) as f64 / 1000.0;
+            format!("npt={:.3}-{:.3}", npt(start_ms), npt(end_ms))
+        }
+    }
+
+    /// RTP-Info for a PLAY starting at rtptime 0 on every track.
+    pub fn rtp_info(&self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        let seqs = self.rtp_seq.lock();
+        record_track_ids(&self.info)
+            .into_iter()
+            .map(|track_id| {
+                format!(
+                    "url={}/trackID={};seq={};rtptime=0",
+                    base_url,
+                    track_id,
+                    seqs.get(&track_id).copied().unwrap_or(0)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_record_urls() {
+        assert_eq!(
+            parse_record_url("rtsp://host:554/record/cam1?start=1700000000000"),
+            Some(("cam1".to_string(), Some(1_700_000_000_000)))
+        );
+        assert_eq!(
+            parse_record_url("rtsp://host/record/cam1?start=20231114T221320Z/trackID=0"),
+            Some(("cam1".to_string(), Some(1_700_000_000_000)))
+        );
+        assert_eq!(
+            parse_record_url("rtsp://host/record/cam1/trackID=1"),
+            Some(("cam1".to_string(), None))
+        );
+        assert_eq!(parse_record_url("rtsp://host/live/cam1"), None);
+        assert_eq!(parse_record_url("rtsp://host/record"), None);
+    }
+
+    #[test]
+    fn parses_npt_and_clock_ranges() {
+        assert_eq!(
+            parse_range("npt=12.5-"),
+            Some(PlayRange::Npt {
+                start: Some(12.5),
+                end: None
+            })
+        );
+        assert_eq!(
+            parse_range("npt=now-0:01:30"),
+            Some(PlayRange::Npt {
+                start: None,
+                end: Some(90.0)
+            })
+        );
+        assert_eq!(
+            parse_range("clock=20231114T221320.250Z-;time=20231114T221320Z"),
+            Some(PlayRange::Clock {
+                start: Some(1_700_000_000_250),
+                end: None
+            })
+        );
+        assert_eq!(parse_range("smpte=0:10:00-"), None);
+        assert_eq!(format_clock(1_700_000_000_250), "20231114T221320.250Z");
+        assert_eq!(parse_clock("20231314T221320Z"), None);
+    }
+
+    #[test]
+    fn header_lookup_ignores_case() {
+        let request = "PLAY rtsp://h/record/a RTSP/1.0\r\nCSeq: 4\r\nscale: 2.0\r\n\r\n";
+        assert_eq!(request_header(request, "Scale"), Some("2.0"));
+        assert_eq!(request_header(request, "Range"), None);
+        assert_eq!(parse_scale("-1"), 1.0);
+    }
+}
@@ -0,0 +1,294 @@
+//! Assemble H265 RTP (RFC 7798) into Annex B access units for StreamManager.
+
+use bytes::{Bytes, BytesMut};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::core::h265::{self, NAL_PPS, NAL_SPS, NAL_VPS};
+use crate::core::{CodecType, MediaFrame, StreamManager, Track, VIDEO_RTP_CLOCK_RATE};
+use crate::server::webrtc::{rtp_h264_media_payload, H264RtpIngest};
+
+/// Aggregation packet (RFC 7798 §4.4.2).
+const AP: u8 = 48;
+/// Fragmentation unit (RFC 7798 §4.4.3).
+const FU: u8 = 49;
+
+/// RFC 7798 depacketizer (no DONL; `sprop-max-don-diff` = 0).
+#[derive(Debug, Default)]
+pub struct H265RtpDepacketizer {
+    fu_buffer: Option<BytesMut>,
+}
+
+impl H265RtpDepacketizer {
+    /// Returns complete NALUs (without start codes) carried by this payload.
+    pub fn push(&mut self, payload: &[u8]) -> Vec<Vec<u8>> {
+        if payload.len() < 3 {
+            return Vec::new();
+        }
+        match h265::nal_type(payload[0]) {
+            AP => parse_aggregation_packet(payload),
+            FU => self.push_fu(payload).into_iter().collect(),
+            0..=47 => vec![payload.to_vec()],
+            other => {
+                debug!("[RTSP H265] Skipping unsupported payload type {}", other);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.fu_buffer = None;
+    }
+
+    fn push_fu(&mut self, payload: &[u8]) -> Option<Vec<u8>> {
+        let fu_header = payload[2];
+        let start = fu_header & 0x80 != 0;
+        let end = fu_header & 0x40 != 0;
+        let nal_type = fu_header & 0x3F;
+
+        if start {
+            let mut buffer = BytesMut::with_capacity(payload.len() * 4);
+            buffer.extend_from_slice(&[(payload[0] & 0x81) | (nal_type << 1), payload[1]]);
+            buffer.extend_from_slice(&payload[3..]);
+            self.fu_buffer = Some(buffer);
+        } else if let Some(buffer) = &mut self.fu_buffer {
+            buffer.extend_from_slice(&payload[3..]);
+        } else {
+            return None;
+        }
+
+        if end {
+            return self.fu_buffer.take().map(|b| b.to_vec());
+        }
+        None
+    }
+}
+
+fn parse_aggregation_packet(payload: &[u8]) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 2;
+    while i + 2 <= payload.len() {
+        let len = u16::from_be_bytes([payload[i], payload[i + 1]]) as usize;
+        i += 2;
+        if len == 0 || i + len > payload.len() {
+            break;
+        }
+        out.push(payload[i..i + len].to_vec());
+        i += len;
+    }
+    out
+}
+
+/// True for an FU that is not the first fragment (cannot resume after loss).
+fn is_fu_continuation(payload: &[u8]) -> bool {
+    payload.len() >= 3 && h265::nal_type(payload[0]) == FU && payload[2] & 0x80 == 0
+}
+
+/// Stateful H265 RTP → Annex B access-unit publisher.
+pub struct H265RtpIngest {
+    stream_id: String,
+    manager: Arc<StreamManager>,
+    depacketizer: H265RtpDepacketizer,
+    timestamp: u32,
+    access_unit: Vec<u8>,
+    expected_seq: Option<u16>,
+    units: u64,
+    label: &'static str,
+}
+
+impl H265RtpIngest {
+    pub fn new(manager: Arc<StreamManager>, stream_id: String, label: &'static str) -> Self {
+        Self {
+            stream_id,
+            manager,
+            depacketizer: H265RtpDepacketizer::default(),
+            timestamp: 0,
+            access_unit: Vec::new(),
+            expected_seq: None,
+            units: 0,
+            label,
+        }
+    }
+
+    /// Parse a full RTP packet (header + payload), ingest H265, publish on marker.
+    pub fn ingest_rtp_packet(&mut self, rtp: &[u8]) -> bool {
+        let Some((payload, timestamp, marker)) = rtp_h264_media_payload(rtp) else {
+            return false;
+        };
+        let seq = u16::from_be_bytes([rtp[2], rtp[3]]);
+        if let Some(expected) = self.expected_seq {
+            if seq != expected {
+                warn!(
+                    "[{}] RTP sequence gap stream='{}' expected={} got={}",
+                    self.label, self.stream_id, expected, seq
+                );
+                self.depacketizer.reset();
+                self.access_unit.clear();
+                if is_fu_continuation(payload) {
+                    self.expected_seq = Some(seq.wrapping_add(1));
+                    return false;
+                }
+            }
+        }
+        self.expected_seq = Some(seq.wrapping_add(1));
+        self.ingest_payload(payload, timestamp, marker)
+    }
+
+    /// Ingest H265 RTP payload with timestamp and marker bit.
+    pub fn ingest_payload(&mut self, payload: &[u8], timestamp: u32, marker: bool) -> bool {
+        let mut published = false;
+        if !self.access_unit.is_empty() && self.timestamp != timestamp {
+            published |= self.flush_access_unit();
+        }
+        for nalu in self.depacketizer.push(payload) {
+            if matches!(h265::nal_type(nalu[0]), NAL_VPS | NAL_SPS | NAL_PPS) {
+                self.manager
+                    .merge_stream_hevc_nalu_config(&self.stream_id, &nalu);
+            }
+            if self.access_unit.is_empty() {
+                self.timestamp = timestamp;
+            }
+            self.access_unit.extend_from_slice(&[0, 0, 0, 1]);
+            self.access_unit.extend_from_slice(&nalu);
+        }
+        if marker {
+            published |= self.flush_access_unit();
+        }
+        published
+    }
+
+    pub fn flush_remaining(&mut self) -> bool {
+        self.flush_access_unit()
+    }
+
+    fn flush_access_unit(&mut self) -> bool {
+        let au = std::mem::take(&mut self.access_unit);
+        if !h265::contains_vcl_nalu(&au) {
+            return false;
+        }
+        let is_keyframe = h265::contains_irap_nalu(&au);
+        self.units += 1;
+        if self.units == 1 || is_keyframe && self.units <= 5 {
+            info!(
+                "[{}] H265 access unit #{} stream='{}' size={} keyframe={} ts={}",
+                self.label,
+                self.units,
+                self.stream_id,
+                au.len(),
+                is_keyframe,
+                self.timestamp
+            );
+        }
+        let frame = MediaFrame::new(
+            self.stream_id.clone(),
+            0,
+            self.timestamp as u64,
+            Bytes::from(au),
+            is_keyframe,
+            CodecType::H265,
+        )
+        .with_clock_rate(VIDEO_RTP_CLOCK_RATE);
+        self.manager.publish_frame(frame);
+        true
+    }
+}
+
+/// Video ingest for track 0, picked from the SDP codec.
+pub enum VideoRtpIngest {
+    H264(H264RtpIngest),
+    H265(H265RtpIngest),
+}
+
+impl VideoRtpIngest {
+    pub fn for_tracks(
+        tracks: &[Track],
+        manager: Arc<StreamManager>,
+        stream_id: String,
+        label: &'static str,
+    ) -> Self {
+        let is_hevc = tracks
+            .iter()
+            .find(|t| t.codec.is_video())
+            .map(|t| t.codec == CodecType::H265)
+            .unwrap_or(false);
+        if is_hevc {
+            Self::H265(H265RtpIngest::new(manager, stream_id, label))
+        } else {
+            Self::H264(H264RtpIngest::new(manager, stream_id, label))
+        }
+    }
+
+    pub fn ingest_rtp_packet(&mut self, rtp: &[u8]) -> bool {
+        match self {
+            Self::H264(ingest) => ingest.ingest_rtp_packet(rtp),
+            Self::H265(ingest) => ingest.ingest_rtp_packet(rtp),
+        }
+    }
+
+    pub fn flush_remaining(&mut self) -> bool {
+        match self {
+            Self::H264(ingest) => ingest.flush_remaining(),
+            Self::H265(ingest) => ingest.flush_remaining(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{DispatchPolicy, StreamProtocol, StreamSourceMode};
+    use crate::server::rtsp::RtspCommon;
+
+    #[test]
+    fn fu_round_trips_through_packetizer() {
+        let mut idr = vec![0x26, 0x01];
+        idr.extend((0..4000u32).map(|i| (i % 251) as u8 | 0x01));
+        let mut annex_b = vec![0, 0, 0, 1, 0x40, 0x01, 0x0C, 0, 0, 0, 1];
+        annex_b.extend_from_slice(&idr);
+
+        let mut seq = 10u16;
+        let packets =
+            RtspCommon::packetize_h265_access_unit_for_rtp(&annex_b, 98, &mut seq, 9000, 1);
+        assert!(packets.len() > 2);
+        assert_eq!(seq, 10 + packets.len() as u16);
+
+        let mut depack = H265RtpDepacketizer::default();
+        let nalus: Vec<Vec<u8>> = packets.iter().flat_map(|p| depack.push(&p[12..])).collect();
+        assert_eq!(nalus, vec![vec![0x40, 0x01, 0x0C], idr]);
+        assert!(packets.last().unwrap()[1] & 0x80 != 0);
+    }
+
+    #[test]
+    fn aggregation_packet_yields_parameter_sets() {
+        let ap = [
+            0x60, 0x01, 0x00, 0x03, 0x40, 0x01, 0x0C, 0x00, 0x03, 0x42, 0x01, 0x01, 0x00, 0x03,
+            0x44, 0x01, 0xC1,
+        ];
+        let nalus = H265RtpDepacketizer::default().push(&ap);
+        assert_eq!(nalus.len(), 3);
+        assert_eq!(nalus[2], vec![0x44, 0x01, 0xC1]);
+    }
+
+    #[tokio::test]
+    async fn ingest_publishes_irap_as_keyframe_with_config() {
+        let manager = Arc::new(StreamManager::new());
+        manager.create_stream("cam", StreamSourceMode::Push, StreamProtocol::RTSP, None);
+        let mut reader = manager
+            .dispatch_subscribe("cam", DispatchPolicy::SequentialFromIdr)
+            .unwrap();
+        let mut ingest = H265RtpIngest::new(manager.clone(), "cam".into(), "test");
+
+        let ap = [
+            0x60, 0x01, 0x00, 0x03, 0x40, 0x01, 0x0C, 0x00, 0x03, 0x42, 0x01, 0x01, 0x00, 0x03,
+            0x44, 0x01, 0xC1,
+        ];
+        assert!(!ingest.ingest_payload(&ap, 3000, false));
+        assert!(ingest.ingest_payload(&[0x26, 0x01, 0xAF], 3000, true));
+
+        let stream = manager.get_stream(&"cam".to_string()).unwrap();
+        assert!(stream.vps.is_some() && stream.sps.is_some() && stream.pps.is_some());
+        let frames = reader.recv_batch().await.unwrap();
+        assert_eq!(frames[0].codec, CodecType::H265);
+        assert!(frames[0].is_keyframe);
+    }
+}
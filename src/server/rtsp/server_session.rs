@@ -2,6 +2,7 @@ use anyhow::Result;
 use bytes::BytesMut;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -12,12 +13,18 @@ use super::common::{
     extract_track_id, extract_transport, format_rtsp_message, is_udp_transport, RtspCommon,
 };
 use super::play_egress::{
-    egress_rtp_packets, is_idr, prime_rtsp_play, recv_coalesced_play_frame, PlayRtpTimeline,
+    default_payload_type, egress_rtp_packets, is_idr, packetize_frame, prime_rtsp_play,
+    recv_coalesced_play_frame, PlayRtpTimeline,
 };
+use super::record_play::{
+    build_record_sdp, parse_range, parse_record_url, parse_scale, request_header, track_clock_rate,
+    PlayRange, RecordPlayback,
+};
+use super::rtp_h265::VideoRtpIngest;
 use super::{RtspRequest, RtspResponse, RtspServer, RtspSession, TransportMode};
 use crate::core::dispatch::DispatchError;
 use crate::core::{CodecType, DispatchPolicy, MediaFrame, StreamManager, AAC_DEFAULT_CLOCK_RATE};
-use crate::server::webrtc::H264RtpIngest;
+use crate::process::record::{PacedReader, RecorderManager, RecordingReader};
 
 pub struct RtspServerSession {
     reader: tokio::net::tcp::OwnedReadHalf,
@@ -34,9 +41,22 @@ pub struct RtspServerSession {
     // H264 codec parameters
     sps_cache: Arc<parking_lot::RwLock<Option<Vec<u8>>>>,
     pps_cache: Arc<parking_lot::RwLock<Option<Vec<u8>>>>,
-    h264_ingest: Option<H264RtpIngest>,
+    video_ingest: Option<VideoRtpIngest>,
     // Track the session state for SDP generation
     sdp_generated: Arc<parking_lot::RwLock<bool>>,
+    recorder: Option<Arc<RecorderManager>>,
+    // Recorded playback (`/record/<stream>`) and the reader opened by DESCRIBE
+    record: Option<RecordPlayback>,
+    record_reader: Option<RecordingReader>,
+    // TCP interleaved RTP channel per track, from SETUP
+    tcp_channels: HashMap<u8, u8>,
+}
+
+/// Where a recorded PLAY starts and stops.
+struct RecordPlayStart {
+    reader: RecordingReader,
+    scale: f64,
+    end_ms: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -52,6 +72,7 @@ impl RtspServerSession {
         socket: TcpStream,
         manager: Arc<StreamManager>,
         hls_server: Option<Arc<crate::server::hls::HlsServer>>,
+        recorder: Option<Arc<RecorderManager>>,
     ) -> Self {
         let peer_addr = socket
             .peer_addr()
@@ -77,8 +98,12 @@ impl RtspServerSession {
             rtp_sender_abort: None,
             sps_cache: Arc::new(parking_lot::RwLock::new(None)),
             pps_cache: Arc::new(parking_lot::RwLock::new(None)),
-            h264_ingest: None,
+            video_ingest: None,
             sdp_generated: Arc::new(parking_lot::RwLock::new(false)),
+            recorder,
+            record: None,
+            record_reader: None,
+            tcp_channels: HashMap::new(),
         }
     }
 
@@ -150,8 +175,13 @@ impl RtspServerSession {
                 track_id, stream_id, peer_addr
             );
             let mut buffer = vec![0u8; 65535];
-            let mut h264_ingest = if track_id == 0 {
-                Some(H264RtpIngest::new(
+            let mut video_ingest = if track_id == 0 {
+                let tracks = manager
+                    .get_stream(&stream_id)
+                    .map(|stream| stream.tracks)
+                    .unwrap_or_default();
+                Some(VideoRtpIngest::for_tracks(
+                    &tracks,
                     manager.clone(),
                     stream_id.clone(),
                     "RTSP-Push-UDP",
@@ -168,7 +198,7 @@ impl RtspServerSession {
                         }
 
                         if track_id == 0 {
-                            if let Some(ingest) = &mut h264_ingest {
+                            if let Some(ingest) = &mut video_ingest {
                                 ingest.ingest_rtp_packet(&buffer[..len]);
                             }
                         } else {
@@ -765,14 +795,20 @@ impl RtspServerSession {
             ]);
 
             if track_id == 0 {
-                if self.h264_ingest.is_none() {
-                    self.h264_ingest = Some(H264RtpIngest::new(
+                if self.video_ingest.is_none() {
+                    let tracks = self
+                        .manager
+                        .get_stream(stream_id)
+                        .map(|stream| stream.tracks)
+                        .unwrap_or_default();
+                    self.video_ingest = Some(VideoRtpIngest::for_tracks(
+                        &tracks,
                         Arc::clone(&self.manager),
                         stream_id.clone(),
                         "RTSP-Push",
                     ));
                 }
-                if let Some(ingest) = &mut self.h264_ingest {
+                if let Some(ingest) = &mut self.video_ingest {
                     ingest.ingest_rtp_packet(rtp_payload);
                 }
                 self.sync_codec_cache_from_manager(stream_id);
@@ -870,6 +906,17 @@ impl RtspServerSession {
         let mut setup_server_ports: Option<(u16, u16)> = None;
         if method == "SETUP" {
             let transport = extract_transport(&request);
+            if !is_udp_transport(&transport) {
+                if let Some((rtp_channel, _)) = transport.client_port {
+                    let url = request
+                        .lines()
+                        .next()
+                        .and_then(|l| l.split_whitespace().nth(1))
+                        .unwrap_or("");
+                    self.tcp_channels
+                        .insert(extract_track_id(url) as u8, rtp_channel as u8);
+                }
+            }
             if is_udp_transport(&transport) {
                 if let Some((client_rtp, client_rtcp)) = transport.client_port {
                     let url = request
@@ -889,9 +936,20 @@ impl RtspServerSession {
             }
         }
 
+        if let Some((response, play)) = self.handle_record_request(method, &request, cseq).await {
+            self.send_response(&response).await?;
+            if let Some(play) = play {
+                self.start_record_sender(play);
+            }
+            return Ok(());
+        }
+
         if method == "TEARDOWN" {
             self.abort_rtp_sender();
             self.udp_tracks.clear();
+            self.tcp_channels.clear();
+            self.record = None;
+            self.record_reader = None;
         } else if method == "PAUSE" {
             self.abort_rtp_sender();
         }
@@ -928,6 +986,306 @@ impl RtspServerSession {
         Ok(())
     }
 
+    /// DESCRIBE/SETUP/PLAY/PAUSE on `/record/<stream>` URLs; `None` hands the
+    /// request to the live handler.
+    async fn handle_record_request(
+        &mut self,
+        method: &str,
+        request: &str,
+        cseq: &str,
+    ) -> Option<(RtspResponse, Option<RecordPlayStart>)> {
+        let url = request
+            .lines()
+            .next()
+            .and_then(|l| l.split_whitespace().nth(1))
+            .unwrap_or("");
+        match method {
+            "DESCRIBE" => {
+                let (stream_id, start_ms) = parse_record_url(url)?;
+                let response = match self.open_recording(stream_id, start_ms).await {
+                    Ok(()) => self.describe_recording(cseq),
+                    Err(response) => response.with_cseq(cseq),
+                };
+                Some((response, None))
+            }
+            // Clients that skip DESCRIBE open the recording on first SETUP.
+            "SETUP" if self.record.is_none() => {
+                let (stream_id, start_ms) = parse_record_url(url)?;
+                match self.open_recording(stream_id, start_ms).await {
+                    Ok(()) => None,
+                    Err(response) => Some((response.with_cseq(cseq), None)),
+                }
+            }
+            "PLAY" if self.record.is_some() => Some(self.play_recording(url, request, cseq).await),
+            "PAUSE" if self.record.is_some() => {
+                self.abort_rtp_sender();
+                self.session.playing = false;
+                let mut response = RtspResponse::new(200, "OK").with_cseq(cseq);
+                if let Some(session_id) = &self.session.session_id {
+                    response = response.header("Session", session_id);
+                }
+                Some((response, None))
+            }
+            _ => None,
+        }
+    }
+
+    /// Bind the session to a recording, with `npt=0` at the first decodable
+    /// frame at or before `start_ms` (or the oldest recording).
+    async fn open_recording(
+        &mut self,
+        stream_id: String,
+        start_ms: Option<u64>,
+    ) -> std::result::Result<(), RtspResponse> {
+        let not_found = || RtspResponse::new(404, "Not Found");
+        let Some(recorder) = self.recorder.clone() else {
+            warn!(
+                "[RTSP] [{}] recorded playback requested but recording is disabled",
+                self.peer_addr
+            );
+            return Err(not_found());
+        };
+        let Some((first_ms, last_ms)) = recorder.recorded_span(&stream_id) else {
+            warn!(
+                "[RTSP] [{}] no recordings for stream {}",
+                self.peer_addr, stream_id
+            );
+            return Err(not_found());
+        };
+        let start_ms = start_ms.unwrap_or(first_ms);
+        if start_ms >= last_ms {
+            return Err(RtspResponse::new(457, "Invalid Range"));
+        }
+        let reader = match recorder
+            .open_playback(&stream_id, start_ms.max(first_ms))
+            .await
+        {
+            Ok(reader) => reader,
+            Err(err) => {
+                warn!(
+                    "[RTSP] [{}] cannot open recording {} at {}: {}",
+                    self.peer_addr, stream_id, start_ms, err
+                );
+                return Err(not_found());
+            }
+        };
+        info!(
+            "[RTSP] [{}] recorded playback stream={} origin={}",
+            self.peer_addr,
+            stream_id,
+            reader.start_ms()
+        );
+        self.session.stream_id = Some(stream_id.clone());
+        self.record = Some(RecordPlayback::new(
+            stream_id,
+            reader.start_ms(),
+            reader.info().clone(),
+        ));
+        self.record_reader = Some(reader);
+        Ok(())
+    }
+
+    fn describe_recording(&self, cseq: &str) -> RtspResponse {
+        let record = self.record.as_ref().expect("record playback opened");
+        let end_ms = self
+            .recorder
+            .as_ref()
+            .and_then(|recorder| recorder.recorded_span(&record.stream_id))
+            .map(|(_, end_ms)| end_ms)
+            .unwrap_or(record.origin_ms);
+        let sdp = build_record_sdp(
+            &record.stream_id,
+            &record.info,
+            end_ms.saturating_sub(record.origin_ms),
+        );
+        RtspResponse::new(200, "OK")
+            .with_cseq(cseq)
+            .header("Content-Type", "application/sdp")
+            .body(&sdp)
+    }
+
+    /// PLAY a recording from the requested Range, or resume where PAUSE left off.
+    async fn play_recording(
+        &mut self,
+        url: &str,
+        request: &str,
+        cseq: &str,
+    ) -> (RtspResponse, Option<RecordPlayStart>) {
+        let Some(mut record) = self.record.clone() else {
+            return (
+                RtspResponse::new(455, "Method Not Valid in This State").with_cseq(cseq),
+                None,
+            );
+        };
+        let Some(recorder) = self.recorder.clone() else {
+            return (RtspResponse::new(404, "Not Found").with_cseq(cseq), None);
+        };
+        let span_end = recorder
+            .recorded_span(&record.stream_id)
+            .map(|(_, end_ms)| end_ms)
+            .unwrap_or_default();
+        let range = request_header(request, "Range").and_then(parse_range);
+        let (start_ms, end_ms) = match range {
+            Some(range) => {
+                record.use_clock = matches!(range, PlayRange::Clock { .. });
+                record.resolve_range(range)
+            }
+            None => (None, None),
+        };
+        let start_ms = start_ms.unwrap_or_else(|| record.position_ms());
+        if start_ms >= span_end || end_ms.is_some_and(|end_ms| end_ms <= start_ms) {
+            return (
+                RtspResponse::new(457, "Invalid Range").with_cseq(cseq),
+                None,
+            );
+        }
+        let scale = request_header(request, "Scale").map(parse_scale);
+
+        // Reuse the reader DESCRIBE opened when playing from the origin.
+        let reader = match self.record_reader.take() {
+            Some(reader) if start_ms <= reader.start_ms() => Ok(reader),
+            _ => recorder.open_playback(&record.stream_id, start_ms).await,
+        };
+        let mut reader = match reader {
+            Ok(reader) => reader,
+            Err(err) => {
+                warn!(
+                    "[RTSP] [{}] recorded PLAY {} at {} failed: {}",
+                    self.peer_addr, record.stream_id, start_ms, err
+                );
+                return (
+                    RtspResponse::new(457, "Invalid Range").with_cseq(cseq),
+                    None,
+                );
+            }
+        };
+        let scale_value = scale.unwrap_or(1.0);
+        reader.set_keyframes_only(scale_value > 1.0);
+        self.abort_rtp_sender();
+        self.session.playing = true;
+
+        let mut response = RtspResponse::new(200, "OK").with_cseq(cseq).header(
+            "Range",
+            &record.format_range(reader.start_ms(), end_ms.unwrap_or(span_end)),
+        );
+        if let Some(scale) = scale {
+            response = response.header("Scale", &format!("{scale:.1}"));
+        }
+        if let Some(session_id) = &self.session.session_id {
+            response = response.header("Session", session_id);
+        }
+        response = response.header("RTP-Info", &record.rtp_info(url));
+        info!(
+            "[RTSP] [{}] recorded PLAY stream={} from={} to={:?} scale={}",
+            self.peer_addr,
+            record.stream_id,
+            reader.start_ms(),
+            end_ms,
+            scale_value
+        );
+        self.record = Some(record);
+        (
+            response,
+            Some(RecordPlayStart {
+                reader,
+                scale: scale_value,
+                end_ms,
+            }),
+        )
+    }
+
+    /// Send recorded frames paced at `scale` times real time. RTP timestamps
+    /// start at 0 for this PLAY and follow the (scaled) presentation time.
+    fn start_record_sender(&mut self, play: RecordPlayStart) {
+        let Some(record) = self.record.clone() else {
+            return;
+        };
+        self.abort_rtp_sender();
+        self.session.rtp_task_started = true;
+
+        let RecordPlayStart {
+            reader,
+            scale,
+            end_ms,
+        } = play;
+        let write_tx = self.write_tx.clone();
+        let rtp_ssrc = self.rtp_ssrc;
+        let peer_addr = self.peer_addr;
+        let use_udp = self.session.transport_mode == TransportMode::Udp;
+        let udp_tracks = self.udp_tracks.clone();
+        let tcp_channels = self.tcp_channels.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut paced = PacedReader::new(reader, scale);
+            let mut frame_count: u64 = 0;
+            'rtp: while let Some((frame, offset_ms)) = paced.next_frame().await {
+                if end_ms.is_some_and(|end_ms| frame.timestamp >= end_ms) {
+                    break;
+                }
+
+                let clock_rate = track_clock_rate(&record.info, frame.codec);
+                let ts = (offset_ms * f64::from(clock_rate) / 1000.0) as u64 as u32;
+                let packets = {
+                    let mut seqs = record.rtp_seq.lock();
+                    let seq = seqs.entry(frame.track_id).or_insert(0);
+                    packetize_frame(
+                        frame.codec,
+                        &frame.data,
+                        default_payload_type(frame.codec),
+                        ts,
+                        seq,
+                        rtp_ssrc,
+                    )
+                };
+
+                if use_udp {
+                    let Some(track) = udp_tracks.get(&frame.track_id) else {
+                        continue;
+                    };
+                    for packet in packets {
+                        if RtspCommon::send_rtp_over_udp(
+                            &track.rtp_socket,
+                            &packet,
+                            track.client_rtp_addr,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            error!(
+                                "[RTSP] [{}] Failed to send recorded UDP RTP track={}",
+                                record.stream_id, frame.track_id
+                            );
+                            break 'rtp;
+                        }
+                    }
+                } else {
+                    let channel = tcp_channels
+                        .get(&frame.track_id)
+                        .copied()
+                        .unwrap_or(frame.track_id);
+                    for packet in packets {
+                        let interleaved = RtspCommon::wrap_interleaved(&packet, channel);
+                        if write_tx.send(interleaved).await.is_err() {
+                            info!(
+                                "[RTSP] [{}] PLAY client {} disconnected — stopping recorded playback",
+                                record.stream_id, peer_addr
+                            );
+                            break 'rtp;
+                        }
+                    }
+                }
+                frame_count += 1;
+                record.position.store(frame.timestamp, Ordering::Relaxed);
+            }
+            info!(
+                "[RTSP] [{}] recorded playback stopped after {} frames at {}",
+                record.stream_id,
+                frame_count,
+                record.position_ms()
+            );
+        });
+        self.rtp_sender_abort = Some(handle.abort_handle());
+    }
     fn abort_rtp_sender(&mut self) {
         if let Some(handle) = self.rtp_sender_abort.take() {
             handle.abort();
@@ -196,6 +196,244 @@ pub fn extract_sps_pps(data: &[u8]) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
     (sps, pps)
 }
 
+/// AVCDecoderConfigurationRecord with 4-byte NALU lengths (one SPS, one PPS).
+pub fn build_avcc(sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(11 + sps.len() + pps.len());
+    out.push(0x01); // configurationVersion
+    if sps.len() >= 4 {
+        out.extend_from_slice(&sps[1..4]); // profile, compatibility, level
+    } else {
+        out.extend_from_slice(&[0x42, 0x00, 0x1F]);
+    }
+    out.push(0xFF); // lengthSizeMinusOne = 3
+    out.push(0xE1); // numOfSPS = 1
+    out.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+    out.extend_from_slice(sps);
+    out.push(0x01); // numOfPPS = 1
+    out.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+    out.extend_from_slice(pps);
+    out
+}
+
+/// Strip emulation-prevention bytes (`00 00 03` → `00 00`).
+pub fn nal_to_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &b in nal {
+        if zeros >= 2 && b == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    out
+}
+
+/// Insert emulation-prevention bytes (`00 00 0x` with x <= 3 → `00 00 03 0x`).
+pub fn rbsp_to_nal(rbsp: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rbsp.len() + rbsp.len() / 64);
+    let mut zeros = 0;
+    for &b in rbsp {
+        if zeros >= 2 && b <= 0x03 {
+            out.push(0x03);
+            zeros = 0;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    // A trailing zero would read as part of the next start code
+    if zeros > 0 {
+        out.push(0x03);
+    }
+    out
+}
+
+/// MSB-first bit reader with Exp-Golomb support for SPS parsing.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Option<u32> {
+        let byte = *self.data.get(self.pos / 8)?;
+        let bit = (byte >> (7 - self.pos % 8)) & 0x01;
+        self.pos += 1;
+        Some(bit as u32)
+    }
+
+    pub fn read_bits(&mut self, n: u32) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..n {
+            value = (value << 1) | self.read_bit()?;
+        }
+        Some(value)
+    }
+
+    pub fn skip_bits(&mut self, n: usize) -> Option<()> {
+        if self.pos + n > self.data.len() * 8 {
+            return None;
+        }
+        self.pos += n;
+        Some(())
+    }
+
+    pub fn read_ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.read_bit()? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some((1u32 << zeros) - 1 + self.read_bits(zeros)?)
+    }
+
+    pub fn read_se(&mut self) -> Option<i32> {
+        let v = self.read_ue()?;
+        Some(if v & 1 == 1 {
+            v.div_ceil(2) as i32
+        } else {
+            -((v / 2) as i32)
+        })
+    }
+}
+
+/// Coded picture size (width, height) after cropping, from an H264 SPS NALU.
+pub fn sps_dimensions(sps: &[u8]) -> Option<(u32, u32)> {
+    sps_info(sps).map(|info| (info.width, info.height))
+}
+
+/// Picture size and VUI frame rate from an H264 SPS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpsInfo {
+    pub width: u32,
+    pub height: u32,
+    /// `time_scale / (2 * num_units_in_tick)` when VUI timing info is present
+    pub framerate: Option<f64>,
+}
+
+/// Parse an H264 SPS NALU (picture size after cropping, VUI timing).
+pub fn sps_info(sps: &[u8]) -> Option<SpsInfo> {
+    let rbsp = nal_to_rbsp(sps);
+    let mut r = BitReader::new(rbsp.get(1..)?);
+    let profile_idc = r.read_bits(8)?;
+    r.skip_bits(16)?; // constraint flags + level_idc
+    r.read_ue()?; // seq_parameter_set_id
+    let mut chroma_format_idc = 1;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format_idc = r.read_ue()?;
+        if chroma_format_idc == 3 {
+            r.read_bit()?; // separate_colour_plane_flag
+        }
+        r.read_ue()?; // bit_depth_luma_minus8
+        r.read_ue()?; // bit_depth_chroma_minus8
+        r.read_bit()?; // qpprime_y_zero_transform_bypass_flag
+        if r.read_bit()? == 1 {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.read_bit()? == 1 {
+                    let size = if i < 6 { 16 } else { 64 };
+                    let (mut last, mut next) = (8i32, 8i32);
+                    for _ in 0..size {
+                        if next != 0 {
+                            next = (last + r.read_se()? + 256) % 256;
+                        }
+                        last = if next == 0 { last } else { next };
+                    }
+                }
+            }
+        }
+    }
+    r.read_ue()?; // log2_max_frame_num_minus4
+    match r.read_ue()? {
+        0 => {
+            r.read_ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.read_bit()?;
+            r.read_se()?;
+            r.read_se()?;
+            for _ in 0..r.read_ue()? {
+                r.read_se()?;
+            }
+        }
+        _ => {}
+    }
+    r.read_ue()?; // max_num_ref_frames
+    r.read_bit()?; // gaps_in_frame_num_value_allowed_flag
+    let width_mbs = r.read_ue()? + 1;
+    let height_map_units = r.read_ue()? + 1;
+    let frame_mbs_only = r.read_bit()?;
+    if frame_mbs_only == 0 {
+        r.read_bit()?; // mb_adaptive_frame_field_flag
+    }
+    r.read_bit()?; // direct_8x8_inference_flag
+    let (mut crop_l, mut crop_r, mut crop_t, mut crop_b) = (0, 0, 0, 0);
+    if r.read_bit()? == 1 {
+        crop_l = r.read_ue()?;
+        crop_r = r.read_ue()?;
+        crop_t = r.read_ue()?;
+        crop_b = r.read_ue()?;
+    }
+    let (sub_width, sub_height) = match chroma_format_idc {
+        0 => (1, 1),
+        1 => (2, 2),
+        2 => (2, 1),
+        _ => (1, 1),
+    };
+    let crop_unit_x = sub_width;
+    let crop_unit_y = sub_height * (2 - frame_mbs_only);
+    let width = (width_mbs * 16).checked_sub((crop_l + crop_r) * crop_unit_x)?;
+    let height = ((2 - frame_mbs_only) * height_map_units * 16)
+        .checked_sub((crop_t + crop_b) * crop_unit_y)?;
+    Some(SpsInfo {
+        width,
+        height,
+        framerate: vui_framerate(&mut r),
+    })
+}
+
+/// Frame rate from VUI timing info; the reader is positioned at vui_parameters_present_flag.
+fn vui_framerate(r: &mut BitReader) -> Option<f64> {
+    if r.read_bit()? == 0 {
+        return None;
+    }
+    if r.read_bit()? == 1 {
+        // aspect_ratio_idc; 255 = Extended_SAR
+        if r.read_bits(8)? == 255 {
+            r.skip_bits(32)?;
+        }
+    }
+    if r.read_bit()? == 1 {
+        r.read_bit()?; // overscan_appropriate_flag
+    }
+    if r.read_bit()? == 1 {
+        r.skip_bits(4)?; // video_format + video_full_range_flag
+        if r.read_bit()? == 1 {
+            r.skip_bits(24)?; // colour primaries / transfer / matrix
+        }
+    }
+    if r.read_bit()? == 1 {
+        r.read_ue()?; // chroma_sample_loc_type_top_field
+        r.read_ue()?; // chroma_sample_loc_type_bottom_field
+    }
+    if r.read_bit()? == 0 {
+        return None;
+    }
+    let num_units_in_tick = r.read_bits(32)?;
+    let time_scale = r.read_bits(32)?;
+    (num_units_in_tick > 0).then(|| time_scale as f64 / (2.0 * num_units_in_tick as f64))
+}
+
 const NALU_NAMES: [&str; 32] = [
     "unspec", "slice", "dpa", "dpb", "dpc", "idr", "sei", "sps", "pps", "aud", "eoseq", "eostr",
     "fill", "spsext", "prefix", "subset", "depth", "resv17", "resv18", "aux", "ext", "cagg",
@@ -285,4 +523,56 @@ mod tests {
         assert!(sps.is_some());
         assert!(pps.is_some());
     }
+
+    #[test]
+    fn sps_dimensions_handles_cropping() {
+        // Baseline 1280x720: 80x45 macroblocks, no cropping.
+        let sps_720p = [0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+        assert_eq!(sps_dimensions(&sps_720p), Some((1280, 720)));
+        // High 4:2:0 1920x1080: 68 macroblock rows, bottom crop of 4 (8 luma lines).
+        let sps_1080p = [
+            0x67, 0x64, 0x00, 0x28, 0xAC, 0xDA, 0x01, 0xE0, 0x08, 0x9F, 0x95,
+        ];
+        assert_eq!(sps_dimensions(&sps_1080p), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn sps_info_reads_vui_framerate() {
+        let mut bits = String::new();
+        let ue = |bits: &mut String, v: u32| {
+            let code = format!("{:b}", v + 1);
+            bits.push_str(&"0".repeat(code.len() - 1));
+            bits.push_str(&code);
+        };
+        bits.push_str("01000010"); // profile_idc 66
+        bits.push_str("0000000000011111"); // constraints + level 31
+        ue(&mut bits, 0); // sps id
+        ue(&mut bits, 0); // log2_max_frame_num_minus4
+        ue(&mut bits, 2); // pic_order_cnt_type
+        ue(&mut bits, 1); // max_num_ref_frames
+        bits.push('0');
+        ue(&mut bits, 79); // 80 MBs wide
+        ue(&mut bits, 44); // 45 MBs high
+        bits.push_str("110"); // frame_mbs_only, direct_8x8, no crop
+        bits.push_str("10000"); // VUI: no aspect/overscan/signal/chroma loc
+        bits.push('1'); // timing_info_present
+        bits.push_str(&format!("{:032b}{:032b}1", 1001, 60_000));
+        bits.push('1'); // rbsp stop bit
+        while !bits.len().is_multiple_of(8) {
+            bits.push('0');
+        }
+        let mut sps = vec![0x67];
+        sps.extend(
+            bits.as_bytes()
+                .chunks(8)
+                .map(|b| u8::from_str_radix(std::str::from_utf8(b).unwrap(), 2).unwrap()),
+        );
+
+        let info = sps_info(&sps).unwrap();
+        assert_eq!((info.width, info.height), (1280, 720));
+        let fps = info.framerate.unwrap();
+        assert!((fps - 29.97).abs() < 0.01, "fps={}", fps);
+        let no_vui = [0x67, 0x42, 0x00, 0x1F, 0xED, 0x00, 0xA0, 0x0B, 0x72];
+        assert_eq!(sps_info(&no_vui).unwrap().framerate, None);
+    }
 }